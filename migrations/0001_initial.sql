@@ -0,0 +1,42 @@
+-- The schema as it stood before migrations. Every statement tolerates an
+-- existing table, so databases created by earlier releases adopt it as is.
+CREATE TABLE IF NOT EXISTS guild_configs (
+    guild_id INTEGER PRIMARY KEY,
+    config   TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS scheduled_unpins (
+    message_id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL,
+    guild_id   INTEGER,
+    unpin_at   INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS scheduled_unpins_due ON scheduled_unpins (unpin_at);
+CREATE TABLE IF NOT EXISTS pin_history (
+    message_id INTEGER PRIMARY KEY,
+    guild_id   INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    author_id  INTEGER NOT NULL,
+    excerpt    TEXT NOT NULL,
+    votes      INTEGER NOT NULL,
+    pinned_at  INTEGER NOT NULL,
+    reason     TEXT,
+    manual     INTEGER NOT NULL DEFAULT 0,
+    unpinned_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS pin_history_guild ON pin_history (guild_id, pinned_at);
+CREATE INDEX IF NOT EXISTS pin_history_channel ON pin_history (channel_id);
+CREATE TABLE IF NOT EXISTS digest_runs (
+    guild_id INTEGER PRIMARY KEY,
+    last_run INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS pin_indexes (
+    channel_id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS pin_snapshots (
+    channel_id  INTEGER PRIMARY KEY,
+    message_ids TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS pin_opt_outs (
+    user_id INTEGER PRIMARY KEY
+);
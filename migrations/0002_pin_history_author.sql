@@ -0,0 +1,2 @@
+-- Purging a user's data looks their pins up by author.
+CREATE INDEX pin_history_author ON pin_history (author_id);
@@ -0,0 +1,5 @@
+-- When each channel's pins were last pruned under its retention policy.
+CREATE TABLE retention_runs (
+    channel_id INTEGER PRIMARY KEY,
+    last_run   INTEGER NOT NULL
+);
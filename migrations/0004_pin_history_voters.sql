@@ -0,0 +1,6 @@
+-- How many members voted for each pin, next to their weighted total in
+-- `votes`. Pins recorded before this keep 0.
+ALTER TABLE pin_history ADD COLUMN voters INTEGER NOT NULL DEFAULT 0;
+
+-- /top-pins ranks a guild's pins by votes.
+CREATE INDEX pin_history_votes ON pin_history (guild_id, votes);
@@ -0,0 +1,6 @@
+-- The pin the bot made last in each channel that rotates its pins, taken
+-- down when the next one replaces it.
+CREATE TABLE rotation_pins (
+    channel_id INTEGER PRIMARY KEY,
+    message_id INTEGER NOT NULL
+);
@@ -0,0 +1,15 @@
+-- What each vote came to once it ended, for /votestats and the weekly
+-- audit line.
+CREATE TABLE vote_stats (
+    session_id    INTEGER PRIMARY KEY,
+    guild_id      INTEGER NOT NULL,
+    channel_id    INTEGER NOT NULL,
+    ended_at      INTEGER NOT NULL,
+    duration_secs INTEGER NOT NULL,
+    outcome       TEXT NOT NULL,
+    votes         INTEGER NOT NULL,
+    peak_votes    INTEGER NOT NULL,
+    removals      INTEGER NOT NULL,
+    voters        INTEGER NOT NULL
+);
+CREATE INDEX vote_stats_guild_ended ON vote_stats (guild_id, ended_at);
@@ -0,0 +1,31 @@
+-- A database as the last release before migrations left it: no
+-- schema_version table, and pin_history from before its later columns.
+CREATE TABLE guild_configs (
+    guild_id INTEGER PRIMARY KEY,
+    config   TEXT NOT NULL
+);
+CREATE TABLE scheduled_unpins (
+    message_id INTEGER PRIMARY KEY,
+    channel_id INTEGER NOT NULL,
+    guild_id   INTEGER,
+    unpin_at   INTEGER NOT NULL
+);
+CREATE INDEX scheduled_unpins_due ON scheduled_unpins (unpin_at);
+CREATE TABLE pin_history (
+    message_id INTEGER PRIMARY KEY,
+    guild_id   INTEGER NOT NULL,
+    channel_id INTEGER NOT NULL,
+    author_id  INTEGER NOT NULL,
+    excerpt    TEXT NOT NULL,
+    votes      INTEGER NOT NULL,
+    pinned_at  INTEGER NOT NULL
+);
+CREATE INDEX pin_history_guild ON pin_history (guild_id, pinned_at);
+CREATE TABLE digest_runs (
+    guild_id INTEGER PRIMARY KEY,
+    last_run INTEGER NOT NULL
+);
+
+INSERT INTO guild_configs (guild_id, config) VALUES (9, '{}');
+INSERT INTO pin_history (message_id, guild_id, channel_id, author_id, excerpt, votes, pinned_at)
+VALUES (1, 9, 2, 3, 'hello', 4, 1700000000);
@@ -0,0 +1,79 @@
+use serenity::all::{Context, CreateAttachment, EmojiId, ReactionType};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+const SLASH_NAME: &str = "pin_slash";
+
+/// Images uploaded as application emojis, keyed by emoji name.
+const ASSETS: [(&str, &[u8]); 11] = [
+    ("pin_1", include_bytes!("../assets/emojis/pin_1.png")),
+    ("pin_2", include_bytes!("../assets/emojis/pin_2.png")),
+    ("pin_3", include_bytes!("../assets/emojis/pin_3.png")),
+    ("pin_4", include_bytes!("../assets/emojis/pin_4.png")),
+    ("pin_5", include_bytes!("../assets/emojis/pin_5.png")),
+    ("pin_6", include_bytes!("../assets/emojis/pin_6.png")),
+    ("pin_7", include_bytes!("../assets/emojis/pin_7.png")),
+    ("pin_8", include_bytes!("../assets/emojis/pin_8.png")),
+    ("pin_9", include_bytes!("../assets/emojis/pin_9.png")),
+    ("pin_10", include_bytes!("../assets/emojis/pin_10.png")),
+    (SLASH_NAME, include_bytes!("../assets/emojis/pin_slash.png")),
+];
+
+/// Application emojis owned by the bot. Any that couldn't be listed or
+/// created are simply absent, and callers fall back to Unicode.
+#[derive(Debug, Default)]
+pub struct AppEmojis {
+    ids: HashMap<&'static str, EmojiId>,
+}
+
+impl AppEmojis {
+    pub fn number(&self, num: u32) -> Option<ReactionType> {
+        if !(1..=10).contains(&num) {
+            return None;
+        }
+        self.reaction(ASSETS[num as usize - 1].0)
+    }
+
+    pub fn slash(&self) -> Option<ReactionType> {
+        self.reaction(SLASH_NAME)
+    }
+
+    fn reaction(&self, name: &str) -> Option<ReactionType> {
+        self.ids.get(name).map(|&id| ReactionType::Custom {
+            animated: false,
+            id,
+            name: Some(name.to_string()),
+        })
+    }
+}
+
+/// Lists the application's emojis and uploads whichever bundled ones are
+/// missing. Failures are logged and leave that emoji unset.
+pub async fn sync(ctx: &Context) -> AppEmojis {
+    let existing = match ctx.get_application_emojis().await {
+        Ok(emojis) => emojis,
+        Err(e) => {
+            warn!(error = %e, "Failed to list application emojis, using Unicode");
+            return AppEmojis::default();
+        }
+    };
+
+    let mut ids = HashMap::new();
+    for (name, image) in ASSETS {
+        if let Some(emoji) = existing.iter().find(|emoji| emoji.name == name) {
+            ids.insert(name, emoji.id);
+            continue;
+        }
+
+        let image = CreateAttachment::bytes(image, format!("{}.png", name)).to_base64();
+        match ctx.create_application_emoji(name, &image).await {
+            Ok(emoji) => {
+                info!(name, "Uploaded application emoji");
+                ids.insert(name, emoji.id);
+            }
+            Err(e) => warn!(name, error = %e, "Failed to upload application emoji"),
+        }
+    }
+
+    AppEmojis { ids }
+}
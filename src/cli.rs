@@ -0,0 +1,174 @@
+//! Command-line interface. Every flag is optional and falls back to the
+//! environment, so running the binary without arguments behaves as before.
+
+use anyhow::{bail, Context as _, Result};
+use clap::{Args, Parser, Subcommand};
+use discord_pin_bot::{
+    commands,
+    config::{Config, MAX_CONFIRM_CAP},
+    message_content_granted,
+    replay::Replay,
+};
+use serenity::all::{ApplicationFlags, GuildId, GuildPagination, Http};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// Page size for listing the bot's guilds, Discord's maximum.
+const GUILD_PAGE_SIZE: u64 = 200;
+
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Read the bot token from this file instead of TOKEN.
+    #[arg(long, global = true, env = "TOKEN_FILE")]
+    token_file: Option<PathBuf>,
+
+    /// TOML config file, watched for changes while the bot runs.
+    #[arg(long, global = true, env = "CONFIG_PATH")]
+    pub config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Connect to the gateway and run the bot (the default).
+    Run(RunArgs),
+    /// Register or update the slash and context-menu commands, then exit.
+    RegisterCommands,
+    /// Check the token, list visible guilds and verify intents, then exit.
+    Validate,
+    /// Feed a file of recorded gateway events to the bot against a mock
+    /// Discord and print what it does, then exit. Needs no token.
+    Replay {
+        /// JSON Lines file, one event per line.
+        file: PathBuf,
+    },
+}
+
+#[derive(Debug, Default, Args)]
+pub struct RunArgs {
+    /// Votes needed to pin, overriding CONFIRM_CAP.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(0..=MAX_CONFIRM_CAP as i64))]
+    confirm_cap: Option<u32>,
+    /// SQLite database file, overriding DATABASE_PATH.
+    #[arg(long)]
+    database_path: Option<String>,
+}
+
+impl RunArgs {
+    /// Applies the flags on top of a loaded config, taking precedence over
+    /// the environment and the config file.
+    pub fn apply(&self, config: &mut Config) {
+        if let Some(confirm_cap) = self.confirm_cap {
+            config.confirm_cap = confirm_cap;
+        }
+        if let Some(database_path) = &self.database_path {
+            config.database_path = database_path.clone();
+        }
+    }
+}
+
+impl Cli {
+    /// The token from `--token-file` when given, otherwise `TOKEN`.
+    pub fn token(&self) -> Result<String> {
+        let token = match &self.token_file {
+            Some(path) => fs::read_to_string(path)
+                .with_context(|| format!("Failed to read token file {}", path.display()))?,
+            None => env::var("TOKEN").context("TOKEN environment variable not set")?,
+        };
+        let token = token.trim();
+        if token.is_empty() {
+            bail!("The bot token is empty");
+        }
+        Ok(token.to_string())
+    }
+}
+
+/// An HTTP client that knows the application id, as command registration
+/// requires.
+pub async fn application_http(token: &str) -> Result<(Http, ApplicationFlags)> {
+    let http = Http::new(token);
+    let application = http
+        .get_current_application_info()
+        .await
+        .context("Discord rejected the token")?;
+    http.set_application_id(application.id);
+    Ok((http, application.flags.unwrap_or_default()))
+}
+
+async fn all_guilds(http: &Http) -> Result<Vec<(GuildId, String)>> {
+    let mut guilds = Vec::new();
+    let mut after = None;
+    loop {
+        let page = http
+            .get_guilds(after.map(GuildPagination::After), Some(GUILD_PAGE_SIZE))
+            .await
+            .context("Failed to list guilds")?;
+        let full_page = page.len() as u64 == GUILD_PAGE_SIZE;
+        after = page.last().map(|guild| guild.id);
+        guilds.extend(page.into_iter().map(|guild| (guild.id, guild.name)));
+        if !full_page {
+            return Ok(guilds);
+        }
+    }
+}
+
+pub async fn register_commands(token: &str, config: &Config) -> Result<()> {
+    let (http, _) = application_http(token).await?;
+    let guilds = if config.register_guild_commands {
+        all_guilds(&http)
+            .await?
+            .into_iter()
+            .map(|(guild_id, _)| guild_id)
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    if !commands::register(&http, &guilds, config.register_guild_commands).await {
+        bail!("Some commands could not be registered");
+    }
+    Ok(())
+}
+
+pub async fn validate(token: &str) -> Result<()> {
+    let (http, flags) = application_http(token).await?;
+    let user = http
+        .get_current_user()
+        .await
+        .context("Failed to fetch the bot user")?;
+    println!("Logged in as {} ({})", user.tag(), user.id);
+
+    let guilds = all_guilds(&http).await?;
+    println!("Visible guilds: {}", guilds.len());
+    for (guild_id, name) in &guilds {
+        println!("  {} ({})", name, guild_id);
+    }
+
+    // The other intents the bot uses aren't privileged
+    if message_content_granted(flags) {
+        println!("The Message Content intent is enabled");
+    } else {
+        println!(
+            "The Message Content intent is not enabled, so the bot will run interaction-only \
+             (/pin, the context menu and reaction votes). Turn it on in the Developer Portal \
+             under Bot → Privileged Gateway Intents to allow @mention requests"
+        );
+    }
+    Ok(())
+}
+
+pub async fn replay(config: Config, file: &Path) -> Result<()> {
+    let input = fs::read_to_string(file)
+        .with_context(|| format!("Failed to read replay file {}", file.display()))?;
+    let events = Replay::parse(&input)?;
+    let replay = Replay::new(config).await?;
+    for action in replay.run(events).await {
+        println!("{}", action);
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use super::{linked_message, member_permissions_in, respond_ephemeral};
+use crate::Handler;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    EditInteractionResponse,
+};
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("adopt-vote")
+        .description("Pick a vote back up that the bot lost track of, as after a restart")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "message",
+                "A link or id of the vote message: the bot's, or the request mentioning it",
+            )
+            .required(true),
+        )
+}
+
+/// Counting the reactions already on the vote can take a few requests, so
+/// the reply is deferred.
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let vote = match linked_message(ctx, command, guild_id).await {
+        Ok(vote) => vote,
+        Err(refusal) => return respond_ephemeral(ctx, command, refusal).await,
+    };
+    if !member_permissions_in(ctx, command, guild_id, vote.channel_id)
+        .is_some_and(|permissions| permissions.manage_messages())
+    {
+        return respond_ephemeral(
+            ctx,
+            command,
+            format!(
+                "You need Manage Messages in <#{}> to do that.",
+                vote.channel_id
+            ),
+        )
+        .await;
+    }
+
+    command.defer_ephemeral(&ctx.http).await?;
+    let reply = match handler
+        .adopt_vote(ctx, guild_id, &vote, command.user.id)
+        .await
+    {
+        Ok(reply) | Err(reply) => reply,
+    };
+    command
+        .edit_response(&ctx.http, EditInteractionResponse::new().content(reply))
+        .await
+        .map(drop)
+}
@@ -0,0 +1,88 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{
+    CommandInteraction, Context, CreateAttachment, CreateCommand, CreateInteractionResponse,
+    CreateInteractionResponseMessage, Timestamp,
+};
+use std::{env, fs, path::Path};
+use tracing::{error, info};
+
+/// Discord's attachment limit for servers without boosts.
+const UPLOAD_LIMIT: u64 = 10 * 1024 * 1024;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("backup")
+        .description("Take a snapshot of the bot's database")
+        .dm_permission(false)
+}
+
+/// The database holds every server's data, so only superusers may take
+/// it. A snapshot small enough is uploaded; a larger one is kept in
+/// `BACKUP_DIR` when that's set.
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let config = data.config();
+    let name = format!("pin-bot-{}.db", Timestamp::now().unix_timestamp());
+    let snapshot = env::temp_dir().join(&name);
+    if let Err(e) = data.store.backup_to(&snapshot) {
+        error!(error = %e, "Failed to snapshot the database");
+        let _ = fs::remove_file(&snapshot);
+        return respond_ephemeral(ctx, command, "Failed to take the backup, see the logs.").await;
+    }
+    let size = fs::metadata(&snapshot)
+        .map(|meta| meta.len())
+        .unwrap_or(u64::MAX);
+    info!(by = %command.user.id, size, "Took a database backup");
+
+    if size <= UPLOAD_LIMIT {
+        let result = match CreateAttachment::path(&snapshot).await {
+            Ok(file) => {
+                command
+                    .create_response(
+                        &ctx.http,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content("Here's the backup.")
+                                .add_file(file)
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to read the database backup");
+                respond_ephemeral(ctx, command, "Failed to take the backup, see the logs.").await
+            }
+        };
+        let _ = fs::remove_file(&snapshot);
+        return result;
+    }
+
+    let reply = match &config.backup_dir {
+        Some(dir) => match keep(&snapshot, &Path::new(dir).join(&name)) {
+            Ok(()) => format!(
+                "The backup is too large to upload, so it was saved as `{}` in `{}`.",
+                name, dir
+            ),
+            Err(e) => {
+                error!(dir = %dir, error = %e, "Failed to save the database backup");
+                "The backup is too large to upload and couldn't be saved, see the logs.".to_string()
+            }
+        },
+        None => "The backup is too large to upload. Set `BACKUP_DIR` to have it saved instead."
+            .to_string(),
+    };
+    let _ = fs::remove_file(&snapshot);
+    respond_ephemeral(ctx, command, reply).await
+}
+
+/// Copies rather than renames, since the temp dir may be on another disk.
+fn keep(snapshot: &Path, destination: &Path) -> std::io::Result<()> {
+    if let Some(dir) = destination.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::copy(snapshot, destination).map(|_| ())
+}
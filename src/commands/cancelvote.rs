@@ -0,0 +1,113 @@
+use super::{member_permissions_in, respond_ephemeral};
+use crate::{links, Handler, CROSS_GUILD_REFUSAL};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedValue,
+};
+use tracing::info;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("cancelvote")
+        .description("Cancel an open pin vote")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "vote",
+                "A link or id of the vote, or of the message being voted on",
+            )
+            .required(true),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let input = command
+        .data
+        .options()
+        .into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::String(value) if option.name == "vote" => Some(value),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let Some(link) = links::parse_message_ref(input) else {
+        return respond_ephemeral(ctx, command, "That doesn't look like a message link or id.")
+            .await;
+    };
+    if link.channel_id.is_some() && link.guild_id != Some(guild_id) {
+        return respond_ephemeral(ctx, command, CROSS_GUILD_REFUSAL).await;
+    }
+
+    // The vote message itself wins over a vote whose target has that id
+    let data = &handler.data;
+    let session = data
+        .session_for_reaction(link.message_id)
+        .and_then(|(session_id, _)| {
+            let session = data.voting_sessions.get(&session_id)?;
+            (session.guild_id == Some(guild_id)).then(|| {
+                (
+                    session_id,
+                    session.vote_channel_id,
+                    session
+                        .target_message_id
+                        .link(session.target_channel_id, Some(guild_id)),
+                    session.progress(),
+                    session.is_pinning(),
+                )
+            })
+        });
+    let Some((session_id, vote_channel_id, target_link, progress, finalizing)) = session else {
+        return respond_ephemeral(ctx, command, "There's no open vote on that message.").await;
+    };
+    if finalizing {
+        return respond_ephemeral(ctx, command, "That vote passed and is being pinned.").await;
+    }
+
+    if !member_permissions_in(ctx, command, guild_id, vote_channel_id)
+        .is_some_and(|permissions| permissions.manage_messages())
+    {
+        return respond_ephemeral(
+            ctx,
+            command,
+            format!(
+                "You need Manage Messages in <#{}> to do that.",
+                vote_channel_id
+            ),
+        )
+        .await;
+    }
+
+    let moderator = command.user.id;
+    let notice = format!("Vote cancelled by a moderator, <@{}>.", moderator);
+    if !data
+        .end_cancelled_session(&ctx.http, session_id, vote_channel_id, notice)
+        .await
+    {
+        return respond_ephemeral(ctx, command, "That vote has already ended.").await;
+    }
+    info!(session_id = %session_id, user_id = %moderator, "Vote cancelled by a moderator");
+
+    data.audit(
+        &ctx.http,
+        guild_id,
+        format!(
+            "🛑 <@{}> cancelled the vote on {} in <#{}> at {}.",
+            moderator, target_link, vote_channel_id, progress
+        ),
+    )
+    .await;
+    respond_ephemeral(
+        ctx,
+        command,
+        format!("Cancelled the vote on {}.", target_link),
+    )
+    .await
+}
@@ -0,0 +1,1335 @@
+use super::respond_ephemeral;
+use crate::{
+    config::MAX_CONFIRM_CAP,
+    content_filter::MAX_MIN_LENGTH,
+    digest::{DigestSchedule, WEEKDAYS},
+    emoji,
+    guild_config::{
+        parse_clock, parse_utc_offset, ArchiveCategory, BrigadeGuard, GuildConfig, QuietHours,
+        QuorumRule, VoteMode, MAX_ARCHIVE_CATEGORIES, MAX_BOOSTER_WEIGHT, MAX_BRIGADE_VOTES,
+        MAX_BRIGADE_WINDOW_SECS, MAX_MIN_VOTE_MINUTES, MAX_REPIN_WINDOW_DAYS,
+        MAX_SUPER_REACTION_WEIGHT,
+    },
+    retention::PinRetention,
+    templates::TemplateKey,
+    BotData, REJECT_EMOJI,
+};
+use serenity::all::{
+    ChannelId, CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedOption, ResolvedValue,
+};
+use std::{collections::HashMap, fmt::Write as _};
+
+pub fn definition() -> CreateCommand {
+    let channel_option = || {
+        CreateCommandOption::new(
+            CommandOptionType::Channel,
+            "channel",
+            "The channel to update",
+        )
+        .required(true)
+    };
+
+    CreateCommand::new("config")
+        .description("Configure the pin bot for this server")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "allow-channel",
+                "Allow pin votes in a channel (once any channel is allowed, others are not)",
+            )
+            .add_sub_option(channel_option()),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "deny-channel",
+                "Disable pin votes in a channel",
+            )
+            .add_sub_option(channel_option()),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reset-channel",
+                "Remove a channel from the allow and deny lists",
+            )
+            .add_sub_option(channel_option()),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "voter-role",
+                "Only count votes from members with this role (omit to let everyone vote)",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Role,
+                "role",
+                "The role required to vote",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "vote-mode",
+                "Choose how members vote on new pin requests",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "mode", "The voting UI")
+                    .required(true)
+                    .add_string_choice("Reactions", "reactions")
+                    .add_string_choice("Buttons", "buttons")
+                    .add_string_choice("Poll", "poll"),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "self-pins",
+                "Choose whether members may start votes on their own messages",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "allowed",
+                    "Allow self-pin votes (moderators are always exempt)",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "bot-pins",
+                "Choose whether messages from bots and webhooks can be pinned",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "allowed",
+                    "Allow pinning bot and webhook messages",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reminders",
+                "Choose whether stalled votes get a reminder halfway to expiring",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "enabled",
+                    "Post reminders on stalled votes",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "timed-out-votes",
+                "Choose whether votes from members serving a timeout count",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "enabled",
+                    "Count votes from timed-out members",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "archive-channel",
+                "Mirror pinned messages to a channel (omit to stop mirroring)",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "The default archive channel",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "audit-channel",
+                "Log moderator actions on votes to a channel (omit to stop logging)",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "The audit channel",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "brigade-guard",
+                "Hold votes for review when they fill up too fast (omit both to turn off)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "votes",
+                    "Most votes allowed within the window",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_BRIGADE_VOTES as u64),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "seconds",
+                    "How long the window is",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_BRIGADE_WINDOW_SECS as u64),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "review-role",
+                "Ping a role when a vote is held for review (omit to stop pinging)",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Role,
+                "role",
+                "The role to ping",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "archive-category",
+                "Offer a category reaction that routes pins to its own archive",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "emoji",
+                    "The category reaction",
+                )
+                .required(true),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "Where pins in this category go (omit to remove the category)",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "booster-weight",
+                "Count server boosters' votes more than once (omit to count them once)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "weight",
+                    "How many votes a booster's approval counts for",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_BOOSTER_WEIGHT as u64),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "super-reaction-weight",
+                "Count a super ✅ more than a normal one (omit to count it once)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "weight",
+                    "How many times a super reaction counts the voter's approval",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_SUPER_REACTION_WEIGHT as u64),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "channel-threshold",
+                "Set the votes needed in one channel (omit the votes to use the server's)",
+            )
+            .add_sub_option(channel_option())
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "votes",
+                    "Votes needed to pin there (0 pins right away)",
+                )
+                .min_int_value(0)
+                .max_int_value(MAX_CONFIRM_CAP as u64),
+            ),
+        )
+        .add_option({
+            let mut weekday = CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "weekday",
+                "Day to post on, in UTC (Sunday by default)",
+            );
+            for (day, name) in WEEKDAYS.iter().enumerate() {
+                weekday = weekday.add_int_choice(*name, day as i32);
+            }
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "digest",
+                "Post a weekly roundup of pins to a channel (omit the channel to stop)",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "Where the digest is posted",
+            ))
+            .add_sub_option(weekday)
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "hour",
+                    "Hour to post at, in UTC (18 by default)",
+                )
+                .min_int_value(0)
+                .max_int_value(23),
+            )
+        })
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "content-filter",
+                "Refuse votes on messages that break these rules (moderators are exempt)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "min-length",
+                    "Fewest characters of text a message needs (0 turns it off)",
+                )
+                .min_int_value(0)
+                .max_int_value(MAX_MIN_LENGTH as u64),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "sticker-only",
+                "Refuse messages that are only a sticker",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "attachment-only",
+                "Refuse messages that are only attachments, with no text",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "links",
+                "Refuse messages containing links",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "pin-index",
+                "Keep a pinned list of the channel's voted pins, updated with every pin and unpin",
+            )
+            .add_sub_option(channel_option())
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "enabled",
+                    "Whether the channel keeps an index",
+                )
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "repin-window",
+                "Ask for confirmation before a vote on a recently pinned message (omit to never ask)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "days",
+                    "How far back a pin counts as recent",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_REPIN_WINDOW_DAYS as u64),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "min-vote-time",
+                "Keep votes open this long before pinning, even once they pass (omit to pin right away)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "minutes",
+                    "Minutes a vote stays open at least",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_MIN_VOTE_MINUTES as u64),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "quiet-hours",
+                "Hold passed votes during these hours and pin them after (omit the start to turn off)",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "start",
+                "When quiet hours start, like 23:00",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "end",
+                "When they end, like 07:00",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "utc-offset",
+                "The times' UTC offset, like +02:00 (UTC by default)",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "quorum",
+                "Pass votes on turnout and a lead over rejections instead of a fixed count (omit to turn off)",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "quorum",
+                    "Votes either way needed before a vote can pass",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_CONFIRM_CAP as u64),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "margin",
+                    "How far approvals must lead rejections (default 1)",
+                )
+                .min_int_value(1)
+                .max_int_value(MAX_CONFIRM_CAP as u64),
+            ),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show the current configuration",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return respond_ephemeral(ctx, command, "Unknown subcommand.").await;
+    };
+
+    if *name == "show" {
+        let reply = describe(&data.guild_config(guild_id));
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "vote-mode" {
+        let mode = match sub_options.first().map(|option| &option.value) {
+            Some(ResolvedValue::String("buttons")) => VoteMode::Buttons,
+            Some(ResolvedValue::String("poll")) => VoteMode::Poll,
+            _ => VoteMode::Reactions,
+        };
+        let reply = match data.update_guild_config(guild_id, |config| config.vote_mode = mode) {
+            Ok(_) => match mode {
+                VoteMode::Reactions => "New votes will use ✅ reactions.".to_string(),
+                VoteMode::Buttons => "New votes will use buttons.".to_string(),
+                VoteMode::Poll => "New votes will use a poll. Polls run for whole hours, so \
+                                   votes last at least an hour."
+                    .to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "self-pins" {
+        let allowed = sub_options
+            .iter()
+            .any(|option| matches!(option.value, ResolvedValue::Boolean(true)));
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.allow_self_pins = allowed)
+        {
+            Ok(_) if allowed => "Members can now start votes on their own messages.".to_string(),
+            Ok(_) => "Members can no longer start votes on their own messages.".to_string(),
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "bot-pins" {
+        let allowed = sub_options
+            .iter()
+            .any(|option| matches!(option.value, ResolvedValue::Boolean(true)));
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.allow_bot_pins = allowed)
+        {
+            Ok(_) if allowed => "Bot and webhook messages can now be pinned.".to_string(),
+            Ok(_) => "Bot and webhook messages can no longer be pinned.".to_string(),
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "reminders" {
+        let enabled = sub_options
+            .iter()
+            .any(|option| matches!(option.value, ResolvedValue::Boolean(true)));
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.skip_reminders = !enabled)
+        {
+            Ok(_) if enabled => "Stalled votes will get a reminder.".to_string(),
+            Ok(_) => "Stalled votes will no longer get reminders.".to_string(),
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "timed-out-votes" {
+        let enabled = sub_options
+            .iter()
+            .any(|option| matches!(option.value, ResolvedValue::Boolean(true)));
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.count_timed_out_votes = enabled)
+        {
+            Ok(_) if enabled => "Votes from timed-out members will count.".to_string(),
+            Ok(_) => "Votes from timed-out members will be ignored.".to_string(),
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "archive-channel" {
+        let channel_id = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) => Some(channel.id),
+            _ => None,
+        });
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.archive_channel = channel_id)
+        {
+            Ok(_) => match channel_id {
+                Some(channel) => format!("Pinned messages will be mirrored to <#{}>.", channel),
+                None => "Pinned messages will no longer be mirrored.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "audit-channel" {
+        let channel_id = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) => Some(channel.id),
+            _ => None,
+        });
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.audit_channel = channel_id)
+        {
+            Ok(_) => match channel_id {
+                Some(channel) => format!("Moderator actions will be logged in <#{}>.", channel),
+                None => "Moderator actions will no longer be logged.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "brigade-guard" {
+        let value = |wanted: &str| {
+            sub_options.iter().find_map(|option| match option.value {
+                ResolvedValue::Integer(value) if option.name == wanted => u32::try_from(value).ok(),
+                _ => None,
+            })
+        };
+        let guard = match (value("votes"), value("seconds")) {
+            (Some(votes), Some(window_secs)) => Some(BrigadeGuard { votes, window_secs }),
+            (None, None) => None,
+            _ => {
+                return respond_ephemeral(ctx, command, "Give both the votes and the seconds.")
+                    .await;
+            }
+        };
+        let reply = match data.update_guild_config(guild_id, |config| config.brigade_guard = guard)
+        {
+            Ok(config) => match guard {
+                Some(guard) => format!(
+                    "New votes getting {} are held for review{}.",
+                    guard.describe(),
+                    match config.audit_channel {
+                        Some(channel) => format!(" in <#{}>", channel),
+                        None => " on the vote".to_string(),
+                    }
+                ),
+                None => "Votes are no longer held for review.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "review-role" {
+        let role_id = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Role(role) => Some(role.id),
+            _ => None,
+        });
+        let reply = match data.update_guild_config(guild_id, |config| config.review_role = role_id)
+        {
+            Ok(_) => match role_id {
+                Some(role) => format!(
+                    "<@&{}> will be pinged when a vote is held for review.",
+                    role
+                ),
+                None => "Nobody will be pinged when a vote is held for review.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "archive-category" {
+        let input = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::String(input) => Some(input),
+            _ => None,
+        });
+        let Some(category_emoji) = input.and_then(emoji::parse_reaction) else {
+            return respond_ephemeral(ctx, command, "That doesn't look like an emoji.").await;
+        };
+        if emoji::checkmark(&category_emoji).is_some() || data.is_cancel_emoji(&category_emoji) {
+            return respond_ephemeral(ctx, command, "That emoji is already used for voting.").await;
+        }
+        let channel_id = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) => Some(channel.id),
+            _ => None,
+        });
+
+        let current = data.guild_config(guild_id);
+        if channel_id.is_some()
+            && current.archive_category(&category_emoji).is_none()
+            && current.archive_categories.len() >= MAX_ARCHIVE_CATEGORIES
+        {
+            return respond_ephemeral(
+                ctx,
+                command,
+                format!(
+                    "You can have at most {} archive categories; remove one first.",
+                    MAX_ARCHIVE_CATEGORIES
+                ),
+            )
+            .await;
+        }
+
+        let update = data.update_guild_config(guild_id, |config| {
+            config
+                .archive_categories
+                .retain(|category| !emoji::same_emoji(&category.emoji, &category_emoji));
+            if let Some(channel_id) = channel_id {
+                config.archive_categories.push(ArchiveCategory {
+                    emoji: category_emoji.clone(),
+                    channel_id,
+                });
+            }
+        });
+        let reply = match update {
+            Ok(_) => match channel_id {
+                Some(channel) => format!(
+                    "Pins voted with {} will be mirrored to <#{}>.",
+                    category_emoji, channel
+                ),
+                None => format!("{} is no longer an archive category.", category_emoji),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "booster-weight" {
+        let weight = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(weight) => u32::try_from(weight).ok(),
+            _ => None,
+        });
+        let weight = weight.filter(|weight| *weight > 1);
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.booster_weight = weight)
+        {
+            Ok(_) => match weight {
+                Some(weight) => format!("Votes from server boosters now count {} times.", weight),
+                None => "Votes from server boosters now count once.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "super-reaction-weight" {
+        let weight = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(weight) => u32::try_from(weight).ok(),
+            _ => None,
+        });
+        let weight = weight.filter(|weight| *weight > 1);
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.super_reaction_weight = weight)
+        {
+            Ok(_) => match weight {
+                Some(weight) => format!("Super reactions now count {} times.", weight),
+                None => "Super reactions now count like normal ones.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "channel-threshold" {
+        let Some(channel_id) = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) => Some(channel.id),
+            _ => None,
+        }) else {
+            return respond_ephemeral(ctx, command, "Please pick a channel.").await;
+        };
+        let votes = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(votes) => u32::try_from(votes).ok(),
+            _ => None,
+        });
+        let reply = match data.update_guild_config(guild_id, |config| match votes {
+            Some(votes) => {
+                config.channel_thresholds.insert(channel_id, votes);
+            }
+            None => {
+                config.channel_thresholds.remove(&channel_id);
+            }
+        }) {
+            Ok(_) => match votes {
+                Some(votes) => format!(
+                    "New votes in <#{}> will need {} approvals.",
+                    channel_id, votes
+                ),
+                None => format!("<#{}> now uses the server's threshold.", channel_id),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "digest" {
+        let channel_id = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) => Some(channel.id),
+            _ => None,
+        });
+        let int_option = |wanted: &str| {
+            sub_options.iter().find_map(|option| match option.value {
+                ResolvedValue::Integer(value) if option.name == wanted => u8::try_from(value).ok(),
+                _ => None,
+            })
+        };
+        let digest = channel_id.map(|channel_id| DigestSchedule {
+            channel_id,
+            weekday: int_option("weekday").unwrap_or(0),
+            hour: int_option("hour").unwrap_or(18),
+        });
+        let reply = match data.set_digest(guild_id, digest) {
+            Ok(_) => match digest {
+                Some(digest) => format!("The weekly digest will go to {}.", digest.describe()),
+                None => "The weekly digest is off.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "content-filter" {
+        let flag = |wanted: &str| {
+            sub_options.iter().find_map(|option| match option.value {
+                ResolvedValue::Boolean(value) if option.name == wanted => Some(value),
+                _ => None,
+            })
+        };
+        let min_length = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(length) => u32::try_from(length).ok(),
+            _ => None,
+        });
+        let (sticker_only, attachment_only, links) =
+            (flag("sticker-only"), flag("attachment-only"), flag("links"));
+        let reply = match data.update_guild_config(guild_id, |config| {
+            let filter = &mut config.content_filter;
+            if let Some(min_length) = min_length {
+                filter.min_length = min_length;
+            }
+            if let Some(reject) = sticker_only {
+                filter.reject_sticker_only = reject;
+            }
+            if let Some(reject) = attachment_only {
+                filter.reject_attachment_only = reject;
+            }
+            if let Some(reject) = links {
+                filter.reject_links = reject;
+            }
+        }) {
+            Ok(config) => format!("Content filter: {}.", config.content_filter.describe()),
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "repin-window" {
+        let days = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(days) => u32::try_from(days).ok(),
+            _ => None,
+        });
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.repin_window_days = days)
+        {
+            Ok(_) => match days {
+                Some(days) => format!(
+                    "Votes on messages pinned in the last {} days will need confirmation.",
+                    days
+                ),
+                None => {
+                    "Votes on previously pinned messages no longer need confirmation.".to_string()
+                }
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "min-vote-time" {
+        let minutes = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Integer(minutes) => u32::try_from(minutes).ok(),
+            _ => None,
+        });
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.min_vote_minutes = minutes)
+        {
+            Ok(_) => match minutes {
+                Some(minutes) => format!(
+                    "New votes stay open at least {} minutes before pinning.",
+                    minutes
+                ),
+                None => "New votes pin as soon as they pass.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "quiet-hours" {
+        let text = |wanted: &str| {
+            sub_options.iter().find_map(|option| match option.value {
+                ResolvedValue::String(value) if option.name == wanted => Some(value),
+                _ => None,
+            })
+        };
+        let quiet_hours = match text("start") {
+            None => None,
+            Some(start) => {
+                let Some(end) = text("end") else {
+                    return respond_ephemeral(ctx, command, "Give the time quiet hours end too.")
+                        .await;
+                };
+                let (Some(start), Some(end)) = (parse_clock(start), parse_clock(end)) else {
+                    return respond_ephemeral(
+                        ctx,
+                        command,
+                        "Give the times as hours and minutes, like 23:00.",
+                    )
+                    .await;
+                };
+                if start == end {
+                    return respond_ephemeral(
+                        ctx,
+                        command,
+                        "Quiet hours need to end at a different time than they start.",
+                    )
+                    .await;
+                }
+                let Some(utc_offset) = parse_utc_offset(text("utc-offset").unwrap_or_default())
+                else {
+                    return respond_ephemeral(
+                        ctx,
+                        command,
+                        "Give the UTC offset like +02:00 or -5, between -12:00 and +14:00.",
+                    )
+                    .await;
+                };
+                Some(QuietHours {
+                    start,
+                    end,
+                    utc_offset,
+                })
+            }
+        };
+        let reply = match data
+            .update_guild_config(guild_id, |config| config.quiet_hours = quiet_hours)
+        {
+            Ok(_) => match quiet_hours {
+                Some(quiet_hours) => format!(
+                    "Votes passing during {} will be pinned once quiet hours end.",
+                    quiet_hours.describe()
+                ),
+                None => "Quiet hours are off; passed votes pin right away.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "quorum" {
+        let value = |wanted: &str| {
+            sub_options.iter().find_map(|option| match option.value {
+                ResolvedValue::Integer(value) if option.name == wanted => u32::try_from(value).ok(),
+                _ => None,
+            })
+        };
+        let rule = match (value("quorum"), value("margin")) {
+            (Some(quorum), margin) => Some(QuorumRule {
+                quorum,
+                margin: margin.unwrap_or(1),
+            }),
+            (None, Some(_)) => {
+                return respond_ephemeral(ctx, command, "A margin needs a quorum to go with it.")
+                    .await;
+            }
+            (None, None) => None,
+        };
+        let reply = match data.update_guild_config(guild_id, |config| config.quorum = rule) {
+            Ok(_) => match rule {
+                Some(rule) => format!(
+                    "New votes pass with {}. Members vote against with {} or the Reject button.",
+                    rule.describe(),
+                    REJECT_EMOJI
+                ),
+                None => "New votes pass on the approval count again.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "pin-index" {
+        let Some(channel_id) = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) => Some(channel.id),
+            _ => None,
+        }) else {
+            return respond_ephemeral(ctx, command, "Please pick a channel.").await;
+        };
+        let enabled = sub_options
+            .iter()
+            .any(|option| matches!(option.value, ResolvedValue::Boolean(true)));
+        let reply = match data
+            .set_pin_index(&ctx.http, guild_id, channel_id, enabled)
+            .await
+        {
+            Ok(_) if enabled => format!(
+                "<#{}> will keep a pinned index, posted with the next pin there.",
+                channel_id
+            ),
+            Ok(_) => format!("<#{}> no longer keeps a pin index.", channel_id),
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    if *name == "voter-role" {
+        let role_id = sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::Role(role) => Some(role.id),
+            _ => None,
+        });
+        let reply = match data.update_guild_config(guild_id, |config| config.voter_role = role_id) {
+            Ok(_) => match role_id {
+                Some(role) => format!("Only members with <@&{}> can vote now.", role),
+                None => "Everyone can vote now.".to_string(),
+            },
+            Err(e) => {
+                tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+                "Failed to save the configuration, please try again.".to_string()
+            }
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    let Some(channel_id) = sub_options.iter().find_map(|option| match option.value {
+        ResolvedValue::Channel(channel) => Some(channel.id),
+        _ => None,
+    }) else {
+        return respond_ephemeral(ctx, command, "Please pick a channel.").await;
+    };
+
+    let update = data.update_guild_config(guild_id, |config| match *name {
+        "allow-channel" => {
+            config.denied_channels.remove(&channel_id);
+            config.allowed_channels.insert(channel_id);
+        }
+        "deny-channel" => {
+            config.allowed_channels.remove(&channel_id);
+            config.denied_channels.insert(channel_id);
+        }
+        _ => {
+            config.allowed_channels.remove(&channel_id);
+            config.denied_channels.remove(&channel_id);
+        }
+    });
+
+    let reply = match update {
+        Ok(_) => match *name {
+            "allow-channel" => format!("Pin votes are now allowed in <#{}>.", channel_id),
+            "deny-channel" => format!("Pin votes are now disabled in <#{}>.", channel_id),
+            _ => format!("<#{}> no longer has a channel rule.", channel_id),
+        },
+        Err(e) => {
+            tracing::error!(guild_id = %guild_id, error = %e, "Failed to save guild config");
+            "Failed to save the configuration, please try again.".to_string()
+        }
+    };
+
+    respond_ephemeral(ctx, command, reply).await
+}
+
+/// Channel overrides as mentions with their values, like vote counts, for
+/// `show` and `/status`.
+pub(crate) fn channel_thresholds(thresholds: &HashMap<ChannelId, u32>) -> String {
+    let mut entries: Vec<_> = thresholds.iter().collect();
+    entries.sort();
+    let mentions: Vec<String> = entries
+        .into_iter()
+        .map(|(id, votes)| format!("<#{}> ({})", id, votes))
+        .collect();
+    if mentions.is_empty() {
+        "none".to_string()
+    } else {
+        mentions.join(", ")
+    }
+}
+
+fn pin_retention(policies: &HashMap<ChannelId, PinRetention>) -> String {
+    let mut entries: Vec<_> = policies.iter().collect();
+    entries.sort_by_key(|(id, _)| **id);
+    let mentions: Vec<String> = entries
+        .into_iter()
+        .map(|(id, retention)| format!("<#{}> ({})", id, retention.describe()))
+        .collect();
+    if mentions.is_empty() {
+        "off".to_string()
+    } else {
+        mentions.join(", ")
+    }
+}
+
+/// The guild's settings as `/config show` lists them, one per line.
+pub(super) fn describe(config: &GuildConfig) -> String {
+    let mut reply = String::new();
+    let _ = writeln!(
+        reply,
+        "**Allowed channels:** {}",
+        channel_list(&config.allowed_channels)
+    );
+    let _ = writeln!(
+        reply,
+        "**Denied channels:** {}",
+        channel_list(&config.denied_channels)
+    );
+    let _ = writeln!(
+        reply,
+        "**Voter role:** {}",
+        config
+            .voter_role
+            .map_or_else(|| "everyone".to_string(), |role| format!("<@&{}>", role))
+    );
+    let _ = writeln!(reply, "**Vote mode:** {:?}", config.vote_mode);
+    let _ = writeln!(
+        reply,
+        "**Self-pin votes:** {}",
+        if config.allow_self_pins {
+            "allowed"
+        } else {
+            "blocked"
+        }
+    );
+    let _ = writeln!(
+        reply,
+        "**Bot and webhook pins:** {}",
+        if config.allow_bot_pins {
+            "allowed"
+        } else {
+            "blocked"
+        }
+    );
+    let _ = writeln!(
+        reply,
+        "**Reminders:** {}",
+        if config.skip_reminders { "off" } else { "on" }
+    );
+    let _ = writeln!(
+        reply,
+        "**Timed-out votes:** {}",
+        if config.count_timed_out_votes {
+            "counted"
+        } else {
+            "ignored"
+        }
+    );
+    let _ = writeln!(
+        reply,
+        "**Archive channel:** {}",
+        channel_list(&config.archive_channel)
+    );
+    let _ = writeln!(
+        reply,
+        "**Audit channel:** {}",
+        channel_list(&config.audit_channel)
+    );
+    let _ = writeln!(
+        reply,
+        "**Pin blocklist:** {} member(s)",
+        config.pin_blocklist.len()
+    );
+    let _ = writeln!(
+        reply,
+        "**Brigade guard:** {}",
+        config
+            .brigade_guard
+            .map_or_else(|| "off".to_string(), |guard| guard.describe())
+    );
+    let _ = writeln!(
+        reply,
+        "**Quiet hours:** {}",
+        config
+            .quiet_hours
+            .map_or_else(|| "off".to_string(), |quiet_hours| quiet_hours.describe())
+    );
+    let _ = writeln!(
+        reply,
+        "**Minimum vote time:** {}",
+        config.min_vote_minutes.map_or_else(
+            || "none".to_string(),
+            |minutes| format!("{} minutes", minutes)
+        )
+    );
+    let _ = writeln!(
+        reply,
+        "**Review role:** {}",
+        config
+            .review_role
+            .map_or_else(|| "none".to_string(), |role| format!("<@&{}>", role))
+    );
+    let categories: Vec<String> = config
+        .archive_categories
+        .iter()
+        .map(|category| format!("{} → <#{}>", category.emoji, category.channel_id))
+        .collect();
+    let _ = writeln!(
+        reply,
+        "**Archive categories:** {}",
+        if categories.is_empty() {
+            "none".to_string()
+        } else {
+            categories.join(", ")
+        }
+    );
+    let _ = writeln!(
+        reply,
+        "**Booster vote weight:** {}",
+        config.vote_weight(true)
+    );
+    let _ = writeln!(
+        reply,
+        "**Channel thresholds:** {}",
+        channel_thresholds(&config.channel_thresholds)
+    );
+    let _ = writeln!(
+        reply,
+        "**Daily pin quota:** {} (channels: {})",
+        config
+            .daily_pin_quota
+            .map_or_else(|| "unlimited".to_string(), |pins| pins.to_string()),
+        channel_thresholds(&config.channel_pin_quotas)
+    );
+    let _ = writeln!(
+        reply,
+        "**Weekly digest:** {}",
+        config
+            .digest
+            .map_or_else(|| "off".to_string(), |digest| digest.describe())
+    );
+    let _ = writeln!(
+        reply,
+        "**Content filter:** {}",
+        config.content_filter.describe()
+    );
+    let _ = writeln!(
+        reply,
+        "**Pin indexes:** {}",
+        channel_list(&config.index_channels)
+    );
+    let _ = writeln!(
+        reply,
+        "**Moderator approval:** {}",
+        channel_list(&config.approval_channels)
+    );
+    let _ = writeln!(
+        reply,
+        "**Pin retention:** {}",
+        pin_retention(&config.pin_retention)
+    );
+    let _ = writeln!(
+        reply,
+        "**Vote threads:** {}",
+        config
+            .vote_thread_name
+            .as_deref()
+            .map_or_else(|| "off".to_string(), |name| format!("`{}`", name))
+    );
+    let _ = writeln!(
+        reply,
+        "**Crosspost pins:** {}",
+        if config.crosspost_pins { "on" } else { "off" }
+    );
+    let _ = writeln!(
+        reply,
+        "**Pin rotation:** {}",
+        channel_list(&config.rotation_channels)
+    );
+    let _ = writeln!(
+        reply,
+        "**Weekly vote stats:** {}",
+        if config.weekly_vote_stats {
+            "on"
+        } else {
+            "off"
+        }
+    );
+    let custom: Vec<&str> = TemplateKey::ALL
+        .into_iter()
+        .filter(|key| config.templates.contains_key(key))
+        .map(TemplateKey::name)
+        .collect();
+    let _ = writeln!(
+        reply,
+        "**Message templates:** {}",
+        if custom.is_empty() {
+            "defaults".to_string()
+        } else {
+            format!("custom {}", custom.join(", "))
+        }
+    );
+    let _ = writeln!(
+        reply,
+        "**Repin window:** {}",
+        config
+            .repin_window_days
+            .map_or_else(|| "off".to_string(), |days| format!("{} days", days))
+    );
+    let _ = writeln!(
+        reply,
+        "**Quorum:** {}",
+        config
+            .quorum
+            .map_or_else(|| "off".to_string(), |rule| rule.describe())
+    );
+    reply
+}
+
+fn channel_list<'a>(channels: impl IntoIterator<Item = &'a ChannelId>) -> String {
+    let mentions: Vec<String> = channels
+        .into_iter()
+        .map(|id| format!("<#{}>", id))
+        .collect();
+    if mentions.is_empty() {
+        "none".to_string()
+    } else {
+        mentions.join(", ")
+    }
+}
@@ -0,0 +1,97 @@
+use super::respond_ephemeral;
+use crate::{ConfigReload, Handler};
+use serenity::all::{CommandInteraction, Context, CreateCommand};
+use std::fmt::Write as _;
+use tracing::{error, info};
+
+/// Changes listed in the reply before the rest are only counted, keeping
+/// it within a message.
+const MAX_LISTED_CHANGES: usize = 20;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("config-reload")
+        .description("Reload the config file and environment without a restart (bot owners only)")
+}
+
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let reply = match handler.data.reload_config() {
+        Ok(reload) => {
+            info!(
+                user_id = %command.user.id,
+                changes = %reload.applied.join("; "),
+                pending = %reload.pending.join(", "),
+                "Config reloaded by command"
+            );
+            report(&reload)
+        }
+        Err(e) => {
+            error!(error = %format!("{:#}", e), "Failed to reload the config");
+            format!(
+                "Kept the current config, the new one failed to load: {:#}",
+                e
+            )
+        }
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
+
+fn report(reload: &ConfigReload) -> String {
+    let mut reply = if reload.applied.is_empty() {
+        "Reloaded the config, nothing that applies while running changed.".to_string()
+    } else {
+        let mut reply = "Reloaded the config. Open votes keep the thresholds they started with; \
+                         new votes use the new settings.\n**Applied:**"
+            .to_string();
+        for change in reload.applied.iter().take(MAX_LISTED_CHANGES) {
+            let _ = write!(reply, "\n- `{}`", change);
+        }
+        if reload.applied.len() > MAX_LISTED_CHANGES {
+            let _ = write!(
+                reply,
+                "\n…and {} more",
+                reload.applied.len() - MAX_LISTED_CHANGES
+            );
+        }
+        reply
+    };
+    if !reload.pending.is_empty() {
+        let _ = write!(
+            reply,
+            "\n**Needs a restart:** {}",
+            reload.pending.join(", ")
+        );
+    }
+    reply.push_str("\nThe bot token and gateway intents only change with a restart.");
+    reply
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_list_applied_and_restart_only_changes() {
+        assert_eq!(
+            report(&ConfigReload::default()),
+            "Reloaded the config, nothing that applies while running changed.\n\
+             The bot token and gateway intents only change with a restart."
+        );
+
+        let reload = ConfigReload {
+            applied: (0..22)
+                .map(|n| format!("confirm_cap: {} → {}", n, n + 1))
+                .collect(),
+            pending: vec!["database_path", "interaction_only"],
+        };
+        let reply = report(&reload);
+        assert!(reply.contains("\n- `confirm_cap: 0 → 1`"));
+        assert!(!reply.contains("confirm_cap: 20 → 21"));
+        assert!(
+            reply.contains("\n…and 2 more\n**Needs a restart:** database_path, interaction_only")
+        );
+    }
+}
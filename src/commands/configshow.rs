@@ -0,0 +1,40 @@
+use super::{config, respond_paged};
+use crate::{pagination::Paginator, Handler};
+use serenity::all::{CommandInteraction, Context, CreateCommand};
+
+const SETTINGS_PER_PAGE: usize = 15;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("config-show")
+        .description("Show the bot's effective configuration (bot owners only)")
+}
+
+/// Lists every process-wide setting as the environment, config file and
+/// command line left it, followed by the `/config` settings of the server
+/// it was run in.
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let mut entries: Vec<String> = handler
+        .data
+        .config()
+        .settings()
+        .into_iter()
+        .map(|(key, value)| format!("`{}` = {}", key, value))
+        .collect();
+    if let Some(guild_id) = command.guild_id {
+        entries.push(format!(
+            "\n**This server's /config settings**\n{}",
+            config::describe(&handler.data.guild_config(guild_id)).trim_end()
+        ));
+    }
+    let paginator = Paginator::new(
+        command.user.id,
+        "Effective configuration",
+        entries,
+        SETTINGS_PER_PAGE,
+    );
+    respond_paged(ctx, handler, command, paginator, true).await
+}
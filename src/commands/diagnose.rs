@@ -0,0 +1,150 @@
+use super::{definitions, respond_ephemeral};
+use crate::{bot_permissions_in, can_read_history, channel_lineage, intents, Handler};
+use serenity::all::{
+    Command, CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage, GatewayIntents,
+    ResolvedValue,
+};
+use std::collections::HashSet;
+use tracing::warn;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("diagnose")
+        .description("Check the bot's permissions and setup in a channel")
+        .dm_permission(false)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Channel,
+            "channel",
+            "The channel to check, this one by default",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let channel_id = command
+        .data
+        .options()
+        .into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Channel(channel) if option.name == "channel" => Some(channel.id),
+            _ => None,
+        })
+        .unwrap_or(command.channel_id);
+
+    let data = &handler.data;
+    let permissions = match bot_permissions_in(ctx, guild_id, channel_id) {
+        Some(permissions) => checklist(&[
+            ("Read messages", can_read_history(permissions)),
+            ("Add reactions", permissions.add_reactions()),
+            ("Manage messages (to pin)", permissions.manage_messages()),
+            ("Send messages", permissions.send_messages()),
+        ]),
+        None => "❌ I couldn't work out my permissions there; the channel isn't in my cache."
+            .to_string(),
+    };
+
+    let listing = data.channel_listing(guild_id, &channel_lineage(ctx, guild_id, channel_id));
+    let mut lists = vec![("Not on a deny list", !listing.denied)];
+    if listing.has_allowlist {
+        lists.push(("On the allow list", listing.allowlisted));
+    } else {
+        lists.push(("No allow list limits it", true));
+    }
+
+    let per_guild = data.config().register_guild_commands;
+    let registered = if per_guild {
+        guild_id.get_commands(&ctx.http).await
+    } else {
+        Command::get_global_commands(&ctx.http).await
+    };
+    let commands = match registered {
+        Ok(registered) => {
+            let names: HashSet<String> =
+                registered.into_iter().map(|command| command.name).collect();
+            let missing = command_names()
+                .into_iter()
+                .filter(|name| !names.contains(name))
+                .count();
+            let scope = if per_guild {
+                "in this server"
+            } else {
+                "globally"
+            };
+            if missing == 0 {
+                format!("✅ Commands registered {}", scope)
+            } else {
+                format!(
+                    "❌ {} commands missing {}; run `register-commands`",
+                    missing, scope
+                )
+            }
+        }
+        Err(e) => {
+            warn!(guild_id = %guild_id, error = %e, "Failed to fetch registered commands");
+            "❌ I couldn't fetch the registered commands".to_string()
+        }
+    };
+
+    let active = intents(data.interaction_only(), data.drop_departed_votes());
+    let server = format!(
+        "{}\n{}",
+        commands,
+        checklist(&[
+            (
+                "Message Content intent (@mention requests)",
+                active.contains(GatewayIntents::MESSAGE_CONTENT),
+            ),
+            (
+                "Server Members intent (dropping departed votes)",
+                active.contains(GatewayIntents::GUILD_MEMBERS),
+            ),
+        ])
+    );
+
+    let embed = CreateEmbed::new()
+        .title("Pin bot diagnostics")
+        .description(format!("Checking <#{}>", channel_id))
+        .field("My permissions", permissions, false)
+        .field("Channel lists", checklist(&lists), false)
+        .field("Server", server, false);
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true),
+            ),
+        )
+        .await
+}
+
+/// Names of every command `register` would set up.
+fn command_names() -> Vec<String> {
+    definitions()
+        .into_iter()
+        .filter_map(|definition| {
+            serde_json::to_value(definition)
+                .ok()?
+                .get("name")?
+                .as_str()
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+fn checklist(checks: &[(&str, bool)]) -> String {
+    checks
+        .iter()
+        .map(|(label, ok)| format!("{} {}", if *ok { "✅" } else { "❌" }, label))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,163 @@
+use super::respond_ephemeral;
+use crate::content_filter::ContentFilter;
+use crate::duration::format_duration;
+use crate::guild_config::VoteMode;
+use crate::{Handler, CHECKMARK_EMOJI, MAX_BATCH_TARGETS, REJECT_EMOJI};
+use serenity::all::{
+    CommandInteraction, Context, CreateCommand, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage,
+};
+use std::time::Duration;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("help")
+        .description("Explain how pinning works in this channel")
+        .dm_permission(false)
+}
+
+/// Everything here is read from the settings in effect for the channel the
+/// command was run in, so servers that customize the bot get a true answer.
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let data = &handler.data;
+    let config = data.config();
+    let guild_config = data.guild_config(guild_id);
+
+    let mut asking = Vec::new();
+    if !data.interaction_only() {
+        asking.push(format!(
+            "Reply to a message and mention <@{}>",
+            ctx.cache.current_user().id
+        ));
+    }
+    asking.push("Run `/pin` with a message link or id".to_string());
+    asking.push("Right-click a message → Apps → **Pin this message**".to_string());
+    asking.push(format!(
+        "Add a duration like `3d` for a temporary pin, a reason, or up to {} links to pin \
+         several messages with one vote",
+        MAX_BATCH_TARGETS
+    ));
+    asking.push(
+        "Add `within 10m` (`within` on `/pin`) to choose how long the vote lasts".to_string(),
+    );
+
+    let threshold = handler.threshold_in(ctx, Some(guild_id), command.channel_id);
+    let votes_needed = if handler
+        .vet_channel(ctx, Some(guild_id), command.channel_id)
+        .is_err()
+    {
+        "Pin votes are disabled in this channel.".to_string()
+    } else if threshold == 0 {
+        "None, messages are pinned right away.".to_string()
+    } else if let Some(rule) = guild_config.quorum {
+        format!("Passes with {}", rule.describe())
+    } else {
+        format!("{} in this channel", threshold)
+    };
+
+    let mut voting = match guild_config.vote_mode {
+        VoteMode::Reactions => format!(
+            "React with {} on the request or on the message itself",
+            CHECKMARK_EMOJI
+        ),
+        VoteMode::Buttons => "Press **Approve** on the vote message".to_string(),
+        VoteMode::Poll => "Answer **Yes** on the vote's poll".to_string(),
+    };
+    if guild_config.quorum.is_some() {
+        voting.push_str(&match guild_config.vote_mode {
+            VoteMode::Reactions => format!(", or {} on the request to vote against", REJECT_EMOJI),
+            VoteMode::Buttons => ", or **Reject** to vote against".to_string(),
+            VoteMode::Poll => ", or **No** to vote against".to_string(),
+        });
+    }
+    if !data.interaction_only() {
+        voting.push_str(if guild_config.quorum.is_some() {
+            ". Replying `yes` or `no` to the vote works too"
+        } else {
+            ". Replying `+1` or `yes` to the vote works too"
+        });
+    }
+
+    let mut voters = vec![match guild_config.voter_role {
+        Some(role_id) => format!("Members with <@&{}>", role_id),
+        None => "Everyone".to_string(),
+    }];
+    if config.min_account_age_days > 0 {
+        voters.push(format!(
+            "Accounts at least {} days old",
+            config.min_account_age_days
+        ));
+    }
+    if config.min_member_age_hours > 0 {
+        voters.push(format!(
+            "In the server for at least {} hours",
+            config.min_member_age_hours
+        ));
+    }
+    if let Some(weight) = guild_config.booster_weight {
+        voters.push(format!("Server boosters' votes count {}×", weight));
+    }
+    if let Some(weight) = guild_config.super_reaction_weight {
+        voters.push(format!("A super reaction counts {}×", weight));
+    }
+    if !guild_config.count_timed_out_votes {
+        voters.push("Not while serving a timeout".to_string());
+    }
+
+    let mut rules = Vec::new();
+    if !guild_config.allow_self_pins {
+        rules.push("You can't start a vote on your own message".to_string());
+    }
+    if !guild_config.allow_bot_pins {
+        rules.push("Bot and webhook messages can't be pinned".to_string());
+    }
+    if guild_config.content_filter != ContentFilter::default() {
+        rules.push(format!(
+            "Messages need {}",
+            guild_config.content_filter.describe()
+        ));
+    }
+    if rules.is_empty() {
+        rules.push("none".to_string());
+    }
+
+    let embed = CreateEmbed::new()
+        .title("How pinning works here")
+        .description("Members vote on messages worth keeping; enough approvals pins them.")
+        .field("Ask for a pin", bullets(&asking), false)
+        .field("Votes needed", votes_needed, true)
+        .field(
+            "Votes last",
+            format_duration(Duration::from_secs(config.vote_timeout_secs)),
+            true,
+        )
+        .field("How to vote", voting, false)
+        .field("Who can vote", bullets(&voters), false)
+        .field("Rules", bullets(&rules), false);
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true),
+            ),
+        )
+        .await
+}
+
+fn bullets(lines: &[String]) -> String {
+    lines
+        .iter()
+        .map(|line| format!("• {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,142 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{
+    ChannelType, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    EditInteractionResponse, ResolvedValue,
+};
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+/// How often the progress message is edited while channels are walked.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(3);
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("import-pins")
+        .description("Add pins made before the bot joined to its history")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "Only import this channel's pins (every channel otherwise)",
+            )
+            .channel_types(vec![ChannelType::Text, ChannelType::News]),
+        )
+}
+
+/// Walks the channels one at a time, so a large server takes a while under
+/// rate limits; the reply shows how far it got. Pins already in the
+/// history are skipped, so an import cut short can simply be run again.
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let requested = command
+        .data
+        .options()
+        .iter()
+        .find_map(|option| match &option.value {
+            ResolvedValue::Channel(channel) if option.name == "channel" => Some(channel.id),
+            _ => None,
+        });
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Defer(
+                CreateInteractionResponseMessage::new().ephemeral(true),
+            ),
+        )
+        .await?;
+
+    let channels = match requested {
+        Some(channel_id) => vec![channel_id],
+        None => match guild_id.channels(&ctx.http).await {
+            Ok(channels) => {
+                let mut channels: Vec<_> = channels
+                    .into_values()
+                    .filter(|channel| matches!(channel.kind, ChannelType::Text | ChannelType::News))
+                    .collect();
+                channels.sort_by_key(|channel| channel.position);
+                channels.into_iter().map(|channel| channel.id).collect()
+            }
+            Err(e) => {
+                error!(guild_id = %guild_id, error = %e, "Failed to list channels to import");
+                let edit = EditInteractionResponse::new()
+                    .content("Failed to list the server's channels, please try again.");
+                return command.edit_response(&ctx.http, edit).await.map(|_| ());
+            }
+        },
+    };
+
+    info!(guild_id = %guild_id, channels = channels.len(), "Importing pins");
+    let (mut imported, mut known, mut unreadable) = (0, 0, 0);
+    let mut last_progress = Instant::now();
+    for (done, channel_id) in channels.iter().enumerate() {
+        match channel_id.pins(&ctx.http).await {
+            Ok(pins) => match data.import_pins(guild_id, *channel_id, &pins) {
+                Ok(new) => {
+                    imported += new;
+                    known += pins.len() - new;
+                }
+                Err(e) => {
+                    error!(channel_id = %channel_id, error = %e, "Failed to import pins");
+                    unreadable += 1;
+                }
+            },
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to list pins to import");
+                unreadable += 1;
+            }
+        }
+
+        if last_progress.elapsed() >= PROGRESS_INTERVAL && done + 1 < channels.len() {
+            last_progress = Instant::now();
+            let edit = EditInteractionResponse::new().content(format!(
+                "Importing pins… {}/{} channels, {} imported so far.",
+                done + 1,
+                channels.len(),
+                imported
+            ));
+            if let Err(e) = command.edit_response(&ctx.http, edit).await {
+                warn!(guild_id = %guild_id, error = %e, "Failed to update import progress");
+            }
+        }
+    }
+    info!(guild_id = %guild_id, imported, known, unreadable, "Imported pins");
+
+    let mut summary = format!(
+        "Imported {} {} from {} {}.",
+        imported,
+        if imported == 1 { "pin" } else { "pins" },
+        channels.len(),
+        if channels.len() == 1 {
+            "channel"
+        } else {
+            "channels"
+        }
+    );
+    if known > 0 {
+        summary.push_str(&format!(" {} were already in the history.", known));
+    }
+    if unreadable > 0 {
+        summary.push_str(&format!(
+            " {} {} couldn't be read; run it again to retry.",
+            unreadable,
+            if unreadable == 1 {
+                "channel"
+            } else {
+                "channels"
+            }
+        ));
+    }
+    command
+        .edit_response(&ctx.http, EditInteractionResponse::new().content(summary))
+        .await
+        .map(|_| ())
+}
@@ -0,0 +1,102 @@
+use super::{member_permissions_in, respond_ephemeral, respond_paged};
+use crate::{pagination::Paginator, Handler};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedValue,
+};
+use std::fmt::Write as _;
+
+/// Votes listed on each page of the reply.
+const VOTES_PER_PAGE: usize = 10;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("listvotes")
+        .description("List the pin votes that are open in this channel")
+        .dm_permission(false)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "all-channels",
+            "List open votes from every channel you can see",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "public",
+            "Post the list for everyone instead of just you",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let flag = |wanted: &str| {
+        command.data.options().into_iter().any(|option| {
+            option.name == wanted && matches!(option.value, ResolvedValue::Boolean(true))
+        })
+    };
+    let all_channels = flag("all-channels");
+    let public = flag("public");
+
+    let mut votes = handler
+        .data
+        .open_votes(guild_id, (!all_channels).then_some(command.channel_id));
+    // Don't reveal votes held in channels the member can't read
+    if all_channels {
+        votes.retain(|vote| {
+            member_permissions_in(ctx, command, guild_id, vote.vote_channel_id)
+                .is_some_and(|permissions| permissions.view_channel())
+        });
+    }
+    if votes.is_empty() {
+        let reply = if all_channels {
+            "There are no open votes right now."
+        } else {
+            "There are no open votes in this channel."
+        };
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    let entries = votes
+        .iter()
+        .enumerate()
+        .map(|(index, vote)| {
+            let target = &vote.target;
+            let mut entry = format!(
+                "**{}.** [Message]({}) · {} · ends <t:{}:R> · [Vote]({})",
+                index + 1,
+                target.message_id.link(target.channel_id, Some(guild_id)),
+                vote.progress,
+                vote.expires_at,
+                vote.session_id.link(vote.vote_channel_id, Some(guild_id))
+            );
+            if vote.awaiting_approval {
+                entry.push_str(" · awaiting moderator approval");
+            }
+            if vote.batch_size > 1 {
+                let _ = write!(entry, " · {} messages", vote.batch_size);
+            }
+            if all_channels {
+                let _ = write!(entry, " · <#{}>", vote.vote_channel_id);
+            }
+            let excerpt = if target.excerpt.is_empty() {
+                "*(no text)*"
+            } else {
+                &target.excerpt
+            };
+            let _ = write!(entry, "\n> {}", excerpt);
+            entry
+        })
+        .collect();
+    let title = if all_channels {
+        "Open votes in this server"
+    } else {
+        "Open votes in this channel"
+    };
+    let paginator = Paginator::new(command.user.id, title, entries, VOTES_PER_PAGE);
+    respond_paged(ctx, handler, command, paginator, !public).await
+}
@@ -0,0 +1,94 @@
+use super::respond_ephemeral;
+use crate::Handler;
+use serenity::all::{CommandInteraction, CommandType, Context, CreateCommand, ResolvedTarget};
+use tracing::{error, info};
+
+pub const LOCK_MENU_NAME: &str = "Lock from pinning";
+pub const UNLOCK_MENU_NAME: &str = "Unlock pinning";
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new(LOCK_MENU_NAME)
+        .kind(CommandType::Message)
+        .dm_permission(false)
+}
+
+pub fn unlock_definition() -> CreateCommand {
+    CreateCommand::new(UNLOCK_MENU_NAME)
+        .kind(CommandType::Message)
+        .dm_permission(false)
+}
+
+/// Locks or unlocks the message the menu was opened on. Locking also
+/// cancels the votes already open on it.
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+    locked: bool,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let Some(ResolvedTarget::Message(target)) = command.data.target() else {
+        return respond_ephemeral(ctx, command, "I couldn't find that message.").await;
+    };
+
+    let data = &handler.data;
+    let saved = data.update_guild_config(guild_id, |config| {
+        if locked {
+            config.locked_messages.insert(target.id);
+        } else {
+            config.locked_messages.remove(&target.id);
+        }
+    });
+    if let Err(e) = saved {
+        error!(guild_id = %guild_id, error = %e, "Failed to save a pin lock");
+        return respond_ephemeral(
+            ctx,
+            command,
+            "Failed to save the configuration, please try again.",
+        )
+        .await;
+    }
+
+    let moderator = command.user.id;
+    let target_link = target.id.link(target.channel_id, Some(guild_id));
+    info!(message_id = %target.id, user_id = %moderator, locked, "Pin lock changed");
+    if !locked {
+        data.audit(
+            &ctx.http,
+            guild_id,
+            format!("🔓 <@{}> unlocked {} for pinning.", moderator, target_link),
+        )
+        .await;
+        return respond_ephemeral(ctx, command, "That message can be pinned again.").await;
+    }
+
+    let mut cancelled = 0;
+    for (session_id, vote_channel_id) in data.sessions_pinning(target.id) {
+        let notice = "Vote cancelled: a moderator locked the message from pinning.".to_string();
+        if data
+            .end_cancelled_session(&ctx.http, session_id, vote_channel_id, notice)
+            .await
+        {
+            cancelled += 1;
+        }
+    }
+    data.audit(
+        &ctx.http,
+        guild_id,
+        format!("🔒 <@{}> locked {} from pinning.", moderator, target_link),
+    )
+    .await;
+
+    let reply = match cancelled {
+        0 => "Locked. Nobody can start a pin vote on that message.".to_string(),
+        1 => "Locked, and cancelled the vote that was open on it.".to_string(),
+        count => format!(
+            "Locked, and cancelled the {} votes that were open on it.",
+            count
+        ),
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
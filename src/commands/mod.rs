@@ -0,0 +1,503 @@
+//! Application (slash) commands: definitions, registration and dispatch.
+
+mod adoptvote;
+mod backup;
+mod cancelvote;
+mod config;
+mod configreload;
+mod configshow;
+mod diagnose;
+mod help;
+mod importpins;
+mod listvotes;
+mod lock;
+mod optout;
+mod pin;
+mod pinapproval;
+mod pinblock;
+mod pincrosspost;
+mod pinquota;
+mod pinretention;
+mod pinrotation;
+mod pintemplate;
+mod purge;
+mod status;
+mod toppins;
+mod unpin;
+mod votestats;
+mod votethreads;
+
+use crate::{
+    batch_too_large_refusal, channel_permissions, links,
+    pagination::{Paginator, PAGE_TIMEOUT},
+    Handler, CROSS_GUILD_REFUSAL, MAX_BATCH_TARGETS,
+};
+use serenity::all::{
+    ChannelId, Command, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, CreateInteractionResponseMessage,
+    EditInteractionResponse, GuildId, Http, Message, Permissions, ResolvedValue,
+};
+use std::sync::Arc;
+use tracing::{debug, error, info, warn};
+
+/// Who may run a command. Every tier but `Everyone` is registered with
+/// default member permissions, so Discord hides it from regular members,
+/// and [`authorize`] checks it again on every use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Everyone,
+    /// Members holding these permissions in the channel.
+    Members(Permissions),
+    /// `SUPERUSERS` only. Hidden from all but administrators.
+    Superusers,
+    /// The bot's owners only. Hidden from all but administrators.
+    Owners,
+}
+
+impl Access {
+    fn default_permissions(self) -> Option<Permissions> {
+        match self {
+            Access::Everyone => None,
+            Access::Members(permissions) => Some(permissions),
+            Access::Superusers | Access::Owners => Some(Permissions::ADMINISTRATOR),
+        }
+    }
+}
+
+/// One application command: the name it is invoked by, who may run it and
+/// how it is defined.
+struct CommandSpec {
+    name: &'static str,
+    access: Access,
+    definition: fn() -> CreateCommand,
+}
+
+const fn spec(
+    name: &'static str,
+    access: Access,
+    definition: fn() -> CreateCommand,
+) -> CommandSpec {
+    CommandSpec {
+        name,
+        access,
+        definition,
+    }
+}
+
+const MANAGE_GUILD: Access = Access::Members(Permissions::MANAGE_GUILD);
+const MANAGE_MESSAGES: Access = Access::Members(Permissions::MANAGE_MESSAGES);
+
+/// Every command the bot registers. A new one goes here as well as in
+/// [`handle`].
+const COMMANDS: &[CommandSpec] = &[
+    spec("adopt-vote", MANAGE_MESSAGES, adoptvote::definition),
+    spec("backup", Access::Superusers, backup::definition),
+    spec("cancelvote", MANAGE_MESSAGES, cancelvote::definition),
+    spec("config", MANAGE_GUILD, config::definition),
+    spec("config-reload", Access::Owners, configreload::definition),
+    spec("config-show", Access::Owners, configshow::definition),
+    spec("diagnose", MANAGE_GUILD, diagnose::definition),
+    spec("help", Access::Everyone, help::definition),
+    spec("import-pins", MANAGE_GUILD, importpins::definition),
+    spec("listvotes", Access::Everyone, listvotes::definition),
+    spec(lock::LOCK_MENU_NAME, MANAGE_MESSAGES, lock::definition),
+    spec(
+        lock::UNLOCK_MENU_NAME,
+        MANAGE_MESSAGES,
+        lock::unlock_definition,
+    ),
+    spec("pin-optout", Access::Everyone, optout::definition),
+    spec("pin-optin", Access::Everyone, optout::opt_in_definition),
+    spec("pinapproval", MANAGE_GUILD, pinapproval::definition),
+    spec("pinblock", MANAGE_MESSAGES, pinblock::definition),
+    spec("pincrosspost", MANAGE_GUILD, pincrosspost::definition),
+    spec("pinquota", MANAGE_GUILD, pinquota::definition),
+    spec("pinretention", MANAGE_GUILD, pinretention::definition),
+    spec("pinrotation", MANAGE_GUILD, pinrotation::definition),
+    spec("pintemplate", MANAGE_GUILD, pintemplate::definition),
+    spec("pin", Access::Everyone, pin::definition),
+    spec(
+        pin::CONTEXT_MENU_NAME,
+        Access::Everyone,
+        pin::context_menu_definition,
+    ),
+    spec("purge-user-data", Access::Superusers, purge::definition),
+    spec("status", MANAGE_GUILD, status::definition),
+    spec("top-pins", Access::Everyone, toppins::definition),
+    spec("unpin", MANAGE_MESSAGES, unpin::definition),
+    spec("votestats", MANAGE_GUILD, votestats::definition),
+    spec("votethreads", MANAGE_GUILD, votethreads::definition),
+];
+
+fn definitions() -> Vec<CreateCommand> {
+    COMMANDS
+        .iter()
+        .map(|spec| {
+            let definition = (spec.definition)();
+            match spec.access.default_permissions() {
+                Some(permissions) => definition.default_member_permissions(permissions),
+                None => definition,
+            }
+        })
+        .collect()
+}
+
+/// Registers commands globally, or per guild when `per_guild` is set. Guild
+/// commands update instantly, which is handy while iterating; global ones
+/// can take a while to propagate. Failures are logged; returns whether
+/// every registration succeeded.
+pub async fn register(http: &Http, guilds: &[GuildId], per_guild: bool) -> bool {
+    if !per_guild {
+        return match Command::set_global_commands(http, definitions()).await {
+            Ok(commands) => {
+                info!(
+                    count = commands.len(),
+                    "Registered global application commands"
+                );
+                true
+            }
+            Err(e) => {
+                error!(error = %e, "Failed to register application commands");
+                false
+            }
+        };
+    }
+
+    let mut all_registered = true;
+    for guild_id in guilds {
+        match guild_id.set_commands(http, definitions()).await {
+            Ok(commands) => info!(
+                guild_id = %guild_id,
+                count = commands.len(),
+                "Registered guild application commands"
+            ),
+            Err(e) => {
+                error!(
+                    guild_id = %guild_id,
+                    error = %e,
+                    "Failed to register application commands"
+                );
+                all_registered = false;
+            }
+        }
+    }
+    all_registered
+}
+
+pub async fn handle(ctx: &Context, handler: &Handler, command: &CommandInteraction) {
+    let Some(spec) = COMMANDS.iter().find(|spec| spec.name == command.data.name) else {
+        warn!("Received unknown command /{}", command.data.name);
+        return;
+    };
+    if let Err(refusal) = authorize(ctx, handler, command, spec.access).await {
+        if let Err(e) = respond_ephemeral(ctx, command, refusal).await {
+            error!(command = %command.data.name, error = %e, "Failed to refuse a command");
+        }
+        return;
+    }
+
+    let result = match command.data.name.as_str() {
+        "adopt-vote" => adoptvote::run(ctx, handler, command).await,
+        "backup" => backup::run(ctx, &handler.data, command).await,
+        "cancelvote" => cancelvote::run(ctx, handler, command).await,
+        "config" => config::run(ctx, &handler.data, command).await,
+        "config-reload" => configreload::run(ctx, handler, command).await,
+        "config-show" => configshow::run(ctx, handler, command).await,
+        "diagnose" => diagnose::run(ctx, handler, command).await,
+        "help" => help::run(ctx, handler, command).await,
+        "import-pins" => importpins::run(ctx, &handler.data, command).await,
+        "listvotes" => listvotes::run(ctx, handler, command).await,
+        lock::LOCK_MENU_NAME => lock::run(ctx, handler, command, true).await,
+        lock::UNLOCK_MENU_NAME => lock::run(ctx, handler, command, false).await,
+        "pin" => pin::run(ctx, handler, command).await,
+        "pin-optin" => optout::run(ctx, &handler.data, command, false).await,
+        "pin-optout" => optout::run(ctx, &handler.data, command, true).await,
+        "pinapproval" => pinapproval::run(ctx, &handler.data, command).await,
+        "pinblock" => pinblock::run(ctx, &handler.data, command).await,
+        "pincrosspost" => pincrosspost::run(ctx, &handler.data, command).await,
+        "pinquota" => pinquota::run(ctx, &handler.data, command).await,
+        "pinretention" => pinretention::run(ctx, &handler.data, command).await,
+        "pinrotation" => pinrotation::run(ctx, &handler.data, command).await,
+        "pintemplate" => pintemplate::run(ctx, &handler.data, command).await,
+        pin::CONTEXT_MENU_NAME => pin::run_context_menu(ctx, handler, command).await,
+        "purge-user-data" => purge::run(ctx, handler, command).await,
+        "status" => status::run(ctx, handler, command).await,
+        "top-pins" => toppins::run(ctx, handler, command).await,
+        "unpin" => unpin::run(ctx, &handler.data, command).await,
+        "votestats" => votestats::run(ctx, &handler.data, command).await,
+        "votethreads" => votethreads::run(ctx, &handler.data, command).await,
+        other => {
+            warn!("Received unknown command /{}", other);
+            return;
+        }
+    };
+
+    if let Err(e) = result {
+        error!(command = %command.data.name, error = %e, "Command failed");
+    }
+}
+
+/// Checks that the invoking user may run a command of `access`, returning
+/// the refusal when not. Discord already hides commands from members
+/// lacking the default permissions, but server admins can override that,
+/// so every command passes through here before it runs.
+async fn authorize(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+    access: Access,
+) -> Result<(), String> {
+    match access {
+        Access::Everyone => Ok(()),
+        Access::Members(permissions) if member_has(command, permissions) => Ok(()),
+        Access::Members(permissions) => Err(format!(
+            "You need {} to do that.",
+            permission_names(permissions)
+        )),
+        Access::Superusers if handler.data.config().superusers.contains(&command.user.id) => Ok(()),
+        Access::Superusers => Err("Only the bot's operators can do that.".to_string()),
+        Access::Owners if handler.data.is_owner(&ctx.http, command.user.id).await => Ok(()),
+        Access::Owners => Err("Only the bot's owners can do that.".to_string()),
+    }
+}
+
+/// Permission names as the server settings show them, which calls Manage
+/// Guild "Manage Server".
+fn permission_names(permissions: Permissions) -> String {
+    permissions
+        .get_permission_names()
+        .into_iter()
+        .map(|name| {
+            if name == "Manage Guilds" {
+                "Manage Server"
+            } else {
+                name
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+fn member_has(command: &CommandInteraction, permissions: Permissions) -> bool {
+    command
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|granted| granted.contains(permissions))
+}
+
+/// The invoking member's permissions in `channel_id`. The interaction only
+/// carries them for the channel it was run in; others come from the cache.
+fn member_permissions_in(
+    ctx: &Context,
+    command: &CommandInteraction,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Option<Permissions> {
+    let member = command.member.as_ref()?;
+    if channel_id == command.channel_id {
+        return member.permissions;
+    }
+    channel_permissions(ctx, guild_id, channel_id, |guild, channel| {
+        guild.user_permissions_in(channel, member)
+    })
+}
+
+/// The required "message" option shared by the commands that take a link.
+fn message_option() -> CreateCommandOption {
+    CreateCommandOption::new(
+        CommandOptionType::String,
+        "message",
+        "A message link or message id",
+    )
+    .required(true)
+}
+
+fn message_input(command: &CommandInteraction) -> &str {
+    command
+        .data
+        .options()
+        .into_iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::String(value) if option.name == "message" => Some(value),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Resolves the "message" option to a message in this guild. A bare id
+/// refers to the channel the command was run in. On failure, returns the
+/// reply for the user.
+async fn linked_message(
+    ctx: &Context,
+    command: &CommandInteraction,
+    guild_id: GuildId,
+) -> Result<Message, String> {
+    fetch_linked(ctx, command, guild_id, message_input(command)).await
+}
+
+/// Like `linked_message`, for an option holding up to `MAX_BATCH_TARGETS`
+/// links or ids separated by spaces. Repeats are dropped.
+async fn linked_messages(
+    ctx: &Context,
+    command: &CommandInteraction,
+    guild_id: GuildId,
+) -> Result<Vec<Message>, String> {
+    let mut inputs: Vec<&str> = Vec::new();
+    for input in message_input(command).split_whitespace() {
+        if !inputs.contains(&input) {
+            inputs.push(input);
+        }
+    }
+    if inputs.len() > MAX_BATCH_TARGETS {
+        return Err(batch_too_large_refusal());
+    }
+    if inputs.is_empty() {
+        return Err("That doesn't look like a message link or id.".to_string());
+    }
+
+    let mut targets = Vec::with_capacity(inputs.len());
+    for input in &inputs {
+        match fetch_linked(ctx, command, guild_id, input).await {
+            Ok(target) if targets.iter().any(|known: &Message| known.id == target.id) => {}
+            Ok(target) => targets.push(target),
+            Err(refusal) if inputs.len() > 1 => return Err(format!("{}: {}", input, refusal)),
+            Err(refusal) => return Err(refusal),
+        }
+    }
+    Ok(targets)
+}
+
+async fn fetch_linked(
+    ctx: &Context,
+    command: &CommandInteraction,
+    guild_id: GuildId,
+    input: &str,
+) -> Result<Message, String> {
+    let Some(link) = links::parse_message_ref(input) else {
+        return Err("That doesn't look like a message link or id.".to_string());
+    };
+
+    if link.channel_id.is_some() && link.guild_id != Some(guild_id) {
+        return Err(CROSS_GUILD_REFUSAL.to_string());
+    }
+
+    let channel_id = link.channel_id.unwrap_or(command.channel_id);
+    channel_id
+        .message(&ctx.http, link.message_id)
+        .await
+        .map_err(|e| {
+            warn!(
+                command = %command.data.name,
+                message_id = %link.message_id,
+                error = %e,
+                "Failed to fetch the command's target"
+            );
+            "I couldn't find that message.".to_string()
+        })
+}
+
+/// Replies with the first page of `paginator`, with buttons to turn the
+/// rest that stop working after [`PAGE_TIMEOUT`].
+async fn respond_paged(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+    paginator: Paginator,
+    ephemeral: bool,
+) -> serenity::Result<()> {
+    let message = CreateInteractionResponseMessage::new()
+        .embed(paginator.embed())
+        .components(paginator.buttons(false))
+        .ephemeral(ephemeral);
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await?;
+    if paginator.pages() <= 1 {
+        return Ok(());
+    }
+
+    let reply_id = command.get_response(&ctx.http).await?.id;
+    handler.data.paginations.insert(reply_id, paginator);
+    // Grey the buttons out once they stop working
+    let (http, data, command) = (
+        Arc::clone(&ctx.http),
+        Arc::clone(&handler.data),
+        command.clone(),
+    );
+    tokio::spawn(async move {
+        tokio::time::sleep(PAGE_TIMEOUT).await;
+        let Some((_, paginator)) = data.paginations.remove(&reply_id) else {
+            return;
+        };
+        let edit = EditInteractionResponse::new().components(paginator.buttons(true));
+        if let Err(e) = command.edit_response(&http, edit).await {
+            debug!(error = %e, "Failed to disable page buttons");
+        }
+    });
+    Ok(())
+}
+
+async fn respond_ephemeral(
+    ctx: &Context,
+    command: &CommandInteraction,
+    content: impl Into<String>,
+) -> serenity::Result<()> {
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    /// The commands meant for every member. Anything else must be
+    /// registered with default member permissions.
+    const PUBLIC: [&str; 7] = [
+        "help",
+        "listvotes",
+        "pin",
+        pin::CONTEXT_MENU_NAME,
+        "pin-optin",
+        "pin-optout",
+        "top-pins",
+    ];
+
+    #[test]
+    fn every_command_outside_the_public_ones_is_registered_with_permissions() {
+        let mut names = HashSet::new();
+        for (spec, definition) in COMMANDS.iter().zip(definitions()) {
+            let definition = serde_json::to_value(definition).unwrap();
+            assert_eq!(definition["name"], spec.name);
+            assert!(names.insert(spec.name), "/{} is listed twice", spec.name);
+
+            let registered = definition["default_member_permissions"].as_str();
+            if PUBLIC.contains(&spec.name) {
+                assert_eq!(spec.access, Access::Everyone, "/{}", spec.name);
+                assert_eq!(registered, None, "/{}", spec.name);
+            } else {
+                assert_ne!(spec.access, Access::Everyone, "/{} is wide open", spec.name);
+                let expected = spec.access.default_permissions().unwrap();
+                assert_eq!(registered, Some(expected.bits().to_string().as_str()));
+            }
+        }
+    }
+
+    #[test]
+    fn refusals_name_permissions_as_the_server_settings_do() {
+        assert_eq!(permission_names(Permissions::MANAGE_GUILD), "Manage Server");
+        assert_eq!(
+            permission_names(Permissions::MANAGE_MESSAGES | Permissions::MANAGE_GUILD),
+            "Manage Server and Manage Messages"
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{CommandInteraction, Context, CreateCommand};
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("pin-optout")
+        .description("Stop your messages from being put up for pin votes")
+}
+
+pub fn opt_in_definition() -> CreateCommand {
+    CreateCommand::new("pin-optin").description("Let your messages be put up for pin votes again")
+}
+
+/// The choice is the user's own and holds in every server, so the commands
+/// also work in DMs.
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+    opted_out: bool,
+) -> serenity::Result<()> {
+    let user_id = command.user.id;
+    if let Err(e) = data.set_opted_out(user_id, opted_out) {
+        error!(user_id = %user_id, error = %e, "Failed to save a pin opt-out");
+        return respond_ephemeral(ctx, command, "Failed to save that, please try again.").await;
+    }
+    info!(user_id = %user_id, opted_out, "Pin opt-out changed");
+
+    let reply = if opted_out {
+        "Done. Nobody can start a pin vote on your messages, and open votes on them won't \
+         pin. Use `/pin-optin` to undo this."
+    } else {
+        "Done. Your messages can be put up for pin votes again."
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
@@ -0,0 +1,366 @@
+use super::{linked_messages, member_permissions_in, message_option, respond_ephemeral};
+use crate::{
+    can_read_history, duration, no_access_refusal, partial_batch_report, pin_failed_notice,
+    pinned_report, reason, recent_pin_warning, templates::TemplateKey, vote_message, with_deadline,
+    Handler, PinOrder, PinTarget, VoteAnchor, VoteIntro, VoteTerms, BLOCKED_REFUSAL,
+    MAX_BATCH_TARGETS,
+};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, CommandType, Context, CreateAllowedMentions,
+    CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, EditInteractionResponse,
+    GuildId, Message, ResolvedTarget, ResolvedValue, Timestamp,
+};
+use std::time::Duration;
+
+pub const CONTEXT_MENU_NAME: &str = "Pin this message";
+
+/// What the requester added to a `/pin`; the context menu takes none of it.
+#[derive(Default)]
+struct RequestOptions {
+    pin_duration: Option<Duration>,
+    reason: Option<String>,
+    /// Go ahead even if a target was pinned recently.
+    confirmed: bool,
+    /// How long the vote stays open, already within the configured bounds.
+    timeout: Option<Duration>,
+}
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("pin")
+        .description("Start a vote to pin a message")
+        .dm_permission(false)
+        .add_option(message_option().description(format!(
+            "A message link or id, or up to {} separated by spaces",
+            MAX_BATCH_TARGETS
+        )))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "duration",
+            "Unpin again after this long, e.g. 3d or 12h (max 30 days)",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "reason",
+                "Why this message deserves a pin",
+            )
+            .max_length(reason::MAX_REASON_CHARS as u16),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "confirm",
+            "Vote even if the message was pinned recently",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "within",
+            "How long the vote stays open, e.g. 10m or 1d",
+        ))
+}
+
+pub fn context_menu_definition() -> CreateCommand {
+    CreateCommand::new(CONTEXT_MENU_NAME)
+        .kind(CommandType::Message)
+        .dm_permission(false)
+}
+
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let string_option = |name: &str| {
+        command
+            .data
+            .options()
+            .into_iter()
+            .find_map(|option| match option.value {
+                ResolvedValue::String(value) if option.name == name => Some(value),
+                _ => None,
+            })
+    };
+    let duration = string_option("duration");
+    let reason = string_option("reason").and_then(reason::clean_reason);
+    let confirmed = command.data.options().into_iter().any(|option| {
+        option.name == "confirm" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+    let pin_duration = match duration {
+        Some(input) => match duration::parse_duration(input) {
+            Some(parsed) => Some(parsed),
+            None => {
+                return respond_ephemeral(
+                    ctx,
+                    command,
+                    "That doesn't look like a duration. Try something like 3d, 12h or 90m.",
+                )
+                .await;
+            }
+        },
+        None => None,
+    };
+    if let Some(Err(refusal)) = pin_duration.map(duration::check_pin_duration) {
+        return respond_ephemeral(ctx, command, refusal).await;
+    }
+    let timeout =
+        match string_option("within").map(|input| handler.data.config().vote_timeout(input)) {
+            Some(Ok(timeout)) => Some(timeout),
+            Some(Err(refusal)) => return respond_ephemeral(ctx, command, refusal).await,
+            None => None,
+        };
+
+    let targets = match linked_messages(ctx, command, guild_id).await {
+        Ok(targets) => targets,
+        Err(refusal) => return respond_ephemeral(ctx, command, refusal).await,
+    };
+
+    request_pin(
+        ctx,
+        handler,
+        command,
+        guild_id,
+        &targets,
+        RequestOptions {
+            pin_duration,
+            reason,
+            confirmed,
+            timeout,
+        },
+    )
+    .await
+}
+
+/// Right-click → Apps → "Pin this message". The resolved target arrives with
+/// the interaction, so this works even without the MESSAGE_CONTENT intent.
+pub async fn run_context_menu(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let Some(ResolvedTarget::Message(target)) = command.data.target() else {
+        return respond_ephemeral(ctx, command, "I couldn't find that message.").await;
+    };
+
+    request_pin(
+        ctx,
+        handler,
+        command,
+        guild_id,
+        std::slice::from_ref(target),
+        RequestOptions::default(),
+    )
+    .await
+}
+
+/// Shared tail of the command entry points: vet the targets, then pin them
+/// directly or open one vote on the command's response.
+async fn request_pin(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+    guild_id: GuildId,
+    targets: &[Message],
+    options: RequestOptions,
+) -> serenity::Result<()> {
+    let RequestOptions {
+        pin_duration,
+        reason,
+        confirmed,
+        timeout,
+    } = options;
+    let batch = targets.len() > 1;
+    let mut is_moderator = true;
+    for target in targets {
+        let target_link = target.id.link(target.channel_id, Some(guild_id));
+        // In a batch, say which message a refusal is about
+        let refuse = |refusal: String| {
+            if batch {
+                format!("{}: {}", target_link, refusal)
+            } else {
+                refusal
+            }
+        };
+
+        let permissions = member_permissions_in(ctx, command, guild_id, target.channel_id);
+        if target.channel_id != command.channel_id && !permissions.is_some_and(can_read_history) {
+            return respond_ephemeral(ctx, command, refuse(no_access_refusal(target.channel_id)))
+                .await;
+        }
+        let moderates = permissions.is_some_and(|permissions| permissions.manage_messages());
+        is_moderator &= moderates;
+
+        if handler
+            .refuse_blocked(ctx, Some(guild_id), target, command.user.id)
+            .await
+        {
+            return respond_ephemeral(ctx, command, BLOCKED_REFUSAL).await;
+        }
+        if let Err(refusal) = handler
+            .vet_request(
+                ctx,
+                Some(guild_id),
+                command.channel_id,
+                target,
+                command.user.id,
+                moderates,
+            )
+            .await
+        {
+            return respond_ephemeral(ctx, command, refuse(refusal)).await;
+        }
+    }
+
+    // Moderators and superusers can pin directly, just like when no votes
+    // are required. A batch needs as many votes as its strictest channel.
+    let config = handler.data.config();
+    let threshold = targets
+        .iter()
+        .map(|target| handler.threshold_in(ctx, Some(guild_id), target.channel_id))
+        .max()
+        .unwrap_or_default();
+    if threshold == 0 || config.superusers.contains(&command.user.id) || is_moderator {
+        // The pins may wait out the channel's cooldown or the server's pin
+        // budget, past the interaction deadline
+        command.defer(&ctx.http).await?;
+        let (pinned, failed) = handler
+            .data
+            .pin_each(
+                Some(guild_id),
+                targets.iter().map(PinTarget::from).collect(),
+            )
+            .await;
+        let guild_config = handler.data.guild_config(guild_id);
+        if pinned.is_empty() {
+            command.delete_response(&ctx.http).await?;
+            command
+                .create_followup(
+                    &ctx.http,
+                    CreateInteractionResponseFollowup::new()
+                        .content(pin_failed_notice(
+                            guild_config.template(TemplateKey::PinFailed),
+                            Some(guild_id),
+                            targets,
+                        ))
+                        .ephemeral(true),
+                )
+                .await?;
+            return Ok(());
+        }
+        let order = PinOrder {
+            guild_id: Some(guild_id),
+            initiator_id: command.user.id,
+            votes: 0,
+            voters: 0,
+            duration: pin_duration,
+            archive: handler.data.default_archive(Some(guild_id)),
+            reason: reason.as_deref(),
+        };
+        let content = handler.data.dry_run_tagged(if failed.is_empty() {
+            pinned_report(
+                guild_config.template(TemplateKey::PinSuccess),
+                &order,
+                &pinned,
+            )
+        } else {
+            partial_batch_report(Some(guild_id), pinned.len(), &failed)
+        });
+        // Answer before the follow-ups, which can take a while
+        let response = command
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new()
+                    .content(content)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await
+            .map(drop);
+        handler.data.after_pins(&ctx.http, &order, &pinned).await;
+        return response;
+    }
+
+    if !confirmed {
+        for target in targets {
+            if let Some(pinned_at) = handler.data.recent_pin(Some(guild_id), target.id) {
+                let mut warning =
+                    recent_pin_warning(pinned_at, "run `/pin` with its link and `confirm` set");
+                if batch {
+                    warning = format!(
+                        "{}: {}",
+                        target.id.link(target.channel_id, Some(guild_id)),
+                        warning
+                    );
+                }
+                return respond_ephemeral(ctx, command, warning).await;
+            }
+        }
+    }
+
+    if let Err(refusal) = handler.data.session_capacity(command.channel_id) {
+        return respond_ephemeral(ctx, command, refusal).await;
+    }
+    let moderator = member_permissions_in(ctx, command, guild_id, command.channel_id)
+        .is_some_and(|permissions| permissions.manage_messages());
+    if let Err(refusal) =
+        handler
+            .data
+            .initiator_capacity(Some(guild_id), command.user.id, moderator)
+    {
+        return respond_ephemeral(ctx, command, refusal).await;
+    }
+    if let Err(refusal) = handler.vet_pin_quota(ctx, Some(guild_id), targets) {
+        return respond_ephemeral(ctx, command, refusal).await;
+    }
+
+    let pin_targets: Vec<PinTarget> = targets.iter().map(PinTarget::from).collect();
+    let subject = targets[0].id.link(targets[0].channel_id, Some(guild_id));
+    let guild_config = handler.data.guild_config(guild_id);
+    let content = vote_message(
+        guild_config.template(TemplateKey::VoteStarted),
+        &VoteIntro {
+            guild_id: Some(guild_id),
+            targets: &pin_targets,
+            subject: &subject,
+            initiator_id: command.user.id,
+            threshold,
+            quorum: guild_config.quorum,
+            reason: reason.as_deref(),
+        },
+    );
+    let expires_at = Timestamp::now().unix_timestamp()
+        + timeout
+            .unwrap_or(Duration::from_secs(config.vote_timeout_secs))
+            .as_secs() as i64;
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(with_deadline(&content, expires_at)),
+            ),
+        )
+        .await?;
+
+    let response = command.get_response(&ctx.http).await?;
+    handler
+        .start_session(
+            ctx,
+            VoteAnchor::Interaction(command, &response),
+            targets,
+            threshold,
+            VoteTerms {
+                pin_duration,
+                reason,
+                timeout,
+            },
+        )
+        .await;
+    Ok(())
+}
@@ -0,0 +1,89 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{
+    ChannelType, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, ResolvedValue,
+};
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("pinapproval")
+        .description("Make passed votes in a channel wait for a moderator's sign-off")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "The channel to update; its threads follow it",
+            )
+            .channel_types(vec![
+                ChannelType::Text,
+                ChannelType::News,
+                ChannelType::Forum,
+            ])
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "required",
+                "Whether a moderator must approve each pin there",
+            )
+            .required(true),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let Some(channel_id) = options.iter().find_map(|option| match &option.value {
+        ResolvedValue::Channel(channel) if option.name == "channel" => Some(channel.id),
+        _ => None,
+    }) else {
+        return respond_ephemeral(ctx, command, "Pick a channel.").await;
+    };
+    let required = options.iter().any(|option| {
+        option.name == "required" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+
+    let result = data.update_guild_config(guild_id, |config| {
+        if required {
+            config.approval_channels.insert(channel_id);
+        } else {
+            config.approval_channels.remove(&channel_id);
+        }
+    });
+    let reply = match result {
+        Ok(config) => {
+            info!(guild_id = %guild_id, channel_id = %channel_id, required, "Moderator approval changed");
+            match (required, config.review_role) {
+                (true, Some(role)) => format!(
+                    "New votes in <#{}> will wait for a moderator's approval once they pass, \
+                     pinging <@&{}>.",
+                    channel_id, role
+                ),
+                (true, None) => format!(
+                    "New votes in <#{}> will wait for a moderator's approval once they pass. \
+                     Set `/config review-role` to have a role pinged.",
+                    channel_id
+                ),
+                (false, _) => format!(
+                    "New votes in <#{}> will pin as soon as they pass.",
+                    channel_id
+                ),
+            }
+        }
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to save moderator approval");
+            "Failed to save the configuration, please try again.".to_string()
+        }
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
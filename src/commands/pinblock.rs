@@ -0,0 +1,115 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedOption, ResolvedValue,
+};
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    let user_option = |description: &str| {
+        CreateCommandOption::new(CommandOptionType::User, "user", description).required(true)
+    };
+    CreateCommand::new("pinblock")
+        .description("Manage members whose messages are never pinned")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "add",
+                "Never pin this member's messages",
+            )
+            .add_sub_option(user_option("The member to block")),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "remove",
+                "Let this member's messages be pinned again",
+            )
+            .add_sub_option(user_option("The member to unblock")),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "list",
+            "Show the blocked members",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return respond_ephemeral(ctx, command, "Unknown subcommand.").await;
+    };
+
+    if *name == "list" {
+        let blocklist = data.guild_config(guild_id).pin_blocklist;
+        if blocklist.is_empty() {
+            return respond_ephemeral(ctx, command, "Nobody is on the pin blocklist.").await;
+        }
+        let mut members: Vec<_> = blocklist.into_iter().collect();
+        members.sort();
+        let mentions: Vec<String> = members
+            .iter()
+            .map(|user_id| format!("<@{}>", user_id))
+            .collect();
+        return respond_ephemeral(
+            ctx,
+            command,
+            format!("**Pin blocklist:** {}", mentions.join(", ")),
+        )
+        .await;
+    }
+
+    let Some(user) = sub_options.iter().find_map(|option| match option.value {
+        ResolvedValue::User(user, _) if option.name == "user" => Some(user),
+        _ => None,
+    }) else {
+        return respond_ephemeral(ctx, command, "Pick a member.").await;
+    };
+    let blocked = *name == "add";
+    match data.update_guild_config(guild_id, |config| {
+        if blocked {
+            config.pin_blocklist.insert(user.id);
+        } else {
+            config.pin_blocklist.remove(&user.id);
+        }
+    }) {
+        Ok(_) => {
+            info!(
+                guild_id = %guild_id,
+                user_id = %user.id,
+                blocked,
+                "Pin blocklist changed"
+            );
+            let reply = if blocked {
+                format!("<@{}>'s messages will no longer be pinned.", user.id)
+            } else {
+                format!("<@{}>'s messages can be pinned again.", user.id)
+            };
+            respond_ephemeral(ctx, command, reply).await
+        }
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to save the pin blocklist");
+            respond_ephemeral(
+                ctx,
+                command,
+                "Failed to save the configuration, please try again.",
+            )
+            .await
+        }
+    }
+}
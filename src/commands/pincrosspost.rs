@@ -0,0 +1,53 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedValue,
+};
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("pincrosspost")
+        .description("Publish voted pins in announcement channels to following servers")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "enabled",
+                "Whether pins in announcement channels are published",
+            )
+            .required(true),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let enabled = command.data.options().iter().any(|option| {
+        option.name == "enabled" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+    let result = data.update_guild_config(guild_id, |config| config.crosspost_pins = enabled);
+    let reply = match result {
+        Ok(_) => {
+            info!(guild_id = %guild_id, enabled, "Pin crossposting changed");
+            if enabled {
+                "Pins voted in announcement channels will be published to following servers. \
+                 The bot needs Send Messages and Manage Messages there."
+            } else {
+                "Pins in announcement channels will no longer be published."
+            }
+            .to_string()
+        }
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to save pin crossposting");
+            "Failed to save the configuration, please try again.".to_string()
+        }
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
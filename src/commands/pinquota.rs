@@ -0,0 +1,94 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedValue,
+};
+use tracing::{error, info};
+
+/// Highest daily quota accepted; Discord keeps at most 50 pins a channel.
+const MAX_DAILY_PIN_QUOTA: u32 = 50;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("pinquota")
+        .description("Limit how many voted pins a channel gets per day")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "pins",
+                "Voted pins allowed per UTC day; 0 is unlimited",
+            )
+            .min_int_value(0)
+            .max_int_value(MAX_DAILY_PIN_QUOTA as u64)
+            .required(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Channel,
+            "channel",
+            "Set it for this channel only (the server's quota otherwise)",
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "reset",
+            "Drop the channel's own quota so the server's applies again",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let pins = options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Integer(pins) if option.name == "pins" => u32::try_from(pins).ok(),
+            _ => None,
+        })
+        .unwrap_or(0);
+    let channel_id = options.iter().find_map(|option| match &option.value {
+        ResolvedValue::Channel(channel) if option.name == "channel" => Some(channel.id),
+        _ => None,
+    });
+    let reset = options.iter().any(|option| {
+        option.name == "reset" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+
+    let result = data.update_guild_config(guild_id, |config| match channel_id {
+        Some(channel_id) if reset => {
+            config.channel_pin_quotas.remove(&channel_id);
+        }
+        Some(channel_id) => {
+            config.channel_pin_quotas.insert(channel_id, pins);
+        }
+        None => config.daily_pin_quota = (pins > 0).then_some(pins),
+    });
+    let reply = match result {
+        Ok(_) => {
+            info!(guild_id = %guild_id, channel_id = ?channel_id, pins, reset, "Daily pin quota changed");
+            let limit = match pins {
+                0 => "no limit on voted pins".to_string(),
+                1 => "at most 1 voted pin a day".to_string(),
+                pins => format!("at most {} voted pins a day", pins),
+            };
+            match channel_id {
+                Some(channel_id) if reset => {
+                    format!("<#{}> follows the server's pin quota again.", channel_id)
+                }
+                Some(channel_id) => format!("<#{}> now gets {}.", channel_id, limit),
+                None => format!("Channels now get {} unless set otherwise.", limit),
+            }
+        }
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to save the pin quota");
+            "Failed to save the configuration, please try again.".to_string()
+        }
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
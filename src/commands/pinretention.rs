@@ -0,0 +1,98 @@
+use super::respond_ephemeral;
+use crate::{
+    retention::{PinRetention, MAX_RETENTION_DAYS},
+    BotData,
+};
+use serenity::all::{
+    ChannelType, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, ResolvedValue,
+};
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("pinretention")
+        .description("Unpin a channel's pins once they get old")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "The channel to prune",
+            )
+            .channel_types(vec![ChannelType::Text, ChannelType::News])
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "days",
+                "Unpin pins older than this many days; 0 stops pruning",
+            )
+            .min_int_value(0)
+            .max_int_value(MAX_RETENTION_DAYS as u64)
+            .required(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "keep-manual",
+            "Leave pins made by hand alone (default true)",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let Some(channel_id) = options.iter().find_map(|option| match &option.value {
+        ResolvedValue::Channel(channel) if option.name == "channel" => Some(channel.id),
+        _ => None,
+    }) else {
+        return respond_ephemeral(ctx, command, "Pick a channel.").await;
+    };
+    let days = options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Integer(days) if option.name == "days" => u32::try_from(days).ok(),
+            _ => None,
+        })
+        .unwrap_or(0)
+        .min(MAX_RETENTION_DAYS);
+    let keep_manual = !options.iter().any(|option| {
+        option.name == "keep-manual" && matches!(option.value, ResolvedValue::Boolean(false))
+    });
+    let retention = (days > 0).then_some(PinRetention { days, keep_manual });
+
+    let result = data.update_guild_config(guild_id, |config| match retention {
+        Some(retention) => {
+            config.pin_retention.insert(channel_id, retention);
+        }
+        None => {
+            config.pin_retention.remove(&channel_id);
+        }
+    });
+    let reply = match result {
+        Ok(_) => {
+            info!(guild_id = %guild_id, channel_id = %channel_id, days, keep_manual, "Pin retention changed");
+            match retention {
+                Some(retention) => format!(
+                    "Pins in <#{}> will be unpinned {}. The first prune runs within the hour \
+                     and is summed up in the audit channel.",
+                    channel_id,
+                    retention.describe()
+                ),
+                None => format!("Pins in <#{}> are no longer pruned.", channel_id),
+            }
+        }
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to save pin retention");
+            "Failed to save the configuration, please try again.".to_string()
+        }
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
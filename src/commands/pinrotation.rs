@@ -0,0 +1,105 @@
+use super::respond_ephemeral;
+use crate::BotData;
+use serenity::all::{
+    ChannelType, CommandInteraction, CommandOptionType, Context, CreateCommand,
+    CreateCommandOption, ResolvedValue,
+};
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("pinrotation")
+        .description("Feature one pin at a time in a channel, each new one replacing the last")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Channel,
+                "channel",
+                "The channel to update",
+            )
+            .channel_types(vec![ChannelType::Text, ChannelType::News])
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "enabled",
+                "Whether the channel rotates its pins",
+            )
+            .required(true),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "featured-message",
+            "Also keep a pinned message showing the featured pin (leave out to keep as is)",
+        ))
+}
+
+/// Only pins the bot makes from here on take part; turning rotation off
+/// leaves every pin where it is.
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let Some(channel_id) = options.iter().find_map(|option| match &option.value {
+        ResolvedValue::Channel(channel) if option.name == "channel" => Some(channel.id),
+        _ => None,
+    }) else {
+        return respond_ephemeral(ctx, command, "Pick a channel.").await;
+    };
+    let flag = |name: &str| {
+        options.iter().find_map(|option| match option.value {
+            ResolvedValue::Boolean(value) if option.name == name => Some(value),
+            _ => None,
+        })
+    };
+    let enabled = flag("enabled").unwrap_or_default();
+    let featured_message = flag("featured-message");
+
+    if let Err(e) = data.set_pin_rotation(guild_id, channel_id, enabled) {
+        error!(guild_id = %guild_id, error = %e, "Failed to save pin rotation");
+        return respond_ephemeral(
+            ctx,
+            command,
+            "Failed to save the configuration, please try again.",
+        )
+        .await;
+    }
+    info!(guild_id = %guild_id, channel_id = %channel_id, enabled, "Pin rotation changed");
+    let mut reply = if enabled {
+        format!(
+            "Each pin I make in <#{}> now replaces the last one I made there. Pins made by hand \
+             stay put.",
+            channel_id
+        )
+    } else {
+        format!(
+            "<#{}> keeps every pin again; the ones up now stay pinned.",
+            channel_id
+        )
+    };
+
+    if let Some(featured_message) = featured_message {
+        match data
+            .set_pin_index(&ctx.http, guild_id, channel_id, featured_message)
+            .await
+        {
+            Ok(_) if featured_message && enabled => reply
+                .push_str(" A pinned message showing the featured pin goes up with the next pin."),
+            Ok(_) if featured_message => {
+                reply.push_str(" The channel keeps a pinned index of its voted pins.")
+            }
+            Ok(_) => reply.push_str(" The channel's pinned index is gone."),
+            Err(e) => {
+                error!(guild_id = %guild_id, error = %e, "Failed to save the pin index");
+                reply.push_str(" The featured message couldn't be changed, please try again.");
+            }
+        }
+    }
+    respond_ephemeral(ctx, command, reply).await
+}
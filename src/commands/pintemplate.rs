@@ -0,0 +1,154 @@
+use super::respond_ephemeral;
+use crate::{
+    templates::{self, TemplateKey, MAX_TEMPLATE_CHARS},
+    BotData,
+};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedOption, ResolvedValue,
+};
+use std::fmt::Write as _;
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    let message_option = |required: bool| {
+        TemplateKey::ALL.into_iter().fold(
+            CreateCommandOption::new(CommandOptionType::String, "message", "Which message")
+                .required(required),
+            |option, key| option.add_string_choice(key.name(), key.name()),
+        )
+    };
+    CreateCommand::new("pintemplate")
+        .description("Reword the bot's messages for this server")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "show",
+                "Show the wording in use and the placeholders each message takes",
+            )
+            .add_sub_option(message_option(false)),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "set",
+                "Use your own wording for a message",
+            )
+            .add_sub_option(message_option(true))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "text",
+                    "The new wording, with placeholders like {count} in braces",
+                )
+                .max_length(MAX_TEMPLATE_CHARS as u16)
+                .required(true),
+            ),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reset",
+                "Go back to the default wording for a message",
+            )
+            .add_sub_option(message_option(true)),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let Some(ResolvedOption {
+        name: subcommand,
+        value: ResolvedValue::SubCommand(sub_options),
+        ..
+    }) = options.first()
+    else {
+        return respond_ephemeral(ctx, command, "Unknown subcommand.").await;
+    };
+    let string = |name: &str| {
+        sub_options.iter().find_map(|option| match option.value {
+            ResolvedValue::String(text) if option.name == name => Some(text),
+            _ => None,
+        })
+    };
+    let key = string("message").and_then(TemplateKey::parse);
+
+    if *subcommand == "show" {
+        let config = data.guild_config(guild_id);
+        let mut reply = String::new();
+        for key in TemplateKey::ALL
+            .into_iter()
+            .filter(|shown| key.is_none_or(|key| key == *shown))
+        {
+            let placeholders: Vec<String> = key
+                .placeholders()
+                .iter()
+                .map(|(name, meaning)| format!("`{{{}}}` {}", name, meaning))
+                .collect();
+            let _ = writeln!(
+                reply,
+                "**{}**{}: {}\n-# {}",
+                key.name(),
+                if config.templates.contains_key(&key) {
+                    ""
+                } else {
+                    " (default)"
+                },
+                config.template(key),
+                placeholders.join(" · ")
+            );
+        }
+        return respond_ephemeral(ctx, command, reply).await;
+    }
+
+    let Some(key) = key else {
+        return respond_ephemeral(ctx, command, "Pick a message.").await;
+    };
+    let text = match *subcommand {
+        "set" => {
+            let text = string("text").unwrap_or_default().trim();
+            if let Err(problem) = templates::validate(key, text) {
+                return respond_ephemeral(ctx, command, problem).await;
+            }
+            Some(text.to_string())
+        }
+        "reset" => None,
+        _ => return respond_ephemeral(ctx, command, "Unknown subcommand.").await,
+    };
+
+    let result = data.update_guild_config(guild_id, |config| match &text {
+        Some(text) => {
+            config.templates.insert(key, text.clone());
+        }
+        None => {
+            config.templates.remove(&key);
+        }
+    });
+    let reply = match result {
+        Ok(_) => {
+            info!(guild_id = %guild_id, message = key.name(), reset = text.is_none(), "Message template changed");
+            match text {
+                Some(text) => format!("**{}** now reads: {}", key.name(), text),
+                None => format!(
+                    "**{}** is back to the default: {}",
+                    key.name(),
+                    key.default_text()
+                ),
+            }
+        }
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to save the message template");
+            "Failed to save the configuration, please try again.".to_string()
+        }
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
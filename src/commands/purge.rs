@@ -0,0 +1,86 @@
+use super::respond_ephemeral;
+use crate::Handler;
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedValue, UserId,
+};
+use tracing::info;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("purge-user-data")
+        .description("Remove what the bot stores about a user")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "user-id",
+                "The user's id; they don't need to be in the server",
+            )
+            .required(true),
+        )
+}
+
+/// Purges reach across every server the bot is in, so the command is for
+/// superusers only.
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let user_id = command
+        .data
+        .options()
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::String(raw) if option.name == "user-id" => raw
+                .trim()
+                .trim_start_matches("<@")
+                .trim_start_matches('!')
+                .trim_end_matches('>')
+                .parse()
+                .ok()
+                .filter(|&id| id != 0)
+                .map(UserId::new),
+            _ => None,
+        });
+    let Some(user_id) = user_id else {
+        return respond_ephemeral(ctx, command, "That isn't a user id.").await;
+    };
+
+    info!(user_id = %user_id, by = %command.user.id, "Purging a user's data");
+    let report = handler.data.purge_user_data(user_id);
+    for &(session_id, outcome) in &report.sessions {
+        handler.finalize_if_passed(ctx, session_id, outcome).await;
+    }
+
+    let line = |label: &str, result: Option<String>| match result {
+        Some(done) => format!("- {}: {}", label, done),
+        None => format!("- {}: failed, left as it was", label),
+    };
+    let lines = [
+        line(
+            "Pin history",
+            report
+                .pins_anonymized
+                .map(|count| format!("{} pins anonymized", count)),
+        ),
+        line(
+            "Pin opt-out",
+            report
+                .opt_out_cleared
+                .map(|cleared| if cleared { "cleared" } else { "none" }.to_string()),
+        ),
+        line(
+            "Pin blocklists",
+            report
+                .blocklists_cleared
+                .map(|count| format!("removed from {} servers", count)),
+        ),
+        line(
+            "Open votes",
+            Some(format!("votes taken off {}", report.sessions.len())),
+        ),
+    ];
+    let reply = format!("Purged the data of <@{}>:\n{}", user_id, lines.join("\n"));
+    respond_ephemeral(ctx, command, reply).await
+}
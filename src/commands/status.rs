@@ -0,0 +1,161 @@
+use super::respond_ephemeral;
+use crate::{duration::format_duration, Handler};
+use serenity::all::{
+    CommandInteraction, Context, CreateCommand, CreateEmbed, CreateInteractionResponse,
+    CreateInteractionResponseMessage,
+};
+
+/// Cooling channels listed before the rest are only counted, keeping the
+/// field within Discord's 1024 characters.
+const MAX_LISTED_COOLDOWNS: usize = 20;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("status")
+        .description("Show the bot's runtime status for this server")
+        .dm_permission(false)
+}
+
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let data = &handler.data;
+    let latency = match data.shard_latency(ctx.shard_id).await {
+        Some(latency) => format!("{} ms", latency.as_millis()),
+        None => "unknown".to_string(),
+    };
+
+    // Only this server's shard is listed, with a count of the others that
+    // are down, so the field fits however many shards the bot runs
+    let shard_health = data.shard_health();
+    let mut shard = match shard_health
+        .iter()
+        .find(|(shard_id, _)| *shard_id == ctx.shard_id)
+    {
+        Some((shard_id, health)) => {
+            let mut line = format!("{}: {}", shard_id.0, health.stage);
+            if let Some(latency) = health.latency {
+                line.push_str(&format!(", {} ms", latency.as_millis()));
+            }
+            if let Some(since) = health.down_since {
+                line.push_str(&format!(", down {}", format_duration(since.elapsed())));
+            }
+            line
+        }
+        None => "not checked yet".to_string(),
+    };
+    let others_down = shard_health
+        .iter()
+        .filter(|(shard_id, health)| *shard_id != ctx.shard_id && health.down_since.is_some())
+        .count();
+    if others_down > 0 {
+        shard.push_str(&format!("\n{} other shards down", others_down));
+    }
+
+    let cooldowns: Vec<String> = data
+        .cooldowns()
+        .into_iter()
+        .filter(|(channel_id, _, _)| {
+            ctx.cache.guild(guild_id).is_some_and(|guild| {
+                guild.channels.contains_key(channel_id)
+                    || guild.threads.iter().any(|thread| thread.id == *channel_id)
+            })
+        })
+        .map(|(channel_id, remaining, rate_limited)| {
+            format!(
+                "<#{}> ({}s{})",
+                channel_id,
+                remaining.as_secs().max(1),
+                if rate_limited { ", rate limited" } else { "" }
+            )
+        })
+        .collect();
+
+    let cap = data.config().threshold(Some(guild_id));
+    let guild_config = data.guild_config(guild_id);
+    let emoji = data
+        .get_number_emoji(cap)
+        .map_or_else(|| "none".to_string(), |emoji| emoji.to_string());
+
+    let (reaction_depth, reaction_peak) = handler.reaction_queue_depth();
+    let budget = match data.guild_pin_budget(guild_id) {
+        (used, 0, _) => format!("{} this minute, unlimited", used),
+        (used, budget, None) => format!("{}/{} this minute", used, budget),
+        (used, budget, Some(wait)) => format!(
+            "{}/{} this minute, next pin in {}s",
+            used,
+            budget,
+            wait.as_secs().max(1)
+        ),
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Pin bot status")
+        .field("Uptime", format_duration(data.uptime()), true)
+        .field("Gateway latency", latency, true)
+        .field("Shard", shard, true)
+        .field(
+            "Active votes",
+            data.active_sessions_in(guild_id).to_string(),
+            true,
+        )
+        .field("Threshold", format!("{} {}", cap, emoji), true)
+        .field(
+            "Channel thresholds",
+            super::config::channel_thresholds(&guild_config.channel_thresholds),
+            true,
+        )
+        .field("Vote mode", format!("{:?}", guild_config.vote_mode), true)
+        .field("Pin queue", data.pin_queue_depth().to_string(), true)
+        .field("Pin budget", budget, true)
+        .field(
+            "Reaction queue",
+            format!("{} (peak {})", reaction_depth, reaction_peak),
+            true,
+        )
+        .field(
+            "Dry run",
+            if data.dry_run() {
+                "on, pins are only logged"
+            } else {
+                "off"
+            },
+            true,
+        )
+        .field(
+            "Locked messages",
+            guild_config.locked_messages.len().to_string(),
+            true,
+        )
+        .field(
+            "Pin cooldowns",
+            if cooldowns.is_empty() {
+                "none".to_string()
+            } else if cooldowns.len() > MAX_LISTED_COOLDOWNS {
+                format!(
+                    "{} +{} more",
+                    cooldowns[..MAX_LISTED_COOLDOWNS].join(", "),
+                    cooldowns.len() - MAX_LISTED_COOLDOWNS
+                )
+            } else {
+                cooldowns.join(", ")
+            },
+            false,
+        );
+
+    command
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .ephemeral(true),
+            ),
+        )
+        .await
+}
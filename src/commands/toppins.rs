@@ -0,0 +1,215 @@
+use super::{member_permissions_in, respond_ephemeral, respond_paged};
+use crate::{pagination::Paginator, store::PinRecord, Handler};
+use serenity::all::{
+    ChannelId, CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    GuildId, ResolvedValue, Timestamp,
+};
+use std::{collections::HashMap, fmt::Write as _};
+use tracing::error;
+
+/// Pins listed when the command doesn't say, and the most it may ask for.
+const DEFAULT_TOP_PINS: usize = 10;
+const MAX_TOP_PINS: usize = 25;
+/// Pins read from the history before dropping the ones in channels the
+/// member can't see.
+const TOP_PINS_SCAN: usize = 200;
+const PINS_PER_PAGE: usize = 10;
+
+/// The `period` choices, with how far back each reaches.
+const PERIODS: [(&str, i64); 4] = [
+    ("day", 86_400),
+    ("week", 7 * 86_400),
+    ("month", 30 * 86_400),
+    ("year", 365 * 86_400),
+];
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("top-pins")
+        .description("Show the pins that got the most votes")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Integer,
+                "count",
+                "How many pins to show (10 by default)",
+            )
+            .min_int_value(1)
+            .max_int_value(MAX_TOP_PINS as u64),
+        )
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Channel,
+            "channel",
+            "Only pins in this channel",
+        ))
+        .add_option(PERIODS.iter().fold(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "period",
+                "Only pins from the last day, week, month or year (all time by default)",
+            ),
+            |option, (name, _)| option.add_string_choice(*name, *name),
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "public",
+            "Post the list for everyone instead of just you",
+        ))
+}
+
+/// Renders from the history alone: a jump link works even when its message
+/// was deleted, so nothing is fetched.
+pub async fn run(
+    ctx: &Context,
+    handler: &Handler,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let count = options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::Integer(count) if option.name == "count" => usize::try_from(count).ok(),
+            _ => None,
+        })
+        .unwrap_or(DEFAULT_TOP_PINS)
+        .clamp(1, MAX_TOP_PINS);
+    let channel_id = options.iter().find_map(|option| match &option.value {
+        ResolvedValue::Channel(channel) if option.name == "channel" => Some(channel.id),
+        _ => None,
+    });
+    let period = options.iter().find_map(|option| match option.value {
+        ResolvedValue::String(name) if option.name == "period" => {
+            PERIODS.iter().find(|(period, _)| *period == name).copied()
+        }
+        _ => None,
+    });
+    let public = options.iter().any(|option| {
+        option.name == "public" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+
+    let since = period.map(|(_, secs)| Timestamp::now().unix_timestamp() - secs);
+    let mut pins = match handler
+        .data
+        .store
+        .top_pins(guild_id, channel_id, since, TOP_PINS_SCAN)
+    {
+        Ok(pins) => pins,
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to load the top pins");
+            return respond_ephemeral(ctx, command, "Failed to load the pin history.").await;
+        }
+    };
+    // Don't reveal pins in channels the member can't read
+    let mut visible: HashMap<ChannelId, bool> = HashMap::new();
+    pins.retain(|pin| {
+        *visible.entry(pin.channel_id).or_insert_with(|| {
+            member_permissions_in(ctx, command, guild_id, pin.channel_id)
+                .is_some_and(|permissions| permissions.view_channel())
+        })
+    });
+    pins.truncate(count);
+    if pins.is_empty() {
+        return respond_ephemeral(ctx, command, "No voted pins to show yet.").await;
+    }
+
+    let entries = pins
+        .iter()
+        .enumerate()
+        .map(|(index, pin)| entry(guild_id, index + 1, pin))
+        .collect();
+    let mut title = "Top pins".to_string();
+    if let Some(channel_id) = channel_id {
+        let _ = write!(title, " in #{}", channel_name(ctx, guild_id, channel_id));
+    }
+    if let Some((period, _)) = period {
+        let _ = write!(title, " of the past {}", period);
+    }
+    let paginator = Paginator::new(command.user.id, title, entries, PINS_PER_PAGE);
+    respond_paged(ctx, handler, command, paginator, !public).await
+}
+
+/// One ranked pin: author, votes, jump link and when it was pinned. The
+/// voter count shows only when weights made it differ from the votes.
+fn entry(guild_id: GuildId, rank: usize, pin: &PinRecord) -> String {
+    let author = pin
+        .author_id
+        .map_or_else(|| "*someone*".to_string(), |id| format!("<@{id}>"));
+    let mut entry = format!(
+        "**{}.** {} · {} {}",
+        rank,
+        author,
+        pin.votes,
+        if pin.votes == 1 { "vote" } else { "votes" }
+    );
+    if pin.voters > 0 && pin.voters != pin.votes {
+        let _ = write!(
+            entry,
+            " from {} {}",
+            pin.voters,
+            if pin.voters == 1 { "voter" } else { "voters" }
+        );
+    }
+    let _ = write!(
+        entry,
+        " · [Jump]({}) · <t:{}:d>",
+        pin.message_id.link(pin.channel_id, Some(guild_id)),
+        pin.pinned_at
+    );
+    let excerpt = if pin.excerpt.is_empty() {
+        "*(no text)*"
+    } else {
+        &pin.excerpt
+    };
+    let _ = write!(entry, "\n> {}", excerpt);
+    entry
+}
+
+fn channel_name(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> String {
+    ctx.cache
+        .guild(guild_id)
+        .and_then(|guild| {
+            guild
+                .channels
+                .get(&channel_id)
+                .map(|channel| channel.name.clone())
+        })
+        .unwrap_or_else(|| channel_id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::all::{MessageId, UserId};
+
+    #[test]
+    fn entries_show_voters_only_when_weights_count() {
+        let mut pin = PinRecord {
+            guild_id: GuildId::new(1),
+            channel_id: ChannelId::new(2),
+            message_id: MessageId::new(3),
+            author_id: Some(UserId::new(4)),
+            excerpt: "best meme".to_string(),
+            votes: 5,
+            voters: 5,
+            pinned_at: 1_700_000_000,
+            reason: None,
+        };
+        assert_eq!(
+            entry(GuildId::new(1), 1, &pin),
+            "**1.** <@4> · 5 votes · [Jump](https://discord.com/channels/1/2/3) · \
+             <t:1700000000:d>\n> best meme"
+        );
+
+        pin.voters = 3;
+        pin.author_id = None;
+        pin.excerpt.clear();
+        assert_eq!(
+            entry(GuildId::new(1), 2, &pin),
+            "**2.** *someone* · 5 votes from 3 voters · \
+             [Jump](https://discord.com/channels/1/2/3) · <t:1700000000:d>\n> *(no text)*"
+        );
+    }
+}
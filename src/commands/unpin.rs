@@ -0,0 +1,78 @@
+use super::{linked_message, member_permissions_in, message_option, respond_ephemeral};
+use crate::{BotData, UnpinOutcome};
+use serenity::all::{CommandInteraction, Context, CreateCommand, HttpError, StatusCode};
+use tracing::warn;
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("unpin")
+        .description("Unpin a message")
+        .dm_permission(false)
+        .add_option(message_option())
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let target = match linked_message(ctx, command, guild_id).await {
+        Ok(target) => target,
+        Err(refusal) => return respond_ephemeral(ctx, command, refusal).await,
+    };
+
+    let channel_id = target.channel_id;
+    if !member_permissions_in(ctx, command, guild_id, channel_id)
+        .is_some_and(|permissions| permissions.manage_messages())
+    {
+        return respond_ephemeral(
+            ctx,
+            command,
+            format!("You need Manage Messages in <#{}> to do that.", channel_id),
+        )
+        .await;
+    }
+
+    if !target.pinned {
+        return respond_ephemeral(ctx, command, "That message isn't pinned.").await;
+    }
+
+    let outcome = data
+        .unpin_message_safely(ctx.http.as_ref(), channel_id, target.id)
+        .await;
+    let unpinned = matches!(outcome, UnpinOutcome::Unpinned);
+    let reply = match outcome {
+        UnpinOutcome::Unpinned => data.dry_run_tagged(format!(
+            "Unpinned {}.",
+            target.id.link(channel_id, Some(guild_id))
+        )),
+        UnpinOutcome::CoolingDown => "Slow down a little, try again in a few seconds.".to_string(),
+        UnpinOutcome::Failed(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+            if response.status_code == StatusCode::FORBIDDEN =>
+        {
+            format!(
+                "I need Manage Messages in <#{}> to unpin there.",
+                channel_id
+            )
+        }
+        UnpinOutcome::Failed(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+            if response.status_code == StatusCode::NOT_FOUND =>
+        {
+            "That message is gone or no longer pinned.".to_string()
+        }
+        UnpinOutcome::Failed(e) => {
+            warn!(message_id = %target.id, error = %e, "Failed to unpin");
+            "I couldn't unpin that message right now.".to_string()
+        }
+    };
+
+    let response = respond_ephemeral(ctx, command, reply).await;
+    if unpinned {
+        data.refresh_pin_index(&ctx.http, Some(guild_id), channel_id)
+            .await;
+    }
+    response
+}
@@ -0,0 +1,125 @@
+use super::respond_ephemeral;
+use crate::{vote_stats::VoteSummary, BotData};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedValue, Timestamp,
+};
+use std::fmt::Write as _;
+use tracing::{error, info};
+
+/// The `period` choices, with how far back each reaches. The week is the
+/// default.
+const PERIODS: [(&str, i64); 4] = [
+    ("day", 86_400),
+    ("week", 7 * 86_400),
+    ("month", 30 * 86_400),
+    ("year", 365 * 86_400),
+];
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("votestats")
+        .description("Show how this server's votes went")
+        .dm_permission(false)
+        .add_option(PERIODS.iter().fold(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "period",
+                "Votes that ended in the last day, week, month or year (a week by default)",
+            ),
+            |option, (name, _)| option.add_string_choice(*name, *name),
+        ))
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::Boolean,
+            "weekly-summary",
+            "Also write the week's stats to the audit channel when the digest goes out",
+        ))
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let (period, secs) = options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::String(name) if option.name == "period" => {
+                PERIODS.iter().find(|(period, _)| *period == name).copied()
+            }
+            _ => None,
+        })
+        .unwrap_or(PERIODS[1]);
+    let weekly_summary = options.iter().find_map(|option| match option.value {
+        ResolvedValue::Boolean(value) if option.name == "weekly-summary" => Some(value),
+        _ => None,
+    });
+
+    let now = Timestamp::now().unix_timestamp();
+    let stats = match data
+        .store
+        .vote_stats_between(guild_id, now - secs, i64::MAX)
+    {
+        Ok(stats) => stats,
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to load vote stats");
+            return respond_ephemeral(ctx, command, "Failed to load the vote stats.").await;
+        }
+    };
+    let mut reply = if stats.is_empty() {
+        format!("No votes ended in the past {}.", period)
+    } else {
+        let summary = VoteSummary::of(&stats);
+        let mut reply = format!("**Past {}:** {}.", period, summary.line());
+        let peak = stats.iter().map(|vote| vote.peak_votes).max().unwrap_or(0);
+        if peak > 0 {
+            let _ = write!(reply, "\nThe busiest vote peaked at {} votes.", peak);
+        }
+        if summary.removals > 0 {
+            let _ = write!(
+                reply,
+                "\n{} {} taken back while votes were open.",
+                summary.removals,
+                if summary.removals == 1 {
+                    "vote was"
+                } else {
+                    "votes were"
+                }
+            );
+        }
+        reply
+    };
+
+    if let Some(enabled) = weekly_summary {
+        match data.update_guild_config(guild_id, |config| config.weekly_vote_stats = enabled) {
+            Ok(config) => {
+                info!(guild_id = %guild_id, enabled, "Weekly vote stats changed");
+                reply.push_str(
+                    match (
+                        enabled,
+                        config.digest.is_some(),
+                        config.audit_channel.is_some(),
+                    ) {
+                        (false, _, _) => "\n\nThe weekly summary is off.",
+                        (true, true, true) => {
+                            "\n\nThe week's stats go to the audit channel with each digest."
+                        }
+                        (true, _, _) => {
+                            "\n\nThe week's stats go to the audit channel with each digest, once \
+                         both are set up with `/config`."
+                        }
+                    },
+                );
+            }
+            Err(e) => {
+                error!(guild_id = %guild_id, error = %e, "Failed to save weekly vote stats");
+                reply.push_str("\n\nFailed to save the weekly summary setting, please try again.");
+            }
+        }
+    }
+    respond_ephemeral(ctx, command, reply).await
+}
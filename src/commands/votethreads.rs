@@ -0,0 +1,76 @@
+use super::respond_ephemeral;
+use crate::{guild_config::DEFAULT_VOTE_THREAD_NAME, BotData};
+use serenity::all::{
+    CommandInteraction, CommandOptionType, Context, CreateCommand, CreateCommandOption,
+    ResolvedValue,
+};
+use tracing::{error, info};
+
+pub fn definition() -> CreateCommand {
+    CreateCommand::new("votethreads")
+        .description("Open a discussion thread on every pin vote")
+        .dm_permission(false)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "enabled",
+                "Whether new votes get a thread",
+            )
+            .required(true),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "name",
+                "Thread name; {excerpt} is the message's text (default \"Pin vote: {excerpt}\")",
+            )
+            .max_length(100),
+        )
+}
+
+pub async fn run(
+    ctx: &Context,
+    data: &BotData,
+    command: &CommandInteraction,
+) -> serenity::Result<()> {
+    let Some(guild_id) = command.guild_id else {
+        return respond_ephemeral(ctx, command, "This command only works in servers.").await;
+    };
+
+    let options = command.data.options();
+    let enabled = options.iter().any(|option| {
+        option.name == "enabled" && matches!(option.value, ResolvedValue::Boolean(true))
+    });
+    let name = options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::String(name) if option.name == "name" => Some(name.trim()),
+            _ => None,
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or(DEFAULT_VOTE_THREAD_NAME)
+        .to_string();
+
+    let template = enabled.then_some(name);
+    let result = data.update_guild_config(guild_id, |config| {
+        config.vote_thread_name = template.clone();
+    });
+    let reply = match result {
+        Ok(_) => {
+            info!(guild_id = %guild_id, template = ?template, "Vote threads changed");
+            match template {
+                Some(template) => format!(
+                    "New votes get a discussion thread named `{}`, archived when the vote ends. \
+                     The bot needs Create Public Threads where votes happen.",
+                    template
+                ),
+                None => "New votes no longer get a discussion thread.".to_string(),
+            }
+        }
+        Err(e) => {
+            error!(guild_id = %guild_id, error = %e, "Failed to save vote threads");
+            "Failed to save the configuration, please try again.".to_string()
+        }
+    };
+    respond_ephemeral(ctx, command, reply).await
+}
@@ -0,0 +1,1001 @@
+use crate::{duration, emoji};
+use anyhow::{bail, Context as _, Result};
+use serde::Deserialize;
+use serenity::all::{ChannelId, EmojiId, GuildId, ReactionType, UserId};
+use std::{
+    collections::{HashMap, HashSet},
+    env, fs,
+    path::Path,
+    str::FromStr,
+    time::Duration,
+};
+
+/// Highest vote threshold, matching the number emojis available.
+pub const MAX_CONFIRM_CAP: u32 = 10;
+
+/// Shortest `shard_stale_secs` other than off: two of Discord's heartbeat
+/// intervals of about 41 seconds, and three of the monitor's polls. Anything
+/// shorter flags healthy shards and restarts them over and over.
+pub const MIN_SHARD_STALE_SECS: u64 = 90;
+
+/// Which emoji set is used for the approvals-needed reaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberEmojiStyle {
+    /// Custom server emojis, falling back to keycaps when they can't be used.
+    #[default]
+    Custom,
+    /// Standard Unicode keycaps only.
+    Unicode,
+}
+
+impl FromStr for NumberEmojiStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "custom" => Ok(Self::Custom),
+            "unicode" => Ok(Self::Unicode),
+            other => Err(format!(
+                "expected \"custom\" or \"unicode\", got {:?}",
+                other
+            )),
+        }
+    }
+}
+
+/// What happens to a shard that has gone quiet for `shard_stale_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StaleShardAction {
+    /// Restart the shard in place.
+    #[default]
+    Restart,
+    /// Quit with `shard_monitor::STALE_SHARD_EXIT_CODE` and leave the
+    /// restart to the supervisor.
+    Exit,
+}
+
+impl FromStr for StaleShardAction {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "restart" => Ok(Self::Restart),
+            "exit" => Ok(Self::Exit),
+            other => Err(format!("expected \"restart\" or \"exit\", got {:?}", other)),
+        }
+    }
+}
+
+/// Process-wide settings. Each value comes from, in order of precedence,
+/// the command line, the environment, the `CONFIG_PATH` file and the
+/// defaults below.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub confirm_cap: u32,
+    pub database_path: String,
+    /// Where `/backup` saves snapshots too large to upload. Without it they
+    /// can only be uploaded.
+    pub backup_dir: Option<String>,
+    pub allowed_channels: HashSet<ChannelId>,
+    pub denied_channels: HashSet<ChannelId>,
+    /// Guilds the bot may operate in. Empty means every guild.
+    pub allowed_guilds: HashSet<GuildId>,
+    /// Votes from accounts younger than this are ignored. 0 disables the check.
+    pub min_account_age_days: u64,
+    /// Votes from members who joined more recently than this are ignored.
+    /// 0 disables the check.
+    pub min_member_age_hours: u64,
+    /// Register application commands per guild instead of globally.
+    pub register_guild_commands: bool,
+    /// Run without the Message Content intent: no `@mention` requests,
+    /// only the application commands and reaction votes. Also switched on
+    /// at startup when the application lacks the intent.
+    pub interaction_only: bool,
+    /// Watch members leaving to take back their votes. Needs the privileged
+    /// Server Members intent, so it is off by default.
+    pub drop_departed_votes: bool,
+    /// Run every vote as usual but only log the pins and unpins instead of
+    /// making them, for trying the bot out on a real server.
+    pub dry_run: bool,
+    /// Take edits of a vote's target into the snapshot that its archive
+    /// copy and pin history show. Off, they show the target as it was when
+    /// the vote opened.
+    pub refresh_target_snapshots: bool,
+    /// Users whose pin requests skip voting everywhere.
+    pub superusers: HashSet<UserId>,
+    /// Users who may inspect and reload the config through the owner
+    /// commands, besides the application's own owners.
+    pub owner_ids: HashSet<UserId>,
+    /// Bots and webhooks whose mentions may start a vote. They still never
+    /// count as voters.
+    pub trusted_bots: HashSet<UserId>,
+    /// Show live stats in the bot's presence (`PRESENCE=off` disables it).
+    pub presence_enabled: bool,
+    /// Presence text; `{votes}` and `{pins}` are replaced with live counts.
+    pub presence_format: String,
+    pub number_emoji_style: NumberEmojiStyle,
+    /// Custom emojis for 1 through 10, in order. Empty means the bot's
+    /// own application emojis are used.
+    pub number_emojis: Vec<ReactionType>,
+    /// Receives a JSON POST after every pin when set.
+    pub webhook_url: Option<String>,
+    /// Also post when votes start and expire.
+    pub webhook_vote_events: bool,
+    /// Open votes allowed at once in one channel.
+    pub max_sessions_per_channel: usize,
+    /// Open votes allowed at once overall; the oldest is evicted beyond it.
+    pub max_sessions: usize,
+    /// Open votes one member may have started at once; moderators are
+    /// exempt. 0 is unlimited.
+    pub max_sessions_per_user: usize,
+    /// How long a vote stays open.
+    pub vote_timeout_secs: u64,
+    /// Bounds for the vote length a request may choose instead.
+    pub min_vote_timeout_secs: u64,
+    pub max_vote_timeout_secs: u64,
+    /// How long a passed vote waits for a moderator's sign-off in channels
+    /// that require one.
+    pub approval_timeout_secs: u64,
+    /// Gap between pins or unpins in one channel around a rate limit:
+    /// after one that didn't say how long to wait, and after the call that
+    /// follows one. Calm calls wait at most a second.
+    pub pin_cooldown_secs: u64,
+    /// Most pins one guild gets per minute, on top of the channel cooldown;
+    /// pins beyond it wait their turn. 0 is unlimited.
+    pub guild_pins_per_minute: u32,
+    /// How often expired votes, cooldowns and temporary pins are swept.
+    pub cleanup_interval_secs: u64,
+    /// How long a shard may go without a heartbeat before it is recovered.
+    /// 0 only logs outages.
+    pub shard_stale_secs: u64,
+    pub shard_stale_action: StaleShardAction,
+    /// Per-guild overrides, only settable from the config file.
+    pub guilds: HashMap<GuildId, GuildOverrides>,
+}
+
+/// A `[guilds.<id>]` section of the config file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GuildOverrides {
+    /// Replaces the global threshold in this guild.
+    pub confirm_cap: Option<u32>,
+    /// Replaces the global pins per minute in this guild.
+    pub pins_per_minute: Option<u32>,
+    /// Added to the global channel lists for this guild.
+    pub allowed_channels: HashSet<ChannelId>,
+    pub denied_channels: HashSet<ChannelId>,
+    /// Added to the global trusted bots for this guild.
+    pub trusted_bots: HashSet<UserId>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            confirm_cap: 3,
+            database_path: "pin-bot.db".to_string(),
+            backup_dir: None,
+            allowed_channels: HashSet::new(),
+            denied_channels: HashSet::new(),
+            allowed_guilds: HashSet::new(),
+            min_account_age_days: 0,
+            min_member_age_hours: 0,
+            register_guild_commands: false,
+            interaction_only: false,
+            drop_departed_votes: false,
+            dry_run: false,
+            refresh_target_snapshots: false,
+            superusers: HashSet::new(),
+            owner_ids: HashSet::new(),
+            trusted_bots: HashSet::new(),
+            presence_enabled: true,
+            presence_format: "{votes} active votes · {pins} pins served".to_string(),
+            number_emoji_style: NumberEmojiStyle::default(),
+            number_emojis: Vec::new(),
+            webhook_url: None,
+            webhook_vote_events: false,
+            max_sessions_per_channel: 5,
+            max_sessions: 1000,
+            max_sessions_per_user: 3,
+            vote_timeout_secs: 3600,
+            min_vote_timeout_secs: 60,
+            max_vote_timeout_secs: 7 * 86_400,
+            approval_timeout_secs: 86_400,
+            pin_cooldown_secs: 5,
+            guild_pins_per_minute: 10,
+            cleanup_interval_secs: 300,
+            shard_stale_secs: 300,
+            shard_stale_action: StaleShardAction::default(),
+            guilds: HashMap::new(),
+        }
+    }
+}
+
+/// The TOML config file. Keys are the lower-case names of the environment
+/// variables; anything unknown is rejected so typos don't go unnoticed.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    confirm_cap: Option<u32>,
+    database_path: Option<String>,
+    backup_dir: Option<String>,
+    allowed_channels: Option<HashSet<ChannelId>>,
+    denied_channels: Option<HashSet<ChannelId>>,
+    allowed_guilds: Option<HashSet<GuildId>>,
+    min_account_age_days: Option<u64>,
+    min_member_age_hours: Option<u64>,
+    register_guild_commands: Option<bool>,
+    interaction_only: Option<bool>,
+    drop_departed_votes: Option<bool>,
+    dry_run: Option<bool>,
+    refresh_target_snapshots: Option<bool>,
+    superusers: Option<HashSet<UserId>>,
+    owner_ids: Option<HashSet<UserId>>,
+    trusted_bots: Option<HashSet<UserId>>,
+    presence: Option<bool>,
+    presence_format: Option<String>,
+    number_emoji_style: Option<String>,
+    number_emoji_ids: Option<Vec<String>>,
+    webhook_url: Option<String>,
+    webhook_vote_events: Option<bool>,
+    max_sessions_per_channel: Option<usize>,
+    max_sessions: Option<usize>,
+    max_sessions_per_user: Option<usize>,
+    vote_timeout_secs: Option<u64>,
+    min_vote_timeout_secs: Option<u64>,
+    max_vote_timeout_secs: Option<u64>,
+    approval_timeout_secs: Option<u64>,
+    pin_cooldown_secs: Option<u64>,
+    guild_pins_per_minute: Option<u32>,
+    cleanup_interval_secs: Option<u64>,
+    shard_stale_secs: Option<u64>,
+    shard_stale_action: Option<String>,
+    guilds: HashMap<GuildId, GuildOverrides>,
+}
+
+impl FileConfig {
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+}
+
+impl Config {
+    /// Loads the optional config file, then applies the environment on top.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let file = path.map(FileConfig::read).transpose()?;
+        Self::resolve(file, &|name| env::var(name).ok())
+    }
+
+    /// Layers `env` over `file` over the defaults. `env` looks up a
+    /// variable by name.
+    pub fn resolve(file: Option<FileConfig>, env: &dyn Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = Self::default();
+        if let Some(file) = file {
+            config.apply_file(file)?;
+        }
+        config.apply_env(env)?;
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, file: FileConfig) -> Result<()> {
+        if let Some(cap) = file.confirm_cap {
+            check_cap("confirm_cap", cap)?;
+            self.confirm_cap = cap;
+        }
+        for (guild_id, overrides) in &file.guilds {
+            if let Some(cap) = overrides.confirm_cap {
+                check_cap(&format!("guilds.{}.confirm_cap", guild_id), cap)?;
+            }
+        }
+        if let Some(style) = file.number_emoji_style {
+            self.number_emoji_style = style
+                .parse()
+                .map_err(|e| anyhow::anyhow!("number_emoji_style is invalid: {}", e))?;
+        }
+        if let Some(action) = file.shard_stale_action {
+            self.shard_stale_action = action
+                .parse()
+                .map_err(|e| anyhow::anyhow!("shard_stale_action is invalid: {}", e))?;
+        }
+        if let Some(ids) = file.number_emoji_ids {
+            self.number_emojis = parse_number_emojis("number_emoji_ids", &ids.join(","))?;
+        }
+
+        macro_rules! take {
+            ($($field:ident),* $(,)?) => {
+                $(if let Some(value) = file.$field {
+                    self.$field = value;
+                })*
+            };
+        }
+        take!(
+            database_path,
+            allowed_channels,
+            denied_channels,
+            allowed_guilds,
+            min_account_age_days,
+            min_member_age_hours,
+            register_guild_commands,
+            interaction_only,
+            drop_departed_votes,
+            dry_run,
+            refresh_target_snapshots,
+            superusers,
+            owner_ids,
+            trusted_bots,
+            presence_format,
+            webhook_vote_events,
+            max_sessions_per_channel,
+            max_sessions,
+            max_sessions_per_user,
+            vote_timeout_secs,
+            min_vote_timeout_secs,
+            max_vote_timeout_secs,
+            approval_timeout_secs,
+            pin_cooldown_secs,
+            guild_pins_per_minute,
+            cleanup_interval_secs,
+            shard_stale_secs,
+        );
+        if let Some(presence) = file.presence {
+            self.presence_enabled = presence;
+        }
+        if let Some(dir) = file.backup_dir {
+            self.backup_dir = Some(dir).filter(|dir| !dir.trim().is_empty());
+        }
+        if let Some(url) = file.webhook_url {
+            self.webhook_url = Some(url).filter(|url| !url.trim().is_empty());
+        }
+        self.guilds = file.guilds;
+
+        check_limits(self, &Keys::FILE)
+    }
+
+    fn apply_env(&mut self, env: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+        let confirm_cap: u32 = parse_or(env, "CONFIRM_CAP", self.confirm_cap)
+            .context("CONFIRM_CAP must be a valid number")?;
+        check_cap("CONFIRM_CAP", confirm_cap)?;
+        self.confirm_cap = confirm_cap;
+
+        if let Some(raw) = env("NUMBER_EMOJI_IDS").filter(|raw| !raw.trim().is_empty()) {
+            self.number_emojis = parse_number_emojis("NUMBER_EMOJI_IDS", &raw)?;
+        }
+        if let Some(path) = env("DATABASE_PATH") {
+            self.database_path = path;
+        }
+        if let Some(dir) = env("BACKUP_DIR") {
+            self.backup_dir = Some(dir).filter(|dir| !dir.trim().is_empty());
+        }
+        if let Some(presence) = env("PRESENCE") {
+            self.presence_enabled = !presence.eq_ignore_ascii_case("off");
+        }
+        if let Some(format) = env("PRESENCE_FORMAT") {
+            self.presence_format = format;
+        }
+        if let Some(url) = env("WEBHOOK_URL") {
+            self.webhook_url = Some(url).filter(|url| !url.trim().is_empty());
+        }
+
+        self.allowed_channels = parse_id_list(env, "ALLOWED_CHANNELS", &self.allowed_channels)?;
+        self.denied_channels = parse_id_list(env, "DENIED_CHANNELS", &self.denied_channels)?;
+        self.allowed_guilds = parse_id_list(env, "ALLOWED_GUILDS", &self.allowed_guilds)?;
+        self.superusers = parse_id_list(env, "SUPERUSERS", &self.superusers)?;
+        self.owner_ids = parse_id_list(env, "OWNER_IDS", &self.owner_ids)?;
+        self.trusted_bots = parse_id_list(env, "TRUSTED_BOTS", &self.trusted_bots)?;
+        self.min_account_age_days =
+            parse_or(env, "MIN_ACCOUNT_AGE_DAYS", self.min_account_age_days)?;
+        self.min_member_age_hours =
+            parse_or(env, "MIN_MEMBER_AGE_HOURS", self.min_member_age_hours)?;
+        self.register_guild_commands =
+            parse_or(env, "REGISTER_GUILD_COMMANDS", self.register_guild_commands)?;
+        self.interaction_only = parse_or(env, "INTERACTION_ONLY", self.interaction_only)?;
+        self.drop_departed_votes = parse_or(env, "DROP_DEPARTED_VOTES", self.drop_departed_votes)?;
+        self.dry_run = parse_or(env, "DRY_RUN", self.dry_run)?;
+        self.refresh_target_snapshots = parse_or(
+            env,
+            "REFRESH_TARGET_SNAPSHOTS",
+            self.refresh_target_snapshots,
+        )?;
+        self.number_emoji_style = parse_or(env, "NUMBER_EMOJI_STYLE", self.number_emoji_style)?;
+        self.webhook_vote_events = parse_or(env, "WEBHOOK_VOTE_EVENTS", self.webhook_vote_events)?;
+        self.max_sessions_per_channel = parse_or(
+            env,
+            "MAX_SESSIONS_PER_CHANNEL",
+            self.max_sessions_per_channel,
+        )?;
+        self.max_sessions = parse_or(env, "MAX_SESSIONS", self.max_sessions)?;
+        self.max_sessions_per_user =
+            parse_or(env, "MAX_SESSIONS_PER_USER", self.max_sessions_per_user)?;
+        self.vote_timeout_secs = parse_or(env, "VOTE_TIMEOUT_SECS", self.vote_timeout_secs)?;
+        self.min_vote_timeout_secs =
+            parse_or(env, "MIN_VOTE_TIMEOUT_SECS", self.min_vote_timeout_secs)?;
+        self.max_vote_timeout_secs =
+            parse_or(env, "MAX_VOTE_TIMEOUT_SECS", self.max_vote_timeout_secs)?;
+        self.approval_timeout_secs =
+            parse_or(env, "APPROVAL_TIMEOUT_SECS", self.approval_timeout_secs)?;
+        self.pin_cooldown_secs = parse_or(env, "PIN_COOLDOWN_SECS", self.pin_cooldown_secs)?;
+        self.guild_pins_per_minute =
+            parse_or(env, "GUILD_PINS_PER_MINUTE", self.guild_pins_per_minute)?;
+        self.cleanup_interval_secs =
+            parse_or(env, "CLEANUP_INTERVAL_SECS", self.cleanup_interval_secs)?;
+        self.shard_stale_secs = parse_or(env, "SHARD_STALE_SECS", self.shard_stale_secs)?;
+        self.shard_stale_action = parse_or(env, "SHARD_STALE_ACTION", self.shard_stale_action)?;
+
+        check_limits(self, &Keys::ENV)
+    }
+
+    /// Votes needed in `guild_id`, honouring its config file section.
+    pub fn threshold(&self, guild_id: Option<GuildId>) -> u32 {
+        guild_id
+            .and_then(|guild_id| self.guilds.get(&guild_id))
+            .and_then(|overrides| overrides.confirm_cap)
+            .unwrap_or(self.confirm_cap)
+    }
+
+    /// Pins per minute allowed in `guild_id`, honouring its config file
+    /// section. 0 is unlimited.
+    pub fn guild_pin_budget(&self, guild_id: GuildId) -> u32 {
+        self.guilds
+            .get(&guild_id)
+            .and_then(|overrides| overrides.pins_per_minute)
+            .unwrap_or(self.guild_pins_per_minute)
+    }
+
+    /// Parses the vote length a request asked for, kept within the
+    /// configured bounds.
+    pub fn vote_timeout(&self, input: &str) -> std::result::Result<Duration, String> {
+        duration::parse_vote_timeout(
+            input,
+            Duration::from_secs(self.min_vote_timeout_secs),
+            Duration::from_secs(self.max_vote_timeout_secs),
+        )
+    }
+
+    /// Whether the bot or webhook `user_id` may start votes in `guild_id`.
+    pub fn is_trusted_bot(&self, guild_id: Option<GuildId>, user_id: UserId) -> bool {
+        self.trusted_bots.contains(&user_id)
+            || guild_id
+                .and_then(|guild_id| self.guilds.get(&guild_id))
+                .is_some_and(|overrides| overrides.trusted_bots.contains(&user_id))
+    }
+
+    /// Every setting by its config file key, with the webhook URL redacted
+    /// since it carries the webhook's token. Guild sections come last, as
+    /// `guilds.<id>.<key>`.
+    pub fn settings(&self) -> Vec<(String, String)> {
+        let mut settings: Vec<(String, String)> = [
+            ("confirm_cap", self.confirm_cap.to_string()),
+            ("database_path", self.database_path.clone()),
+            ("backup_dir", optional(self.backup_dir.as_deref())),
+            ("allowed_channels", id_list(&self.allowed_channels)),
+            ("denied_channels", id_list(&self.denied_channels)),
+            ("allowed_guilds", id_list(&self.allowed_guilds)),
+            (
+                "min_account_age_days",
+                self.min_account_age_days.to_string(),
+            ),
+            (
+                "min_member_age_hours",
+                self.min_member_age_hours.to_string(),
+            ),
+            (
+                "register_guild_commands",
+                self.register_guild_commands.to_string(),
+            ),
+            ("interaction_only", self.interaction_only.to_string()),
+            ("drop_departed_votes", self.drop_departed_votes.to_string()),
+            ("dry_run", self.dry_run.to_string()),
+            (
+                "refresh_target_snapshots",
+                self.refresh_target_snapshots.to_string(),
+            ),
+            ("superusers", id_list(&self.superusers)),
+            ("owner_ids", id_list(&self.owner_ids)),
+            ("trusted_bots", id_list(&self.trusted_bots)),
+            ("presence", self.presence_enabled.to_string()),
+            ("presence_format", format!("{:?}", self.presence_format)),
+            (
+                "number_emoji_style",
+                format!("{:?}", self.number_emoji_style).to_lowercase(),
+            ),
+            (
+                "number_emoji_ids",
+                if self.number_emojis.is_empty() {
+                    "none".to_string()
+                } else {
+                    self.number_emojis
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                },
+            ),
+            (
+                "webhook_url",
+                optional(self.webhook_url.as_ref().map(|_| "(redacted)")),
+            ),
+            ("webhook_vote_events", self.webhook_vote_events.to_string()),
+            (
+                "max_sessions_per_channel",
+                self.max_sessions_per_channel.to_string(),
+            ),
+            ("max_sessions", self.max_sessions.to_string()),
+            (
+                "max_sessions_per_user",
+                self.max_sessions_per_user.to_string(),
+            ),
+            ("vote_timeout_secs", self.vote_timeout_secs.to_string()),
+            (
+                "min_vote_timeout_secs",
+                self.min_vote_timeout_secs.to_string(),
+            ),
+            (
+                "max_vote_timeout_secs",
+                self.max_vote_timeout_secs.to_string(),
+            ),
+            (
+                "approval_timeout_secs",
+                self.approval_timeout_secs.to_string(),
+            ),
+            ("pin_cooldown_secs", self.pin_cooldown_secs.to_string()),
+            (
+                "guild_pins_per_minute",
+                self.guild_pins_per_minute.to_string(),
+            ),
+            (
+                "cleanup_interval_secs",
+                self.cleanup_interval_secs.to_string(),
+            ),
+            ("shard_stale_secs", self.shard_stale_secs.to_string()),
+            (
+                "shard_stale_action",
+                format!("{:?}", self.shard_stale_action).to_lowercase(),
+            ),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect();
+
+        let mut guilds: Vec<_> = self.guilds.iter().collect();
+        guilds.sort_by_key(|(guild_id, _)| **guild_id);
+        for (guild_id, overrides) in guilds {
+            let key = |name: &str| format!("guilds.{}.{}", guild_id, name);
+            if let Some(cap) = overrides.confirm_cap {
+                settings.push((key("confirm_cap"), cap.to_string()));
+            }
+            if let Some(pins) = overrides.pins_per_minute {
+                settings.push((key("pins_per_minute"), pins.to_string()));
+            }
+            for (name, ids) in [
+                ("allowed_channels", id_list(&overrides.allowed_channels)),
+                ("denied_channels", id_list(&overrides.denied_channels)),
+                ("trusted_bots", id_list(&overrides.trusted_bots)),
+            ] {
+                settings.push((key(name), ids));
+            }
+        }
+        settings
+    }
+
+    /// The settings that differ in `next`, each as `key: old → new`, going
+    /// by `settings` so the webhook URL stays redacted.
+    pub fn changes(&self, next: &Config) -> Vec<String> {
+        let old: HashMap<String, String> = self.settings().into_iter().collect();
+        let new = next.settings();
+        let mut changes: Vec<String> = new
+            .iter()
+            .filter(|(key, value)| old.get(key) != Some(value))
+            .map(|(key, value)| {
+                let before = old.get(key).map_or("unset", String::as_str);
+                format!("{}: {} → {}", key, before, value)
+            })
+            .collect();
+        let kept: HashSet<&String> = new.iter().map(|(key, _)| key).collect();
+        let mut removed: Vec<&String> = old.keys().filter(|key| !kept.contains(key)).collect();
+        removed.sort();
+        changes.extend(
+            removed
+                .into_iter()
+                .map(|key| format!("{}: {} → unset", key, old[key])),
+        );
+        changes
+    }
+
+    /// Takes the settings from a reloaded `next` that can change while the
+    /// bot runs, keeping the rest. Returns the merged config and the keys
+    /// whose new values only apply after a restart.
+    pub fn reloaded(&self, next: Config) -> (Config, Vec<&'static str>) {
+        let mut pending = Vec::new();
+        if next.database_path != self.database_path {
+            pending.push("database_path");
+        }
+        if next.register_guild_commands != self.register_guild_commands {
+            pending.push("register_guild_commands");
+        }
+        if next.interaction_only != self.interaction_only {
+            pending.push("interaction_only");
+        }
+        if next.drop_departed_votes != self.drop_departed_votes {
+            pending.push("drop_departed_votes");
+        }
+        if next.dry_run != self.dry_run {
+            pending.push("dry_run");
+        }
+        if next.presence_enabled != self.presence_enabled {
+            pending.push("presence");
+        }
+        if next.webhook_url != self.webhook_url {
+            pending.push("webhook_url");
+        }
+        if next.pin_cooldown_secs != self.pin_cooldown_secs {
+            pending.push("pin_cooldown_secs");
+        }
+        if next.cleanup_interval_secs != self.cleanup_interval_secs {
+            pending.push("cleanup_interval_secs");
+        }
+
+        let merged = Config {
+            database_path: self.database_path.clone(),
+            register_guild_commands: self.register_guild_commands,
+            interaction_only: self.interaction_only,
+            drop_departed_votes: self.drop_departed_votes,
+            dry_run: self.dry_run,
+            presence_enabled: self.presence_enabled,
+            webhook_url: self.webhook_url.clone(),
+            pin_cooldown_secs: self.pin_cooldown_secs,
+            cleanup_interval_secs: self.cleanup_interval_secs,
+            ..next
+        };
+        (merged, pending)
+    }
+}
+
+/// How the limit-checked settings are named in each source, for errors.
+struct Keys {
+    sessions: (&'static str, &'static str),
+    timings: [&'static str; 5],
+    vote_timeouts: (&'static str, &'static str),
+    shard_stale: &'static str,
+}
+
+impl Keys {
+    const FILE: Keys = Keys {
+        sessions: ("max_sessions_per_channel", "max_sessions"),
+        timings: [
+            "vote_timeout_secs",
+            "min_vote_timeout_secs",
+            "approval_timeout_secs",
+            "pin_cooldown_secs",
+            "cleanup_interval_secs",
+        ],
+        vote_timeouts: ("min_vote_timeout_secs", "max_vote_timeout_secs"),
+        shard_stale: "shard_stale_secs",
+    };
+    const ENV: Keys = Keys {
+        sessions: ("MAX_SESSIONS_PER_CHANNEL", "MAX_SESSIONS"),
+        timings: [
+            "VOTE_TIMEOUT_SECS",
+            "MIN_VOTE_TIMEOUT_SECS",
+            "APPROVAL_TIMEOUT_SECS",
+            "PIN_COOLDOWN_SECS",
+            "CLEANUP_INTERVAL_SECS",
+        ],
+        vote_timeouts: ("MIN_VOTE_TIMEOUT_SECS", "MAX_VOTE_TIMEOUT_SECS"),
+        shard_stale: "SHARD_STALE_SECS",
+    };
+}
+
+fn check_cap(key: &str, cap: u32) -> Result<()> {
+    if cap > MAX_CONFIRM_CAP {
+        bail!("{} must be between 0 and {}", key, MAX_CONFIRM_CAP);
+    }
+    Ok(())
+}
+
+fn check_limits(config: &Config, keys: &Keys) -> Result<()> {
+    if config.max_sessions_per_channel == 0 || config.max_sessions == 0 {
+        bail!(
+            "{} and {} must be at least 1",
+            keys.sessions.0,
+            keys.sessions.1
+        );
+    }
+    let timings = [
+        config.vote_timeout_secs,
+        config.min_vote_timeout_secs,
+        config.approval_timeout_secs,
+        config.pin_cooldown_secs,
+        config.cleanup_interval_secs,
+    ];
+    if let Some((key, _)) = keys
+        .timings
+        .iter()
+        .zip(timings)
+        .find(|(_, secs)| *secs == 0)
+    {
+        bail!("{} must be at least 1", key);
+    }
+    if config.max_vote_timeout_secs < config.min_vote_timeout_secs {
+        bail!(
+            "{} must be at least {}",
+            keys.vote_timeouts.1,
+            keys.vote_timeouts.0
+        );
+    }
+    if config.shard_stale_secs != 0 && config.shard_stale_secs < MIN_SHARD_STALE_SECS {
+        bail!(
+            "{} must be 0 or at least {}",
+            keys.shard_stale,
+            MIN_SHARD_STALE_SECS
+        );
+    }
+    Ok(())
+}
+
+/// Parses an env var, falling back to `default` when it is unset or empty.
+fn parse_or<T>(env: &dyn Fn(&str) -> Option<String>, name: &str, default: T) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    match env(name) {
+        Some(raw) if !raw.trim().is_empty() => raw
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("{} is invalid: {}", name, e)),
+        _ => Ok(default),
+    }
+}
+
+/// Parses a comma-separated list of snowflake ids from an env var,
+/// keeping `current` when it is unset. A set but empty variable clears it.
+fn optional(value: Option<&str>) -> String {
+    value.map_or_else(|| "none".to_string(), ToString::to_string)
+}
+
+/// Ids in ascending order, so the same set always reads the same.
+fn id_list<T: ToString + Ord>(ids: &HashSet<T>) -> String {
+    if ids.is_empty() {
+        return "none".to_string();
+    }
+    let mut sorted: Vec<&T> = ids.iter().collect();
+    sorted.sort();
+    sorted
+        .into_iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_id_list<T>(
+    env: &dyn Fn(&str) -> Option<String>,
+    name: &str,
+    current: &HashSet<T>,
+) -> Result<HashSet<T>>
+where
+    T: FromStr + Eq + std::hash::Hash + Clone,
+{
+    let Some(raw) = env(name) else {
+        return Ok(current.clone());
+    };
+
+    Ok(parse_id_seq(name, &raw)?.into_iter().collect())
+}
+
+/// Parses the 10 number emojis, each a bare id or `<:name:id>` markup.
+fn parse_number_emojis(name: &str, raw: &str) -> Result<Vec<ReactionType>> {
+    let emojis = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .zip(1..)
+        .map(|(part, num)| match part.parse::<EmojiId>() {
+            Ok(id) => Ok(emoji::custom_number(num, id)),
+            Err(_) => emoji::parse_custom(part)
+                .ok_or_else(|| anyhow::anyhow!("{} contains an invalid emoji: {:?}", name, part)),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if emojis.len() != 10 {
+        bail!("{} must list exactly 10 emojis, one per number", name);
+    }
+    Ok(emojis)
+}
+
+/// Parses comma-separated ids in order, naming `name` in the error.
+fn parse_id_seq<T: FromStr>(name: &str, raw: &str) -> Result<Vec<T>> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.parse::<T>()
+                .map_err(|_| anyhow::anyhow!("{} contains an invalid id: {:?}", name, part))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env_of(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn resolve(toml: &str, vars: &[(&str, &str)]) -> Result<Config> {
+        Config::resolve(Some(FileConfig::parse(toml)?), &env_of(vars))
+    }
+
+    #[test]
+    fn defaults_apply_without_file_or_env() {
+        let config = Config::resolve(None, &env_of(&[])).unwrap();
+        assert_eq!(config, Config::default());
+    }
+
+    #[test]
+    fn env_overrides_file_and_file_overrides_defaults() {
+        let config = resolve(
+            r#"
+            confirm_cap = 5
+            vote_timeout_secs = 600
+            allowed_channels = [111, "222"]
+            presence = false
+            "#,
+            &[("CONFIRM_CAP", "7"), ("DENIED_CHANNELS", "333")],
+        )
+        .unwrap();
+
+        assert_eq!(config.confirm_cap, 7);
+        assert_eq!(config.vote_timeout_secs, 600);
+        assert_eq!(
+            config.allowed_channels,
+            HashSet::from([ChannelId::new(111), ChannelId::new(222)])
+        );
+        assert_eq!(config.denied_channels, HashSet::from([ChannelId::new(333)]));
+        assert!(!config.presence_enabled);
+        assert_eq!(config.max_sessions, Config::default().max_sessions);
+    }
+
+    #[test]
+    fn an_empty_env_list_clears_the_file_list() {
+        let config = resolve("superusers = [1, 2]", &[("SUPERUSERS", "")]).unwrap();
+        assert!(config.superusers.is_empty());
+    }
+
+    #[test]
+    fn guild_sections_override_the_threshold() {
+        let config = resolve(
+            r#"
+            confirm_cap = 4
+
+            [guilds.1234]
+            confirm_cap = 1
+            denied_channels = [99]
+            trusted_bots = [42]
+            "#,
+            &[],
+        )
+        .unwrap();
+
+        assert_eq!(config.threshold(Some(GuildId::new(1234))), 1);
+        assert_eq!(config.threshold(Some(GuildId::new(5678))), 4);
+        assert_eq!(config.threshold(None), 4);
+        assert_eq!(
+            config.guilds[&GuildId::new(1234)].denied_channels,
+            HashSet::from([ChannelId::new(99)])
+        );
+        assert!(config.is_trusted_bot(Some(GuildId::new(1234)), UserId::new(42)));
+        assert!(!config.is_trusted_bot(Some(GuildId::new(5678)), UserId::new(42)));
+        assert!(!config.is_trusted_bot(None, UserId::new(42)));
+    }
+
+    #[test]
+    fn malformed_files_name_the_offending_key() {
+        for (toml, key) in [
+            ("confim_cap = 3", "confim_cap"),
+            ("confirm_cap = \"three\"", "confirm_cap"),
+            ("confirm_cap = 11", "confirm_cap"),
+            ("max_sessions = 0", "max_sessions"),
+            ("pin_cooldown_secs = 0", "pin_cooldown_secs"),
+            ("max_vote_timeout_secs = 30", "max_vote_timeout_secs"),
+            ("number_emoji_style = \"fancy\"", "number_emoji_style"),
+            ("number_emoji_ids = [\"1\", \"2\"]", "number_emoji_ids"),
+            ("shard_stale_action = \"panic\"", "shard_stale_action"),
+            ("shard_stale_secs = 30", "shard_stale_secs"),
+            ("[guilds.1]\nconfirm_cap = 20", "guilds.1.confirm_cap"),
+            ("[guilds.1]\nthreshold = 2", "threshold"),
+            ("[guilds.not-an-id]", "not-an-id"),
+        ] {
+            let error = format!("{:#}", resolve(toml, &[]).unwrap_err());
+            assert!(error.contains(key), "{:?} gave {:?}", toml, error);
+        }
+        assert!(FileConfig::parse("confirm_cap = ").is_err());
+    }
+
+    #[test]
+    fn stale_shard_recovery_can_exit_or_be_switched_off() {
+        let config = resolve("shard_stale_secs = 0\nshard_stale_action = \"Exit\"", &[]).unwrap();
+        assert_eq!(config.shard_stale_secs, 0);
+        assert_eq!(config.shard_stale_action, StaleShardAction::Exit);
+
+        let config = resolve(
+            "shard_stale_action = \"exit\"",
+            &[("SHARD_STALE_ACTION", "restart")],
+        )
+        .unwrap();
+        assert_eq!(config.shard_stale_action, StaleShardAction::Restart);
+    }
+
+    #[test]
+    fn env_errors_name_the_variable() {
+        let error = resolve("", &[("MAX_SESSIONS", "zero")]).unwrap_err();
+        assert!(error.to_string().contains("MAX_SESSIONS"));
+        let error = resolve("", &[("CONFIRM_CAP", "11")]).unwrap_err();
+        assert!(error.to_string().contains("CONFIRM_CAP"));
+        let error = resolve("", &[("SHARD_STALE_SECS", "45")]).unwrap_err();
+        assert!(error.to_string().contains("SHARD_STALE_SECS"));
+    }
+
+    #[test]
+    fn settings_redact_the_webhook_and_diff_by_key() {
+        let current = resolve(
+            "owner_ids = [9]\nwebhook_url = \"https://example.com/hooks/secret\"",
+            &[("OWNER_IDS", "4,2")],
+        )
+        .unwrap();
+        assert_eq!(
+            current.owner_ids,
+            HashSet::from([UserId::new(2), UserId::new(4)])
+        );
+        let settings = current.settings();
+        assert!(settings.contains(&("owner_ids".to_string(), "2, 4".to_string())));
+        assert!(settings.contains(&("webhook_url".to_string(), "(redacted)".to_string())));
+        assert!(!format!("{:?}", settings).contains("secret"));
+
+        let next = resolve(
+            "confirm_cap = 5\n[guilds.7]\nconfirm_cap = 2",
+            &[("WEBHOOK_URL", "https://example.com/hooks/other")],
+        )
+        .unwrap();
+        assert_eq!(
+            current.changes(&next),
+            [
+                "confirm_cap: 3 → 5",
+                "owner_ids: 2, 4 → none",
+                "guilds.7.confirm_cap: unset → 2",
+                "guilds.7.allowed_channels: unset → none",
+                "guilds.7.denied_channels: unset → none",
+                "guilds.7.trusted_bots: unset → none",
+            ]
+        );
+        assert!(next.changes(&next).is_empty());
+    }
+
+    #[test]
+    fn reloads_keep_restart_only_settings() {
+        let current = Config::default();
+        let next = Config {
+            confirm_cap: 6,
+            allowed_channels: HashSet::from([ChannelId::new(5)]),
+            database_path: "elsewhere.db".to_string(),
+            pin_cooldown_secs: 30,
+            ..Config::default()
+        };
+
+        let (merged, pending) = current.reloaded(next);
+        assert_eq!(merged.confirm_cap, 6);
+        assert_eq!(merged.allowed_channels, HashSet::from([ChannelId::new(5)]));
+        assert_eq!(merged.database_path, current.database_path);
+        assert_eq!(merged.pin_cooldown_secs, current.pin_cooldown_secs);
+        assert_eq!(pending, ["database_path", "pin_cooldown_secs"]);
+    }
+}
@@ -0,0 +1,207 @@
+//! Per-guild rules on what a message must look like to be worth a vote.
+
+use crate::emoji;
+use serde::{Deserialize, Serialize};
+use serenity::all::Message;
+use std::fmt;
+
+/// Longest minimum length a guild may require, Discord's message limit.
+pub const MAX_MIN_LENGTH: u32 = 2000;
+
+/// Every rule is off by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentFilter {
+    /// Fewest characters of text a message needs; 0 is off. Messages with
+    /// an attachment or sticker are left to the rules below.
+    pub min_length: u32,
+    pub reject_sticker_only: bool,
+    pub reject_attachment_only: bool,
+    pub reject_links: bool,
+}
+
+/// The rule a message broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterRejection {
+    TooShort { min_length: u32 },
+    StickerOnly,
+    AttachmentOnly,
+    ContainsLink,
+}
+
+impl fmt::Display for FilterRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooShort { min_length } => write!(
+                f,
+                "Messages need at least {} characters of text to be pinned here.",
+                min_length
+            ),
+            Self::StickerOnly => f.write_str("Sticker-only messages can't be pinned here."),
+            Self::AttachmentOnly => {
+                f.write_str("Messages with only attachments and no text can't be pinned here.")
+            }
+            Self::ContainsLink => f.write_str("Messages with links can't be pinned here."),
+        }
+    }
+}
+
+impl ContentFilter {
+    pub fn check(&self, message: &Message) -> Result<(), FilterRejection> {
+        let text = message.content.trim();
+        let has_attachments = !message.attachments.is_empty();
+        let has_stickers = !message.sticker_items.is_empty();
+
+        if self.reject_sticker_only && has_stickers && text.is_empty() && !has_attachments {
+            return Err(FilterRejection::StickerOnly);
+        }
+        if self.reject_attachment_only && has_attachments && text.is_empty() {
+            return Err(FilterRejection::AttachmentOnly);
+        }
+        if self.reject_links && contains_link(text) {
+            return Err(FilterRejection::ContainsLink);
+        }
+        if self.min_length > 0
+            && !has_attachments
+            && !has_stickers
+            && text_length(text) < self.min_length as usize
+        {
+            return Err(FilterRejection::TooShort {
+                min_length: self.min_length,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn describe(&self) -> String {
+        let mut rules = Vec::new();
+        if self.min_length > 0 {
+            rules.push(format!("at least {} characters", self.min_length));
+        }
+        if self.reject_sticker_only {
+            rules.push("no sticker-only messages".to_string());
+        }
+        if self.reject_attachment_only {
+            rules.push("no attachment-only messages".to_string());
+        }
+        if self.reject_links {
+            rules.push("no links".to_string());
+        }
+        if rules.is_empty() {
+            "off".to_string()
+        } else {
+            rules.join(", ")
+        }
+    }
+}
+
+fn contains_link(text: &str) -> bool {
+    let lower = text.to_ascii_lowercase();
+    lower.contains("http://") || lower.contains("https://")
+}
+
+/// Characters of text, counting each custom emoji as one.
+fn text_length(text: &str) -> usize {
+    let mut length = 0;
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        length += rest[..start].chars().count();
+        let tail = &rest[start..];
+        match tail.find('>') {
+            Some(end) if emoji::parse_custom(&tail[..=end]).is_some() => {
+                length += 1;
+                rest = &tail[end + 1..];
+            }
+            _ => {
+                length += 1;
+                rest = &tail[1..];
+            }
+        }
+    }
+    length + rest.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn message(content: &str) -> Message {
+        let mut message = Message::default();
+        message.content = content.to_string();
+        message
+    }
+
+    fn with_attachment(content: &str) -> Message {
+        let mut message = message(content);
+        message.attachments = vec![serde_json::from_value(json!({
+            "id": "1",
+            "filename": "cat.png",
+            "size": 1024,
+            "url": "https://cdn.discordapp.com/attachments/1/1/cat.png",
+            "proxy_url": "https://media.discordapp.net/attachments/1/1/cat.png"
+        }))
+        .unwrap()];
+        message
+    }
+
+    fn with_sticker() -> Message {
+        let mut message = message("");
+        message.sticker_items = vec![serde_json::from_value(json!({
+            "id": "2",
+            "name": "wave",
+            "format_type": 1
+        }))
+        .unwrap()];
+        message
+    }
+
+    #[test]
+    fn nothing_is_filtered_by_default() {
+        let filter = ContentFilter::default();
+        assert_eq!(filter.check(&message("😂")), Ok(()));
+        assert_eq!(filter.check(&with_sticker()), Ok(()));
+        assert_eq!(filter.check(&with_attachment("")), Ok(()));
+        assert_eq!(filter.describe(), "off");
+    }
+
+    #[test]
+    fn short_text_is_rejected_counting_custom_emojis_once() {
+        let filter = ContentFilter {
+            min_length: 5,
+            ..ContentFilter::default()
+        };
+        assert_eq!(
+            filter.check(&message("  <:kekw:123456789012345678>  ")),
+            Err(FilterRejection::TooShort { min_length: 5 })
+        );
+        assert_eq!(filter.check(&message("lol <3")), Ok(()));
+        // Attachments and stickers are judged by their own rules
+        assert_eq!(filter.check(&with_attachment("")), Ok(()));
+        assert_eq!(filter.check(&with_sticker()), Ok(()));
+    }
+
+    #[test]
+    fn sticker_attachment_and_link_rules_apply_separately() {
+        let filter = ContentFilter {
+            reject_sticker_only: true,
+            reject_attachment_only: true,
+            reject_links: true,
+            ..ContentFilter::default()
+        };
+        assert_eq!(
+            filter.check(&with_sticker()),
+            Err(FilterRejection::StickerOnly)
+        );
+        assert_eq!(
+            filter.check(&with_attachment(" ")),
+            Err(FilterRejection::AttachmentOnly)
+        );
+        assert_eq!(filter.check(&with_attachment("our new logo")), Ok(()));
+        assert_eq!(
+            filter.check(&message("see HTTPS://example.com")),
+            Err(FilterRejection::ContainsLink)
+        );
+        assert_eq!(filter.check(&message("meet at 5")), Ok(()));
+    }
+}
@@ -0,0 +1,183 @@
+//! The weekly roundup of pins: when it is due and how it is laid out.
+
+use crate::store::PinRecord;
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, CreateEmbed, CreateEmbedFooter, GuildId, Timestamp};
+
+pub const DAY_SECS: i64 = 86_400;
+pub const WEEK_SECS: i64 = 7 * DAY_SECS;
+
+/// Most pins listed in one digest; the rest are summarized as a count.
+pub const MAX_DIGEST_ENTRIES: usize = 50;
+/// Pins per embed. Each entry stays under 300 characters, so a page fits
+/// Discord's 4096-character description and 6000-character message limits.
+pub const DIGEST_PAGE_SIZE: usize = 10;
+const EXCERPT_CHARS: usize = 120;
+
+pub const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// When and where a guild's digest goes out. Times are UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DigestSchedule {
+    pub channel_id: ChannelId,
+    /// 0 is Sunday, as in `WEEKDAYS`.
+    pub weekday: u8,
+    /// Hour of the day, 0 to 23.
+    pub hour: u8,
+}
+
+impl DigestSchedule {
+    /// The most recent scheduled run at or before `now`, a unix timestamp.
+    pub fn latest_run(&self, now: i64) -> i64 {
+        let day = now.div_euclid(DAY_SECS);
+        // The unix epoch fell on a Thursday
+        let today = (day + 4).rem_euclid(7);
+        let days_back = (today - i64::from(self.weekday)).rem_euclid(7);
+        let run = (day - days_back) * DAY_SECS + i64::from(self.hour) * 3600;
+        if run > now {
+            run - WEEK_SECS
+        } else {
+            run
+        }
+    }
+
+    /// The first scheduled run after `now`.
+    pub fn next_run(&self, now: i64) -> i64 {
+        self.latest_run(now) + WEEK_SECS
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "<#{}>, {}s at {:02}:00 UTC",
+            self.channel_id,
+            WEEKDAYS[usize::from(self.weekday) % 7],
+            self.hour
+        )
+    }
+}
+
+/// A message's text on one line, shortened for a digest entry.
+pub fn excerpt(content: &str) -> String {
+    let flat = content.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= EXCERPT_CHARS {
+        return flat;
+    }
+    let mut short: String = flat.chars().take(EXCERPT_CHARS - 1).collect();
+    short.push('…');
+    short
+}
+
+/// One embed per page of pins, in the order given. `pins` is expected to
+/// be capped at `MAX_DIGEST_ENTRIES`; `total` counts every pin in the week.
+pub fn digest_embeds(
+    guild_id: GuildId,
+    pins: &[PinRecord],
+    total: usize,
+    until: i64,
+) -> Vec<CreateEmbed> {
+    let pages = pins.chunks(DIGEST_PAGE_SIZE).count();
+    pins.chunks(DIGEST_PAGE_SIZE)
+        .enumerate()
+        .map(|(page, chunk)| {
+            let mut description = String::new();
+            for (offset, pin) in chunk.iter().enumerate() {
+                let excerpt = if pin.excerpt.is_empty() {
+                    "*(no text)*".to_string()
+                } else {
+                    pin.excerpt.clone()
+                };
+                let author = pin
+                    .author_id
+                    .map_or_else(|| "*someone*".to_string(), |id| format!("<@{id}>"));
+                description.push_str(&format!(
+                    "**{}.** {} · {} {} · [Jump]({})\n> {}\n",
+                    page * DIGEST_PAGE_SIZE + offset + 1,
+                    author,
+                    pin.votes,
+                    if pin.votes == 1 { "vote" } else { "votes" },
+                    pin.message_id.link(pin.channel_id, Some(guild_id)),
+                    excerpt
+                ));
+            }
+            let mut embed = CreateEmbed::new()
+                .title(if pages > 1 {
+                    format!("Pins of the week ({}/{})", page + 1, pages)
+                } else {
+                    "Pins of the week".to_string()
+                })
+                .description(description);
+            if page + 1 == pages {
+                if total > pins.len() {
+                    embed = embed.footer(CreateEmbedFooter::new(format!(
+                        "…and {} more",
+                        total - pins.len()
+                    )));
+                }
+                if let Ok(timestamp) = Timestamp::from_unix_timestamp(until) {
+                    embed = embed.timestamp(timestamp);
+                }
+            }
+            embed
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sunday 2024-01-07 00:00:00 UTC
+    const SUNDAY: i64 = 1_704_585_600;
+
+    fn schedule(weekday: u8, hour: u8) -> DigestSchedule {
+        DigestSchedule {
+            channel_id: ChannelId::new(1),
+            weekday,
+            hour,
+        }
+    }
+
+    #[test]
+    fn the_latest_run_is_the_last_matching_hour() {
+        let schedule = schedule(0, 18);
+        let sunday_evening = SUNDAY + 18 * 3600;
+
+        assert_eq!(schedule.latest_run(sunday_evening), sunday_evening);
+        assert_eq!(schedule.latest_run(sunday_evening + 60), sunday_evening);
+        // Earlier that Sunday, the run is a week back
+        assert_eq!(
+            schedule.latest_run(sunday_evening - 60),
+            sunday_evening - WEEK_SECS
+        );
+        assert_eq!(schedule.latest_run(SUNDAY + 3 * DAY_SECS), sunday_evening);
+        assert_eq!(
+            schedule.next_run(sunday_evening),
+            sunday_evening + WEEK_SECS
+        );
+    }
+
+    #[test]
+    fn other_weekdays_line_up() {
+        let wednesday_nine = SUNDAY + 3 * DAY_SECS + 9 * 3600;
+        let schedule = schedule(3, 9);
+        assert_eq!(schedule.latest_run(wednesday_nine + 1), wednesday_nine);
+        assert_eq!(schedule.next_run(SUNDAY), wednesday_nine);
+    }
+
+    #[test]
+    fn excerpts_are_flattened_and_shortened() {
+        assert_eq!(excerpt("look at\n\nthis"), "look at this");
+        let long = "a".repeat(500);
+        let short = excerpt(&long);
+        assert_eq!(short.chars().count(), EXCERPT_CHARS);
+        assert!(short.ends_with('…'));
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+/// Longest temporary pin accepted.
+pub const MAX_PIN_DURATION: Duration = Duration::from_secs(30 * 86_400);
+
+/// Checks a requested temporary pin length, returning the refusal to show.
+pub fn check_pin_duration(duration: Duration) -> Result<(), String> {
+    if duration > MAX_PIN_DURATION {
+        return Err("Temporary pins can last at most 30 days.".to_string());
+    }
+    Ok(())
+}
+
+/// Parses how long a vote should stay open, clamped to `min..=max`.
+/// Returns the error to show when `input` isn't a duration.
+pub fn parse_vote_timeout(input: &str, min: Duration, max: Duration) -> Result<Duration, String> {
+    match parse_duration(input) {
+        Some(timeout) => Ok(timeout.clamp(min, max)),
+        None => Err(
+            "That doesn't look like a vote length. Try something like 10m, 2h or 1d.".to_string(),
+        ),
+    }
+}
+
+/// Parses a compact duration such as `3d`, `12h`, `90m` or `1d12h`.
+/// Units are `s`, `m`, `h`, `d` and `w`; a bare number is rejected so it
+/// can't be confused with a message id.
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let mut total: u64 = 0;
+    let mut digits = String::new();
+
+    for c in input.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86_400,
+            'w' => 604_800,
+            _ => return None,
+        };
+        let amount: u64 = digits.parse().ok()?;
+        total = total.checked_add(amount.checked_mul(unit)?)?;
+        digits.clear();
+    }
+
+    (digits.is_empty() && total > 0).then(|| Duration::from_secs(total))
+}
+
+/// Renders e.g. `3d 4h 05m`, dropping leading zero units.
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    let (days, hours, minutes) = (minutes / 1440, minutes / 60 % 24, minutes % 60);
+    match (days, hours) {
+        (0, 0) => format!("{}m", minutes),
+        (0, _) => format!("{}h {:02}m", hours, minutes),
+        _ => format!("{}d {}h {:02}m", days, hours, minutes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_and_combined_units() {
+        assert_eq!(parse_duration("3d"), Some(Duration::from_secs(3 * 86_400)));
+        assert_eq!(parse_duration("90m"), Some(Duration::from_secs(5_400)));
+        assert_eq!(parse_duration("1d12H"), Some(Duration::from_secs(129_600)));
+        assert_eq!(parse_duration(" 2w "), Some(Duration::from_secs(1_209_600)));
+    }
+
+    #[test]
+    fn vote_timeouts_parse_and_clamp() {
+        let (min, max) = (Duration::from_secs(60), Duration::from_secs(7 * 86_400));
+        let parse = |input| parse_vote_timeout(input, min, max);
+        assert_eq!(parse("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse("2h"), Ok(Duration::from_secs(7_200)));
+        assert_eq!(parse("1d"), Ok(Duration::from_secs(86_400)));
+        assert_eq!(parse("10s"), Ok(min));
+        assert_eq!(parse("3w"), Ok(max));
+        assert!(parse("soon").is_err());
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn rejects_bare_numbers_and_garbage() {
+        for input in [
+            "",
+            "3",
+            "d",
+            "0h",
+            "3x",
+            "1.5h",
+            "-2d",
+            "h3",
+            "99999999999999999999w",
+        ] {
+            assert_eq!(parse_duration(input), None, "{:?}", input);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use serenity::all::{EmojiId, ReactionType};
+
+/// Standard keycap emojis for 1 through 10, usable on any server.
+const KEYCAPS: [&str; 10] = [
+    "1\u{FE0F}\u{20E3}",
+    "2\u{FE0F}\u{20E3}",
+    "3\u{FE0F}\u{20E3}",
+    "4\u{FE0F}\u{20E3}",
+    "5\u{FE0F}\u{20E3}",
+    "6\u{FE0F}\u{20E3}",
+    "7\u{FE0F}\u{20E3}",
+    "8\u{FE0F}\u{20E3}",
+    "9\u{FE0F}\u{20E3}",
+    "\u{1F51F}",
+];
+
+/// The Unicode keycap for `num`, or `None` outside 1..=10.
+pub fn keycap(num: u32) -> Option<ReactionType> {
+    let index = num.checked_sub(1)? as usize;
+    KEYCAPS
+        .get(index)
+        .map(|name| ReactionType::Unicode(name.to_string()))
+}
+
+/// A custom number emoji reaction for `num` backed by `id`.
+pub fn custom_number(num: u32, id: EmojiId) -> ReactionType {
+    ReactionType::Custom {
+        animated: false,
+        id,
+        name: Some(format!("{}_", num)),
+    }
+}
+
+/// Parses custom emoji markup as pasted from Discord, `<:name:id>` or
+/// `<a:name:id>`. Returns `None` for anything malformed rather than
+/// guessing, so a typo never turns into a Unicode reaction.
+pub fn parse_custom(input: &str) -> Option<ReactionType> {
+    let inner = input.trim().strip_prefix('<')?.strip_suffix('>')?;
+    let (animated, rest) = match inner.strip_prefix("a:") {
+        Some(rest) => (true, rest),
+        None => (false, inner.strip_prefix(':')?),
+    };
+    let (name, id) = rest.split_once(':')?;
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    if id.is_empty() || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let id = id.parse::<u64>().ok().filter(|&id| id != 0)?;
+
+    Some(ReactionType::Custom {
+        animated,
+        id: EmojiId::new(id),
+        name: Some(name.to_string()),
+    })
+}
+
+/// Parses an emoji typed into a command option: custom emoji markup or a
+/// single Unicode emoji. Plain words are rejected.
+pub fn parse_reaction(input: &str) -> Option<ReactionType> {
+    let input = input.trim();
+    if input.starts_with('<') {
+        return parse_custom(input);
+    }
+    let plausible = !input.is_empty()
+        && input.chars().count() <= 8
+        && !input
+            .chars()
+            .any(|c| c.is_ascii_alphanumeric() || c.is_whitespace() || c == ':');
+    plausible.then(|| ReactionType::Unicode(input.to_string()))
+}
+
+/// Whether two reactions are the same emoji. Custom emojis compare by id
+/// alone, since reaction events may carry a different name.
+pub fn same_emoji(a: &ReactionType, b: &ReactionType) -> bool {
+    match (a, b) {
+        (ReactionType::Custom { id: a, .. }, ReactionType::Custom { id: b, .. }) => a == b,
+        (ReactionType::Unicode(a), ReactionType::Unicode(b)) => {
+            a.trim_end_matches('\u{FE0F}') == b.trim_end_matches('\u{FE0F}')
+        }
+        _ => false,
+    }
+}
+
+/// The checkmark variants accepted as an approve vote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Checkmark {
+    /// ✅ U+2705
+    WhiteHeavy,
+    /// ☑ U+2611
+    Ballot,
+    /// ✔ U+2714
+    Heavy,
+}
+
+/// Maps a reaction to the checkmark it represents, ignoring variation
+/// selectors: clients send ☑ and ✔ both bare and with U+FE0F appended, and
+/// occasionally with the text-style U+FE0E.
+pub fn checkmark(emoji: &ReactionType) -> Option<Checkmark> {
+    let ReactionType::Unicode(name) = emoji else {
+        return None;
+    };
+
+    match name.trim_end_matches(['\u{FE0F}', '\u{FE0E}']) {
+        "\u{2705}" => Some(Checkmark::WhiteHeavy),
+        "\u{2611}" => Some(Checkmark::Ballot),
+        "\u{2714}" => Some(Checkmark::Heavy),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unicode(name: &str) -> ReactionType {
+        ReactionType::Unicode(name.to_string())
+    }
+
+    #[test]
+    fn matches_all_checkmarks_with_and_without_selectors() {
+        let cases = [
+            ("\u{2705}", Checkmark::WhiteHeavy),
+            ("\u{2705}\u{FE0F}", Checkmark::WhiteHeavy),
+            ("\u{2611}", Checkmark::Ballot),
+            ("\u{2611}\u{FE0F}", Checkmark::Ballot),
+            ("\u{2611}\u{FE0E}", Checkmark::Ballot),
+            ("\u{2714}", Checkmark::Heavy),
+            ("\u{2714}\u{FE0F}", Checkmark::Heavy),
+            ("\u{2714}\u{FE0E}", Checkmark::Heavy),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(checkmark(&unicode(name)), Some(expected), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_other_emojis() {
+        for name in [
+            "",
+            "\u{FE0F}",
+            "❌",
+            "✖️",
+            "\u{2705}\u{2705}",
+            "\u{FE0F}\u{2705}",
+        ] {
+            assert_eq!(checkmark(&unicode(name)), None, "{:?}", name);
+        }
+
+        let custom = "<:check:1404872667189743697>".parse().unwrap();
+        assert_eq!(checkmark(&custom), None);
+    }
+
+    #[test]
+    fn parses_static_and_animated_custom_emojis() {
+        assert_eq!(
+            parse_custom("<:slash:1404872667189743697>"),
+            Some(ReactionType::Custom {
+                animated: false,
+                id: EmojiId::new(1404872667189743697),
+                name: Some("slash".to_string()),
+            })
+        );
+        assert_eq!(
+            parse_custom(" <a:party_1:42> "),
+            Some(ReactionType::Custom {
+                animated: true,
+                id: EmojiId::new(42),
+                name: Some("party_1".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_custom_emojis() {
+        for input in [
+            "",
+            "✅",
+            "1404872667189743697",
+            ":slash:1404872667189743697",
+            "<:slash:1404872667189743697",
+            "<slash:1404872667189743697>",
+            "<b:slash:1404872667189743697>",
+            "<::1404872667189743697>",
+            "<:sl ash:1404872667189743697>",
+            "<:slash:>",
+            "<:slash:0>",
+            "<:slash:-1>",
+            "<:slash:+1>",
+            "<:slash:12ab>",
+            "<:slash:1404872667189743697:1>",
+            "<:slash:99999999999999999999999>",
+        ] {
+            assert_eq!(parse_custom(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parses_category_emojis_and_compares_them() {
+        let custom = parse_reaction("<:useful:1404872667189743697>").unwrap();
+        assert!(same_emoji(
+            &custom,
+            &ReactionType::Custom {
+                animated: false,
+                id: EmojiId::new(1404872667189743697),
+                name: None,
+            }
+        ));
+        assert_eq!(parse_reaction(" 😂 "), Some(unicode("😂")));
+        assert!(same_emoji(&unicode("❤\u{FE0F}"), &unicode("❤")));
+        assert!(!same_emoji(&custom, &unicode("😂")));
+        for input in ["", "memes", ":joy:", "😂 😂", "<:bad>"] {
+            assert_eq!(parse_reaction(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn keycaps_cover_one_through_ten() {
+        assert_eq!(keycap(0), None);
+        assert_eq!(keycap(1), Some(unicode("1\u{FE0F}\u{20E3}")));
+        assert_eq!(keycap(10), Some(unicode("\u{1F51F}")));
+        assert_eq!(keycap(11), None);
+    }
+}
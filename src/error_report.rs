@@ -0,0 +1,543 @@
+//! Optional reporting of errors and panics to Sentry or a plain webhook.
+//!
+//! Nothing here is installed unless `SENTRY_DSN` or `ERROR_WEBHOOK_URL` is
+//! set. Error-level events are grouped by the line that logged them and
+//! sent in batches, and a line that keeps failing is reported at most once
+//! per `REPEAT_INTERVAL` with a count of how often it fired.
+
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use serde_json::json;
+use serenity::all::Timestamp;
+use std::{
+    collections::{hash_map::RandomState, BTreeMap, HashMap},
+    hash::{BuildHasher, Hasher},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tracing::{
+    field::{Field, Visit},
+    span, warn, Event, Level, Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+/// Shortest gap between two reports from the same line.
+const REPEAT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Distinct lines held between flushes; further ones are only counted.
+const MAX_PENDING: usize = 50;
+/// Reports sent per flush; the rest wait for the next one.
+const MAX_BATCH: usize = 20;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Set while a panic is being reported, so a panic in the reporting itself
+/// doesn't recurse.
+static REPORTING_PANIC: AtomicBool = AtomicBool::new(false);
+
+/// One error, or a run of them from the same line.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ErrorReport {
+    /// `error`, or `fatal` for a panic.
+    pub level: &'static str,
+    pub message: String,
+    pub target: String,
+    pub location: Option<String>,
+    /// The event's fields and those of its spans, such as `guild_id`, plus
+    /// `event` naming the handler it happened in.
+    pub fields: BTreeMap<String, String>,
+    pub count: u32,
+    pub first_seen: Timestamp,
+}
+
+/// Reports waiting for the next flush.
+#[derive(Default)]
+struct Batch {
+    pending: HashMap<&'static str, ErrorReport>,
+    last_sent: HashMap<&'static str, Instant>,
+    /// Errors from lines that found `pending` full.
+    dropped: u32,
+}
+
+impl Batch {
+    fn add(&mut self, key: &'static str, report: ErrorReport) {
+        if let Some(pending) = self.pending.get_mut(key) {
+            pending.count += 1;
+        } else if self.pending.len() < MAX_PENDING {
+            self.pending.insert(key, report);
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    /// Takes the reports whose line wasn't reported within
+    /// `REPEAT_INTERVAL`; the others keep counting until it has passed.
+    fn take_ready(&mut self, now: Instant) -> (Vec<ErrorReport>, u32) {
+        let mut ready: Vec<&'static str> = self
+            .pending
+            .keys()
+            .copied()
+            .filter(|key| {
+                self.last_sent
+                    .get(key)
+                    .is_none_or(|sent| now.duration_since(*sent) >= REPEAT_INTERVAL)
+            })
+            .collect();
+        ready.sort_by_key(|key| self.pending[key].first_seen.unix_timestamp());
+        ready.truncate(MAX_BATCH);
+
+        let reports = ready
+            .into_iter()
+            .filter_map(|key| {
+                self.last_sent.insert(key, now);
+                self.pending.remove(key)
+            })
+            .collect();
+        self.last_sent
+            .retain(|_, sent| now.duration_since(*sent) < REPEAT_INTERVAL);
+        (reports, std::mem::take(&mut self.dropped))
+    }
+
+    /// Takes every pending report, reported recently or not, for the last
+    /// flush before exiting.
+    fn take_all(&mut self) -> (Vec<ErrorReport>, u32) {
+        let mut reports: Vec<ErrorReport> =
+            self.pending.drain().map(|(_, report)| report).collect();
+        reports.sort_by_key(|report| report.first_seen.unix_timestamp());
+        (reports, std::mem::take(&mut self.dropped))
+    }
+}
+
+/// Where reports go.
+enum Sink {
+    /// Receives `{"reports": [...], "dropped": n}` as JSON.
+    Webhook(String),
+    Sentry(SentryDsn),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct SentryDsn {
+    envelope_url: String,
+    public_key: String,
+}
+
+impl SentryDsn {
+    /// Parses `https://<key>@<host>[/<path>]/<project>`.
+    fn parse(dsn: &str) -> Result<Self> {
+        let url = reqwest::Url::parse(dsn).context("SENTRY_DSN is not a URL")?;
+        let public_key = url.username();
+        anyhow::ensure!(!public_key.is_empty(), "SENTRY_DSN has no public key");
+        let host = url.host_str().context("SENTRY_DSN has no host")?;
+        let path = url.path().trim_end_matches('/');
+        let (prefix, project) = path
+            .rsplit_once('/')
+            .context("SENTRY_DSN has no project id")?;
+        anyhow::ensure!(!project.is_empty(), "SENTRY_DSN has no project id");
+        let port = url
+            .port()
+            .map(|port| format!(":{}", port))
+            .unwrap_or_default();
+        Ok(Self {
+            envelope_url: format!(
+                "{}://{}{}{}/api/{}/envelope/",
+                url.scheme(),
+                host,
+                port,
+                prefix,
+                project
+            ),
+            public_key: public_key.to_string(),
+        })
+    }
+}
+
+struct Reporter {
+    client: reqwest::Client,
+    sinks: Vec<Sink>,
+}
+
+impl Reporter {
+    async fn send(&self, reports: &[ErrorReport], dropped: u32) {
+        for sink in &self.sinks {
+            let result = match sink {
+                Sink::Webhook(url) => self
+                    .client
+                    .post(url)
+                    .json(&json!({ "reports": reports, "dropped": dropped }))
+                    .send()
+                    .await
+                    .and_then(reqwest::Response::error_for_status)
+                    .map(drop),
+                Sink::Sentry(dsn) => self.send_to_sentry(dsn, reports).await,
+            };
+            // Logged below error level so a broken sink can't report itself
+            if let Err(e) = result {
+                warn!(error = %e, reports = reports.len(), "Failed to send error reports");
+            }
+        }
+    }
+
+    /// One envelope per report, as Sentry takes a single event per envelope.
+    async fn send_to_sentry(
+        &self,
+        dsn: &SentryDsn,
+        reports: &[ErrorReport],
+    ) -> reqwest::Result<()> {
+        let auth = format!(
+            "Sentry sentry_version=7, sentry_client=discord-pin-bot/{}, sentry_key={}",
+            env!("CARGO_PKG_VERSION"),
+            dsn.public_key
+        );
+        for report in reports {
+            let event_id = event_id();
+            let mut extra: BTreeMap<&str, serde_json::Value> = BTreeMap::new();
+            extra.insert("count", report.count.into());
+            if let Some(location) = &report.location {
+                extra.insert("location", location.clone().into());
+            }
+            let event = json!({
+                "event_id": event_id,
+                "timestamp": report.first_seen.unix_timestamp(),
+                "level": report.level,
+                "logger": report.target,
+                "platform": "other",
+                "release": env!("CARGO_PKG_VERSION"),
+                "message": { "formatted": report.message },
+                "tags": report.fields,
+                "extra": extra,
+            });
+            let body = format!(
+                "{}\n{}\n{}\n",
+                json!({ "event_id": event_id }),
+                json!({ "type": "event" }),
+                event
+            );
+            self.client
+                .post(&dsn.envelope_url)
+                .header("X-Sentry-Auth", &auth)
+                .header("Content-Type", "application/x-sentry-envelope")
+                .body(body)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        Ok(())
+    }
+}
+
+/// A random 32-digit hex id, as Sentry wants for `event_id`.
+fn event_id() -> String {
+    let half = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_nanos())
+                .unwrap_or_default(),
+        );
+        hasher.finish()
+    };
+    format!("{:016x}{:016x}", half(), half())
+}
+
+/// Span fields kept for the events inside the span.
+#[derive(Default)]
+struct SpanFields(BTreeMap<String, String>);
+
+/// Collects an event's or span's fields as strings, `message` apart.
+#[derive(Default)]
+struct FieldVisitor {
+    message: Option<String>,
+    fields: BTreeMap<String, String>,
+}
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = Some(value.to_string());
+        } else {
+            self.fields
+                .insert(field.name().to_string(), value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = Some(format!("{:?}", value));
+        } else {
+            self.fields
+                .insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+}
+
+/// The tracing layer feeding error-level events into the batch.
+pub struct ErrorReportLayer {
+    batch: Arc<Mutex<Batch>>,
+}
+
+/// Sends what is still in the batch when the bot stops; the flush task
+/// only runs every `FLUSH_INTERVAL` and dies with the runtime.
+pub struct FinalFlush {
+    batch: Arc<Mutex<Batch>>,
+    reporter: Arc<Reporter>,
+}
+
+impl FinalFlush {
+    pub async fn flush(self) {
+        let (reports, dropped) = self
+            .batch
+            .lock()
+            .expect("error batch mutex poisoned")
+            .take_all();
+        if !reports.is_empty() || dropped > 0 {
+            self.reporter.send(&reports, dropped).await;
+        }
+    }
+}
+
+impl<S> Layer<S> for ErrorReportLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        span.extensions_mut().insert(SpanFields(visitor.fields));
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldVisitor::default();
+        values.record(&mut visitor);
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            fields.0.extend(visitor.fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() != Level::ERROR {
+            return;
+        }
+
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        let mut fields = visitor.fields;
+        for span in ctx.event_scope(event).into_iter().flatten() {
+            if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                for (name, value) in &span_fields.0 {
+                    fields.entry(name.clone()).or_insert_with(|| value.clone());
+                }
+            }
+            // Ends up as the outermost span: the gateway event or task
+            fields.insert("event".to_string(), span.name().to_string());
+        }
+
+        let report = ErrorReport {
+            level: "error",
+            message: visitor.message.unwrap_or_default(),
+            target: metadata.target().to_string(),
+            location: metadata
+                .file()
+                .zip(metadata.line())
+                .map(|(file, line)| format!("{}:{}", file, line)),
+            fields,
+            count: 1,
+            first_seen: Timestamp::now(),
+        };
+        // The callsite name is unique to the line that logged the event
+        self.batch
+            .lock()
+            .expect("error batch mutex poisoned")
+            .add(metadata.name(), report);
+    }
+}
+
+/// Builds the layer from `SENTRY_DSN` and `ERROR_WEBHOOK_URL`, starting
+/// the flush task and the panic hook, along with the flush to run before
+/// exiting. `None` when neither is set.
+///
+/// Must be called inside the Tokio runtime.
+pub fn from_env() -> Result<Option<(ErrorReportLayer, FinalFlush)>> {
+    let var = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let mut sinks = Vec::new();
+    if let Some(dsn) = var("SENTRY_DSN") {
+        sinks.push(Sink::Sentry(SentryDsn::parse(&dsn)?));
+    }
+    if let Some(url) = var("ERROR_WEBHOOK_URL") {
+        reqwest::Url::parse(&url).context("ERROR_WEBHOOK_URL is not a URL")?;
+        sinks.push(Sink::Webhook(url));
+    }
+    if sinks.is_empty() {
+        return Ok(None);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Failed to build the error reporting HTTP client")?;
+    let reporter = Arc::new(Reporter { client, sinks });
+    let batch = Arc::new(Mutex::new(Batch::default()));
+
+    let flush_batch = Arc::clone(&batch);
+    let flush_reporter = Arc::clone(&reporter);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let (reports, dropped) = flush_batch
+                .lock()
+                .expect("error batch mutex poisoned")
+                .take_ready(Instant::now());
+            if !reports.is_empty() || dropped > 0 {
+                flush_reporter.send(&reports, dropped).await;
+            }
+        }
+    });
+
+    install_panic_hook(Arc::clone(&reporter));
+    let last = FinalFlush {
+        batch: Arc::clone(&batch),
+        reporter,
+    };
+    Ok(Some((ErrorReportLayer { batch }, last)))
+}
+
+/// Reports panics right away, before the default hook prints them. The
+/// release build aborts on panic, so the report is sent from a thread of
+/// its own and waited for.
+fn install_panic_hook(reporter: Arc<Reporter>) {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        if REPORTING_PANIC.swap(true, Ordering::SeqCst) {
+            previous(info);
+            return;
+        }
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "panic".to_string());
+        let mut fields = BTreeMap::new();
+        if let Some(name) = std::thread::current().name() {
+            fields.insert("thread".to_string(), name.to_string());
+        }
+        let report = ErrorReport {
+            level: "fatal",
+            message,
+            target: "panic".to_string(),
+            location: info
+                .location()
+                .map(|location| format!("{}:{}", location.file(), location.line())),
+            fields,
+            count: 1,
+            first_seen: Timestamp::now(),
+        };
+
+        let reporter = Arc::clone(&reporter);
+        let sent = std::thread::spawn(move || {
+            if let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                runtime.block_on(reporter.send(&[report], 0));
+            }
+        });
+        let _ = sent.join();
+        REPORTING_PANIC.store(false, Ordering::SeqCst);
+        previous(info);
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(message: &str) -> ErrorReport {
+        ErrorReport {
+            level: "error",
+            message: message.to_string(),
+            target: "discord_pin_bot".to_string(),
+            location: None,
+            fields: BTreeMap::new(),
+            count: 1,
+            first_seen: Timestamp::now(),
+        }
+    }
+
+    #[test]
+    fn a_flapping_line_is_reported_once_per_interval() {
+        let mut batch = Batch::default();
+        let start = Instant::now();
+        for _ in 0..1000 {
+            batch.add("event src/lib.rs:10", report("Missing permissions"));
+        }
+
+        let (reports, dropped) = batch.take_ready(start);
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].count, 1000);
+        assert_eq!(dropped, 0);
+
+        // Repeats keep counting until the interval has passed
+        batch.add("event src/lib.rs:10", report("Missing permissions"));
+        batch.add("event src/lib.rs:10", report("Missing permissions"));
+        assert!(batch.take_ready(start + FLUSH_INTERVAL).0.is_empty());
+        let (reports, _) = batch.take_ready(start + REPEAT_INTERVAL);
+        assert_eq!(reports[0].count, 2);
+
+        // The last flush before exiting doesn't wait for the interval
+        batch.add("event src/lib.rs:10", report("Exiting for a restart"));
+        assert!(batch.take_ready(start + REPEAT_INTERVAL).0.is_empty());
+        assert_eq!(batch.take_all().0.len(), 1);
+        assert!(batch.take_all().0.is_empty());
+    }
+
+    #[test]
+    fn distinct_lines_past_the_cap_are_only_counted() {
+        let mut batch = Batch::default();
+        let keys: Vec<&'static str> = (0..MAX_PENDING + 5)
+            .map(|line| &*Box::leak(format!("event src/lib.rs:{}", line).into_boxed_str()))
+            .collect();
+        for key in &keys {
+            batch.add(key, report(key));
+        }
+
+        let now = Instant::now();
+        let (first, dropped) = batch.take_ready(now);
+        assert_eq!(first.len(), MAX_BATCH);
+        assert_eq!(dropped, 5);
+        assert_eq!(batch.take_ready(now).0.len(), MAX_BATCH);
+    }
+
+    #[test]
+    fn sentry_dsns_point_at_the_envelope_endpoint() {
+        let dsn = SentryDsn::parse("https://abc123@o1.ingest.sentry.io/42").unwrap();
+        assert_eq!(dsn.public_key, "abc123");
+        assert_eq!(
+            dsn.envelope_url,
+            "https://o1.ingest.sentry.io/api/42/envelope/"
+        );
+
+        let hosted = SentryDsn::parse("http://key@localhost:9000/sentry/7").unwrap();
+        assert_eq!(
+            hosted.envelope_url,
+            "http://localhost:9000/sentry/api/7/envelope/"
+        );
+        assert!(SentryDsn::parse("https://o1.ingest.sentry.io/42").is_err());
+    }
+}
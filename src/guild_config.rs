@@ -0,0 +1,294 @@
+use crate::{
+    content_filter::ContentFilter,
+    digest::{DigestSchedule, DAY_SECS},
+    retention::PinRetention,
+    templates::TemplateKey,
+};
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, MessageId, ReactionType, RoleId, UserId};
+use std::collections::{HashMap, HashSet};
+
+/// Most category emojis a guild may configure, keeping the vote message
+/// well under Discord's reaction limit.
+pub const MAX_ARCHIVE_CATEGORIES: usize = 5;
+
+/// Largest vote weight a guild may give its boosters.
+pub const MAX_BOOSTER_WEIGHT: u32 = 5;
+
+/// Largest vote weight a guild may give super reactions.
+pub const MAX_SUPER_REACTION_WEIGHT: u32 = 5;
+
+/// Longest lookback for the recently-pinned warning.
+pub const MAX_REPIN_WINDOW_DAYS: u32 = 365;
+
+/// Longest a passed vote may be held open before it pins.
+pub const MAX_MIN_VOTE_MINUTES: u32 = 120;
+
+/// Thread name used when `/votethreads` is turned on without one.
+pub const DEFAULT_VOTE_THREAD_NAME: &str = "Pin vote: {excerpt}";
+
+/// Longest window the brigade guard may watch, and the most votes it may
+/// allow within it.
+pub const MAX_BRIGADE_WINDOW_SECS: u32 = 3_600;
+pub const MAX_BRIGADE_VOTES: u32 = 100;
+
+/// How members cast their votes on a new session.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VoteMode {
+    /// React with ✅ on the command message.
+    #[default]
+    Reactions,
+    /// Press the buttons on a vote message posted by the bot.
+    Buttons,
+    /// Answer a native Discord poll posted by the bot. Polls run for whole
+    /// hours, so the vote lasts at least one.
+    Poll,
+}
+
+/// Quorum-plus-majority voting: a vote passes once at least `quorum` votes
+/// were cast either way and approvals lead rejections by `margin`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuorumRule {
+    pub quorum: u32,
+    pub margin: u32,
+}
+
+impl QuorumRule {
+    pub fn passes(&self, participation: u32, approvals: u32, rejections: u32) -> bool {
+        participation >= self.quorum && approvals >= rejections.saturating_add(self.margin)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} votes either way, with approvals ahead by {}",
+            self.quorum, self.margin
+        )
+    }
+}
+
+/// Holds a vote for moderator review when more than `votes` approvals land
+/// within `window_secs`, the signature of a coordinated pile-on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrigadeGuard {
+    pub votes: u32,
+    pub window_secs: u32,
+}
+
+impl BrigadeGuard {
+    pub fn describe(&self) -> String {
+        format!(
+            "more than {} votes within {} seconds",
+            self.votes, self.window_secs
+        )
+    }
+}
+
+/// A daily window in which passed votes wait to be pinned. Times are
+/// minutes past midnight at a fixed UTC offset; a window whose start is
+/// later than its end runs past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: u16,
+    pub end: u16,
+    /// Minutes ahead of UTC, e.g. 120 for UTC+02:00.
+    pub utc_offset: i16,
+}
+
+impl QuietHours {
+    /// When the quiet window holding `now` ends, both unix timestamps, or
+    /// `None` outside the window. A window starting and ending at the same
+    /// time is never quiet.
+    pub fn ends_after(&self, now: i64) -> Option<i64> {
+        let offset = i64::from(self.utc_offset) * 60;
+        let local = now + offset;
+        let minute = local.rem_euclid(DAY_SECS) / 60;
+        let (start, end) = (i64::from(self.start), i64::from(self.end));
+        let quiet = if start <= end {
+            start <= minute && minute < end
+        } else {
+            minute >= start || minute < end
+        };
+        if !quiet {
+            return None;
+        }
+        let mut ends = local - local.rem_euclid(DAY_SECS) + end * 60;
+        if ends <= local {
+            ends += DAY_SECS;
+        }
+        Some(ends - offset)
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{}–{} {}",
+            clock(self.start),
+            clock(self.end),
+            utc_offset(self.utc_offset)
+        )
+    }
+}
+
+fn clock(minutes: u16) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+fn utc_offset(minutes: i16) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let minutes = minutes.unsigned_abs();
+    format!("UTC{}{:02}:{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Reads a time of day like `23:30` or `7`, as minutes past midnight.
+pub fn parse_clock(text: &str) -> Option<u16> {
+    let (hours, minutes) = text.trim().split_once(':').unwrap_or((text.trim(), "0"));
+    let (hours, minutes) = (hours.parse::<u16>().ok()?, minutes.parse::<u16>().ok()?);
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+/// Reads a UTC offset like `+02:00`, `-5` or `UTC+5:30`, as minutes ahead
+/// of UTC. Offsets run from UTC-12:00 to UTC+14:00.
+pub fn parse_utc_offset(text: &str) -> Option<i16> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("UTC")
+        .or_else(|| text.strip_prefix("utc"))
+        .unwrap_or(text);
+    if text.is_empty() {
+        return Some(0);
+    }
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "0"));
+    let (hours, minutes) = (hours.parse::<i16>().ok()?, minutes.parse::<i16>().ok()?);
+    if !(0..60).contains(&minutes) {
+        return None;
+    }
+    let offset = sign * (hours * 60 + minutes);
+    (-12 * 60..=14 * 60).contains(&offset).then_some(offset)
+}
+
+/// Pins voted with `emoji` are mirrored to `channel_id`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveCategory {
+    pub emoji: ReactionType,
+    pub channel_id: ChannelId,
+}
+
+/// Per-guild settings, persisted as a JSON document in the store.
+///
+/// Every field has a default so older records keep loading as new
+/// settings are added.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuildConfig {
+    pub allowed_channels: HashSet<ChannelId>,
+    pub denied_channels: HashSet<ChannelId>,
+    /// When set, only members holding this role have their votes counted.
+    pub voter_role: Option<RoleId>,
+    pub vote_mode: VoteMode,
+    /// Let members start votes on their own messages.
+    pub allow_self_pins: bool,
+    /// Allow pinning messages posted by bots and webhooks.
+    pub allow_bot_pins: bool,
+    /// Don't post reminders on votes that are halfway to expiring.
+    pub skip_reminders: bool,
+    /// Where pinned messages are mirrored when no category wins.
+    pub archive_channel: Option<ChannelId>,
+    /// Category reactions offered on votes, in the order they are added.
+    pub archive_categories: Vec<ArchiveCategory>,
+    /// How many votes a server booster's approval counts for; `None` counts
+    /// boosters like everyone else.
+    pub booster_weight: Option<u32>,
+    /// How many times a super (burst) ✅ counts the voter's approval;
+    /// `None` counts it like a normal one.
+    pub super_reaction_weight: Option<u32>,
+    /// Votes needed in specific channels, ahead of the guild's threshold.
+    /// Threads fall back to their parent's entry.
+    pub channel_thresholds: HashMap<ChannelId, u32>,
+    /// Where and when the weekly pin digest is posted; off when unset.
+    pub digest: Option<DigestSchedule>,
+    /// Channels that keep a pinned index of their voted pins.
+    pub index_channels: HashSet<ChannelId>,
+    /// Rules a message must pass before a vote on it starts.
+    pub content_filter: ContentFilter,
+    /// A vote on a message pinned within this many days needs the
+    /// requester's confirmation; `None` never asks.
+    pub repin_window_days: Option<u32>,
+    /// Pass votes by quorum and majority instead of the plain threshold;
+    /// `None` keeps the threshold.
+    pub quorum: Option<QuorumRule>,
+    /// Count votes from members serving a timeout, which are ignored by
+    /// default.
+    pub count_timed_out_votes: bool,
+    /// Where moderator actions on votes are logged; off when unset.
+    pub audit_channel: Option<ChannelId>,
+    /// Members whose messages are never pinned, however a vote goes.
+    pub pin_blocklist: HashSet<UserId>,
+    /// Hold votes that fill up suspiciously fast; off when unset.
+    pub brigade_guard: Option<BrigadeGuard>,
+    /// Pinged when a vote is held for review.
+    pub review_role: Option<RoleId>,
+    /// A vote that passes sooner than this many minutes after it opened
+    /// waits out the rest before pinning; `None` pins right away.
+    pub min_vote_minutes: Option<u32>,
+    /// Messages moderators locked from pinning.
+    pub locked_messages: HashSet<MessageId>,
+    /// When passed votes wait to be pinned; off when unset.
+    pub quiet_hours: Option<QuietHours>,
+    /// Most voted pins a channel may get per UTC day; `None` or 0 is
+    /// unlimited.
+    pub daily_pin_quota: Option<u32>,
+    /// Quotas for specific channels, ahead of `daily_pin_quota`; 0 lifts
+    /// the limit there. Threads fall back to their parent's entry.
+    pub channel_pin_quotas: HashMap<ChannelId, u32>,
+    /// Channels where a passed vote waits for a moderator's sign-off
+    /// before pinning. Threads follow their parent.
+    pub approval_channels: HashSet<ChannelId>,
+    /// Channels whose old pins are taken down once a day.
+    pub pin_retention: HashMap<ChannelId, PinRetention>,
+    /// Name of the discussion thread opened on each vote, with `{excerpt}`
+    /// for the target's text; no threads when unset.
+    pub vote_thread_name: Option<String>,
+    /// Publish voted pins in announcement channels to following servers.
+    pub crosspost_pins: bool,
+    /// Channels that feature one pin at a time: each pin the bot makes
+    /// there replaces the one it made before.
+    pub rotation_channels: HashSet<ChannelId>,
+    /// Write the week's vote stats to the audit channel each time the
+    /// digest goes out.
+    pub weekly_vote_stats: bool,
+    /// The guild's own wording for key messages, already validated; the
+    /// rest keep their defaults.
+    pub templates: HashMap<TemplateKey, String>,
+}
+
+impl GuildConfig {
+    /// The wording for `key`: the guild's own, or the default.
+    pub fn template(&self, key: TemplateKey) -> &str {
+        self.templates
+            .get(&key)
+            .map_or_else(|| key.default_text(), String::as_str)
+    }
+
+    /// The weight of a vote from a member who is or isn't boosting.
+    pub fn vote_weight(&self, boosting: bool) -> u32 {
+        match self.booster_weight {
+            Some(weight) if boosting => weight.max(1),
+            _ => 1,
+        }
+    }
+
+    /// The factor a super reaction applies to a voter's weight.
+    pub fn super_reaction_factor(&self) -> u32 {
+        self.super_reaction_weight.unwrap_or(1).max(1)
+    }
+
+    pub fn archive_category(&self, emoji: &ReactionType) -> Option<&ArchiveCategory> {
+        self.archive_categories
+            .iter()
+            .find(|category| crate::emoji::same_emoji(&category.emoji, emoji))
+    }
+}
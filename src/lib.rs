@@ -0,0 +1,10303 @@
+//! The pin bot: voting sessions, the gateway event handler and the slash
+//! commands. The binary in `main.rs` only wires it to a Discord client.
+
+mod app_emojis;
+pub mod commands;
+pub mod config;
+mod content_filter;
+mod digest;
+mod duration;
+mod emoji;
+pub mod error_report;
+mod guild_config;
+mod links;
+mod migrations;
+mod pagination;
+mod pin_api;
+mod pin_index;
+mod reaction_queue;
+mod reason;
+pub mod replay;
+mod retention;
+mod shard_monitor;
+mod snapshot;
+pub mod store;
+mod templates;
+mod ttl_map;
+mod vote_stats;
+mod webhook;
+
+use anyhow::Result;
+use app_emojis::AppEmojis;
+use config::{Config, NumberEmojiStyle, StaleShardAction};
+use dashmap::{mapref::entry::Entry, DashMap, DashSet};
+use digest::{DigestSchedule, MAX_DIGEST_ENTRIES, WEEK_SECS};
+use emoji::Checkmark;
+use futures::StreamExt;
+use guild_config::{ArchiveCategory, BrigadeGuard, GuildConfig, QuorumRule, VoteMode};
+use pagination::{PageTurn, Paginator, PAGE_BUTTON_PREFIX};
+use pin_api::PinApi;
+use reaction_queue::ReactionQueue;
+use retention::{PinAge, PinRetention};
+use serenity::{
+    all::{
+        ActivityData, AnswerId, ApplicationFlags, Attachment, ButtonStyle, ChannelId,
+        ChannelPinsUpdateEvent, ChannelType, CommandInteraction, ComponentInteraction, Context,
+        CreateActionRow, CreateAllowedMentions, CreateButton, CreateEmbed, CreateEmbedAuthor,
+        CreateInteractionResponse, CreateInteractionResponseFollowup,
+        CreateInteractionResponseMessage, CreateMessage, CreatePoll, CreatePollAnswer,
+        CreateThread, EditInteractionResponse, EditMessage, EditThread, Event, EventHandler,
+        GatewayIntents, Guild, GuildChannel, GuildId, Http, HttpError, Interaction, Member,
+        Message, MessageId, MessageInteractionMetadata, MessagePollVoteAddEvent,
+        MessagePollVoteRemoveEvent, MessageType, MessageUpdateEvent, Permissions, Reaction,
+        ReactionAddEvent, ReactionRemoveEvent, ReactionType, Ready, ShardId, StatusCode,
+        TeamMemberRole, Timestamp, User, UserId,
+    },
+    async_trait, collector,
+    gateway::ShardManager,
+};
+use shard_monitor::{ShardChange, ShardHealth, ShardMonitor, STALE_SHARD_EXIT_CODE};
+use snapshot::TargetSnapshot;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock, RwLock,
+    },
+    time::{Duration, Instant},
+};
+use store::{PinRecord, ScheduledUnpin, Store};
+use templates::TemplateKey;
+use tokio::{
+    sync::{mpsc, oneshot, watch, Notify, OnceCell},
+    time::{interval, sleep, timeout},
+};
+use tracing::{debug, error, field, info, instrument, warn, Instrument, Span};
+use ttl_map::TtlMap;
+use vote_stats::{VoteEnding, VoteStats, VoteSummary};
+use webhook::{PinEvent, PinEventKind, Webhook};
+
+const CHECKMARK_EMOJI: &str = "✅";
+/// Votes against a pin, counted in quorum mode.
+const REJECT_EMOJI: &str = "❌";
+/// Cancel reaction used until the application's slash emoji is available.
+const CANCEL_FALLBACK_EMOJI: &str = "🚫";
+/// Acknowledges an @mention request while its vote is set up.
+const PENDING_EMOJI: &str = "⏳";
+/// Left on an @mention request that didn't take.
+const FAILED_EMOJI: &str = "❌";
+const VOTE_BUTTON_PREFIX: &str = "pin_vote:";
+const REVIEW_BUTTON_PREFIX: &str = "pin_review:";
+const APPROVAL_BUTTON_PREFIX: &str = "pin_approval:";
+const PRESENCE_INTERVAL_SECS: u64 = 60;
+const CONFIG_POLL_SECS: u64 = 5;
+/// Longest the digest task sleeps, so schedule changes are picked up.
+const DIGEST_POLL_SECS: i64 = 300;
+/// How often the retention task looks for channels due a prune.
+const RETENTION_POLL_SECS: u64 = 3_600;
+/// How often the shard monitor polls the shard runners. Heartbeats come
+/// every 40 seconds or so, so most polls see a fresh one.
+const SHARD_POLL_SECS: u64 = 30;
+/// Least time between the retention task's requests, so many channels
+/// coming due at once don't turn into a burst.
+const PRUNE_SPACING: Duration = Duration::from_secs(2);
+/// How long a crosspost may wait on the rate limiter before it counts as
+/// rate limited. Publishing is limited to a handful per channel an hour,
+/// so an honest wait could hold the pin's follow-ups far too long.
+const CROSSPOST_TIMEOUT: Duration = Duration::from_secs(5);
+/// When a failed crosspost gets its one retry, past the hourly limit.
+const CROSSPOST_RETRY_DELAY: Duration = Duration::from_secs(3_600);
+/// Discord's error code for a message that was already published.
+const ALREADY_CROSSPOSTED: isize = 40033;
+/// Longest thread name Discord accepts.
+const MAX_THREAD_NAME_CHARS: usize = 100;
+/// Most pruned pins linked in the audit summary.
+const MAX_PRUNE_LINKS: usize = 10;
+/// Tries at taking down a rotated-out pin when other pins in the channel
+/// keep putting it back on cooldown.
+const ROTATION_ATTEMPTS: usize = 3;
+/// Least time between two pins anywhere, keeping bursts of finished votes
+/// clear of Discord's global rate limit.
+const PIN_SPACING: Duration = Duration::from_millis(250);
+/// The span a guild's pin budget covers.
+const GUILD_PIN_WINDOW: Duration = Duration::from_secs(60);
+/// Queue depth at which waiting pins are logged as a warning.
+const PIN_QUEUE_WARN_DEPTH: usize = 10;
+/// Reaction workers; reactions on one vote always go to the same one.
+const REACTION_WORKERS: usize = 4;
+/// Reactions each worker holds before the gateway handlers wait for it.
+const REACTION_QUEUE_CAPACITY: usize = 256;
+const PIN_ATTEMPTS: u32 = 3;
+const PIN_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// The cooldown after a pin or unpin when neither it nor the call before
+/// it in the channel ran into a rate limit, at most `PIN_COOLDOWN_SECS`.
+const CALM_PIN_COOLDOWN: Duration = Duration::from_secs(1);
+/// How long pin changes in a channel settle before the bot looks at them.
+const PIN_SYNC_DEBOUNCE: Duration = Duration::from_secs(3);
+/// Most messages one request may pin together.
+const MAX_BATCH_TARGETS: usize = 5;
+
+const CROSS_GUILD_REFUSAL: &str = "That message is in another server; I can only pin messages \
+     from this one.";
+
+/// Kept vague so a blocked member learns nothing from trying.
+const BLOCKED_REFUSAL: &str = "That message can't be pinned.";
+/// Posted on a vote dropped to make room under `MAX_SESSIONS`.
+const EVICTED_NOTICE: &str =
+    "⌛ Too many votes were open at once, so this one, the oldest, was dropped.";
+const ON_HOLD_NOTICE: &str = "This vote is on hold for moderator review.";
+/// A shown deadline this close to the real one is left alone rather than
+/// edited, so timing jitter never costs an API call.
+const DEADLINE_SLACK_SECS: i64 = 60;
+/// Starts the line `with_deadline` appends to a vote message.
+const DEADLINE_MARKER: &str = "\n⏳ ";
+/// How long a refusal that only the requester needs stays up.
+const BRIEF_REPLY: Duration = Duration::from_secs(10);
+
+/// Deliberately says nothing about who asked, or when.
+const OPTED_OUT_REFUSAL: &str = "The author of that message has asked not to have their messages \
+     pinned.";
+
+const REACTION_FALLBACK_NOTE: &str = "I can't add reactions here, so vote with the buttons below. \
+     (Moderators: grant me Add Reactions to vote with ✅ instead.)";
+/// Shown with the vote buttons when the guild votes by poll but the poll
+/// couldn't be posted.
+const POLL_FALLBACK_NOTE: &str = "I can't post polls here, so vote with the buttons below. \
+     (Moderators: grant me Send Polls to vote with a poll instead.)";
+const VOTE_SETUP_FAILED: &str = "I couldn't set up a pin vote: I need the Add Reactions or \
+     Send Messages permission in that channel. Ask a moderator to check my permissions.";
+
+/// Sent to anyone who messages the bot directly, at most once an hour each.
+const DM_REPLY: &str = "I only work in servers. To pin a message there, reply to it with a \
+     mention of me, use `/pin` with its link, or pick **Pin this message** from its Apps menu; \
+     members then vote with ✅. `/help` in a server explains how pinning works in that channel.";
+/// How long the bot stays quiet to someone it sent `DM_REPLY` to.
+const DM_REPLY_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// The question a poll vote asks; its first answer approves, the second
+/// rejects.
+const POLL_QUESTION: &str = "Pin this message?";
+/// Discord runs polls for whole hours, between these two.
+const POLL_MIN_HOURS: u64 = 1;
+const POLL_MAX_HOURS: u64 = 168;
+/// How long past its deadline a poll vote is left to its collector, which
+/// counts the poll's final voters, before the cleanup task expires it.
+const POLL_TALLY_GRACE: Duration = Duration::from_secs(300);
+
+/// Gateway intents - minimal for performance. GUILDS delivers guild_create
+/// and keeps the channel/thread cache populated. Guild message events are
+/// requested in every mode, since deleting a vote or its target ends the
+/// vote; their text is only requested for `@mention` requests, which
+/// interaction-only mode turns off. Direct messages come along only to point
+/// their senders to a server. Reactions in DMs are left out on purpose,
+/// since votes only run in guilds. Poll votes are requested in every mode,
+/// for guilds that vote by poll.
+pub fn intents(interaction_only: bool, member_events: bool) -> GatewayIntents {
+    let mut intents = GatewayIntents::GUILDS
+        | GatewayIntents::GUILD_MESSAGES
+        | GatewayIntents::GUILD_MESSAGE_REACTIONS
+        | GatewayIntents::GUILD_MESSAGE_POLLS;
+    if member_events {
+        intents |= GatewayIntents::GUILD_MEMBERS;
+    }
+    if interaction_only {
+        intents
+    } else {
+        intents | GatewayIntents::MESSAGE_CONTENT | GatewayIntents::DIRECT_MESSAGES
+    }
+}
+
+/// Whether the application may request the Server Members intent, going by
+/// the flags on its application info.
+pub fn server_members_granted(flags: ApplicationFlags) -> bool {
+    flags.intersects(
+        ApplicationFlags::GATEWAY_GUILD_MEMBERS | ApplicationFlags::GATEWAY_GUILD_MEMBERS_LIMITED,
+    )
+}
+
+/// Whether the application may request the Message Content intent, going
+/// by the flags on its application info.
+pub fn message_content_granted(flags: ApplicationFlags) -> bool {
+    flags.intersects(
+        ApplicationFlags::GATEWAY_MESSAGE_CONTENT
+            | ApplicationFlags::GATEWAY_MESSAGE_CONTENT_LIMITED,
+    )
+}
+
+#[derive(Debug, Clone)]
+struct VotingSession {
+    target_message_id: MessageId,
+    target_channel_id: ChannelId,
+    /// Where the vote takes place; differs from the target channel when the
+    /// request linked to a message elsewhere in the guild.
+    vote_channel_id: ChannelId,
+    guild_id: Option<GuildId>,
+    /// Each counted voter with the weight their vote was added at, so a
+    /// retraction subtracts the same amount even if their boost lapsed.
+    voters: HashMap<UserId, u32>,
+    /// Every way each voter is currently approving. A voter stays counted
+    /// until their last source is withdrawn.
+    vote_sources: HashMap<UserId, HashSet<VoteSource>>,
+    /// The weighted total of `voters`.
+    vote_count: Arc<AtomicU32>,
+    /// Votes needed to pin, resolved when the session opened. Every check
+    /// and progress message uses this, so a threshold changed mid-vote only
+    /// affects new sessions.
+    threshold: u32,
+    created_at: Instant,
+    /// How long the vote stays open when its request chose; `None` follows
+    /// `VOTE_TIMEOUT_SECS`.
+    timeout: Option<Duration>,
+    /// When the vote started by the wall clock, for showing voters when it
+    /// ends; expiry itself runs on `created_at`.
+    started_at: i64,
+    /// The bot's message presenting the vote, if it posted one.
+    notice: Option<VoteNotice>,
+    /// The bot's button prompt, when the session votes via buttons.
+    prompt_message_id: Option<MessageId>,
+    /// How members vote on this session, once its voting UI is up. It can
+    /// differ from the guild's mode when that one couldn't be set up.
+    vote_mode: VoteMode,
+    /// The bot's poll, when the session votes by poll.
+    poll: Option<VotePoll>,
+    /// Who asked for the pin; they may cancel the vote.
+    initiator_id: UserId,
+    /// The target as it was when the vote opened, for the pin history and
+    /// the archive copy. `None` when the session only knows its ids.
+    target_snapshot: Option<TargetSnapshot>,
+    /// Unpin this long after the vote succeeds; `None` pins permanently.
+    pin_duration: Option<Duration>,
+    /// Why the initiator asked for the pin, already cleaned up for display.
+    reason: Option<String>,
+    /// Set once the halfway reminder went out, so it is only sent once.
+    reminded: bool,
+    /// Whether the bot put its ✅ on the target, to take back at the end.
+    target_reacted: bool,
+    /// The vote's discussion thread, archived once the vote ends.
+    thread_id: Option<ChannelId>,
+    /// Where the pin is mirrored when no category reaction wins.
+    default_archive: Option<ChannelId>,
+    /// The guild's category reactions as configured when the vote started.
+    categories: Vec<CategoryTally>,
+    /// The other messages of a batch request, pinned along with the target.
+    batch: Vec<PinTarget>,
+    /// The guild's quorum rule when the vote started; `None` passes on
+    /// `threshold` approvals alone.
+    quorum: Option<QuorumRule>,
+    /// Each member voting against, with their weight. Only quorum votes
+    /// take rejections.
+    rejections: HashMap<UserId, u32>,
+    /// Claimed by the one handler that pins once the vote passes, so votes
+    /// landing at the same moment don't pin twice.
+    finalizing: Arc<AtomicBool>,
+    /// Woken when the claim is handed back or the session ends, which is
+    /// all a collector waits for once a claimed vote is past its deadline.
+    hold_released: Arc<Notify>,
+    /// The guild's brigade guard when the vote started; `None` never holds
+    /// the vote.
+    brigade_guard: Option<BrigadeGuard>,
+    /// When the latest approvals landed, oldest first; one more than the
+    /// guard allows is all it needs to keep.
+    recent_votes: VecDeque<Instant>,
+    /// Held for moderator review: new votes aren't counted and the vote
+    /// can't pass.
+    paused: bool,
+    /// A moderator let the vote carry on, so the guard leaves it alone.
+    reviewed: bool,
+    /// How long a passed vote waits for a moderator's sign-off before
+    /// pinning; `None` pins without one, as does a vote already approved.
+    approval_timeout: Option<Duration>,
+    /// Passed and waiting for that sign-off. The vote stays open that much
+    /// longer and can't pass again meanwhile.
+    awaiting_approval: bool,
+    /// How long the vote stays open at least, even once it has passed.
+    min_open: Duration,
+    /// Set while a passed vote waits out `min_open`, so only one wait runs.
+    pin_scheduled: bool,
+    /// Each target channel's daily pin quota when the vote started; 0 or a
+    /// missing entry is unlimited.
+    pin_quotas: HashMap<ChannelId, u32>,
+    /// When the guild's quiet hours end, while a passed vote waits for
+    /// them. The vote stays claimed meanwhile, so it neither expires nor
+    /// passes twice, but it may still be cancelled.
+    quiet_until: Option<i64>,
+    /// The most `vote_count` reached, for the vote's stats.
+    peak_votes: u32,
+    /// How many times a counted vote was taken back, for the vote's stats.
+    removals: u32,
+}
+
+/// A bot message presenting a vote. Its deadline follows the session's
+/// expiry and comes off when the vote ends.
+#[derive(Debug, Clone)]
+struct VoteNotice {
+    message_id: MessageId,
+    /// The message's text without the deadline.
+    text: String,
+    /// The expiry the message shows right now.
+    shown_expiry: i64,
+}
+
+/// The poll a session votes with. The answer ids come from the posted
+/// poll rather than being assumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct VotePoll {
+    message_id: MessageId,
+    yes: AnswerId,
+    no: AnswerId,
+}
+
+/// A message to pin, with what the pin history keeps about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PinTarget {
+    channel_id: ChannelId,
+    message_id: MessageId,
+    author_id: Option<UserId>,
+    excerpt: String,
+    /// The message as it was when the request came in, for the archive
+    /// copy; without one the archive fetches the live message.
+    snapshot: Option<TargetSnapshot>,
+}
+
+impl PinTarget {
+    /// The history fields come from `snapshot` when there is one.
+    fn new(channel_id: ChannelId, message_id: MessageId, snapshot: Option<TargetSnapshot>) -> Self {
+        Self {
+            channel_id,
+            message_id,
+            author_id: snapshot.as_ref().map(|snapshot| snapshot.author_id),
+            excerpt: snapshot
+                .as_ref()
+                .map(TargetSnapshot::excerpt)
+                .unwrap_or_default(),
+            snapshot,
+        }
+    }
+}
+
+impl From<&Message> for PinTarget {
+    fn from(message: &Message) -> Self {
+        Self::new(
+            message.channel_id,
+            message.id,
+            Some(TargetSnapshot::capture(message)),
+        )
+    }
+}
+
+/// How a channel, or a thread with its parent, fares on the channel lists.
+struct ChannelListing {
+    denied: bool,
+    has_allowlist: bool,
+    allowlisted: bool,
+}
+
+impl ChannelListing {
+    fn allows(&self) -> bool {
+        !self.denied && (!self.has_allowlist || self.allowlisted)
+    }
+}
+
+/// An open vote as `/listvotes` shows it.
+struct OpenVote {
+    session_id: MessageId,
+    vote_channel_id: ChannelId,
+    /// The first message pinned; a batch has `batch_size` in all.
+    target: PinTarget,
+    batch_size: usize,
+    /// `votes/threshold`, or both tallies for a quorum vote.
+    progress: String,
+    expires_at: i64,
+    /// Passed and waiting for a moderator's sign-off.
+    awaiting_approval: bool,
+}
+
+/// What one pin request asked for, shared by every message it covers.
+struct PinOrder<'a> {
+    guild_id: Option<GuildId>,
+    initiator_id: UserId,
+    /// The weighted total of the votes, and how many members cast them.
+    votes: u32,
+    voters: u32,
+    duration: Option<Duration>,
+    archive: Option<ChannelId>,
+    reason: Option<&'a str>,
+}
+
+/// What a request asked of its vote besides the messages.
+#[derive(Default)]
+struct VoteTerms {
+    /// Unpin this long after the vote succeeds; `None` pins permanently.
+    pin_duration: Option<Duration>,
+    reason: Option<String>,
+    /// The vote's own length, already within the configured bounds.
+    timeout: Option<Duration>,
+}
+
+/// Who reacted with one archive category's emoji.
+#[derive(Debug, Clone)]
+struct CategoryTally {
+    emoji: ReactionType,
+    channel_id: ChannelId,
+    reactors: HashSet<UserId>,
+}
+
+impl From<&ArchiveCategory> for CategoryTally {
+    fn from(category: &ArchiveCategory) -> Self {
+        Self {
+            emoji: category.emoji.clone(),
+            channel_id: category.channel_id,
+            reactors: HashSet::new(),
+        }
+    }
+}
+
+impl VotingSession {
+    fn new(
+        target_message_id: MessageId,
+        target_channel_id: ChannelId,
+        vote_channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+        initiator_id: UserId,
+        threshold: u32,
+    ) -> Self {
+        Self {
+            target_message_id,
+            target_channel_id,
+            vote_channel_id,
+            guild_id,
+            voters: HashMap::new(),
+            vote_sources: HashMap::new(),
+            vote_count: Arc::new(AtomicU32::new(0)),
+            threshold,
+            created_at: Instant::now(),
+            timeout: None,
+            started_at: Timestamp::now().unix_timestamp(),
+            notice: None,
+            prompt_message_id: None,
+            vote_mode: VoteMode::Reactions,
+            poll: None,
+            initiator_id,
+            target_snapshot: None,
+            pin_duration: None,
+            reason: None,
+            reminded: false,
+            target_reacted: false,
+            thread_id: None,
+            default_archive: None,
+            categories: Vec::new(),
+            batch: Vec::new(),
+            quorum: None,
+            rejections: HashMap::new(),
+            finalizing: Arc::new(AtomicBool::new(false)),
+            hold_released: Arc::new(Notify::new()),
+            brigade_guard: None,
+            recent_votes: VecDeque::new(),
+            paused: false,
+            reviewed: false,
+            approval_timeout: None,
+            awaiting_approval: false,
+            min_open: Duration::ZERO,
+            pin_scheduled: false,
+            pin_quotas: HashMap::new(),
+            quiet_until: None,
+            peak_votes: 0,
+            removals: 0,
+        }
+    }
+
+    /// `Passed` for the one caller that claims a vote that has passed, and
+    /// `Counted` for everyone else, including votes landing while the pin
+    /// is underway.
+    fn outcome(&self) -> VoteOutcome {
+        let votes = self.get_vote_count();
+        if self.paused || self.awaiting_approval || !self.has_passed() {
+            return VoteOutcome::Counted(votes);
+        }
+        if !self.open_time_left().is_zero() {
+            return VoteOutcome::Deferred(votes);
+        }
+        if self.claim_finalization() {
+            VoteOutcome::Passed {
+                votes,
+                channel_id: self.target_channel_id,
+                message_id: self.target_message_id,
+            }
+        } else {
+            VoteOutcome::Counted(votes)
+        }
+    }
+
+    /// `votes/threshold`, or both tallies for a quorum vote.
+    fn progress(&self) -> String {
+        match self.quorum {
+            Some(_) => self.tally(),
+            None => format!("{}/{}", self.get_vote_count(), self.threshold),
+        }
+    }
+
+    /// Notes an approval landing at `now`. Returns true when it makes more
+    /// votes within the guard's window than the guard allows.
+    fn note_vote_at(&mut self, now: Instant) -> bool {
+        let Some(guard) = self.brigade_guard.filter(|_| !self.reviewed) else {
+            return false;
+        };
+        let limit = guard.votes as usize;
+        self.recent_votes.push_back(now);
+        while self.recent_votes.len() > limit + 1 {
+            self.recent_votes.pop_front();
+        }
+        self.recent_votes.len() > limit
+            && self.recent_votes.front().is_some_and(|oldest| {
+                now.duration_since(*oldest) <= Duration::from_secs(guard.window_secs.into())
+            })
+    }
+
+    fn claim_finalization(&self) -> bool {
+        self.finalizing
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    /// Hands the session back to voting after a pin attempt that failed.
+    fn release_finalization(&self) {
+        self.finalizing.store(false, Ordering::Release);
+        self.hold_released.notify_one();
+    }
+
+    fn is_finalizing(&self) -> bool {
+        self.finalizing.load(Ordering::Acquire)
+    }
+
+    /// Whether the pin is underway, which is too late to call it off. A
+    /// vote held through quiet hours isn't yet.
+    fn is_pinning(&self) -> bool {
+        self.is_finalizing() && self.quiet_until.is_none()
+    }
+
+    /// Every message the vote pins: the target, then the rest of a batch.
+    fn pin_targets(&self) -> Vec<PinTarget> {
+        let target = PinTarget::new(
+            self.target_channel_id,
+            self.target_message_id,
+            self.target_snapshot.clone(),
+        );
+        std::iter::once(target)
+            .chain(self.batch.iter().cloned())
+            .collect()
+    }
+
+    fn has_category(&self, emoji: &ReactionType) -> bool {
+        self.categories
+            .iter()
+            .any(|category| emoji::same_emoji(&category.emoji, emoji))
+    }
+
+    /// Adds or withdraws a category reaction. Returns whether it changed
+    /// the tally.
+    fn tally_category(&mut self, emoji: &ReactionType, user_id: UserId, added: bool) -> bool {
+        let Some(category) = self
+            .categories
+            .iter_mut()
+            .find(|category| emoji::same_emoji(&category.emoji, emoji))
+        else {
+            return false;
+        };
+        if added {
+            category.reactors.insert(user_id)
+        } else {
+            category.reactors.remove(&user_id)
+        }
+    }
+
+    /// The category channel with the most reactions, or the default archive
+    /// when no category got any or several share the lead.
+    fn archive_channel(&self) -> Option<ChannelId> {
+        let most = self
+            .categories
+            .iter()
+            .map(|category| category.reactors.len())
+            .max()
+            .unwrap_or(0);
+        let mut leaders = self
+            .categories
+            .iter()
+            .filter(|category| most > 0 && category.reactors.len() == most);
+        match (leaders.next(), leaders.next()) {
+            (Some(leader), None) => Some(leader.channel_id),
+            _ => self.default_archive,
+        }
+    }
+
+    /// Records an approval from `source`, counting `weight` votes for a new
+    /// voter. Returns true only when this made the user a new voter; a
+    /// repeat approval keeps the weight it was first counted at.
+    fn add_vote(&mut self, user_id: UserId, source: VoteSource, weight: u32) -> bool {
+        self.vote_sources.entry(user_id).or_default().insert(source);
+        if self.voters.contains_key(&user_id) {
+            return false;
+        }
+        self.voters.insert(user_id, weight);
+        let votes = self.vote_count.fetch_add(weight, Ordering::Relaxed) + weight;
+        self.peak_votes = self.peak_votes.max(votes);
+        true
+    }
+
+    /// Withdraws the approval from `source`. Returns true only when it was
+    /// the user's last one and their vote no longer counts.
+    fn remove_vote(&mut self, user_id: UserId, source: VoteSource) -> bool {
+        let Some(sources) = self.vote_sources.get_mut(&user_id) else {
+            return false;
+        };
+        if !sources.remove(&source) || !sources.is_empty() {
+            return false;
+        }
+        self.vote_sources.remove(&user_id);
+
+        if let Some(weight) = self.voters.remove(&user_id) {
+            self.vote_count.fetch_sub(weight, Ordering::Relaxed);
+            self.removals += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn get_vote_count(&self) -> u32 {
+        self.vote_count.load(Ordering::Relaxed)
+    }
+
+    /// Takes back everything a member who left put on the vote: approval,
+    /// rejection and category reactions. Returns whether any of it counted.
+    fn drop_voter(&mut self, user_id: UserId) -> bool {
+        self.vote_sources.remove(&user_id);
+        let approved = match self.voters.remove(&user_id) {
+            Some(weight) => {
+                self.vote_count.fetch_sub(weight, Ordering::Relaxed);
+                self.removals += 1;
+                true
+            }
+            None => false,
+        };
+        let rejected = self.rejections.remove(&user_id).is_some();
+        for category in &mut self.categories {
+            category.reactors.remove(&user_id);
+        }
+        approved || rejected
+    }
+
+    /// Records a rejection. Returns false if the user already rejected.
+    fn add_rejection(&mut self, user_id: UserId, weight: u32) -> bool {
+        if self.rejections.contains_key(&user_id) {
+            return false;
+        }
+        self.rejections.insert(user_id, weight);
+        true
+    }
+
+    fn remove_rejection(&mut self, user_id: UserId) -> bool {
+        self.rejections.remove(&user_id).is_some()
+    }
+
+    fn rejection_count(&self) -> u32 {
+        self.rejections.values().sum()
+    }
+
+    /// The weight of everyone who voted either way, counting a member who
+    /// did both once.
+    fn participation(&self) -> u32 {
+        let both: u32 = self
+            .rejections
+            .iter()
+            .filter_map(|(user_id, rejected)| {
+                self.voters
+                    .get(user_id)
+                    .map(|approved| (*approved).min(*rejected))
+            })
+            .sum();
+        self.get_vote_count() + self.rejection_count() - both
+    }
+
+    fn has_passed(&self) -> bool {
+        match self.quorum {
+            Some(rule) => rule.passes(
+                self.participation(),
+                self.get_vote_count(),
+                self.rejection_count(),
+            ),
+            None => self.get_vote_count() >= self.threshold,
+        }
+    }
+
+    /// Where a quorum vote stands, e.g. `✅ 4 / ❌ 2`.
+    fn tally(&self) -> String {
+        format!(
+            "{} {} / {} {}",
+            CHECKMARK_EMOJI,
+            self.get_vote_count(),
+            REJECT_EMOJI,
+            self.rejection_count()
+        )
+    }
+
+    fn event(&self, kind: PinEventKind) -> PinEvent {
+        PinEvent::new(
+            kind,
+            self.guild_id,
+            self.target_channel_id,
+            self.target_message_id,
+            self.initiator_id,
+            self.get_vote_count(),
+        )
+    }
+
+    /// The vote's own length, else `default`.
+    fn timeout(&self, default: Duration) -> Duration {
+        self.timeout.unwrap_or(default)
+    }
+
+    fn is_expired(&self, default: Duration) -> bool {
+        self.created_at.elapsed() > self.timeout(default)
+    }
+
+    /// Expired, and past the grace a poll vote gets for its final count.
+    /// The cleanup task only expires sessions that are overdue.
+    fn is_overdue(&self, default: Duration) -> bool {
+        let grace = if self.poll.is_some() {
+            POLL_TALLY_GRACE
+        } else {
+            Duration::ZERO
+        };
+        self.created_at.elapsed() > self.timeout(default) + grace
+    }
+
+    /// Voters currently approving through the poll.
+    fn poll_voters(&self) -> HashSet<UserId> {
+        self.vote_sources
+            .iter()
+            .filter(|(_, sources)| sources.contains(&VoteSource::Poll))
+            .map(|(user_id, _)| *user_id)
+            .collect()
+    }
+
+    /// How much longer the vote must stay open before it may pin.
+    fn open_time_left(&self) -> Duration {
+        self.min_open.saturating_sub(self.created_at.elapsed())
+    }
+
+    /// When the vote expires by the wall clock, as a Unix timestamp.
+    fn expires_at(&self, default: Duration) -> i64 {
+        self.started_at + self.timeout(default).as_secs() as i64
+    }
+
+    /// Halfway to expiry with some support but not enough, and not yet
+    /// reminded.
+    fn needs_reminder(&self, default: Duration) -> bool {
+        let votes = self.get_vote_count();
+        // The votes missing from a quorum vote aren't a single count
+        self.quorum.is_none()
+            && !self.reminded
+            && !self.awaiting_approval
+            && votes > 0
+            && votes < self.threshold
+            && self.created_at.elapsed() >= self.timeout(default) / 2
+    }
+}
+
+/// How a voter expressed their approval.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum VoteSource {
+    Reaction(Checkmark),
+    /// A checkmark on the message being pinned rather than the vote.
+    TargetReaction(Checkmark),
+    /// Super (burst) reactions sit next to a member's normal one with the
+    /// same emoji, so they are tracked apart from it.
+    SuperReaction(Checkmark),
+    TargetSuperReaction(Checkmark),
+    Button,
+    /// The approving answer on the vote's poll.
+    Poll,
+    /// A "+1" or "yes" replied to the vote or its target. It can't be taken
+    /// back, as there is no reaction to remove.
+    Reply,
+}
+
+impl VoteSource {
+    fn reaction(checkmark: Checkmark, on_target: bool, burst: bool) -> Self {
+        match (on_target, burst) {
+            (false, false) => VoteSource::Reaction(checkmark),
+            (true, false) => VoteSource::TargetReaction(checkmark),
+            (false, true) => VoteSource::SuperReaction(checkmark),
+            (true, true) => VoteSource::TargetSuperReaction(checkmark),
+        }
+    }
+
+    fn is_super(self) -> bool {
+        matches!(
+            self,
+            VoteSource::SuperReaction(_) | VoteSource::TargetSuperReaction(_)
+        )
+    }
+}
+
+/// What `/purge-user-data` removed. A table that failed to purge is `None`
+/// and was left untouched.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PurgeReport {
+    pins_anonymized: Option<usize>,
+    opt_out_cleared: Option<bool>,
+    blocklists_cleared: Option<usize>,
+    /// Open votes the user was counted on, with the outcome after.
+    sessions: Vec<(MessageId, VoteOutcome)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VoteOutcome {
+    NoSession,
+    /// Already voted (when adding) or never voted (when removing).
+    Duplicate,
+    Counted(u32),
+    /// The vote was counted and came in too fast on the heels of the others;
+    /// the session is now held for review.
+    Flagged(u32),
+    /// The session is held for review, so the vote wasn't counted.
+    Paused,
+    /// The vote passed but hasn't been open long enough yet; the pin waits.
+    Deferred(u32),
+    /// The vote reached the threshold; the target is ready to pin.
+    Passed {
+        votes: u32,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    },
+}
+
+/// The message a new vote is attached to.
+enum VoteAnchor<'a> {
+    /// The member's own reply-mention; reactions go on it, buttons on a
+    /// separate prompt.
+    Reply(&'a Message),
+    /// The bot's public response to a command; it doubles as the prompt.
+    Interaction(&'a CommandInteraction, &'a Message),
+}
+
+impl VoteAnchor<'_> {
+    fn message(&self) -> &Message {
+        match self {
+            VoteAnchor::Reply(message) | VoteAnchor::Interaction(_, message) => message,
+        }
+    }
+
+    fn guild_id(&self) -> Option<GuildId> {
+        match self {
+            VoteAnchor::Reply(message) => message.guild_id,
+            VoteAnchor::Interaction(command, _) => command.guild_id,
+        }
+    }
+
+    fn initiator(&self) -> UserId {
+        match self {
+            VoteAnchor::Reply(message) => message.author.id,
+            VoteAnchor::Interaction(command, _) => command.user.id,
+        }
+    }
+}
+
+/// A pin handed to the pin worker, answered on `reply` once it ran.
+struct PinRequest {
+    guild_id: Option<GuildId>,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    /// Pin calls made for it so far.
+    attempts: u32,
+    /// Set after an error that may pass; the next try waits for it.
+    retry_at: Option<Instant>,
+    reply: oneshot::Sender<bool>,
+}
+
+/// What one pin call came to.
+enum PinAttempt {
+    Pinned,
+    Failed,
+    /// The error may pass; try again after this long.
+    Retry(Duration),
+}
+
+/// A pin in an announcement channel whose publishing failed, waiting for
+/// its one retry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PendingCrosspost {
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    retry_at: Instant,
+}
+
+/// Result of an unpin request; errors are left to the caller to explain.
+enum UnpinOutcome {
+    Unpinned,
+    CoolingDown,
+    Failed(serenity::Error),
+}
+
+enum VoteAction {
+    Approve,
+    Reject,
+    Cancel,
+}
+
+/// Rebuilds the full config, flags and environment included.
+type ConfigLoader = Box<dyn Fn() -> Result<Config> + Send + Sync>;
+
+/// What a config reload did.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct ConfigReload {
+    /// Settings now in effect, each as `key: old → new`.
+    applied: Vec<String>,
+    /// Changed settings that only take effect after a restart.
+    pending: Vec<&'static str>,
+}
+
+pub struct BotData {
+    voting_sessions: DashMap<MessageId, VotingSession>,
+    /// Session ids by target message, so a ✅ on the message being pinned
+    /// counts too. Points at the newest session when several share a target.
+    sessions_by_target: DashMap<MessageId, MessageId>,
+    /// Session ids by the poll they vote with.
+    sessions_by_poll: DashMap<MessageId, MessageId>,
+    /// Targets of ended sessions whose ✅ from the bot is still up, taken
+    /// down by `clear_target_reactions`.
+    stale_target_reactions: Mutex<Vec<(ChannelId, MessageId)>>,
+    /// Discussion threads of ended sessions, archived by
+    /// `archive_vote_threads`.
+    stale_threads: Mutex<Vec<ChannelId>>,
+    /// Crossposts that failed once, retried by `retry_crossposts`.
+    pending_crossposts: Mutex<Vec<PendingCrosspost>>,
+    /// Votes that expired or were evicted with something to say about it,
+    /// as (vote channel, session id, notice), posted by `announce_failed_votes`.
+    failed_votes: Mutex<Vec<(ChannelId, MessageId, String)>>,
+    /// Notices of expired or evicted sessions, as (vote channel, notice), whose
+    /// deadline `settle_vote_deadlines` still has to take off.
+    expired_notices: Mutex<Vec<(ChannelId, VoteNotice)>>,
+    /// Passed votes waiting for their guild's quiet hours to end, pinned
+    /// by `release_quiet_pins`.
+    quiet_pins: Mutex<Vec<MessageId>>,
+    /// Channels that saw a pin or unpin within the pin cooldown, and
+    /// whether Discord rate limited them.
+    pin_cooldowns: TtlMap<ChannelId, bool>,
+    /// When the wait Discord last asked for on a channel's pins runs out,
+    /// as the HTTP client's ratelimiter reports it. The next pin or unpin
+    /// there takes it for its cooldown.
+    pin_rate_limits: DashMap<ChannelId, Instant>,
+    /// Channels whose latest pin or unpin ran into a rate limit.
+    rate_limited_channels: DashSet<ChannelId>,
+    /// When each guild's pins within `GUILD_PIN_WINDOW` went through,
+    /// oldest first, for its pins-per-minute budget.
+    guild_pins: DashMap<GuildId, VecDeque<Instant>>,
+    /// Paged command replies, by message; dropped once their buttons stop
+    /// working.
+    paginations: DashMap<MessageId, Paginator>,
+    /// Feeds the pin worker; every pin goes through `queue_pin`.
+    pin_queue: mpsc::UnboundedSender<PinRequest>,
+    /// Taken by the worker when it starts.
+    pin_requests: Mutex<Option<mpsc::UnboundedReceiver<PinRequest>>>,
+    /// Pins queued or waiting out a cooldown.
+    pin_queue_depth: AtomicUsize,
+    guild_configs: DashMap<GuildId, GuildConfig>,
+    store: Store,
+    /// Swapped wholesale when the config file is reloaded; see `config()`.
+    config: RwLock<Arc<Config>>,
+    /// How often the reaction bot check had to fall back to HTTP.
+    user_fetch_fallbacks: AtomicU64,
+    /// Completed passes of the cleanup task.
+    cleanup_runs: AtomicU64,
+    /// Successful pins since startup.
+    pins_served: AtomicU64,
+    /// Channels where reactions failed and the buttons fallback was logged.
+    reaction_fallback_channels: DashSet<ChannelId>,
+    /// Set once the missing Message Content intent was logged.
+    empty_content_warned: AtomicBool,
+    /// Who was sent `DM_REPLY` within the last `DM_REPLY_INTERVAL`.
+    dm_replies: TtlMap<UserId, ()>,
+    /// Open sessions per vote channel, kept in step with `voting_sessions`
+    /// so the per-channel cap is checked without scanning the map.
+    channel_session_counts: DashMap<ChannelId, usize>,
+    /// Open sessions per initiator, kept the same way for the per-member
+    /// cap.
+    initiator_session_counts: DashMap<UserId, usize>,
+    /// Session ids in creation order, for evicting the oldest. Ids of
+    /// sessions that already ended are skipped on eviction and pruned by
+    /// the cleanup task.
+    session_order: Mutex<VecDeque<MessageId>>,
+    webhook: Option<Arc<Webhook>>,
+    started_at: Instant,
+    /// Set through `set_shard_manager` once the client exists, for latency
+    /// lookups.
+    shard_manager: OnceLock<Arc<ShardManager>>,
+    /// What the shard monitor last saw of each shard, for `/status`.
+    shard_monitor: Mutex<ShardMonitor>,
+    /// Set when the shard monitor stopped the client over a stale shard.
+    stale_shard_exit: AtomicBool,
+    /// Set once per process on the first ready event.
+    app_emojis: OnceCell<AppEmojis>,
+    /// Rebuilds the config for `/config-reload` and the config watch; set
+    /// through `Handler::set_config_loader`.
+    config_loader: OnceLock<ConfigLoader>,
+    /// The application's owners, looked up the first time an owner command
+    /// is used.
+    app_owners: OnceCell<HashSet<UserId>>,
+    /// Held while an index message is rewritten, so two pins landing
+    /// together can't both post a new one.
+    pin_index_lock: tokio::sync::Mutex<()>,
+    /// Channels with a pin sync waiting out `PIN_SYNC_DEBOUNCE`.
+    pending_pin_syncs: DashSet<ChannelId>,
+}
+
+impl BotData {
+    fn new(config: Config, store: Store) -> Self {
+        let webhook = config
+            .webhook_url
+            .clone()
+            .and_then(|url| match Webhook::new(url) {
+                Ok(webhook) => Some(Arc::new(webhook)),
+                Err(e) => {
+                    warn!(error = %e, "Webhook disabled, failed to build the HTTP client");
+                    None
+                }
+            });
+
+        let (pin_queue, pin_requests) = mpsc::unbounded_channel();
+        Self {
+            voting_sessions: DashMap::new(),
+            sessions_by_target: DashMap::new(),
+            sessions_by_poll: DashMap::new(),
+            stale_target_reactions: Mutex::new(Vec::new()),
+            stale_threads: Mutex::new(Vec::new()),
+            pending_crossposts: Mutex::new(Vec::new()),
+            failed_votes: Mutex::new(Vec::new()),
+            expired_notices: Mutex::new(Vec::new()),
+            quiet_pins: Mutex::new(Vec::new()),
+            pin_cooldowns: TtlMap::new(Duration::from_secs(config.pin_cooldown_secs)),
+            pin_rate_limits: DashMap::new(),
+            rate_limited_channels: DashSet::new(),
+            guild_pins: DashMap::new(),
+            paginations: DashMap::new(),
+            pin_queue,
+            pin_requests: Mutex::new(Some(pin_requests)),
+            pin_queue_depth: AtomicUsize::new(0),
+            guild_configs: DashMap::new(),
+            store,
+            config: RwLock::new(Arc::new(config)),
+            user_fetch_fallbacks: AtomicU64::new(0),
+            cleanup_runs: AtomicU64::new(0),
+            pins_served: AtomicU64::new(0),
+            reaction_fallback_channels: DashSet::new(),
+            empty_content_warned: AtomicBool::new(false),
+            dm_replies: TtlMap::new(DM_REPLY_INTERVAL),
+            channel_session_counts: DashMap::new(),
+            initiator_session_counts: DashMap::new(),
+            session_order: Mutex::new(VecDeque::new()),
+            webhook,
+            started_at: Instant::now(),
+            shard_manager: OnceLock::new(),
+            shard_monitor: Mutex::new(ShardMonitor::default()),
+            stale_shard_exit: AtomicBool::new(false),
+            app_emojis: OnceCell::new(),
+            config_loader: OnceLock::new(),
+            app_owners: OnceCell::new(),
+            pin_index_lock: tokio::sync::Mutex::new(()),
+            pending_pin_syncs: DashSet::new(),
+        }
+    }
+
+    /// Returns the guild's settings, loading them from the store on first use.
+    fn guild_config(&self, guild_id: GuildId) -> GuildConfig {
+        if let Some(config) = self.guild_configs.get(&guild_id) {
+            return config.clone();
+        }
+
+        let config = match self.store.load_guild_config(guild_id) {
+            Ok(config) => config.unwrap_or_default(),
+            Err(e) => {
+                error!(guild_id = %guild_id, error = %e, "Failed to load guild config");
+                GuildConfig::default()
+            }
+        };
+        self.guild_configs.insert(guild_id, config.clone());
+        config
+    }
+
+    /// Applies `update` to the guild's settings and persists the result.
+    /// The cached copy is only replaced once the write succeeded.
+    fn update_guild_config(
+        &self,
+        guild_id: GuildId,
+        update: impl FnOnce(&mut GuildConfig),
+    ) -> Result<GuildConfig> {
+        let mut config = self.guild_config(guild_id);
+        update(&mut config);
+        self.store.save_guild_config(guild_id, &config)?;
+        self.guild_configs.insert(guild_id, config.clone());
+        Ok(config)
+    }
+
+    /// Whether a guild the gateway just delivered needs onboarding: the bot
+    /// was just added (`is_new`), or the guild has no settings record yet,
+    /// as when the bot joined while offline. A missing record is created
+    /// with the defaults, so onboarding doesn't run again on the next
+    /// reconnect.
+    fn claim_onboarding(&self, guild_id: GuildId, is_new: Option<bool>) -> bool {
+        let known = match self.store.load_guild_config(guild_id) {
+            Ok(config) => config.is_some(),
+            Err(e) => {
+                error!(guild_id = %guild_id, error = %e, "Failed to look up the guild config");
+                return false;
+            }
+        };
+        if known {
+            return is_new == Some(true);
+        }
+        let config = self.guild_config(guild_id);
+        if let Err(e) = self.store.save_guild_config(guild_id, &config) {
+            error!(guild_id = %guild_id, error = %e, "Failed to record the guild config");
+            return false;
+        }
+        true
+    }
+
+    /// Turns the guild's digest on or off. A new schedule counts as having
+    /// just run, so the first digest goes out at its next slot.
+    fn set_digest(&self, guild_id: GuildId, digest: Option<DigestSchedule>) -> Result<GuildConfig> {
+        let config = self.update_guild_config(guild_id, |config| config.digest = digest)?;
+        if digest.is_some() {
+            self.store
+                .set_digest_last_run(guild_id, Timestamp::now().unix_timestamp())?;
+        }
+        Ok(config)
+    }
+
+    /// The current settings. Callers hold the snapshot for the duration of
+    /// one decision so a reload can't change it halfway through.
+    fn config(&self) -> Arc<Config> {
+        Arc::clone(&self.config.read().expect("config lock poisoned"))
+    }
+
+    fn set_config(&self, config: Config) {
+        *self.config.write().expect("config lock poisoned") = Arc::new(config);
+    }
+
+    /// Rebuilds the config and swaps in the settings that can change while
+    /// the bot runs. The swap replaces the whole config at once, so no
+    /// handler sees half of a reload. Open votes keep the thresholds they
+    /// started with; only new ones see the change. A config that fails to
+    /// load leaves the current one alone.
+    fn reload_config(&self) -> Result<ConfigReload> {
+        let Some(load) = self.config_loader.get() else {
+            anyhow::bail!("Config reloading isn't set up");
+        };
+        let next = load()?;
+        let current = self.config();
+        let (merged, pending) = current.reloaded(next);
+        let applied = current.changes(&merged);
+        self.set_config(merged);
+        Ok(ConfigReload { applied, pending })
+    }
+
+    /// Whether `user_id` may use the owner commands: listed in `owner_ids`,
+    /// or an owner of the application. For an application owned by a team
+    /// that is the team's owner and its admins.
+    async fn is_owner(&self, http: &Http, user_id: UserId) -> bool {
+        if self.config().owner_ids.contains(&user_id) {
+            return true;
+        }
+        match self
+            .app_owners
+            .get_or_try_init(|| application_owners(http))
+            .await
+        {
+            Ok(owners) => owners.contains(&user_id),
+            Err(e) => {
+                warn!(error = %e, "Failed to look up the application's owners");
+                false
+            }
+        }
+    }
+
+    /// Events without a guild (DMs) are not subject to the guild allowlist.
+    fn is_guild_allowed(&self, guild_id: Option<GuildId>) -> bool {
+        let config = self.config();
+        match guild_id {
+            Some(id) => config.allowed_guilds.is_empty() || config.allowed_guilds.contains(&id),
+            None => true,
+        }
+    }
+
+    /// Checks the channel against the process-wide, config file guild
+    /// section and per-guild allow/deny lists. `channels` holds the channel
+    /// itself followed by its parent for threads, so a thread inherits its
+    /// parent's status unless listed explicitly.
+    fn is_channel_allowed(&self, guild_id: GuildId, channels: &[ChannelId]) -> bool {
+        self.channel_listing(guild_id, channels).allows()
+    }
+
+    /// Where `channels` stand on the allow and deny lists from the
+    /// environment, the config file and `/config`, all merged.
+    fn channel_listing(&self, guild_id: GuildId, channels: &[ChannelId]) -> ChannelListing {
+        let config = self.config();
+        let overrides = config.guilds.get(&guild_id).cloned().unwrap_or_default();
+        let guild = self.guild_config(guild_id);
+
+        let denied = |id: &ChannelId| {
+            config.denied_channels.contains(id)
+                || overrides.denied_channels.contains(id)
+                || guild.denied_channels.contains(id)
+        };
+        let allowed = |id: &ChannelId| {
+            config.allowed_channels.contains(id)
+                || overrides.allowed_channels.contains(id)
+                || guild.allowed_channels.contains(id)
+        };
+        ChannelListing {
+            denied: channels.iter().any(denied),
+            has_allowlist: !config.allowed_channels.is_empty()
+                || !overrides.allowed_channels.is_empty()
+                || !guild.allowed_channels.is_empty(),
+            allowlisted: channels.iter().any(allowed),
+        }
+    }
+
+    /// Votes needed to pin in a channel: the first of `channels` with an
+    /// override, then the guild's threshold, then `CONFIRM_CAP`.
+    fn threshold(&self, guild_id: Option<GuildId>, channels: &[ChannelId]) -> u32 {
+        let config = self.config();
+        let Some(guild_id) = guild_id else {
+            return config.threshold(None);
+        };
+        let guild = self.guild_config(guild_id);
+        channels
+            .iter()
+            .find_map(|id| guild.channel_thresholds.get(id).copied())
+            .unwrap_or_else(|| config.threshold(Some(guild_id)))
+    }
+
+    /// The reaction showing how many approvals are needed: configured ids
+    /// first, then the application emojis, then Unicode keycaps.
+    fn get_number_emoji(&self, num: u32) -> Option<ReactionType> {
+        let config = self.config();
+        if config.number_emoji_style == NumberEmojiStyle::Unicode {
+            return emoji::keycap(num);
+        }
+
+        let configured = num
+            .checked_sub(1)
+            .and_then(|index| config.number_emojis.get(index as usize))
+            .cloned();
+        configured
+            .or_else(|| self.app_emojis.get().and_then(|emojis| emojis.number(num)))
+            .or_else(|| emoji::keycap(num))
+    }
+
+    fn cancel_emoji(&self) -> ReactionType {
+        self.app_emojis
+            .get()
+            .and_then(AppEmojis::slash)
+            .unwrap_or_else(|| ReactionType::Unicode(CANCEL_FALLBACK_EMOJI.to_string()))
+    }
+
+    fn is_cancel_emoji(&self, emoji: &ReactionType) -> bool {
+        emoji::same_emoji(emoji, &self.cancel_emoji())
+    }
+
+    /// Fetches the live message over HTTP to check whether it is already pinned.
+    /// Errors are treated as "not pinned" so a flaky fetch never blocks a pin.
+    async fn is_already_pinned(
+        &self,
+        api: &impl PinApi,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> bool {
+        match api.message(channel_id, message_id).await {
+            Ok(message) => message.pinned,
+            Err(e) => {
+                warn!(message_id = %message_id, error = %e, "Failed to fetch message for pin check");
+                false
+            }
+        }
+    }
+
+    /// Pins through the worker, waiting for its turn, the channel's cooldown
+    /// and the guild's budget. False if the pin failed or the worker has
+    /// stopped.
+    async fn queue_pin(
+        &self,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> bool {
+        let (reply, outcome) = oneshot::channel();
+        let depth = self.pin_queue_depth.fetch_add(1, Ordering::Relaxed) + 1;
+        if depth >= PIN_QUEUE_WARN_DEPTH {
+            warn!(depth, message_id = %message_id, "Pin queue is backing up");
+        } else {
+            debug!(depth, message_id = %message_id, "Queued pin");
+        }
+        let request = PinRequest {
+            guild_id,
+            channel_id,
+            message_id,
+            attempts: 0,
+            retry_at: None,
+            reply,
+        };
+        if self.pin_queue.send(request).is_err() {
+            self.pin_queue_depth.fetch_sub(1, Ordering::Relaxed);
+            return false;
+        }
+        outcome.await.unwrap_or(false)
+    }
+
+    /// Runs queued pins one at a time, at least `PIN_SPACING` apart. A
+    /// request for a channel on cooldown, a guild out of its budget or a
+    /// pin to retry after an error waits without holding up the others.
+    /// Requests still waiting at shutdown are answered false.
+    async fn run_pin_worker(
+        &self,
+        api: &impl PinApi,
+        mut requests: mpsc::UnboundedReceiver<PinRequest>,
+        mut shutdown: watch::Receiver<bool>,
+    ) {
+        let mut waiting: VecDeque<PinRequest> = VecDeque::new();
+        let mut last_pin: Option<Instant> = None;
+        loop {
+            while let Ok(request) = requests.try_recv() {
+                waiting.push_back(request);
+            }
+
+            let now = Instant::now();
+            let ready = waiting
+                .iter()
+                .position(|request| self.pin_wait(request, now).is_none());
+            if let Some(mut request) = ready.and_then(|index| waiting.remove(index)) {
+                if let Some(last_pin) = last_pin {
+                    tokio::time::sleep_until((last_pin + PIN_SPACING).into()).await;
+                }
+                request.attempts += 1;
+                let attempt = self
+                    .attempt_pin(
+                        api,
+                        request.channel_id,
+                        request.message_id,
+                        request.attempts,
+                    )
+                    .await;
+                last_pin = Some(Instant::now());
+                let pinned = match attempt {
+                    PinAttempt::Retry(wait) => {
+                        request.retry_at = Some(Instant::now() + wait);
+                        waiting.push_back(request);
+                        continue;
+                    }
+                    PinAttempt::Pinned => {
+                        self.note_guild_pin(request.guild_id, Instant::now());
+                        true
+                    }
+                    PinAttempt::Failed => false,
+                };
+                self.pin_queue_depth.fetch_sub(1, Ordering::Relaxed);
+                let _ = request.reply.send(pinned);
+                continue;
+            }
+
+            let wait = waiting
+                .iter()
+                .filter_map(|request| self.pin_wait(request, now))
+                .min();
+            tokio::select! {
+                request = requests.recv() => match request {
+                    Some(request) => waiting.push_back(request),
+                    None => break,
+                },
+                _ = sleep(wait.unwrap_or_default()), if wait.is_some() => {}
+                _ = shutdown.wait_for(|stop| *stop) => {
+                    info!(dropped = waiting.len(), "Pin worker stopped");
+                    break;
+                }
+            }
+        }
+        self.pin_queue_depth
+            .fetch_sub(waiting.len(), Ordering::Relaxed);
+    }
+
+    /// How long a queued pin still waits at `now`, for its channel's
+    /// cooldown, its guild's budget or its retry; `None` when it may go.
+    fn pin_wait(&self, request: &PinRequest, now: Instant) -> Option<Duration> {
+        let retry = request
+            .retry_at
+            .and_then(|at| at.checked_duration_since(now))
+            .filter(|wait| !wait.is_zero());
+        self.pin_cooldowns
+            .time_left(&request.channel_id)
+            .max(self.guild_pin_wait(request.guild_id, now))
+            .max(retry)
+    }
+
+    /// How long a pin in `guild_id` waits at `now` for the guild's budget
+    /// of pins per minute to free up; `None` when it may go right away.
+    fn guild_pin_wait(&self, guild_id: Option<GuildId>, now: Instant) -> Option<Duration> {
+        let guild_id = guild_id?;
+        let budget = self.config().guild_pin_budget(guild_id) as usize;
+        if budget == 0 {
+            return None;
+        }
+        let pins = self.guild_pins.get(&guild_id)?;
+        let recent: Vec<Instant> = pins
+            .iter()
+            .copied()
+            .filter(|at| now.saturating_duration_since(*at) < GUILD_PIN_WINDOW)
+            .collect();
+        // The budget frees up once the oldest of its latest pins ages out
+        let oldest = recent
+            .len()
+            .checked_sub(budget)
+            .map(|index| recent[index])?;
+        (oldest + GUILD_PIN_WINDOW)
+            .checked_duration_since(now)
+            .filter(|wait| !wait.is_zero())
+    }
+
+    fn note_guild_pin(&self, guild_id: Option<GuildId>, at: Instant) {
+        let Some(guild_id) = guild_id else {
+            return;
+        };
+        let mut pins = self.guild_pins.entry(guild_id).or_default();
+        pins.push_back(at);
+        while pins
+            .front()
+            .is_some_and(|first| at.saturating_duration_since(*first) >= GUILD_PIN_WINDOW)
+        {
+            pins.pop_front();
+        }
+    }
+
+    /// The guild's pins within the past minute, its budget and how long
+    /// the next pin would wait for it, for `/status`.
+    fn guild_pin_budget(&self, guild_id: GuildId) -> (usize, u32, Option<Duration>) {
+        let now = Instant::now();
+        let used = self.guild_pins.get(&guild_id).map_or(0, |pins| {
+            pins.iter()
+                .filter(|at| now.saturating_duration_since(**at) < GUILD_PIN_WINDOW)
+                .count()
+        });
+        (
+            used,
+            self.config().guild_pin_budget(guild_id),
+            self.guild_pin_wait(Some(guild_id), now),
+        )
+    }
+
+    /// Pins once, without the worker's retries. Refuses a channel still on
+    /// cooldown.
+    #[cfg(test)]
+    async fn pin_message_safely(
+        &self,
+        api: &impl PinApi,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> bool {
+        matches!(
+            self.attempt_pin(api, channel_id, message_id, PIN_ATTEMPTS)
+                .await,
+            PinAttempt::Pinned
+        )
+    }
+
+    /// The pin step of the worker, as its `attempt`th call for the pin.
+    /// Refuses a channel still on cooldown; errors that may pass, like
+    /// Discord's 5xx responses, ask for a retry until `PIN_ATTEMPTS`.
+    async fn attempt_pin(
+        &self,
+        api: &impl PinApi,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        attempt: u32,
+    ) -> PinAttempt {
+        if self.pin_cooldowns.get_fresh(&channel_id).is_some() {
+            warn!(channel_id = %channel_id, "Pin rate limited");
+            return PinAttempt::Failed;
+        }
+        if self.dry_run() {
+            self.start_pin_cooldown(channel_id, false);
+            self.pins_served.fetch_add(1, Ordering::Relaxed);
+            info!(message_id = %message_id, channel_id = %channel_id, "Dry run, would have pinned message");
+            return PinAttempt::Pinned;
+        }
+
+        match api.pin(channel_id, message_id).await {
+            Ok(_) => {
+                self.start_pin_cooldown(channel_id, false);
+                self.pins_served.fetch_add(1, Ordering::Relaxed);
+                info!(message_id = %message_id, channel_id = %channel_id, "Pinned message");
+                PinAttempt::Pinned
+            }
+            Err(e) if attempt < PIN_ATTEMPTS && is_transient(&e) => {
+                // A rate limit is retried once Discord's wait is over
+                let wait = if is_rate_limited(&e) {
+                    self.start_pin_cooldown(channel_id, true);
+                    self.pin_cooldowns
+                        .time_left(&channel_id)
+                        .unwrap_or_default()
+                } else {
+                    PIN_RETRY_DELAY * attempt
+                };
+                warn!(message_id = %message_id, attempt, retry_in = ?wait, error = %e, "Pin failed, retrying");
+                PinAttempt::Retry(wait)
+            }
+            Err(e) => {
+                if is_rate_limited(&e) {
+                    self.start_pin_cooldown(channel_id, true);
+                }
+                error!(message_id = %message_id, error = %e, "Failed to pin message");
+                PinAttempt::Failed
+            }
+        }
+    }
+
+    /// Starts a channel's cooldown after a pin or unpin call there. A wait
+    /// Discord asked for is kept to exactly; a rate limit without one, or
+    /// a call right after a rate limit, gets `PIN_COOLDOWN_SECS`; a call
+    /// with no sign of rate limiting on either only `CALM_PIN_COOLDOWN`.
+    fn start_pin_cooldown(&self, channel_id: ChannelId, rate_limited: bool) {
+        let configured = self.pin_cooldowns.ttl();
+        let asked = self
+            .pin_rate_limits
+            .remove(&channel_id)
+            .map(|(_, until)| until.saturating_duration_since(Instant::now()));
+        let limited = rate_limited || asked.is_some();
+        let was_limited = self.rate_limited_channels.remove(&channel_id).is_some();
+        if limited {
+            self.rate_limited_channels.insert(channel_id);
+        }
+        let cooldown = match asked.filter(|wait| !wait.is_zero()) {
+            Some(wait) => wait,
+            None if limited || was_limited => configured,
+            None => configured.min(CALM_PIN_COOLDOWN),
+        };
+        if limited {
+            debug!(channel_id = %channel_id, cooldown = ?cooldown, "Pins rate limited, cooling down");
+        }
+        self.pin_cooldowns.insert_for(channel_id, limited, cooldown);
+    }
+
+    /// Takes note of a rate limit the HTTP client ran into, as its
+    /// ratelimiter reports it. Only the pin route counts, per channel.
+    pub fn note_rate_limit(&self, path: &str, retry_after: Duration) {
+        let Some(channel_id) = pin_route_channel(path) else {
+            return;
+        };
+        warn!(channel_id = %channel_id, retry_after = ?retry_after, "Discord rate limited pins");
+        self.pin_rate_limits
+            .insert(channel_id, Instant::now() + retry_after);
+    }
+
+    /// Posts the event to the webhook in the background, so a slow or
+    /// failing endpoint never holds up Discord handling.
+    fn notify(&self, event: PinEvent) {
+        let Some(webhook) = &self.webhook else {
+            return;
+        };
+        if event.event != PinEventKind::Pinned && !self.config().webhook_vote_events {
+            return;
+        }
+        let webhook = Arc::clone(webhook);
+        tokio::spawn(async move { webhook.send(&event).await });
+    }
+
+    /// Logs a moderation line to the guild's audit channel, if it has one.
+    /// Mentions are shown but never ping.
+    async fn audit(&self, http: &Http, guild_id: GuildId, line: String) {
+        let Some(channel_id) = self.guild_config(guild_id).audit_channel else {
+            return;
+        };
+        let message = CreateMessage::new()
+            .content(line)
+            .allowed_mentions(CreateAllowedMentions::new());
+        if let Err(e) = channel_id.send_message(http, message).await {
+            warn!(guild_id = %guild_id, channel_id = %channel_id, error = %e, "Failed to write to the audit channel");
+        }
+    }
+
+    fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Whether the bot runs without message content; fixed at startup.
+    pub fn interaction_only(&self) -> bool {
+        self.config().interaction_only
+    }
+
+    /// Whether votes are taken back from members who leave; fixed at startup.
+    pub fn drop_departed_votes(&self) -> bool {
+        self.config().drop_departed_votes
+    }
+
+    /// Whether pins and unpins are only logged; fixed at startup.
+    pub fn dry_run(&self) -> bool {
+        self.config().dry_run
+    }
+
+    /// Marks success feedback for a pin or unpin that a dry run only logged.
+    pub fn dry_run_tagged(&self, feedback: String) -> String {
+        if self.dry_run() {
+            format!("[dry run] {}", feedback)
+        } else {
+            feedback
+        }
+    }
+
+    /// Says what a request pinned when the guild has its own wording for
+    /// it. In a dry run nothing shows up in the channel when a pin goes
+    /// through, so the bot always says what it would have pinned there.
+    async fn report_pins(
+        &self,
+        http: &Http,
+        channel_id: ChannelId,
+        order: &PinOrder<'_>,
+        pinned: &[PinTarget],
+    ) {
+        let guild_config = order
+            .guild_id
+            .map(|guild_id| self.guild_config(guild_id))
+            .unwrap_or_default();
+        let reworded = guild_config
+            .templates
+            .contains_key(&TemplateKey::PinSuccess);
+        if !(self.dry_run() || reworded) || pinned.is_empty() {
+            return;
+        }
+        let report = self.dry_run_tagged(pinned_report(
+            guild_config.template(TemplateKey::PinSuccess),
+            order,
+            pinned,
+        ));
+        let message = CreateMessage::new()
+            .content(report)
+            .allowed_mentions(CreateAllowedMentions::new());
+        if let Err(e) = channel_id.send_message(http, message).await {
+            warn!(channel_id = %channel_id, error = %e, "Failed to report a pin");
+        }
+    }
+
+    /// How the process should exit once the client has stopped: with
+    /// `STALE_SHARD_EXIT_CODE` when the shard monitor stopped it.
+    pub fn exit_code(&self) -> ExitCode {
+        if self.stale_shard_exit.load(Ordering::Acquire) {
+            ExitCode::from(STALE_SHARD_EXIT_CODE)
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+
+    /// Called once the client exists; later calls are ignored.
+    pub fn set_shard_manager(&self, shard_manager: Arc<ShardManager>) {
+        let _ = self.shard_manager.set(shard_manager);
+    }
+
+    async fn shard_latency(&self, shard_id: ShardId) -> Option<Duration> {
+        let runners = self.shard_manager.get()?.runners.lock().await;
+        runners.get(&shard_id)?.latency
+    }
+
+    /// Every shard's health as of the shard monitor's last poll.
+    fn shard_health(&self) -> Vec<(ShardId, ShardHealth)> {
+        self.shard_monitor.lock().unwrap().shards()
+    }
+
+    /// Voted pins allowed per UTC day in a channel: the first of `channels`
+    /// with its own quota, then the guild's. 0 is unlimited.
+    fn daily_pin_quota(&self, guild_id: Option<GuildId>, channels: &[ChannelId]) -> u32 {
+        let Some(guild_id) = guild_id else {
+            return 0;
+        };
+        let guild = self.guild_config(guild_id);
+        channels
+            .iter()
+            .find_map(|id| guild.channel_pin_quotas.get(id).copied())
+            .unwrap_or_else(|| guild.daily_pin_quota.unwrap_or(0))
+    }
+
+    /// How long a passed vote in `channels` waits for a moderator's
+    /// sign-off, or `None` when none of them asks for one.
+    fn approval_timeout(
+        &self,
+        guild_id: Option<GuildId>,
+        channels: &[ChannelId],
+    ) -> Option<Duration> {
+        let guild = self.guild_config(guild_id?);
+        channels
+            .iter()
+            .any(|id| guild.approval_channels.contains(id))
+            .then(|| Duration::from_secs(self.config().approval_timeout_secs))
+    }
+
+    /// The first channel that `pins` would take past its quota for the UTC
+    /// day holding `now`, with the quota. Each entry is a message's channel
+    /// and that channel's quota. A failed count lets the pins through.
+    fn over_pin_quota(&self, pins: &[(ChannelId, u32)], now: i64) -> Option<(ChannelId, u32)> {
+        let since = now - now.rem_euclid(digest::DAY_SECS);
+        let mut checked = Vec::new();
+        for &(channel_id, quota) in pins {
+            if quota == 0 || checked.contains(&channel_id) {
+                continue;
+            }
+            checked.push(channel_id);
+            let adding = pins.iter().filter(|(id, _)| *id == channel_id).count() as u32;
+            match self.store.pins_in_channel_since(channel_id, since) {
+                Ok(pinned) if pinned + adding > quota => return Some((channel_id, quota)),
+                Ok(_) => {}
+                Err(e) => {
+                    error!(channel_id = %channel_id, error = %e, "Failed to count today's pins");
+                }
+            }
+        }
+        None
+    }
+
+    /// Refuses a new vote in a channel that already has the maximum open.
+    fn session_capacity(&self, vote_channel_id: ChannelId) -> Result<(), String> {
+        let open = self
+            .channel_session_counts
+            .get(&vote_channel_id)
+            .map_or(0, |count| *count);
+        if open >= self.config().max_sessions_per_channel {
+            return Err(full_channel_refusal(open));
+        }
+        Ok(())
+    }
+
+    /// Refuses a new vote from a member who already started the maximum
+    /// open, listing those votes so they can call one off. Moderators are
+    /// `exempt`.
+    fn initiator_capacity(
+        &self,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+        exempt: bool,
+    ) -> Result<(), String> {
+        let cap = self.config().max_sessions_per_user;
+        let open = self
+            .initiator_session_counts
+            .get(&user_id)
+            .map_or(0, |count| *count);
+        if exempt || cap == 0 || open < cap {
+            return Ok(());
+        }
+        let votes: Vec<String> = self
+            .voting_sessions
+            .iter()
+            .filter(|session| session.initiator_id == user_id)
+            .map(|session| {
+                session
+                    .key()
+                    .link(session.vote_channel_id, guild_id)
+                    .to_string()
+            })
+            .collect();
+        Err(initiator_cap_refusal(open, &votes))
+    }
+
+    /// Registers a session, refusing it when its channel is full and
+    /// evicting the oldest sessions when the global cap would be exceeded.
+    fn insert_session(&self, session_id: MessageId, session: VotingSession) -> Result<(), String> {
+        let config = self.config();
+        let channel_id = session.vote_channel_id;
+        // A refusal leaves no empty count behind
+        match self.channel_session_counts.entry(channel_id) {
+            Entry::Occupied(open) if *open.get() >= config.max_sessions_per_channel => {
+                return Err(full_channel_refusal(*open.get()));
+            }
+            Entry::Occupied(mut open) => *open.get_mut() += 1,
+            Entry::Vacant(_) if config.max_sessions_per_channel == 0 => {
+                return Err(full_channel_refusal(0));
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(1);
+            }
+        }
+        *self
+            .initiator_session_counts
+            .entry(session.initiator_id)
+            .or_insert(0) += 1;
+
+        // Only picked under the lock; ending them writes their stats
+        let mut evicted = Vec::new();
+        let mut order = self.session_order.lock().expect("session order poisoned");
+        let mut open = self.voting_sessions.len();
+        while open >= config.max_sessions {
+            let Some(oldest) = order.pop_front() else {
+                break;
+            };
+            if self.voting_sessions.contains_key(&oldest) {
+                evicted.push(oldest);
+                open -= 1;
+            }
+        }
+        order.push_back(session_id);
+        drop(order);
+        for oldest in evicted {
+            if let Some(mut ended) = self.end_session(oldest, VoteEnding::Evicted) {
+                info!(session_id = %oldest, "Evicted vote to stay under the session cap");
+                self.queue_eviction_notice(oldest, &mut ended);
+            }
+        }
+
+        let target_id = session.target_message_id;
+        if let Some(replaced) = self.voting_sessions.insert(session_id, session) {
+            self.forget_session(session_id, &replaced);
+        }
+        self.sessions_by_target.insert(target_id, session_id);
+        Ok(())
+    }
+
+    /// Tells an evicted vote's channel it was dropped, and takes the
+    /// deadline off its notice, as for an expired vote.
+    fn queue_eviction_notice(&self, session_id: MessageId, session: &mut VotingSession) {
+        if let Some(notice) = session.notice.take() {
+            self.expired_notices
+                .lock()
+                .expect("expired notices poisoned")
+                .push((session.vote_channel_id, notice));
+        }
+        self.failed_votes
+            .lock()
+            .expect("failed votes poisoned")
+            .push((
+                session.vote_channel_id,
+                session_id,
+                EVICTED_NOTICE.to_string(),
+            ));
+    }
+
+    fn remove_session(&self, session_id: MessageId) -> Option<VotingSession> {
+        let (_, session) = self.voting_sessions.remove(&session_id)?;
+        self.forget_session(session_id, &session);
+        Some(session)
+    }
+
+    /// Removes a session that ended as `ending` and keeps its stats.
+    fn end_session(&self, session_id: MessageId, ending: VoteEnding) -> Option<VotingSession> {
+        let session = self.remove_session(session_id)?;
+        self.record_vote_stats(session_id, &session, ending);
+        Some(session)
+    }
+
+    /// Keeps what a guild's vote came to for `/votestats`. Failing to is
+    /// only logged; the vote itself is over either way.
+    fn record_vote_stats(
+        &self,
+        session_id: MessageId,
+        session: &VotingSession,
+        ending: VoteEnding,
+    ) {
+        let Some(guild_id) = session.guild_id else {
+            return;
+        };
+        let stats = VoteStats {
+            guild_id,
+            channel_id: session.vote_channel_id,
+            session_id,
+            ended_at: Timestamp::now().unix_timestamp(),
+            duration_secs: session.created_at.elapsed().as_secs(),
+            ending,
+            votes: session.get_vote_count(),
+            peak_votes: session.peak_votes,
+            removals: session.removals,
+            voters: session.voters.len() as u32,
+        };
+        if let Err(e) = self.store.record_vote_stats(&stats) {
+            error!(session_id = %session_id, error = %e, "Failed to record vote stats");
+        }
+    }
+
+    /// Bookkeeping for a session that just left `voting_sessions`.
+    fn forget_session(&self, session_id: MessageId, session: &VotingSession) {
+        session.hold_released.notify_one();
+        self.release_channel_slot(session.vote_channel_id);
+        self.initiator_session_counts
+            .remove_if_mut(&session.initiator_id, |_, open| {
+                *open = open.saturating_sub(1);
+                *open == 0
+            });
+        self.sessions_by_target
+            .remove_if(&session.target_message_id, |_, id| *id == session_id);
+        if let Some(poll) = session.poll {
+            self.sessions_by_poll
+                .remove_if(&poll.message_id, |_, id| *id == session_id);
+        }
+        if session.target_reacted {
+            self.stale_target_reactions
+                .lock()
+                .expect("stale reactions poisoned")
+                .push((session.target_channel_id, session.target_message_id));
+        }
+        if let Some(thread_id) = session.thread_id {
+            self.stale_threads
+                .lock()
+                .expect("stale threads poisoned")
+                .push(thread_id);
+        }
+    }
+
+    /// What to tell someone messaging the bot outside a server, unless they
+    /// were told within the hour. Other bots are never answered, so two
+    /// bots can't keep replying to each other.
+    fn dm_reply(&self, msg: &Message) -> Option<&'static str> {
+        if msg.guild_id.is_some()
+            || msg.author.bot
+            || self.dm_replies.get_fresh(&msg.author.id).is_some()
+        {
+            return None;
+        }
+        self.dm_replies.insert_now(msg.author.id, ());
+        Some(DM_REPLY)
+    }
+
+    /// The session a reaction belongs to, as `session_for_reaction` finds
+    /// it. Votes only run in guilds, so a reaction without one never counts.
+    fn session_for_guild_reaction(&self, reaction: &Reaction) -> Option<(MessageId, bool)> {
+        reaction.guild_id?;
+        self.session_for_reaction(reaction.message_id)
+    }
+
+    /// The session a reaction on `message_id` belongs to, and whether the
+    /// message is the session's target rather than its vote message.
+    fn session_for_reaction(&self, message_id: MessageId) -> Option<(MessageId, bool)> {
+        if self.voting_sessions.contains_key(&message_id) {
+            return Some((message_id, false));
+        }
+        self.sessions_by_target
+            .get(&message_id)
+            .map(|session_id| (*session_id, true))
+    }
+
+    /// The session voting with the poll on `message_id`, and its poll.
+    fn session_for_poll(&self, message_id: MessageId) -> Option<(MessageId, VotePoll)> {
+        let session_id = *self.sessions_by_poll.get(&message_id)?;
+        let poll = self.voting_sessions.get(&session_id)?.poll?;
+        Some((session_id, poll))
+    }
+
+    /// Switches a session over to the poll just posted for it, which runs
+    /// `duration` from now; the session now ends when the poll does.
+    /// Returns false when the session already ended.
+    fn attach_poll(&self, session_id: MessageId, poll: VotePoll, duration: Duration) -> bool {
+        let Some(mut session) = self.voting_sessions.get_mut(&session_id) else {
+            return false;
+        };
+        session.vote_mode = VoteMode::Poll;
+        session.poll = Some(poll);
+        session.timeout = Some(session.created_at.elapsed() + duration);
+        self.sessions_by_poll.insert(poll.message_id, session_id);
+        true
+    }
+
+    /// Takes an edit of a vote's target into its snapshot, when
+    /// `refresh_target_snapshots` asks for it. Returns whether a snapshot
+    /// changed.
+    fn refresh_target_snapshot(
+        &self,
+        message_id: MessageId,
+        content: Option<&str>,
+        attachments: Option<&[Attachment]>,
+    ) -> bool {
+        if !self.config().refresh_target_snapshots {
+            return false;
+        }
+        let Some(session_id) = self.sessions_by_target.get(&message_id).map(|id| *id) else {
+            return false;
+        };
+        let Some(mut session) = self.voting_sessions.get_mut(&session_id) else {
+            return false;
+        };
+        match &mut session.target_snapshot {
+            Some(snapshot) => {
+                snapshot.refresh(content, attachments);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes the bot's ✅ off the targets of ended sessions, unless another
+    /// vote on the same message is still open.
+    async fn clear_target_reactions(&self, api: &impl PinApi) {
+        let stale = std::mem::take(
+            &mut *self
+                .stale_target_reactions
+                .lock()
+                .expect("stale reactions poisoned"),
+        );
+        for (channel_id, message_id) in stale {
+            if self.sessions_by_target.contains_key(&message_id) {
+                continue;
+            }
+            let checkmark = ReactionType::Unicode(CHECKMARK_EMOJI.to_string());
+            if let Err(e) = api.unreact(channel_id, message_id, checkmark).await {
+                debug!(message_id = %message_id, error = %e, "Failed to remove ✅ from the target");
+            }
+        }
+    }
+
+    /// Archives the discussion threads of ended votes. A thread that was
+    /// deleted or can't be edited is left as it is.
+    async fn archive_vote_threads(&self, http: &Http) {
+        let stale =
+            std::mem::take(&mut *self.stale_threads.lock().expect("stale threads poisoned"));
+        for thread_id in stale {
+            if let Err(e) = thread_id
+                .edit_thread(http, EditThread::new().archived(true))
+                .await
+            {
+                debug!(thread_id = %thread_id, error = %e, "Failed to archive the vote's thread");
+            }
+        }
+    }
+
+    fn release_channel_slot(&self, channel_id: ChannelId) {
+        self.channel_session_counts
+            .remove_if_mut(&channel_id, |_, open| {
+                *open = open.saturating_sub(1);
+                *open == 0
+            });
+    }
+
+    /// The guild's open votes, soonest to expire first. With `channel_id`,
+    /// only votes held in or pinning to that channel. A scan of every
+    /// session, which is fine at this scale; index here if that changes.
+    fn open_votes(&self, guild_id: GuildId, channel_id: Option<ChannelId>) -> Vec<OpenVote> {
+        let timeout = Duration::from_secs(self.config().vote_timeout_secs);
+        let mut votes: Vec<OpenVote> = self
+            .voting_sessions
+            .iter()
+            .filter(|session| {
+                session.guild_id == Some(guild_id)
+                    && !session.is_expired(timeout)
+                    && channel_id.is_none_or(|channel_id| {
+                        session.vote_channel_id == channel_id
+                            || session.target_channel_id == channel_id
+                    })
+            })
+            .map(|session| OpenVote {
+                session_id: *session.key(),
+                vote_channel_id: session.vote_channel_id,
+                target: session.pin_targets().swap_remove(0),
+                batch_size: session.batch.len() + 1,
+                progress: session.progress(),
+                expires_at: session.expires_at(timeout),
+                awaiting_approval: session.awaiting_approval,
+            })
+            .collect();
+        votes.sort_by_key(|vote| vote.expires_at);
+        votes
+    }
+
+    fn active_sessions_in(&self, guild_id: GuildId) -> usize {
+        self.voting_sessions
+            .iter()
+            .filter(|session| session.guild_id == Some(guild_id))
+            .count()
+    }
+
+    /// Whether the user asked to keep their messages out of pin votes. Store
+    /// errors are logged and count as opted out, so a broken database never
+    /// pins someone who said no.
+    fn has_opted_out(&self, user_id: UserId) -> bool {
+        self.store.is_pin_opted_out(user_id).unwrap_or_else(|e| {
+            warn!(user_id = %user_id, error = %e, "Failed to look up a pin opt-out");
+            true
+        })
+    }
+
+    /// Whether the guild's moderators put the author on the pin blocklist.
+    fn is_pin_blocked(&self, guild_id: Option<GuildId>, author_id: UserId) -> bool {
+        guild_id.is_some_and(|guild_id| {
+            self.guild_config(guild_id)
+                .pin_blocklist
+                .contains(&author_id)
+        })
+    }
+
+    fn is_locked(&self, guild_id: Option<GuildId>, message_id: MessageId) -> bool {
+        guild_id.is_some_and(|guild_id| {
+            self.guild_config(guild_id)
+                .locked_messages
+                .contains(&message_id)
+        })
+    }
+
+    /// Open votes that would pin `message_id`, alone or in a batch, as
+    /// (session id, vote channel). Votes already pinning are left out.
+    fn sessions_pinning(&self, message_id: MessageId) -> Vec<(MessageId, ChannelId)> {
+        self.voting_sessions
+            .iter()
+            .filter(|session| {
+                !session.is_pinning()
+                    && (session.target_message_id == message_id
+                        || session
+                            .batch
+                            .iter()
+                            .any(|target| target.message_id == message_id))
+            })
+            .map(|session| (*session.key(), session.vote_channel_id))
+            .collect()
+    }
+
+    /// Applies to every server the user shares with the bot.
+    fn set_opted_out(&self, user_id: UserId, opted_out: bool) -> Result<()> {
+        self.store.set_pin_opt_out(user_id, opted_out)
+    }
+
+    /// When the message was last pinned, if that falls within the guild's
+    /// repin window. Store errors are logged and let the vote go ahead.
+    fn recent_pin(&self, guild_id: Option<GuildId>, message_id: MessageId) -> Option<i64> {
+        let days = self.guild_config(guild_id?).repin_window_days?;
+        let pinned_at = match self.store.last_pinned_at(message_id) {
+            Ok(pinned_at) => pinned_at?,
+            Err(e) => {
+                warn!(message_id = %message_id, error = %e, "Failed to look up pin history");
+                return None;
+            }
+        };
+        let age = Timestamp::now().unix_timestamp() - pinned_at;
+        (age < i64::from(days) * 86_400).then_some(pinned_at)
+    }
+
+    /// Follow-up for every successful pin: schedule the unpin of a
+    /// temporary pin, record it for the digest and tell the webhook.
+    fn pin_succeeded(
+        &self,
+        event: PinEvent,
+        author_id: Option<UserId>,
+        excerpt: String,
+        voters: u32,
+        duration: Option<Duration>,
+        reason: Option<String>,
+    ) {
+        if let Some(duration) = duration {
+            let unpin = ScheduledUnpin {
+                guild_id: event.guild_id,
+                channel_id: event.channel_id,
+                message_id: event.message_id,
+                unpin_at: event.timestamp.unix_timestamp() + duration.as_secs() as i64,
+            };
+            if let Err(e) = self.store.schedule_unpin(&unpin) {
+                error!(message_id = %event.message_id, error = %e, "Failed to schedule unpin");
+            }
+        }
+        if let (Some(guild_id), Some(author_id)) = (event.guild_id, author_id) {
+            let record = PinRecord {
+                guild_id,
+                channel_id: event.channel_id,
+                message_id: event.message_id,
+                author_id: Some(author_id),
+                excerpt,
+                votes: event.voter_count,
+                voters,
+                pinned_at: event.timestamp.unix_timestamp(),
+                reason,
+            };
+            if let Err(e) = self.store.record_pin(&record) {
+                error!(message_id = %event.message_id, error = %e, "Failed to record pin");
+            }
+        }
+        self.notify(event);
+    }
+
+    /// Queues the targets' pins one after another. Returns the pinned and
+    /// the failed targets, each in request order.
+    async fn pin_each(
+        &self,
+        guild_id: Option<GuildId>,
+        targets: Vec<PinTarget>,
+    ) -> (Vec<PinTarget>, Vec<PinTarget>) {
+        let mut pinned = Vec::new();
+        let mut failed = Vec::new();
+        for target in targets {
+            if self
+                .queue_pin(guild_id, target.channel_id, target.message_id)
+                .await
+            {
+                pinned.push(target);
+            } else {
+                failed.push(target);
+            }
+        }
+        (pinned, failed)
+    }
+
+    /// Follow-ups for the messages one request just pinned: the history,
+    /// each channel's index and the archive copies. The archive shows each
+    /// target's snapshot; targets without one are fetched.
+    async fn after_pins(&self, http: &Http, order: &PinOrder<'_>, pinned: &[PinTarget]) {
+        let mut channels = Vec::new();
+        for target in pinned {
+            self.pin_succeeded(
+                PinEvent::new(
+                    PinEventKind::Pinned,
+                    order.guild_id,
+                    target.channel_id,
+                    target.message_id,
+                    order.initiator_id,
+                    order.votes,
+                ),
+                target.author_id,
+                target.excerpt.clone(),
+                order.voters,
+                order.duration,
+                order.reason.map(str::to_string),
+            );
+            if !channels.contains(&target.channel_id) {
+                channels.push(target.channel_id);
+            }
+        }
+        if let Some(guild_id) = order.guild_id {
+            for target in pinned {
+                if let Err(line) = self
+                    .rotate_pin(http, guild_id, target.channel_id, target.message_id)
+                    .await
+                {
+                    self.audit(http, guild_id, line).await;
+                }
+            }
+        }
+        for channel_id in channels {
+            self.refresh_pin_index(http, order.guild_id, channel_id)
+                .await;
+        }
+        if let Some(guild_id) = order.guild_id {
+            self.crosspost_pins(http, guild_id, pinned).await;
+        }
+
+        let Some(archive) = order.archive else {
+            return;
+        };
+        for target in pinned {
+            let fetched;
+            let snapshot = match &target.snapshot {
+                Some(snapshot) => snapshot,
+                None => match http.get_message(target.channel_id, target.message_id).await {
+                    Ok(message) => {
+                        fetched = TargetSnapshot::capture(&message);
+                        &fetched
+                    }
+                    Err(e) => {
+                        warn!(message_id = %target.message_id, error = %e, "Failed to fetch a pin to archive");
+                        continue;
+                    }
+                },
+            };
+            self.archive_pin(
+                http,
+                archive,
+                order.guild_id,
+                target,
+                snapshot,
+                order.reason,
+            )
+            .await;
+        }
+    }
+
+    /// Makes a fresh pin the channel's featured one when the channel rotates
+    /// its pins, taking down the pin it replaces. Only a pin the bot made
+    /// and still has on record as pinned is taken down, never one made by
+    /// hand. Returns the previous pin if it came down, or the line for the
+    /// audit channel when it couldn't.
+    async fn rotate_pin(
+        &self,
+        api: &impl PinApi,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<Option<MessageId>, String> {
+        if !self
+            .guild_config(guild_id)
+            .rotation_channels
+            .contains(&channel_id)
+        {
+            return Ok(None);
+        }
+        let previous = match self.store.replace_rotation_pin(channel_id, message_id) {
+            Ok(previous) => previous.filter(|previous| *previous != message_id),
+            Err(e) => {
+                error!(channel_id = %channel_id, error = %e, "Failed to save the rotation pin");
+                return Ok(None);
+            }
+        };
+        let Some(previous) = previous else {
+            return Ok(None);
+        };
+        match self.store.is_bot_pin(previous) {
+            Ok(true) => {}
+            Ok(false) => {
+                debug!(message_id = %previous, "Previous rotation pin is no longer the bot's, leaving it");
+                return Ok(None);
+            }
+            Err(e) => {
+                error!(message_id = %previous, error = %e, "Failed to look up the previous rotation pin");
+                return Ok(None);
+            }
+        }
+
+        // The fresh pin just put the channel on cooldown
+        let mut outcome = UnpinOutcome::CoolingDown;
+        for _ in 0..ROTATION_ATTEMPTS {
+            if let Some(wait) = self.pin_cooldowns.time_left(&channel_id) {
+                sleep(wait).await;
+            }
+            outcome = self.unpin_message_safely(api, channel_id, previous).await;
+            if !matches!(outcome, UnpinOutcome::CoolingDown) {
+                break;
+            }
+        }
+        let link = previous.link(channel_id, Some(guild_id));
+        match outcome {
+            UnpinOutcome::Unpinned => {
+                if !self.dry_run() {
+                    let now = Timestamp::now().unix_timestamp();
+                    if let Err(e) = self.store.mark_unpinned(previous, now) {
+                        error!(message_id = %previous, error = %e, "Failed to record rotated-out pin");
+                    }
+                }
+                info!(channel_id = %channel_id, previous = %previous, current = %message_id, "Rotated pins");
+                Ok(Some(previous))
+            }
+            // Deleted or unpinned by hand in the meantime
+            UnpinOutcome::Failed(serenity::Error::Http(HttpError::UnsuccessfulRequest(
+                response,
+            ))) if response.status_code == StatusCode::NOT_FOUND => Ok(None),
+            UnpinOutcome::Failed(e) => {
+                warn!(message_id = %previous, error = %e, "Failed to unpin the previous rotation pin");
+                Err(format!(
+                    "🔄 Couldn't take down {} to make way for the new featured pin in <#{}>.",
+                    link, channel_id
+                ))
+            }
+            UnpinOutcome::CoolingDown => {
+                warn!(message_id = %previous, "Channel kept cooling down, previous rotation pin left up");
+                Err(format!(
+                    "🔄 {} is still pinned in <#{}>: pins there kept coming too fast to take it down.",
+                    link, channel_id
+                ))
+            }
+        }
+    }
+
+    /// Turns pin rotation on or off for a channel. Turning it off forgets
+    /// the featured pin and leaves every pin where it is.
+    fn set_pin_rotation(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        enabled: bool,
+    ) -> Result<GuildConfig> {
+        let config = self.update_guild_config(guild_id, |config| {
+            if enabled {
+                config.rotation_channels.insert(channel_id);
+            } else {
+                config.rotation_channels.remove(&channel_id);
+            }
+        })?;
+        if !enabled {
+            self.store.remove_rotation_pin(channel_id)?;
+        }
+        Ok(config)
+    }
+
+    /// Publishes fresh pins in announcement channels to following servers,
+    /// when the guild asks for it. A failure is reported to the audit
+    /// channel and queued for one retry; the pin stands either way.
+    async fn crosspost_pins(&self, http: &Http, guild_id: GuildId, pinned: &[PinTarget]) {
+        if !self.guild_config(guild_id).crosspost_pins {
+            return;
+        }
+        let mut announcement_channels = HashMap::new();
+        for target in pinned {
+            let announcement = match announcement_channels.get(&target.channel_id) {
+                Some(announcement) => *announcement,
+                None => {
+                    let announcement = match http.get_channel(target.channel_id).await {
+                        Ok(channel) => channel
+                            .guild()
+                            .is_some_and(|channel| channel.kind == ChannelType::News),
+                        Err(e) => {
+                            warn!(channel_id = %target.channel_id, error = %e, "Failed to look up the pin's channel");
+                            false
+                        }
+                    };
+                    announcement_channels.insert(target.channel_id, announcement);
+                    announcement
+                }
+            };
+            if !announcement {
+                continue;
+            }
+
+            let Err(reason) = self
+                .crosspost(http, target.channel_id, target.message_id)
+                .await
+            else {
+                continue;
+            };
+            self.pending_crossposts
+                .lock()
+                .expect("pending crossposts poisoned")
+                .push(PendingCrosspost {
+                    guild_id,
+                    channel_id: target.channel_id,
+                    message_id: target.message_id,
+                    retry_at: Instant::now() + CROSSPOST_RETRY_DELAY,
+                });
+            let line = format!(
+                "📣 Couldn't publish the pin {} to following servers ({}); trying once more in an hour.",
+                target.message_id.link(target.channel_id, Some(guild_id)),
+                reason
+            );
+            self.audit(http, guild_id, line).await;
+        }
+    }
+
+    /// Gives each crosspost that failed its one retry once it is due, and
+    /// reports the ones that fail again.
+    async fn retry_crossposts(&self, http: &Http) {
+        for pending in self.take_due_crossposts(Instant::now()) {
+            let Err(reason) = self
+                .crosspost(http, pending.channel_id, pending.message_id)
+                .await
+            else {
+                continue;
+            };
+            let line = format!(
+                "📣 Gave up publishing the pin {} to following servers: {}.",
+                pending
+                    .message_id
+                    .link(pending.channel_id, Some(pending.guild_id)),
+                reason
+            );
+            self.audit(http, pending.guild_id, line).await;
+        }
+    }
+
+    /// Takes the failed crossposts whose retry is due at `now` off the
+    /// queue.
+    fn take_due_crossposts(&self, now: Instant) -> Vec<PendingCrosspost> {
+        let mut pending = self
+            .pending_crossposts
+            .lock()
+            .expect("pending crossposts poisoned");
+        let (due, waiting) = pending
+            .drain(..)
+            .partition(|pending| pending.retry_at <= now);
+        *pending = waiting;
+        due
+    }
+
+    /// Publishes one message. A message that was already published counts
+    /// as done; anything else comes back as the reason it failed.
+    async fn crosspost(
+        &self,
+        http: &Http,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<(), String> {
+        if self.dry_run() {
+            info!(message_id = %message_id, channel_id = %channel_id, "Dry run, would have crossposted message");
+            return Ok(());
+        }
+        match timeout(
+            CROSSPOST_TIMEOUT,
+            http.crosspost_message(channel_id, message_id),
+        )
+        .await
+        {
+            Ok(Ok(_)) => {
+                info!(message_id = %message_id, channel_id = %channel_id, "Crossposted pinned message");
+                Ok(())
+            }
+            Ok(Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response))))
+                if response.error.code == ALREADY_CROSSPOSTED =>
+            {
+                debug!(message_id = %message_id, "Pinned message was already crossposted");
+                Ok(())
+            }
+            Ok(Err(e)) => {
+                warn!(message_id = %message_id, error = %e, "Failed to crosspost pinned message");
+                Err(e.to_string())
+            }
+            Err(_) => {
+                warn!(message_id = %message_id, "Crossposting pinned message is rate limited");
+                Err("rate limited".to_string())
+            }
+        }
+    }
+
+    /// Guilds whose digest is due at `now`, with the scheduled run each is
+    /// for. A guild seen for the first time starts from its latest run, so
+    /// turning the digest on never posts a week right away.
+    fn due_digests(&self, now: i64) -> Vec<(GuildId, DigestSchedule, i64)> {
+        let guilds = match self.store.guild_configs() {
+            Ok(guilds) => guilds,
+            Err(e) => {
+                error!(error = %e, "Failed to load guild configs for the digest");
+                return Vec::new();
+            }
+        };
+
+        let mut due = Vec::new();
+        for (guild_id, config) in guilds {
+            let Some(schedule) = config.digest else {
+                continue;
+            };
+            if !self.is_guild_allowed(Some(guild_id)) {
+                continue;
+            }
+            let run = schedule.latest_run(now);
+            match self.store.digest_last_run(guild_id) {
+                Ok(Some(last_run)) if last_run >= run => {}
+                Ok(Some(_)) => due.push((guild_id, schedule, run)),
+                Ok(None) => {
+                    if let Err(e) = self.store.set_digest_last_run(guild_id, run) {
+                        error!(guild_id = %guild_id, error = %e, "Failed to save digest run");
+                    }
+                }
+                Err(e) => error!(guild_id = %guild_id, error = %e, "Failed to load digest run"),
+            }
+        }
+        due
+    }
+
+    /// Posts the week before `run` to the digest channel, one embed per
+    /// page, and the week's vote stats to the audit channel when the guild
+    /// asked for them. The run is recorded first, so a crash mid-post never
+    /// repeats it; a week without pins posts no digest.
+    async fn post_digest(
+        &self,
+        http: &Http,
+        guild_id: GuildId,
+        schedule: DigestSchedule,
+        run: i64,
+    ) {
+        if let Err(e) = self.store.set_digest_last_run(guild_id, run) {
+            error!(guild_id = %guild_id, error = %e, "Failed to save digest run, skipping it");
+            return;
+        }
+        if self.guild_config(guild_id).weekly_vote_stats {
+            match self
+                .store
+                .vote_stats_between(guild_id, run - WEEK_SECS, run)
+            {
+                Ok(week) => {
+                    let line = format!("📊 This week: {}.", VoteSummary::of(&week).line());
+                    self.audit(http, guild_id, line).await;
+                }
+                Err(e) => {
+                    error!(guild_id = %guild_id, error = %e, "Failed to load the week's vote stats")
+                }
+            }
+        }
+        let (pins, total) =
+            match self
+                .store
+                .pins_between(guild_id, run - WEEK_SECS, run, MAX_DIGEST_ENTRIES)
+            {
+                Ok(week) => week,
+                Err(e) => {
+                    error!(guild_id = %guild_id, error = %e, "Failed to load pins for the digest");
+                    return;
+                }
+            };
+        if pins.is_empty() {
+            debug!(guild_id = %guild_id, "No pins this week, skipping the digest");
+            return;
+        }
+
+        for embed in digest::digest_embeds(guild_id, &pins, total, run) {
+            if let Err(e) = schedule
+                .channel_id
+                .send_message(http, CreateMessage::new().embed(embed))
+                .await
+            {
+                warn!(
+                    guild_id = %guild_id,
+                    channel_id = %schedule.channel_id,
+                    error = %e,
+                    "Failed to post the digest"
+                );
+                return;
+            }
+        }
+        info!(guild_id = %guild_id, pins = total, "Posted the weekly digest");
+    }
+
+    /// Seconds until the next digest run in any guild, at most
+    /// `DIGEST_POLL_SECS`.
+    fn digest_wait(&self, now: i64) -> u64 {
+        let next = self
+            .store
+            .guild_configs()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(_, config)| config.digest)
+            .map(|schedule| schedule.next_run(now))
+            .min()
+            .unwrap_or(i64::MAX);
+        (next - now).clamp(1, DIGEST_POLL_SECS) as u64
+    }
+
+    /// Channels whose daily prune is due at `now`, with their retention
+    /// policy.
+    fn due_prunings(&self, now: i64) -> Vec<(GuildId, ChannelId, PinRetention)> {
+        let guilds = match self.store.guild_configs() {
+            Ok(guilds) => guilds,
+            Err(e) => {
+                error!(error = %e, "Failed to load guild configs for pruning");
+                return Vec::new();
+            }
+        };
+
+        let mut due = Vec::new();
+        for (guild_id, config) in guilds {
+            if !self.is_guild_allowed(Some(guild_id)) {
+                continue;
+            }
+            for (channel_id, retention) in config.pin_retention {
+                match self.store.retention_last_run(channel_id) {
+                    Ok(Some(last_run)) if last_run + digest::DAY_SECS > now => {}
+                    Ok(_) => due.push((guild_id, channel_id, retention)),
+                    Err(e) => {
+                        error!(channel_id = %channel_id, error = %e, "Failed to load prune run");
+                    }
+                }
+            }
+        }
+        due
+    }
+
+    /// Unpins the channel's pins that outlived its retention policy and
+    /// sums it up in the audit channel. The run is recorded first, so a
+    /// channel that keeps failing is tried once a day rather than every
+    /// pass. Requests are spaced out, which makes a big prune slow but
+    /// never bursty.
+    async fn prune_channel(
+        &self,
+        http: &Http,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        retention: PinRetention,
+        now: i64,
+    ) {
+        if let Err(e) = self.store.set_retention_last_run(channel_id, now) {
+            error!(channel_id = %channel_id, error = %e, "Failed to save prune run, skipping it");
+            return;
+        }
+        let pins = match channel_id.pins(http).await {
+            Ok(pins) => pins,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to list pins to prune");
+                return;
+            }
+        };
+        let (index_id, recorded) = match (
+            self.store.pin_index(channel_id),
+            self.store.pin_dates(channel_id),
+        ) {
+            (Ok(index_id), Ok(recorded)) => (index_id, recorded),
+            (Err(e), _) | (_, Err(e)) => {
+                error!(channel_id = %channel_id, error = %e, "Failed to load pin dates to prune");
+                return;
+            }
+        };
+        let ages: Vec<PinAge> = pins
+            .iter()
+            .filter(|message| Some(message.id) != index_id)
+            .map(|message| {
+                PinAge::of(
+                    message.id,
+                    message.timestamp.unix_timestamp(),
+                    recorded.get(&message.id).copied(),
+                )
+            })
+            .collect();
+        let expired = retention.expired(&ages, now);
+        if expired.is_empty() {
+            debug!(channel_id = %channel_id, "No pins to prune");
+            return;
+        }
+
+        let spacing = PRUNE_SPACING.max(Duration::from_secs(self.config().pin_cooldown_secs));
+        let (mut pruned, mut failed) = (Vec::new(), 0);
+        for message_id in expired {
+            sleep(spacing).await;
+            match self
+                .unpin_message_safely(http, channel_id, message_id)
+                .await
+            {
+                UnpinOutcome::Unpinned => {
+                    if !self.dry_run() {
+                        if let Err(e) = self.store.mark_unpinned(message_id, now) {
+                            error!(message_id = %message_id, error = %e, "Failed to record pruned pin");
+                        }
+                    }
+                    pruned.push(message_id);
+                }
+                UnpinOutcome::CoolingDown => failed += 1,
+                UnpinOutcome::Failed(e) => {
+                    warn!(message_id = %message_id, error = %e, "Failed to prune pin");
+                    failed += 1;
+                }
+            }
+        }
+        info!(channel_id = %channel_id, pruned = pruned.len(), failed, "Pruned old pins");
+        if !pruned.is_empty() {
+            self.refresh_pin_index(http, Some(guild_id), channel_id)
+                .await;
+        }
+
+        let mut line = format!(
+            "🧹 Pruned {} {} from <#{}> pinned more than {} days ago",
+            pruned.len(),
+            if pruned.len() == 1 { "pin" } else { "pins" },
+            channel_id,
+            retention.days
+        );
+        if !pruned.is_empty() {
+            let links: Vec<String> = pruned
+                .iter()
+                .take(MAX_PRUNE_LINKS)
+                .map(|message_id| message_id.link(channel_id, Some(guild_id)))
+                .collect();
+            line.push_str(&format!(": {}", links.join(" ")));
+            if pruned.len() > MAX_PRUNE_LINKS {
+                line.push_str(&format!(" and {} more", pruned.len() - MAX_PRUNE_LINKS));
+            }
+        }
+        line.push('.');
+        if failed > 0 {
+            line.push_str(&format!(
+                " {} couldn't be unpinned and will be tried again tomorrow.",
+                failed
+            ));
+        }
+        if self.dry_run() {
+            line.insert_str(0, "[dry run] ");
+        }
+        self.audit(http, guild_id, line).await;
+    }
+
+    /// The archive for pins made without a vote, which have no categories.
+    fn default_archive(&self, guild_id: Option<GuildId>) -> Option<ChannelId> {
+        guild_id.and_then(|guild_id| self.guild_config(guild_id).archive_channel)
+    }
+
+    /// Posts a copy of a freshly pinned message to an archive channel, as
+    /// `snapshot` shows it, with the requester's reason if they gave one.
+    /// Failures are only logged; the pin itself already happened.
+    async fn archive_pin(
+        &self,
+        http: &Http,
+        archive: ChannelId,
+        guild_id: Option<GuildId>,
+        target: &PinTarget,
+        snapshot: &TargetSnapshot,
+        reason: Option<&str>,
+    ) {
+        let mut embed = CreateEmbed::new()
+            .author(
+                CreateEmbedAuthor::new(snapshot.author_name.clone())
+                    .icon_url(snapshot.author_avatar.clone()),
+            )
+            .field(
+                "Source",
+                target.message_id.link(target.channel_id, guild_id),
+                false,
+            )
+            .timestamp(snapshot.timestamp);
+        if let Some(reason) = reason {
+            embed = embed.field("Reason", reason, false);
+        }
+        if !snapshot.content.is_empty() {
+            embed = embed.description(snapshot.content.clone());
+        }
+        if let Some(image) = &snapshot.image_url {
+            embed = embed.image(image.clone());
+        }
+
+        if let Err(e) = archive
+            .send_message(http, CreateMessage::new().embed(embed))
+            .await
+        {
+            warn!(
+                message_id = %target.message_id,
+                archive_id = %archive,
+                error = %e,
+                "Failed to archive pin"
+            );
+        }
+    }
+
+    /// Rewrites the channel's index of voted pins, if the guild turned it
+    /// on there. Pins are checked against the channel's current ones, so
+    /// messages unpinned or deleted by hand drop off too. An index message
+    /// that was deleted is posted and pinned again.
+    async fn refresh_pin_index(
+        &self,
+        http: &Http,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+    ) {
+        let Some(guild_id) = guild_id else {
+            return;
+        };
+        let guild_config = self.guild_config(guild_id);
+        if !guild_config.index_channels.contains(&channel_id) {
+            return;
+        }
+        let rotating = guild_config.rotation_channels.contains(&channel_id);
+        let _guard = self.pin_index_lock.lock().await;
+
+        let pinned: HashSet<MessageId> = match http.get_pins(channel_id).await {
+            Ok(pins) => pins.into_iter().map(|message| message.id).collect(),
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to list pins for the index");
+                return;
+            }
+        };
+        let pins: Vec<PinRecord> = match self.store.pins_in_channel(channel_id) {
+            Ok(pins) => pins
+                .into_iter()
+                .filter(|pin| pinned.contains(&pin.message_id))
+                .collect(),
+            Err(e) => {
+                error!(channel_id = %channel_id, error = %e, "Failed to load pins for the index");
+                return;
+            }
+        };
+        // A rotating channel's index shows just the pin it features
+        let embed = if rotating {
+            let featured = match self.store.rotation_pin(channel_id) {
+                Ok(featured) => featured,
+                Err(e) => {
+                    error!(channel_id = %channel_id, error = %e, "Failed to load the rotation pin");
+                    return;
+                }
+            };
+            pin_index::featured_embed(
+                guild_id,
+                pins.iter().find(|pin| Some(pin.message_id) == featured),
+            )
+        } else {
+            pin_index::index_embed(guild_id, &pins)
+        };
+
+        match self.store.pin_index(channel_id) {
+            Ok(Some(index_id)) => {
+                match channel_id
+                    .edit_message(http, index_id, EditMessage::new().embed(embed.clone()))
+                    .await
+                {
+                    Ok(_) => return,
+                    Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+                        if response.status_code == StatusCode::NOT_FOUND =>
+                    {
+                        info!(channel_id = %channel_id, "Pin index was deleted, posting a new one");
+                    }
+                    Err(e) => {
+                        warn!(channel_id = %channel_id, error = %e, "Failed to update the pin index");
+                        return;
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => {
+                error!(channel_id = %channel_id, error = %e, "Failed to load the pin index");
+                return;
+            }
+        }
+
+        let index = match channel_id
+            .send_message(http, CreateMessage::new().embed(embed))
+            .await
+        {
+            Ok(index) => index,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to post the pin index");
+                return;
+            }
+        };
+        if let Err(e) = self.store.set_pin_index(channel_id, index.id) {
+            error!(channel_id = %channel_id, error = %e, "Failed to save the pin index");
+        }
+        if self.dry_run() {
+            info!(channel_id = %channel_id, "Dry run, would have pinned the pin index");
+        } else if let Err(e) = http.pin(channel_id, index.id).await {
+            warn!(channel_id = %channel_id, error = %e, "Failed to pin the pin index");
+        }
+    }
+
+    /// Re-reads the channel's pins after moderators changed them by hand,
+    /// refreshing its index when a recorded pin went away. A failed fetch
+    /// keeps the previous snapshot for the next change to compare with.
+    async fn sync_pins(&self, http: &Http, guild_id: GuildId, channel_id: ChannelId) {
+        let pins = match http.get_pins(channel_id).await {
+            Ok(pins) => pins,
+            Err(e) => {
+                warn!(channel_id = %channel_id, error = %e, "Failed to list pins to sync");
+                return;
+            }
+        };
+        match self.apply_pin_snapshot(guild_id, channel_id, &pins) {
+            Ok(true) => {
+                self.refresh_pin_index(http, Some(guild_id), channel_id)
+                    .await
+            }
+            Ok(false) => {}
+            Err(e) => error!(channel_id = %channel_id, error = %e, "Failed to sync pins"),
+        }
+    }
+
+    /// Compares the channel's current pins with the last snapshot. Pins
+    /// that appeared without a vote are recorded as manual and pins that
+    /// went away are marked unpinned; the first look only takes the
+    /// snapshot. Returns whether a recorded pin went away.
+    fn apply_pin_snapshot(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        pins: &[Message],
+    ) -> Result<bool> {
+        let current: HashSet<MessageId> = pins.iter().map(|message| message.id).collect();
+        let Some(known) = self.store.pin_snapshot(channel_id)? else {
+            self.store.save_pin_snapshot(channel_id, &current)?;
+            return Ok(false);
+        };
+        let index_id = self.store.pin_index(channel_id)?;
+        let now = Timestamp::now().unix_timestamp();
+
+        for message in pins
+            .iter()
+            .filter(|message| !known.contains(&message.id) && Some(message.id) != index_id)
+        {
+            // The bot's own pins are in the history already
+            if self.store.is_recorded_pinned(message.id)? {
+                continue;
+            }
+            info!(message_id = %message.id, channel_id = %channel_id, "Noticed a manual pin");
+            self.store.record_manual_pin(&PinRecord {
+                guild_id,
+                channel_id,
+                message_id: message.id,
+                author_id: Some(message.author.id),
+                excerpt: digest::excerpt(&message.content),
+                votes: 0,
+                pinned_at: now,
+                reason: None,
+                voters: 0,
+            })?;
+        }
+
+        let mut unpinned = false;
+        for message_id in known.difference(&current) {
+            if self.store.mark_unpinned(*message_id, now)? {
+                info!(message_id = %message_id, channel_id = %channel_id, "Noticed an unpin");
+                unpinned = true;
+            }
+        }
+        self.store.save_pin_snapshot(channel_id, &current)?;
+        Ok(unpinned)
+    }
+
+    /// Records a channel's current pins, dated when each message was sent,
+    /// as manual pins. Pins already in the history are skipped, so running
+    /// it again is harmless. Returns how many were new.
+    fn import_pins(
+        &self,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        pins: &[Message],
+    ) -> Result<usize> {
+        let index_id = self.store.pin_index(channel_id)?;
+        let records: Vec<PinRecord> = pins
+            .iter()
+            .filter(|message| Some(message.id) != index_id)
+            .map(|message| PinRecord {
+                guild_id,
+                channel_id,
+                message_id: message.id,
+                author_id: Some(message.author.id),
+                excerpt: digest::excerpt(&message.content),
+                votes: 0,
+                pinned_at: message.timestamp.unix_timestamp(),
+                reason: None,
+                voters: 0,
+            })
+            .collect();
+        let imported = self.store.import_pins(&records)?;
+        // Later syncs then notice pins made from here on
+        if self.store.pin_snapshot(channel_id)?.is_none() {
+            let current = pins.iter().map(|message| message.id).collect();
+            self.store.save_pin_snapshot(channel_id, &current)?;
+        }
+        Ok(imported)
+    }
+
+    /// Turns the channel's index on or off. Turning it off deletes the
+    /// index message; it is posted again with the next pin if turned back on.
+    async fn set_pin_index(
+        &self,
+        http: &Http,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+        enabled: bool,
+    ) -> Result<GuildConfig> {
+        let config = self.update_guild_config(guild_id, |config| {
+            if enabled {
+                config.index_channels.insert(channel_id);
+            } else {
+                config.index_channels.remove(&channel_id);
+            }
+        })?;
+        if !enabled {
+            let _guard = self.pin_index_lock.lock().await;
+            if let Some(index_id) = self.store.pin_index(channel_id)? {
+                if let Err(e) = channel_id.delete_message(http, index_id).await {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to delete the pin index");
+                }
+                self.store.remove_pin_index(channel_id)?;
+            }
+        }
+        Ok(config)
+    }
+
+    /// Replies once to votes that are halfway to expiring, unless the guild
+    /// turned reminders off.
+    async fn send_reminders(&self, http: &Http) {
+        let config = self.config();
+        let timeout = Duration::from_secs(config.vote_timeout_secs);
+        let mut due = Vec::new();
+        for mut session in self.voting_sessions.iter_mut() {
+            if !session.needs_reminder(timeout) {
+                continue;
+            }
+            if session
+                .guild_id
+                .is_some_and(|guild_id| self.guild_config(guild_id).skip_reminders)
+            {
+                continue;
+            }
+            session.reminded = true;
+            due.push((
+                *session.key(),
+                session.vote_channel_id,
+                session.threshold - session.get_vote_count(),
+                session.expires_at(timeout),
+            ));
+        }
+
+        for (session_id, channel_id, missing, expires_at) in due {
+            let reminder = CreateMessage::new()
+                .content(format!(
+                    "{} more {} needed, expires <t:{}:R>",
+                    missing,
+                    if missing == 1 { "vote" } else { "votes" },
+                    expires_at
+                ))
+                .reference_message((channel_id, session_id));
+            if let Err(e) = channel_id.send_message(http, reminder).await {
+                warn!(session_id = %session_id, error = %e, "Failed to post reminder");
+            }
+        }
+    }
+
+    /// Takes down temporary pins whose time is up. Entries for messages that
+    /// were deleted or unpinned by hand in the meantime are just dropped;
+    /// anything else that fails is retried on the next pass.
+    async fn expire_temporary_pins(&self, http: &Http) {
+        let due = match self.store.due_unpins(Timestamp::now().unix_timestamp()) {
+            Ok(due) => due,
+            Err(e) => {
+                error!(error = %e, "Failed to load scheduled unpins");
+                return;
+            }
+        };
+
+        for unpin in due {
+            let still_pinned = match http.get_message(unpin.channel_id, unpin.message_id).await {
+                Ok(message) => message.pinned,
+                Err(serenity::Error::Http(HttpError::UnsuccessfulRequest(response)))
+                    if response.status_code == StatusCode::NOT_FOUND =>
+                {
+                    false
+                }
+                Err(e) => {
+                    warn!(message_id = %unpin.message_id, error = %e, "Failed to check temporary pin");
+                    continue;
+                }
+            };
+
+            if still_pinned {
+                match self
+                    .unpin_message_safely(http, unpin.channel_id, unpin.message_id)
+                    .await
+                {
+                    UnpinOutcome::Unpinned => {
+                        self.refresh_pin_index(http, unpin.guild_id, unpin.channel_id)
+                            .await;
+                        let link = unpin.message_id.link(unpin.channel_id, unpin.guild_id);
+                        let note =
+                            format!("⌛ The temporary pin on {} expired, unpinned it.", link);
+                        if let Err(e) = unpin.channel_id.say(http, note).await {
+                            warn!(
+                                message_id = %unpin.message_id,
+                                error = %e,
+                                "Failed to announce expired pin"
+                            );
+                        }
+                        continue;
+                    }
+                    UnpinOutcome::CoolingDown => continue,
+                    UnpinOutcome::Failed(e) => {
+                        warn!(message_id = %unpin.message_id, error = %e, "Failed to unpin temporary pin");
+                        continue;
+                    }
+                }
+            }
+
+            debug!(message_id = %unpin.message_id, "Temporary pin already gone");
+            if let Err(e) = self.store.remove_scheduled_unpin(unpin.message_id) {
+                error!(message_id = %unpin.message_id, error = %e, "Failed to drop scheduled unpin");
+            }
+        }
+    }
+
+    /// Unpins under the same per-channel cooldown as pinning, so bulk
+    /// cleanups don't hammer the endpoint.
+    async fn unpin_message_safely(
+        &self,
+        api: &impl PinApi,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> UnpinOutcome {
+        if self.pin_cooldowns.get_fresh(&channel_id).is_some() {
+            return UnpinOutcome::CoolingDown;
+        }
+
+        let unpinned = if self.dry_run() {
+            info!(message_id = %message_id, channel_id = %channel_id, "Dry run, would have unpinned message");
+            Ok(())
+        } else {
+            api.unpin(channel_id, message_id).await
+        };
+        match unpinned {
+            Ok(()) => {
+                self.start_pin_cooldown(channel_id, false);
+                if let Err(e) = self.store.remove_scheduled_unpin(message_id) {
+                    error!(message_id = %message_id, error = %e, "Failed to drop scheduled unpin");
+                }
+                info!(message_id = %message_id, channel_id = %channel_id, "Unpinned message");
+                UnpinOutcome::Unpinned
+            }
+            Err(e) => {
+                if is_rate_limited(&e) {
+                    self.start_pin_cooldown(channel_id, true);
+                }
+                UnpinOutcome::Failed(e)
+            }
+        }
+    }
+
+    /// Adds a vote to the session keyed by `session_id`. Reaching the
+    /// threshold reports `Passed` with the target to pin; the session stays
+    /// open until the caller finalizes it.
+    fn record_vote(
+        &self,
+        session_id: MessageId,
+        user_id: UserId,
+        source: VoteSource,
+        weight: u32,
+    ) -> VoteOutcome {
+        let Some(mut session_entry) = self.voting_sessions.get_mut(&session_id) else {
+            return VoteOutcome::NoSession;
+        };
+        let session = session_entry.value_mut();
+
+        if session.paused {
+            return VoteOutcome::Paused;
+        }
+        if !session.add_vote(user_id, source, weight) {
+            return VoteOutcome::Duplicate;
+        }
+
+        let current_votes = session.get_vote_count();
+        info!(
+            user_id = %user_id,
+            session_id = %session_id,
+            weight,
+            super_reaction = source.is_super(),
+            votes = current_votes,
+            "Vote added"
+        );
+        if session.note_vote_at(Instant::now()) {
+            session.paused = true;
+            warn!(session_id = %session_id, votes = current_votes, "Vote held for review");
+            return VoteOutcome::Flagged(current_votes);
+        }
+        session.outcome()
+    }
+
+    /// Claims the wait for a vote that passed too soon. Returns how long it
+    /// is and the vote's channel, or `None` when a wait already runs.
+    fn schedule_deferred_pin(&self, session_id: MessageId) -> Option<(Duration, ChannelId)> {
+        let mut session = self.voting_sessions.get_mut(&session_id)?;
+        if session.pin_scheduled {
+            return None;
+        }
+        session.pin_scheduled = true;
+        Some((session.open_time_left(), session.vote_channel_id))
+    }
+
+    /// Where a deferred vote stands once its wait is over. The wait stays
+    /// claimed only while the vote is still deferred.
+    fn deferred_pin_due(&self, session_id: MessageId) -> Option<VoteOutcome> {
+        let mut session = self.voting_sessions.get_mut(&session_id)?;
+        let outcome = session.outcome();
+        if !matches!(outcome, VoteOutcome::Deferred(_)) {
+            session.pin_scheduled = false;
+        }
+        Some(outcome)
+    }
+
+    fn is_paused(&self, session_id: MessageId) -> bool {
+        self.voting_sessions
+            .get(&session_id)
+            .is_some_and(|session| session.paused)
+    }
+
+    /// Sets a passed vote that needs a moderator's sign-off waiting for it,
+    /// giving it the approval timeout on top of the time it was open.
+    /// Returns the vote channel and tally, or `None` when it may pin now.
+    fn await_approval(&self, session_id: MessageId) -> Option<(ChannelId, String)> {
+        let mut session = self.voting_sessions.get_mut(&session_id)?;
+        let wait = session
+            .approval_timeout
+            .filter(|_| !session.awaiting_approval)?;
+        session.awaiting_approval = true;
+        session.timeout = Some(session.created_at.elapsed() + wait);
+        session.release_finalization();
+        Some((session.vote_channel_id, session.progress()))
+    }
+
+    /// A moderator's sign-off on a vote waiting for one. Returns the outcome
+    /// after, or `None` when it wasn't waiting.
+    fn grant_approval(&self, session_id: MessageId) -> Option<VoteOutcome> {
+        let mut session = self.voting_sessions.get_mut(&session_id)?;
+        if !session.awaiting_approval {
+            return None;
+        }
+        session.awaiting_approval = false;
+        session.approval_timeout = None;
+        Some(session.outcome())
+    }
+
+    /// Lets a held vote carry on after review. Returns `None` when it wasn't
+    /// held, which includes a session that already ended.
+    fn resume_session(&self, session_id: MessageId) -> Option<VoteOutcome> {
+        let mut session = self.voting_sessions.get_mut(&session_id)?;
+        if !session.paused {
+            return None;
+        }
+        session.paused = false;
+        session.reviewed = true;
+        session.recent_votes.clear();
+        Some(session.outcome())
+    }
+
+    fn retract_vote(
+        &self,
+        session_id: MessageId,
+        user_id: UserId,
+        source: VoteSource,
+    ) -> VoteOutcome {
+        let Some(mut session_entry) = self.voting_sessions.get_mut(&session_id) else {
+            return VoteOutcome::NoSession;
+        };
+        let session = session_entry.value_mut();
+
+        if !session.remove_vote(user_id, source) {
+            return VoteOutcome::Duplicate;
+        }
+
+        let current_votes = session.get_vote_count();
+        info!(
+            user_id = %user_id,
+            session_id = %session_id,
+            votes = current_votes,
+            "Vote removed"
+        );
+        VoteOutcome::Counted(current_votes)
+    }
+
+    /// Takes back every checkmark vote cast by reacting on the vote message,
+    /// or on its target, after all reactions there were cleared. Returns how
+    /// many voters lost their vote.
+    fn retract_reaction_votes(&self, session_id: MessageId, on_target: bool) -> usize {
+        let Some(mut session) = self.voting_sessions.get_mut(&session_id) else {
+            return 0;
+        };
+        let cleared: Vec<(UserId, VoteSource)> = session
+            .vote_sources
+            .iter()
+            .flat_map(|(user_id, sources)| sources.iter().map(move |source| (*user_id, *source)))
+            .filter(|(_, source)| match source {
+                VoteSource::Reaction(_) | VoteSource::SuperReaction(_) => !on_target,
+                VoteSource::TargetReaction(_) | VoteSource::TargetSuperReaction(_) => on_target,
+                VoteSource::Button | VoteSource::Poll | VoteSource::Reply => false,
+            })
+            .collect();
+        let retracted = cleared
+            .into_iter()
+            .filter(|(user_id, source)| session.remove_vote(*user_id, *source))
+            .count();
+        if retracted > 0 {
+            info!(
+                session_id = %session_id,
+                retracted,
+                votes = session.get_vote_count(),
+                "Reactions cleared, votes removed"
+            );
+        }
+        retracted
+    }
+
+    /// Records a vote against on a quorum session. Sessions without a
+    /// quorum take no rejections and report them as duplicates.
+    fn record_rejection(&self, session_id: MessageId, user_id: UserId, weight: u32) -> VoteOutcome {
+        let Some(mut session) = self.voting_sessions.get_mut(&session_id) else {
+            return VoteOutcome::NoSession;
+        };
+        if session.paused {
+            return VoteOutcome::Paused;
+        }
+        if session.quorum.is_none() || !session.add_rejection(user_id, weight) {
+            return VoteOutcome::Duplicate;
+        }
+        info!(
+            user_id = %user_id,
+            session_id = %session_id,
+            weight,
+            tally = %session.tally(),
+            "Rejection added"
+        );
+        // A rejection still counts toward the quorum, so it can be the vote
+        // that carries a motion already far enough ahead
+        session.outcome()
+    }
+
+    /// Withdraws a rejection, which can leave approvals far enough ahead
+    /// to pass.
+    fn retract_rejection(&self, session_id: MessageId, user_id: UserId) -> VoteOutcome {
+        let Some(mut session) = self.voting_sessions.get_mut(&session_id) else {
+            return VoteOutcome::NoSession;
+        };
+        if !session.remove_rejection(user_id) {
+            return VoteOutcome::Duplicate;
+        }
+        info!(
+            user_id = %user_id,
+            session_id = %session_id,
+            tally = %session.tally(),
+            "Rejection removed"
+        );
+        session.outcome()
+    }
+
+    /// Takes a departed member's votes off every open vote in the guild.
+    /// Returns the sessions they were counted on with the outcome after;
+    /// dropping a rejection can pass a quorum vote.
+    fn drop_departed_voter(
+        &self,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Vec<(MessageId, VoteOutcome)> {
+        self.drop_voter(Some(guild_id), user_id)
+    }
+
+    /// Takes the user's votes off open votes in `guild_id`, or in every
+    /// guild when it's `None`.
+    fn drop_voter(
+        &self,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+    ) -> Vec<(MessageId, VoteOutcome)> {
+        let mut affected = Vec::new();
+        for mut session in self.voting_sessions.iter_mut() {
+            if guild_id.is_some_and(|guild_id| session.guild_id != Some(guild_id))
+                || !session.drop_voter(user_id)
+            {
+                continue;
+            }
+            info!(
+                user_id = %user_id,
+                session_id = %session.key(),
+                tally = %session.progress(),
+                "Dropped a voter's votes"
+            );
+            affected.push((*session.key(), session.outcome()));
+        }
+        affected
+    }
+
+    /// Removes what the bot keeps about a user, one table at a time so a
+    /// failure leaves that table as it was. Their pins stay in the history
+    /// without the attribution.
+    fn purge_user_data(&self, user_id: UserId) -> PurgeReport {
+        let pins_anonymized = match self.store.anonymize_pins(user_id) {
+            Ok(count) => Some(count),
+            Err(e) => {
+                error!(user_id = %user_id, error = %e, "Failed to anonymize pins");
+                None
+            }
+        };
+        let opt_out_cleared = match self.store.purge_pin_opt_out(user_id) {
+            Ok(cleared) => Some(cleared),
+            Err(e) => {
+                error!(user_id = %user_id, error = %e, "Failed to clear a pin opt-out");
+                None
+            }
+        };
+        let blocklists_cleared = match self.store.purge_from_blocklists(user_id) {
+            Ok(changed) => {
+                let count = changed.len();
+                for (guild_id, config) in changed {
+                    self.guild_configs.insert(guild_id, config);
+                }
+                Some(count)
+            }
+            Err(e) => {
+                error!(user_id = %user_id, error = %e, "Failed to clear pin blocklists");
+                None
+            }
+        };
+        let sessions = self.drop_voter(None, user_id);
+        info!(
+            user_id = %user_id,
+            ?pins_anonymized,
+            ?opt_out_cleared,
+            ?blocklists_cleared,
+            votes = sessions.len(),
+            "Purged a user's data"
+        );
+        PurgeReport {
+            pins_anonymized,
+            opt_out_cleared,
+            blocklists_cleared,
+            sessions,
+        }
+    }
+
+    /// Posts the notices of votes that ran out of time or were evicted.
+    async fn announce_failed_votes(&self, http: &Http) {
+        let failed = std::mem::take(&mut *self.failed_votes.lock().expect("failed votes poisoned"));
+        for (channel_id, session_id, notice) in failed {
+            let message = CreateMessage::new()
+                .content(notice)
+                .reference_message((channel_id, session_id));
+            if let Err(e) = channel_id.send_message(http, message).await {
+                warn!(session_id = %session_id, error = %e, "Failed to announce a failed vote");
+            }
+        }
+    }
+
+    /// Keeps the deadlines on vote messages true: moved when the vote
+    /// timeout changed since they were posted, removed once a vote expired.
+    async fn settle_vote_deadlines(&self, http: &Http) {
+        let timeout = Duration::from_secs(self.config().vote_timeout_secs);
+        let mut edits = Vec::new();
+        for mut session in self.voting_sessions.iter_mut() {
+            let expires_at = session.expires_at(timeout);
+            let channel_id = session.vote_channel_id;
+            let Some(notice) = session.notice.as_mut() else {
+                continue;
+            };
+            if (notice.shown_expiry - expires_at).abs() <= DEADLINE_SLACK_SECS {
+                continue;
+            }
+            notice.shown_expiry = expires_at;
+            edits.push((
+                channel_id,
+                notice.message_id,
+                with_deadline(&notice.text, expires_at),
+            ));
+        }
+        let expired = std::mem::take(
+            &mut *self
+                .expired_notices
+                .lock()
+                .expect("expired notices poisoned"),
+        );
+        edits.extend(
+            expired
+                .into_iter()
+                .map(|(channel_id, notice)| (channel_id, notice.message_id, notice.text)),
+        );
+
+        for (channel_id, message_id, content) in edits {
+            if let Err(e) = channel_id
+                .edit_message(http, message_id, EditMessage::new().content(content))
+                .await
+            {
+                warn!(message_id = %message_id, error = %e, "Failed to update a vote deadline");
+            }
+        }
+    }
+
+    /// Pins a passed vote, unless it first needs a moderator's sign-off or
+    /// the guild's quiet hours hold it; a held vote says when it will be
+    /// pinned and is released when they end.
+    async fn finalize_unless_quiet(
+        self: &Arc<Self>,
+        http: &Arc<Http>,
+        session_id: MessageId,
+        target_channel_id: ChannelId,
+        target_message_id: MessageId,
+    ) {
+        if let Some((vote_channel_id, progress)) = self.await_approval(session_id) {
+            self.request_approval(http, session_id, vote_channel_id, &progress)
+                .await;
+            return;
+        }
+        let now = Timestamp::now().unix_timestamp();
+        let Some((ends, vote_channel_id)) = self.hold_for_quiet_hours(session_id, now) else {
+            self.finalize_session(http, session_id, target_channel_id, target_message_id)
+                .await;
+            return;
+        };
+        info!(session_id = %session_id, ends, "Vote passed during quiet hours, pin held");
+        let notice = CreateMessage::new()
+            .content(format!(
+                "✅ Approved — will be pinned at <t:{}:t>, once quiet hours end.",
+                ends
+            ))
+            .reference_message((vote_channel_id, session_id));
+        if let Err(e) = vote_channel_id.send_message(http, notice).await {
+            warn!(session_id = %session_id, error = %e, "Failed to announce a held pin");
+        }
+
+        let data = Arc::clone(self);
+        let http = Arc::clone(http);
+        tokio::spawn(async move {
+            sleep(Duration::from_secs((ends - now).max(0) as u64)).await;
+            data.release_quiet_pins(&http, Timestamp::now().unix_timestamp())
+                .await;
+        });
+    }
+
+    /// Asks moderators to approve or reject a passed vote, pinging the
+    /// guild's review role.
+    async fn request_approval(
+        &self,
+        http: &Http,
+        session_id: MessageId,
+        vote_channel_id: ChannelId,
+        progress: &str,
+    ) {
+        info!(session_id = %session_id, "Vote passed, waiting for a moderator's approval");
+        let timeout = Duration::from_secs(self.config().vote_timeout_secs);
+        let Some((guild_id, expires_at)) = self
+            .voting_sessions
+            .get(&session_id)
+            .map(|session| (session.guild_id, session.expires_at(timeout)))
+        else {
+            return;
+        };
+        let review_role = guild_id.and_then(|guild_id| self.guild_config(guild_id).review_role);
+
+        let mut content = String::new();
+        if let Some(role_id) = review_role {
+            content.push_str(&format!("<@&{}> ", role_id));
+        }
+        content.push_str(&format!(
+            "🛡️ This vote passed with {} and needs a moderator with Manage Messages to approve \
+             the pin. Unless someone decides, it expires <t:{}:R>.",
+            progress, expires_at
+        ));
+        let request = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new().roles(review_role))
+            .components(approval_buttons(session_id, false))
+            .reference_message((vote_channel_id, session_id));
+        if let Err(e) = vote_channel_id.send_message(http, request).await {
+            warn!(session_id = %session_id, error = %e, "Failed to ask for approval of a pin");
+        }
+    }
+
+    /// Holds a passed vote while its guild is in quiet hours at `now`,
+    /// queueing it for `release_quiet_pins`. Returns when they end and the
+    /// vote channel, or `None` when the vote may pin right away.
+    fn hold_for_quiet_hours(&self, session_id: MessageId, now: i64) -> Option<(i64, ChannelId)> {
+        let guild_id = self.voting_sessions.get(&session_id)?.guild_id?;
+        let ends = self.guild_config(guild_id).quiet_hours?.ends_after(now)?;
+        let vote_channel_id = {
+            let mut session = self.voting_sessions.get_mut(&session_id)?;
+            session.quiet_until = Some(ends);
+            session.vote_channel_id
+        };
+        self.quiet_pins
+            .lock()
+            .expect("quiet pins poisoned")
+            .push(session_id);
+        Some((ends, vote_channel_id))
+    }
+
+    /// Pins the votes held through quiet hours that ended by `now`. Votes
+    /// cancelled meanwhile are dropped.
+    async fn release_quiet_pins(&self, http: &Http, now: i64) {
+        let held = std::mem::take(&mut *self.quiet_pins.lock().expect("quiet pins poisoned"));
+        let mut waiting = Vec::new();
+        let mut due = Vec::new();
+        for session_id in held {
+            let Some(mut session) = self.voting_sessions.get_mut(&session_id) else {
+                continue;
+            };
+            match session.quiet_until {
+                Some(ends) if ends > now => waiting.push(session_id),
+                _ => {
+                    session.quiet_until = None;
+                    due.push((
+                        session_id,
+                        session.target_channel_id,
+                        session.target_message_id,
+                    ));
+                }
+            }
+        }
+        self.quiet_pins
+            .lock()
+            .expect("quiet pins poisoned")
+            .extend(waiting);
+
+        for (session_id, channel_id, message_id) in due {
+            info!(session_id = %session_id, "Quiet hours over, pinning held vote");
+            self.finalize_session(http, session_id, channel_id, message_id)
+                .await;
+        }
+    }
+
+    async fn finalize_session(
+        &self,
+        http: &Http,
+        session_id: MessageId,
+        target_channel_id: ChannelId,
+        target_message_id: MessageId,
+    ) {
+        let Some((targets, event, voters, vote_channel_id, duration, archive, reason, quotas)) =
+            self.voting_sessions.get(&session_id).map(|session| {
+                let target = PinTarget::new(
+                    target_channel_id,
+                    target_message_id,
+                    session.target_snapshot.clone(),
+                );
+                (
+                    std::iter::once(target)
+                        .chain(session.batch.iter().cloned())
+                        .collect::<Vec<_>>(),
+                    session.event(PinEventKind::Pinned),
+                    session.voters.len() as u32,
+                    session.vote_channel_id,
+                    session.pin_duration,
+                    session.archive_channel(),
+                    session.reason.clone(),
+                    session.pin_quotas.clone(),
+                )
+            })
+        else {
+            return;
+        };
+
+        // An author may have been blocklisted after the vote started
+        if let Some((blocked, author_id)) = targets.iter().find_map(|target| {
+            let author_id = target.author_id?;
+            self.is_pin_blocked(event.guild_id, author_id)
+                .then_some((target, author_id))
+        }) {
+            info!(session_id = %session_id, "Vote passed on a blocklisted author's message");
+            let line = format!(
+                "🚷 A vote on {} passed but was called off: <@{}> is on the pin blocklist.",
+                blocked.message_id.link(blocked.channel_id, event.guild_id),
+                author_id
+            );
+            self.end_cancelled_session(
+                http,
+                session_id,
+                vote_channel_id,
+                BLOCKED_REFUSAL.to_string(),
+            )
+            .await;
+            if let Some(guild_id) = event.guild_id {
+                self.audit(http, guild_id, line).await;
+            }
+            return;
+        }
+
+        // An author may have opted out after the vote started
+        if targets.iter().any(|target| {
+            target
+                .author_id
+                .is_some_and(|author_id| self.has_opted_out(author_id))
+        }) {
+            info!(session_id = %session_id, "Vote passed on a message whose author opted out");
+            let notice = "Vote called off: the author of a message in it has asked not to have \
+                          their messages pinned."
+                .to_string();
+            self.end_cancelled_session(http, session_id, vote_channel_id, notice)
+                .await;
+            return;
+        }
+
+        // Someone may have pinned some by hand while the vote was open
+        let mut unpinned = Vec::new();
+        for target in targets {
+            if self
+                .is_already_pinned(http, target.channel_id, target.message_id)
+                .await
+            {
+                info!(message_id = %target.message_id, "Message was pinned during the vote");
+            } else {
+                unpinned.push(target);
+            }
+        }
+        if unpinned.is_empty() {
+            self.close_session(http, session_id, VoteEnding::Cancelled)
+                .await;
+            return;
+        }
+
+        // The channel may have used up its quota while the vote was open
+        let now = Timestamp::now().unix_timestamp();
+        let quotas: Vec<(ChannelId, u32)> = unpinned
+            .iter()
+            .map(|target| {
+                (
+                    target.channel_id,
+                    quotas.get(&target.channel_id).copied().unwrap_or(0),
+                )
+            })
+            .collect();
+        if let Some((channel_id, quota)) = self.over_pin_quota(&quotas, now) {
+            info!(session_id = %session_id, channel_id = %channel_id, "Vote passed over the daily pin quota");
+            let notice = format!(
+                "The vote passed, but {}",
+                pin_quota_refusal(channel_id, quota, now)
+            );
+            self.end_cancelled_session(http, session_id, vote_channel_id, notice)
+                .await;
+            return;
+        }
+
+        let (pinned, failed) = self.pin_each(event.guild_id, unpinned).await;
+        // With nothing pinned the vote stays open, so the next vote retries
+        if pinned.is_empty() {
+            if let Some(session) = self.voting_sessions.get(&session_id) {
+                session.release_finalization();
+            }
+            return;
+        }
+        self.close_session(http, session_id, VoteEnding::Pinned)
+            .await;
+        let order = PinOrder {
+            guild_id: event.guild_id,
+            initiator_id: event.initiator_id,
+            votes: event.voter_count,
+            voters,
+            duration,
+            archive,
+            reason: reason.as_deref(),
+        };
+        self.after_pins(http, &order, &pinned).await;
+        self.report_pins(http, vote_channel_id, &order, &pinned)
+            .await;
+
+        // Only a batch can get here with failures: part of it went through
+        if !failed.is_empty() {
+            let report =
+                self.dry_run_tagged(partial_batch_report(order.guild_id, pinned.len(), &failed));
+            if let Err(e) = vote_channel_id.say(http, report).await {
+                warn!(session_id = %session_id, error = %e, "Failed to report a partial batch");
+            }
+        }
+    }
+
+    /// Closes a called-off vote, takes the bot's reactions off the vote
+    /// message and replies there with `notice`. Returns false when the
+    /// session was already gone.
+    async fn end_cancelled_session(
+        &self,
+        http: &Http,
+        session_id: MessageId,
+        channel_id: ChannelId,
+        notice: String,
+    ) -> bool {
+        if !self
+            .close_session(http, session_id, VoteEnding::Cancelled)
+            .await
+        {
+            return false;
+        }
+
+        // Only our own reactions are removed, which needs no extra permission
+        match channel_id.message(http, session_id).await {
+            Ok(message) => {
+                for reaction in message.reactions.iter().filter(|reaction| reaction.me) {
+                    if let Err(e) = channel_id
+                        .delete_reaction(http, session_id, None, reaction.reaction_type.clone())
+                        .await
+                    {
+                        warn!(session_id = %session_id, error = %e, "Failed to clear reaction");
+                    }
+                }
+            }
+            Err(e) => warn!(session_id = %session_id, error = %e, "Failed to fetch cancelled vote"),
+        }
+
+        let notice = CreateMessage::new()
+            .content(notice)
+            .reference_message((channel_id, session_id));
+        if let Err(e) = channel_id.send_message(http, notice).await {
+            warn!(session_id = %session_id, error = %e, "Failed to acknowledge cancellation");
+        }
+        true
+    }
+
+    /// Ends the session as `ending` and disables its vote buttons, if it
+    /// had any. Returns false when the session was already gone.
+    async fn close_session(&self, http: &Http, session_id: MessageId, ending: VoteEnding) -> bool {
+        let Some(session) = self.end_session(session_id, ending) else {
+            return false;
+        };
+        self.clear_target_reactions(http).await;
+        self.archive_vote_threads(http).await;
+
+        // A prompt is also the notice, so one edit disables the buttons and
+        // takes the deadline off
+        let vote_message_id = session
+            .prompt_message_id
+            .or(session.notice.as_ref().map(|notice| notice.message_id));
+        if let Some(message_id) = vote_message_id {
+            let mut edit = EditMessage::new();
+            if let Some(notice) = &session.notice {
+                edit = edit.content(&notice.text);
+            }
+            if session.prompt_message_id.is_some() {
+                edit = edit.components(vote_buttons(session_id, true, session.quorum.is_some()));
+            }
+            if let Err(e) = session
+                .vote_channel_id
+                .edit_message(http, message_id, edit)
+                .await
+            {
+                warn!(message_id = %message_id, error = %e, "Failed to close the vote message");
+            }
+        }
+        // A poll left running would keep taking answers that no longer count
+        if let Some(poll) = session.poll {
+            if let Err(e) = session
+                .vote_channel_id
+                .end_poll(http, poll.message_id)
+                .await
+            {
+                debug!(message_id = %poll.message_id, error = %e, "Failed to end the vote poll, it may have run out already");
+            }
+        }
+        true
+    }
+
+    /// Pins waiting for the worker, for `/status`.
+    fn pin_queue_depth(&self) -> usize {
+        self.pin_queue_depth.load(Ordering::Relaxed)
+    }
+
+    /// Channels still under the pin cooldown, with the time left and
+    /// whether Discord rate limited them.
+    fn cooldowns(&self) -> Vec<(ChannelId, Duration, bool)> {
+        self.pin_cooldowns
+            .fresh_entries()
+            .into_iter()
+            .map(|(channel_id, rate_limited, remaining)| (channel_id, remaining, rate_limited))
+            .collect()
+    }
+
+    fn presence_text(&self) -> String {
+        self.config()
+            .presence_format
+            .replace("{votes}", &self.voting_sessions.len().to_string())
+            .replace(
+                "{pins}",
+                &self.pins_served.load(Ordering::Relaxed).to_string(),
+            )
+    }
+
+    /// Ends one session whose time ran out, unless it is being pinned.
+    /// Returns whether it ended.
+    fn expire_session(&self, session_id: MessageId) -> bool {
+        let timeout = Duration::from_secs(self.config().vote_timeout_secs);
+        let Some((_, mut session)) = self.voting_sessions.remove_if(&session_id, |_, session| {
+            session.is_expired(timeout) && !session.is_finalizing()
+        }) else {
+            return false;
+        };
+        info!(session_id = %session_id, "Vote expired");
+        self.retire_expired(session_id, &mut session);
+        true
+    }
+
+    /// When a session runs out of time, or `None` once it has ended.
+    fn session_deadline(&self, session_id: MessageId) -> Option<Instant> {
+        let timeout = Duration::from_secs(self.config().vote_timeout_secs);
+        self.voting_sessions
+            .get(&session_id)
+            .map(|session| session.created_at + session.timeout(timeout))
+    }
+
+    /// What wakes the collector of a session claimed for pinning, such as a
+    /// vote held through quiet hours; `None` when it isn't claimed.
+    fn session_hold(&self, session_id: MessageId) -> Option<Arc<Notify>> {
+        self.voting_sessions
+            .get(&session_id)
+            .filter(|session| session.is_finalizing())
+            .map(|session| Arc::clone(&session.hold_released))
+    }
+
+    /// Bookkeeping for an expired session that just left
+    /// `voting_sessions`: its stats are kept, and its deadline and, for a
+    /// quorum vote, its tally are queued for the channel.
+    fn retire_expired(&self, session_id: MessageId, session: &mut VotingSession) {
+        self.forget_session(session_id, session);
+        self.record_vote_stats(session_id, session, VoteEnding::Expired);
+        if let Some(notice) = session.notice.take() {
+            self.expired_notices
+                .lock()
+                .expect("expired notices poisoned")
+                .push((session.vote_channel_id, notice));
+        }
+        if session.awaiting_approval {
+            self.failed_votes
+                .lock()
+                .expect("failed votes poisoned")
+                .push((
+                    session.vote_channel_id,
+                    session_id,
+                    "⌛ No moderator approved this pin in time, so the vote expired.".to_string(),
+                ));
+        } else {
+            // Quorum votes always say how they fell short; others only in
+            // a guild's own wording
+            let guild_config = session
+                .guild_id
+                .map(|guild_id| self.guild_config(guild_id))
+                .unwrap_or_default();
+            if session.quorum.is_some()
+                || guild_config
+                    .templates
+                    .contains_key(&TemplateKey::VoteExpired)
+            {
+                let notice =
+                    expired_notice(guild_config.template(TemplateKey::VoteExpired), session);
+                self.failed_votes
+                    .lock()
+                    .expect("failed votes poisoned")
+                    .push((session.vote_channel_id, session_id, notice));
+            }
+        }
+        self.notify(session.event(PinEventKind::VoteExpired));
+    }
+
+    /// One pass of the cleanup loop: expires overdue votes, then catches up
+    /// on whatever they and the pins before them left to do.
+    async fn clean_up(&self, http: &Http) {
+        self.cleanup_expired_sessions();
+        self.settle_vote_deadlines(http).await;
+        self.release_quiet_pins(http, Timestamp::now().unix_timestamp())
+            .await;
+        self.announce_failed_votes(http).await;
+        self.clear_target_reactions(http).await;
+        self.archive_vote_threads(http).await;
+        self.retry_crossposts(http).await;
+        self.send_reminders(http).await;
+        self.expire_temporary_pins(http).await;
+    }
+
+    fn cleanup_expired_sessions(&self) {
+        self.cleanup_runs.fetch_add(1, Ordering::Relaxed);
+        let timeout = Duration::from_secs(self.config().vote_timeout_secs);
+        // A session being pinned is left to the handler pinning it
+        let retirable =
+            |session: &VotingSession| session.is_overdue(timeout) && !session.is_finalizing();
+        // Retiring writes stats to the store, so each session leaves the map
+        // first rather than inside a `retain` holding every shard's lock
+        let overdue: Vec<MessageId> = self
+            .voting_sessions
+            .iter()
+            .filter(|entry| retirable(entry.value()))
+            .map(|entry| *entry.key())
+            .collect();
+        let mut removed_count = 0;
+        for session_id in overdue {
+            let Some((_, mut session)) = self
+                .voting_sessions
+                .remove_if(&session_id, |_, session| retirable(session))
+            else {
+                continue;
+            };
+            removed_count += 1;
+            self.retire_expired(session_id, &mut session);
+        }
+        self.session_order
+            .lock()
+            .expect("session order poisoned")
+            .retain(|session_id| self.voting_sessions.contains_key(session_id));
+
+        self.dm_replies.retain_fresh();
+        let now = Instant::now();
+        self.guild_pins.retain(|_, pins| {
+            pins.retain(|at| now.saturating_duration_since(*at) < GUILD_PIN_WINDOW);
+            !pins.is_empty()
+        });
+        let cooled = self.pin_cooldowns.retain_fresh();
+        if cooled > 0 {
+            debug!(count = cooled, "Dropped expired pin cooldowns");
+        }
+        self.paginations
+            .retain(|_, paginator| !paginator.is_expired());
+
+        if removed_count > 0 {
+            info!(count = removed_count, "Cleaned up expired voting sessions");
+        }
+    }
+}
+
+pub struct Handler {
+    data: Arc<BotData>,
+    /// Set once the cleanup task is running; ready() fires again after some
+    /// reconnects and must not spawn a second loop.
+    cleanup_started: AtomicBool,
+    presence_started: AtomicBool,
+    digest_started: AtomicBool,
+    retention_started: AtomicBool,
+    shard_monitor_started: AtomicBool,
+    shutdown: watch::Receiver<bool>,
+    /// Takes reactions on votes off the gateway handlers once ready()
+    /// started its workers; until then they are handled inline.
+    reactions: Arc<OnceLock<ReactionQueue<ReactionJob>>>,
+}
+
+/// A reaction on a vote, handed to the reaction workers.
+struct ReactionJob {
+    ctx: Context,
+    reaction: Reaction,
+    added: bool,
+    /// Carries the reaction's ids into the worker's logs.
+    span: Span,
+}
+
+impl Handler {
+    pub fn new(config: Config, store: Store, shutdown: watch::Receiver<bool>) -> Self {
+        Self {
+            data: Arc::new(BotData::new(config, store)),
+            cleanup_started: AtomicBool::new(false),
+            presence_started: AtomicBool::new(false),
+            digest_started: AtomicBool::new(false),
+            retention_started: AtomicBool::new(false),
+            shard_monitor_started: AtomicBool::new(false),
+            shutdown,
+            reactions: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// A handler over the same state for the reaction workers. It never
+    /// starts the background tasks itself.
+    fn worker_view(&self) -> Handler {
+        Self {
+            data: Arc::clone(&self.data),
+            cleanup_started: AtomicBool::new(true),
+            presence_started: AtomicBool::new(true),
+            digest_started: AtomicBool::new(true),
+            retention_started: AtomicBool::new(true),
+            shard_monitor_started: AtomicBool::new(true),
+            shutdown: self.shutdown.clone(),
+            reactions: Arc::clone(&self.reactions),
+        }
+    }
+
+    /// The shared state, for wiring up the client after the handler moves
+    /// into it.
+    pub fn data(&self) -> Arc<BotData> {
+        Arc::clone(&self.data)
+    }
+
+    /// Decides whether a reaction came from a bot without a REST call in the
+    /// common cases: our own id, the member payload, then the cache. The HTTP
+    /// fallback is counted, and a failed fetch counts the vote rather than
+    /// dropping it.
+    async fn is_bot_reaction(&self, ctx: &Context, reaction: &Reaction) -> bool {
+        let Some(user_id) = reaction.user_id else {
+            return false;
+        };
+        if user_id == ctx.cache.current_user().id {
+            return true;
+        }
+        if let Some(member) = &reaction.member {
+            return member.user.bot;
+        }
+        if let Some(user) = ctx.cache.user(user_id) {
+            return user.bot;
+        }
+
+        let fallbacks = self
+            .data
+            .user_fetch_fallbacks
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        debug!(
+            user_id = %user_id,
+            fallbacks,
+            "Fetching user over HTTP for bot check"
+        );
+        match ctx.http.get_user(user_id).await {
+            Ok(user) => user.bot,
+            Err(e) => {
+                warn!(user_id = %user_id, error = %e, "Failed to fetch user for bot check");
+                false
+            }
+        }
+    }
+
+    /// Applies the voter filters, cheapest first: account age comes straight
+    /// from the snowflake, and the member is only resolved when a role or
+    /// membership-age rule actually needs it.
+    async fn is_eligible_voter(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        user_id: UserId,
+        member: Option<&Member>,
+        session_id: MessageId,
+    ) -> bool {
+        let config = self.data.config();
+        let now = Timestamp::now().unix_timestamp();
+
+        if config.min_account_age_days > 0 {
+            let account_age = now - user_id.created_at().unix_timestamp();
+            if account_age < (config.min_account_age_days * 86_400) as i64 {
+                debug!(
+                    user_id = %user_id,
+                    session_id = %session_id,
+                    min_days = config.min_account_age_days,
+                    "Ignoring vote: account too young"
+                );
+                return false;
+            }
+        }
+
+        let Some(guild_id) = guild_id else {
+            return true;
+        };
+        let guild_config = self.data.guild_config(guild_id);
+        let voter_role = guild_config.voter_role;
+        if voter_role.is_none()
+            && config.min_member_age_hours == 0
+            && guild_config.count_timed_out_votes
+        {
+            return true;
+        }
+
+        let Some(member) = resolve_member(ctx, guild_id, user_id, member).await else {
+            debug!(
+                user_id = %user_id,
+                session_id = %session_id,
+                "Ignoring vote: member could not be resolved"
+            );
+            return false;
+        };
+
+        if let Some(role_id) = voter_role {
+            if !member.roles.contains(&role_id) {
+                debug!(
+                    user_id = %user_id,
+                    session_id = %session_id,
+                    role_id = %role_id,
+                    "Ignoring vote: missing voter role"
+                );
+                return false;
+            }
+        }
+
+        if config.min_member_age_hours > 0 {
+            let joined_recently = member.joined_at.is_none_or(|joined| {
+                now - joined.unix_timestamp() < (config.min_member_age_hours * 3_600) as i64
+            });
+            if joined_recently {
+                debug!(
+                    user_id = %user_id,
+                    session_id = %session_id,
+                    min_hours = config.min_member_age_hours,
+                    "Ignoring vote: member joined too recently"
+                );
+                return false;
+            }
+        }
+
+        if !guild_config.count_timed_out_votes && is_timed_out(&member, now) {
+            debug!(
+                user_id = %user_id,
+                session_id = %session_id,
+                "Ignoring vote: member is timed out"
+            );
+            return false;
+        }
+
+        true
+    }
+
+    /// Takes a timed-out member's uncounted reaction back off the message so
+    /// the visible count matches the tally. Only the member sent with the
+    /// event is checked; removing someone else's reaction needs Manage
+    /// Messages, and a failure is only logged.
+    async fn drop_timed_out_reaction(&self, ctx: &Context, reaction: &Reaction) {
+        let (Some(guild_id), Some(member)) = (reaction.guild_id, reaction.member.as_ref()) else {
+            return;
+        };
+        // Only tidying up, so it gives way when the workers are swamped
+        if self
+            .reactions
+            .get()
+            .is_some_and(|queue| queue.is_saturated())
+        {
+            debug!(message_id = %reaction.message_id, "Reaction queue full, leaving a timed-out member's reaction");
+            return;
+        }
+        if self.data.guild_config(guild_id).count_timed_out_votes
+            || !is_timed_out(member, Timestamp::now().unix_timestamp())
+        {
+            return;
+        }
+        if let Err(e) = reaction.delete(&ctx.http).await {
+            warn!(
+                message_id = %reaction.message_id,
+                error = %e,
+                "Failed to remove a timed-out member's reaction"
+            );
+        }
+    }
+
+    /// Checks whether pin votes may be started in the channel at all.
+    fn vet_channel(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+    ) -> Result<(), String> {
+        match guild_id {
+            Some(guild_id) => {
+                self.vet_lineage(guild_id, &channel_lineage(ctx, guild_id, channel_id))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// [`Self::vet_channel`] for a channel already resolved to its lineage.
+    fn vet_lineage(&self, guild_id: GuildId, channels: &[ChannelId]) -> Result<(), String> {
+        if !self.data.is_channel_allowed(guild_id, channels) {
+            return Err("Pin votes are disabled in this channel.".to_string());
+        }
+        Ok(())
+    }
+
+    /// Each target's channel with its daily pin quota, honouring overrides
+    /// on the channel or, for a thread, its parent.
+    fn pin_quotas(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        targets: &[Message],
+    ) -> Vec<(ChannelId, u32)> {
+        targets
+            .iter()
+            .map(|target| {
+                let quota = match guild_id {
+                    Some(guild_id) => self.data.daily_pin_quota(
+                        Some(guild_id),
+                        &channel_lineage(ctx, guild_id, target.channel_id),
+                    ),
+                    None => 0,
+                };
+                (target.channel_id, quota)
+            })
+            .collect()
+    }
+
+    /// Refuses a vote on `targets` when a channel has no pins left today.
+    fn vet_pin_quota(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        targets: &[Message],
+    ) -> Result<(), String> {
+        let now = Timestamp::now().unix_timestamp();
+        match self
+            .data
+            .over_pin_quota(&self.pin_quotas(ctx, guild_id, targets), now)
+        {
+            Some((channel_id, quota)) => Err(pin_quota_refusal(channel_id, quota, now)),
+            None => Ok(()),
+        }
+    }
+
+    /// Votes needed to pin a message in `channel_id`, honouring overrides on
+    /// the channel or, for a thread, its parent.
+    fn threshold_in(&self, ctx: &Context, guild_id: Option<GuildId>, channel_id: ChannelId) -> u32 {
+        match guild_id {
+            Some(guild_id) => self
+                .data
+                .threshold(Some(guild_id), &channel_lineage(ctx, guild_id, channel_id)),
+            None => self.data.threshold(None, &[]),
+        }
+    }
+
+    /// Checks whether `requester` may open a vote on the target message.
+    /// Moderators are exempt from the self-pin guard.
+    fn vet_target(
+        &self,
+        guild_id: Option<GuildId>,
+        target: &Message,
+        requester: UserId,
+        is_moderator: bool,
+    ) -> Result<(), String> {
+        if target.pinned {
+            return Err("That message is already pinned.".to_string());
+        }
+        if is_system_message(target.kind) {
+            return Err("System messages can't be pinned.".to_string());
+        }
+        if self.data.is_locked(guild_id, target.id) {
+            return Err("A moderator locked that message from pinning.".to_string());
+        }
+        if self.data.has_opted_out(target.author.id) {
+            return Err(OPTED_OUT_REFUSAL.to_string());
+        }
+
+        let guild_config = guild_id.map(|guild_id| self.data.guild_config(guild_id));
+        if (target.author.bot || target.webhook_id.is_some())
+            && !guild_config
+                .as_ref()
+                .is_some_and(|config| config.allow_bot_pins)
+        {
+            return Err("Pinning bot and webhook messages is turned off here.".to_string());
+        }
+        // Without message content every text rule would misfire
+        if !is_moderator && !self.data.interaction_only() {
+            if let Some(config) = &guild_config {
+                config
+                    .content_filter
+                    .check(target)
+                    .map_err(|rule| rule.to_string())?;
+            }
+        }
+        if target.author.id == requester
+            && !is_moderator
+            && !guild_config.is_some_and(|config| config.allow_self_pins)
+        {
+            return Err("Nice try! You can't start a pin vote on your own message. 😄".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether the target's author is on the guild's pin blocklist. A blocked
+    /// attempt is logged and reported to the audit channel; the caller only
+    /// gives the requester a quiet refusal.
+    async fn refuse_blocked(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        target: &Message,
+        requester: UserId,
+    ) -> bool {
+        let Some(guild_id) = guild_id else {
+            return false;
+        };
+        if !self.data.is_pin_blocked(Some(guild_id), target.author.id) {
+            return false;
+        }
+        info!(
+            message_id = %target.id,
+            user_id = %requester,
+            author_id = %target.author.id,
+            "Refused a pin request on a blocklisted author"
+        );
+        self.data
+            .audit(
+                &ctx.http,
+                guild_id,
+                format!(
+                    "🚷 <@{}> asked to pin {} by <@{}>, who is on the pin blocklist.",
+                    requester,
+                    target.id.link(target.channel_id, Some(guild_id)),
+                    target.author.id
+                ),
+            )
+            .await;
+        true
+    }
+
+    /// Runs every guard a new pin request made in `channel_id` must pass,
+    /// returning the refusal to show the requester. Shared by all entry points.
+    async fn vet_request(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        target: &Message,
+        requester: UserId,
+        is_moderator: bool,
+    ) -> Result<(), String> {
+        let lineage = |channel_id| {
+            guild_id
+                .map(|guild_id| channel_lineage(ctx, guild_id, channel_id))
+                .unwrap_or_default()
+        };
+        self.vet_request_in(
+            guild_id,
+            [&lineage(channel_id), &lineage(target.channel_id)],
+            target,
+            requester,
+            is_moderator,
+        )
+    }
+
+    /// The checks behind [`Self::vet_request`], given the lineages of the
+    /// channel the request was made in and of the target's channel. The vote
+    /// is posted where it was asked for, so both must allow votes.
+    fn vet_request_in(
+        &self,
+        guild_id: Option<GuildId>,
+        lineages: [&[ChannelId]; 2],
+        target: &Message,
+        requester: UserId,
+        is_moderator: bool,
+    ) -> Result<(), String> {
+        if let Some(guild_id) = guild_id {
+            for channels in lineages {
+                self.vet_lineage(guild_id, channels)?;
+            }
+        }
+        self.vet_target(guild_id, target, requester, is_moderator)
+    }
+
+    /// Opens a voting session on `anchor` and sets up the voting UI for the
+    /// guild's vote mode. `threshold` is fixed for the life of the session.
+    /// The first of `targets` is the session's target; any others are
+    /// pinned along with it. Returns whether the vote is open.
+    async fn start_session(
+        &self,
+        ctx: &Context,
+        anchor: VoteAnchor<'_>,
+        targets: &[Message],
+        threshold: u32,
+        terms: VoteTerms,
+    ) -> bool {
+        let Some((target, batch)) = targets.split_first() else {
+            return false;
+        };
+        let anchor_msg = anchor.message();
+        let guild_id = anchor.guild_id();
+        let guild_config = guild_id
+            .map(|guild_id| self.data.guild_config(guild_id))
+            .unwrap_or_default();
+        let vote_mode = guild_config.vote_mode;
+        let mut session = VotingSession::new(
+            target.id,
+            target.channel_id,
+            anchor_msg.channel_id,
+            guild_id,
+            anchor.initiator(),
+            threshold,
+        );
+        session.pin_duration = terms.pin_duration;
+        session.reason = terms.reason;
+        session.timeout = terms.timeout;
+        session.batch = batch.iter().map(PinTarget::from).collect();
+        session.target_snapshot = Some(TargetSnapshot::capture(target));
+        session.default_archive = guild_config.archive_channel;
+        session.quorum = guild_config.quorum;
+        session.brigade_guard = guild_config.brigade_guard;
+        session.min_open =
+            Duration::from_secs(u64::from(guild_config.min_vote_minutes.unwrap_or(0)) * 60);
+        session.pin_quotas = self
+            .pin_quotas(ctx, guild_id, targets)
+            .into_iter()
+            .collect();
+        session.approval_timeout = guild_id.and_then(|guild_id| {
+            let channels: Vec<ChannelId> = targets
+                .iter()
+                .flat_map(|target| channel_lineage(ctx, guild_id, target.channel_id))
+                .collect();
+            self.data.approval_timeout(Some(guild_id), &channels)
+        });
+        // A command's response is the vote message, deadline and all
+        if let VoteAnchor::Interaction(_, response) = anchor {
+            let timeout = Duration::from_secs(self.data.config().vote_timeout_secs);
+            session.notice = Some(VoteNotice {
+                message_id: response.id,
+                text: strip_deadline(&response.content).to_string(),
+                shown_expiry: session.expires_at(timeout),
+            });
+        }
+        // Category reactions only make sense when voting happens on reactions
+        if vote_mode == VoteMode::Reactions {
+            session.categories = guild_config
+                .archive_categories
+                .iter()
+                .map(CategoryTally::from)
+                .collect();
+        }
+        let category_emojis: Vec<ReactionType> = session
+            .categories
+            .iter()
+            .map(|category| category.emoji.clone())
+            .collect();
+        // The number reaction shows how many votes a quorum vote needs
+        let cap = session.quorum.map_or(threshold, |rule| rule.quorum);
+        let rejectable = session.quorum.is_some();
+        let started = session.event(PinEventKind::VoteStarted);
+        if let Err(refusal) = self.data.insert_session(anchor_msg.id, session) {
+            // Another vote raced in after the entry point checked capacity
+            warn!(session_id = %anchor_msg.id, %refusal, "Not starting a vote");
+            return false;
+        }
+        self.collect_reactions(ctx, anchor_msg.id, target.id);
+
+        // Skip straight to buttons when the cache already says reacting
+        // would fail; an uncached channel still gets to try
+        let may_react = guild_id.is_none_or(|guild_id| {
+            bot_permissions_in(ctx, guild_id, anchor_msg.channel_id)
+                .is_none_or(|permissions| permissions.add_reactions())
+        });
+        let ready = if vote_mode == VoteMode::Buttons {
+            self.attach_vote_buttons(ctx, &anchor, threshold, None)
+                .await
+        } else if vote_mode == VoteMode::Poll {
+            self.attach_vote_poll(ctx, &anchor, threshold).await
+                || self
+                    .attach_vote_buttons(ctx, &anchor, threshold, Some(POLL_FALLBACK_NOTE))
+                    .await
+        } else if may_react
+            && self
+                .add_vote_reactions(
+                    ctx.http.as_ref(),
+                    anchor_msg,
+                    cap,
+                    rejectable,
+                    &category_emojis,
+                )
+                .await
+        {
+            self.add_target_checkmark(ctx.http.as_ref(), anchor_msg.id, target)
+                .await;
+            true
+        } else {
+            if self
+                .data
+                .reaction_fallback_channels
+                .insert(anchor_msg.channel_id)
+            {
+                warn!(
+                    channel_id = %anchor_msg.channel_id,
+                    "Can't add reactions, falling back to vote buttons"
+                );
+            }
+            self.attach_vote_buttons(ctx, &anchor, threshold, Some(REACTION_FALLBACK_NOTE))
+                .await
+        };
+
+        if ready {
+            self.data.notify(started);
+            self.open_discussion_thread(ctx, anchor_msg.id, target)
+                .await;
+        } else {
+            self.abandon_session(ctx, &anchor).await;
+        }
+        ready
+    }
+
+    /// Opens a public thread for talking the vote over, when the guild asks
+    /// for one: off the vote message, or the target when that can't take a
+    /// thread. The vote carries on without it if neither works.
+    async fn open_discussion_thread(&self, ctx: &Context, session_id: MessageId, target: &Message) {
+        let Some((guild_id, vote_channel_id, vote_message_id, reason)) = self
+            .data
+            .voting_sessions
+            .get(&session_id)
+            .and_then(|session| {
+                Some((
+                    session.guild_id?,
+                    session.vote_channel_id,
+                    session.prompt_message_id.unwrap_or(session_id),
+                    session.reason.clone(),
+                ))
+            })
+        else {
+            return;
+        };
+        let Some(template) = self.data.guild_config(guild_id).vote_thread_name else {
+            return;
+        };
+        let name = vote_thread_name(&template, &target.content);
+
+        let mut thread = None;
+        for (channel_id, message_id) in [
+            (vote_channel_id, vote_message_id),
+            (target.channel_id, target.id),
+        ] {
+            match channel_id
+                .create_thread_from_message(&ctx.http, message_id, CreateThread::new(name.clone()))
+                .await
+            {
+                Ok(created) => {
+                    thread = Some(created.id);
+                    break;
+                }
+                Err(e) => {
+                    debug!(message_id = %message_id, error = %e, "Failed to open a thread for the vote");
+                }
+            }
+        }
+        let Some(thread_id) = thread else {
+            warn!(session_id = %session_id, "Couldn't open a discussion thread, voting without one");
+            return;
+        };
+        info!(session_id = %session_id, thread_id = %thread_id, "Opened a discussion thread");
+
+        if let Some(reason) = reason {
+            let message = CreateMessage::new()
+                .content(format!("**Reason:** {}", reason))
+                .allowed_mentions(CreateAllowedMentions::new());
+            if let Err(e) = thread_id.send_message(&ctx.http, message).await {
+                warn!(thread_id = %thread_id, error = %e, "Failed to post the reason in the thread");
+            }
+        }
+        match self.data.voting_sessions.get_mut(&session_id) {
+            Some(mut session) => session.thread_id = Some(thread_id),
+            // The vote ended while the thread opened
+            None => {
+                self.data
+                    .stale_threads
+                    .lock()
+                    .expect("stale threads poisoned")
+                    .push(thread_id);
+                self.data.archive_vote_threads(&ctx.http).await;
+            }
+        }
+    }
+
+    /// Puts a ✅ on the message being pinned as well, so members can vote
+    /// right there. Missing permissions in the target channel are fine; the
+    /// vote message still works.
+    async fn add_target_checkmark(
+        &self,
+        api: &impl PinApi,
+        session_id: MessageId,
+        target: &Message,
+    ) {
+        let checkmark = ReactionType::Unicode(CHECKMARK_EMOJI.to_string());
+        if let Err(e) = api.react(target.channel_id, target.id, checkmark).await {
+            debug!(message_id = %target.id, error = %e, "Failed to add ✅ to the target");
+            return;
+        }
+        match self.data.voting_sessions.get_mut(&session_id) {
+            Some(mut session) => session.target_reacted = true,
+            // The vote ended while the reaction went on
+            None => self
+                .data
+                .stale_target_reactions
+                .lock()
+                .expect("stale reactions poisoned")
+                .push((target.channel_id, target.id)),
+        }
+    }
+
+    /// Puts Approve/Cancel buttons on the vote, with an optional note for
+    /// voters. Returns whether the buttons are in place.
+    async fn attach_vote_buttons(
+        &self,
+        ctx: &Context,
+        anchor: &VoteAnchor<'_>,
+        threshold: u32,
+        note: Option<&str>,
+    ) -> bool {
+        let timeout = Duration::from_secs(self.data.config().vote_timeout_secs);
+        let guild_config = anchor
+            .guild_id()
+            .map(|guild_id| self.data.guild_config(guild_id))
+            .unwrap_or_default();
+        let Some((mut text, expires_at, rejectable)) = self
+            .data
+            .voting_sessions
+            .get(&anchor.message().id)
+            .map(|session| {
+                // A command's response already presents the vote
+                let text = match &session.notice {
+                    Some(notice) => notice.text.clone(),
+                    None => vote_prompt_text(&guild_config, &session, threshold),
+                };
+                (text, session.expires_at(timeout), session.quorum.is_some())
+            })
+        else {
+            // The vote ended before its buttons went up
+            return false;
+        };
+        if let Some(note) = note {
+            text.push('\n');
+            text.push_str(note);
+        }
+        let content = with_deadline(&text, expires_at);
+        let prompt_id = match *anchor {
+            VoteAnchor::Reply(msg) => self.post_vote_prompt(ctx, msg, content, rejectable).await,
+            VoteAnchor::Interaction(command, response) => {
+                let mut edit = EditInteractionResponse::new().components(vote_buttons(
+                    response.id,
+                    false,
+                    rejectable,
+                ));
+                if note.is_some() {
+                    edit = edit.content(content);
+                }
+                match command.edit_response(&ctx.http, edit).await {
+                    Ok(_) => Some(response.id),
+                    Err(e) => {
+                        warn!(message_id = %response.id, error = %e, "Failed to attach vote buttons");
+                        None
+                    }
+                }
+            }
+        };
+
+        let Some(prompt_id) = prompt_id else {
+            return false;
+        };
+        if let Some(mut session) = self.data.voting_sessions.get_mut(&anchor.message().id) {
+            session.vote_mode = VoteMode::Buttons;
+            session.prompt_message_id = Some(prompt_id);
+            session.notice = Some(VoteNotice {
+                message_id: prompt_id,
+                text,
+                shown_expiry: expires_at,
+            });
+        }
+        true
+    }
+
+    /// Posts a poll for the vote in reply to its anchor, running as long as
+    /// the vote rounded up to whole hours, and has the session end with it.
+    /// A command's response already presents the vote, so only a reply to
+    /// a mention carries the vote's text. Returns false when the poll
+    /// couldn't be posted or the vote already ended.
+    async fn attach_vote_poll(
+        &self,
+        ctx: &Context,
+        anchor: &VoteAnchor<'_>,
+        threshold: u32,
+    ) -> bool {
+        let session_id = anchor.message().id;
+        let default = Duration::from_secs(self.data.config().vote_timeout_secs);
+        let guild_config = anchor
+            .guild_id()
+            .map(|guild_id| self.data.guild_config(guild_id))
+            .unwrap_or_default();
+        let Some((duration, text)) = self.data.voting_sessions.get(&session_id).map(|session| {
+            let text = session
+                .notice
+                .is_none()
+                .then(|| vote_prompt_text(&guild_config, &session, threshold));
+            (poll_duration(session.timeout(default)), text)
+        }) else {
+            return false;
+        };
+
+        let poll = CreatePoll::new()
+            .question(POLL_QUESTION)
+            .answers(vec![
+                CreatePollAnswer::new()
+                    .text("Yes")
+                    .emoji(CHECKMARK_EMOJI.to_string()),
+                CreatePollAnswer::new()
+                    .text("No")
+                    .emoji(REJECT_EMOJI.to_string()),
+            ])
+            .duration(duration);
+        let mut message = CreateMessage::new()
+            .poll(poll)
+            .reference_message(anchor.message());
+        if let Some(text) = text {
+            message = message.content(text);
+        }
+        let channel_id = anchor.message().channel_id;
+        let posted = match channel_id.send_message(&ctx.http, message).await {
+            Ok(posted) => posted,
+            Err(e) => {
+                warn!(session_id = %session_id, error = %e, "Failed to post the vote poll");
+                return false;
+            }
+        };
+        let answers: Vec<AnswerId> = posted
+            .poll
+            .iter()
+            .flat_map(|poll| poll.answers.iter().map(|answer| answer.answer_id))
+            .collect();
+        let [yes, no] = answers[..] else {
+            warn!(session_id = %session_id, "The vote poll came back without its two answers");
+            return false;
+        };
+        let poll = VotePoll {
+            message_id: posted.id,
+            yes,
+            no,
+        };
+        if self.data.attach_poll(session_id, poll, duration) {
+            return true;
+        }
+        // The vote ended while the poll went up
+        if let Err(e) = channel_id.end_poll(&ctx.http, posted.id).await {
+            debug!(message_id = %posted.id, error = %e, "Failed to end an orphaned vote poll");
+        }
+        false
+    }
+
+    /// Counts a vote added to or removed from a session's poll: its first
+    /// answer approves and the second rejects, counted only on quorum
+    /// votes. Ineligible voters can't be kept off a poll, so their answers
+    /// are simply not counted.
+    async fn handle_poll_vote(
+        &self,
+        ctx: &Context,
+        guild_id: Option<GuildId>,
+        message_id: MessageId,
+        user_id: UserId,
+        answer_id: AnswerId,
+        added: bool,
+    ) {
+        // Votes only run in guilds
+        if guild_id.is_none() {
+            return;
+        }
+        let Some((session_id, poll)) = self.data.session_for_poll(message_id) else {
+            return;
+        };
+        let approve = if answer_id == poll.yes {
+            true
+        } else if answer_id == poll.no {
+            false
+        } else {
+            return;
+        };
+        if !added {
+            if approve {
+                self.data
+                    .retract_vote(session_id, user_id, VoteSource::Poll);
+            } else {
+                self.withdraw_rejection(ctx, session_id, user_id).await;
+            }
+            return;
+        }
+        if !self
+            .is_eligible_voter(ctx, guild_id, user_id, None, session_id)
+            .await
+        {
+            return;
+        }
+        if approve {
+            self.cast_vote(ctx, session_id, user_id, None, VoteSource::Poll)
+                .await;
+        } else {
+            self.cast_rejection(ctx, session_id, user_id, None).await;
+        }
+    }
+
+    /// Brings a poll vote that reached its deadline in line with the poll
+    /// itself before it expires, in case gateway events were missed while
+    /// it ran: answers the bot never heard of are counted, and approvals
+    /// the poll no longer shows are taken back. A vote that passes this
+    /// way pins as usual.
+    async fn tally_poll(&self, ctx: &Context, session_id: MessageId) {
+        let timeout = Duration::from_secs(self.data.config().vote_timeout_secs);
+        let Some((guild_id, channel_id, poll, counted, rejecting, quorum)) = self
+            .data
+            .voting_sessions
+            .get(&session_id)
+            .filter(|session| session.is_expired(timeout) && !session.is_finalizing())
+            .and_then(|session| {
+                Some((
+                    session.guild_id,
+                    session.vote_channel_id,
+                    session.poll?,
+                    session.poll_voters(),
+                    session.rejections.keys().copied().collect::<HashSet<_>>(),
+                    session.quorum.is_some(),
+                ))
+            })
+        else {
+            return;
+        };
+
+        let approving = match poll_answer_voters(ctx, channel_id, poll.message_id, poll.yes).await {
+            Ok(voters) => voters,
+            Err(e) => {
+                warn!(session_id = %session_id, error = %e, "Failed to count the vote poll, expiring on the tally so far");
+                return;
+            }
+        };
+        for user_id in counted.difference(&approving) {
+            self.data
+                .retract_vote(session_id, *user_id, VoteSource::Poll);
+        }
+        let mut missed: Vec<(UserId, bool)> = approving
+            .difference(&counted)
+            .map(|user_id| (*user_id, true))
+            .collect();
+        if quorum {
+            match poll_answer_voters(ctx, channel_id, poll.message_id, poll.no).await {
+                Ok(voters) => missed.extend(
+                    voters
+                        .difference(&rejecting)
+                        .map(|user_id| (*user_id, false)),
+                ),
+                Err(e) => {
+                    warn!(session_id = %session_id, error = %e, "Failed to count the poll's rejections");
+                }
+            }
+        }
+        if !missed.is_empty() {
+            info!(session_id = %session_id, missed = missed.len(), "Counting poll answers missed while the vote ran");
+        }
+        for (user_id, approve) in missed {
+            if !self
+                .is_eligible_voter(ctx, guild_id, user_id, None, session_id)
+                .await
+            {
+                continue;
+            }
+            let outcome = if approve {
+                self.cast_vote(ctx, session_id, user_id, None, VoteSource::Poll)
+                    .await
+            } else {
+                self.cast_rejection(ctx, session_id, user_id, None).await
+            };
+            if matches!(outcome, VoteOutcome::NoSession | VoteOutcome::Passed { .. }) {
+                break;
+            }
+        }
+    }
+
+    /// Drops a session whose voting UI couldn't be set up and lets the
+    /// initiator know, privately since the channel is evidently off limits.
+    async fn abandon_session(&self, ctx: &Context, anchor: &VoteAnchor<'_>) {
+        let anchor_msg = anchor.message();
+        self.data.remove_session(anchor_msg.id);
+        self.data.clear_target_reactions(ctx.http.as_ref()).await;
+        warn!(
+            session_id = %anchor_msg.id,
+            channel_id = %anchor_msg.channel_id,
+            "No way to vote, dropped the session"
+        );
+
+        let result = match *anchor {
+            VoteAnchor::Reply(_) => anchor
+                .initiator()
+                .direct_message(&ctx.http, CreateMessage::new().content(VOTE_SETUP_FAILED))
+                .await
+                .map(|_| ()),
+            VoteAnchor::Interaction(command, _) => command
+                .create_followup(
+                    &ctx.http,
+                    CreateInteractionResponseFollowup::new()
+                        .content(VOTE_SETUP_FAILED)
+                        .ephemeral(true),
+                )
+                .await
+                .map(|_| ()),
+        };
+        if let Err(e) = result {
+            warn!(
+                user_id = %anchor.initiator(),
+                error = %e,
+                "Failed to tell the initiator the vote was dropped"
+            );
+        }
+    }
+
+    /// Adds the vote reactions in order. Every emoji is already a typed
+    /// reaction, so each is sent once. serenity's rate limiter already
+    /// spaces the calls, so they are awaited back to back without a delay.
+    /// The archive `categories` follow the number. Returns whether the ✅
+    /// went on, i.e. whether reaction voting works.
+    async fn add_vote_reactions(
+        &self,
+        api: &impl PinApi,
+        msg: &Message,
+        cap: u32,
+        rejectable: bool,
+        categories: &[ReactionType],
+    ) -> bool {
+        let checkmark = ReactionType::Unicode(CHECKMARK_EMOJI.to_string());
+        if let Err(e) = api.react(msg.channel_id, msg.id, checkmark).await {
+            warn!(message_id = %msg.id, emoji = CHECKMARK_EMOJI, error = %e, "Failed to add reaction");
+            return false;
+        }
+        if rejectable {
+            let reject = ReactionType::Unicode(REJECT_EMOJI.to_string());
+            if let Err(e) = api.react(msg.channel_id, msg.id, reject).await {
+                warn!(message_id = %msg.id, emoji = REJECT_EMOJI, error = %e, "Failed to add reaction");
+            }
+        }
+
+        let cancel = self.data.cancel_emoji();
+        if let Err(e) = api.react(msg.channel_id, msg.id, cancel.clone()).await {
+            warn!(message_id = %msg.id, emoji = %cancel, error = %e, "Failed to add reaction");
+        }
+
+        match self.data.get_number_emoji(cap) {
+            Some(number) => {
+                if let Err(e) = api.react(msg.channel_id, msg.id, number.clone()).await {
+                    // Custom emojis from another server aren't usable here; keycaps always are
+                    match emoji::keycap(cap).filter(|keycap| *keycap != number) {
+                        Some(keycap) => {
+                            debug!(emoji = %number, error = %e, "Number emoji unavailable, using keycap");
+                            if let Err(e) = api.react(msg.channel_id, msg.id, keycap).await {
+                                warn!(message_id = %msg.id, cap, error = %e, "Failed to add keycap reaction");
+                            }
+                        }
+                        None => {
+                            warn!(message_id = %msg.id, emoji = %number, error = %e, "Failed to add reaction")
+                        }
+                    }
+                }
+            }
+            None => debug!(cap, "No number emoji for this threshold"),
+        }
+
+        for category in categories {
+            if let Err(e) = api.react(msg.channel_id, msg.id, category.clone()).await {
+                warn!(message_id = %msg.id, emoji = %category, error = %e, "Failed to add category reaction");
+            }
+        }
+        true
+    }
+
+    /// How many votes the voter's approval counts for: the guild's booster
+    /// weight while they are boosting, otherwise one, times the super
+    /// reaction weight for a `super_reaction`. The member is only resolved
+    /// when the guild gives boosters extra weight.
+    async fn vote_weight(
+        &self,
+        ctx: &Context,
+        session_id: MessageId,
+        user_id: UserId,
+        member: Option<&Member>,
+        super_reaction: bool,
+    ) -> u32 {
+        let Some(guild_id) = self
+            .data
+            .voting_sessions
+            .get(&session_id)
+            .and_then(|session| session.guild_id)
+        else {
+            return 1;
+        };
+        let guild_config = self.data.guild_config(guild_id);
+        let factor = if super_reaction {
+            guild_config.super_reaction_factor()
+        } else {
+            1
+        };
+        if guild_config.booster_weight.is_none() {
+            return factor;
+        }
+        let boosting = resolve_member(ctx, guild_id, user_id, member)
+            .await
+            .is_some_and(|member| member.premium_since.is_some());
+        guild_config.vote_weight(boosting) * factor
+    }
+
+    /// Records a vote on the session keyed by `session_id`, finalizing the
+    /// session once the threshold is reached.
+    async fn cast_vote(
+        &self,
+        ctx: &Context,
+        session_id: MessageId,
+        user_id: UserId,
+        member: Option<&Member>,
+        source: VoteSource,
+    ) -> VoteOutcome {
+        let weight = self
+            .vote_weight(ctx, session_id, user_id, member, source.is_super())
+            .await;
+        let outcome = self.data.record_vote(session_id, user_id, source, weight);
+        if let VoteOutcome::Flagged(_) = outcome {
+            self.request_review(ctx, session_id).await;
+        }
+        self.finalize_if_passed(ctx, session_id, outcome).await;
+        outcome
+    }
+
+    /// Records a vote against a quorum session, weighted like an approval,
+    /// finalizing the session if it tipped the quorum.
+    async fn cast_rejection(
+        &self,
+        ctx: &Context,
+        session_id: MessageId,
+        user_id: UserId,
+        member: Option<&Member>,
+    ) -> VoteOutcome {
+        let weight = self
+            .vote_weight(ctx, session_id, user_id, member, false)
+            .await;
+        let outcome = self.data.record_rejection(session_id, user_id, weight);
+        self.finalize_if_passed(ctx, session_id, outcome).await;
+        outcome
+    }
+
+    /// Counts a reply like "+1" or "no" to a vote message or its target as
+    /// a vote from its author, for members who can't easily react. Counted
+    /// votes get a reaction back so the voter knows.
+    async fn handle_text_vote(&self, ctx: &Context, msg: &Message) {
+        let Some(replied_to) = msg.message_reference.as_ref().and_then(|r| r.message_id) else {
+            return;
+        };
+        let Some(approve) = parse_text_vote(&msg.content) else {
+            return;
+        };
+        let Some((session_id, _)) = self.data.session_for_reaction(replied_to) else {
+            return;
+        };
+        if !self
+            .is_eligible_voter(ctx, msg.guild_id, msg.author.id, None, session_id)
+            .await
+        {
+            return;
+        }
+
+        let outcome = if approve {
+            self.cast_vote(ctx, session_id, msg.author.id, None, VoteSource::Reply)
+                .await
+        } else {
+            self.cast_rejection(ctx, session_id, msg.author.id, None)
+                .await
+        };
+        if matches!(
+            outcome,
+            VoteOutcome::Duplicate | VoteOutcome::NoSession | VoteOutcome::Paused
+        ) {
+            return;
+        }
+        let emoji = if approve {
+            CHECKMARK_EMOJI
+        } else {
+            REJECT_EMOJI
+        };
+        if let Err(e) = msg
+            .react(&ctx.http, ReactionType::Unicode(emoji.to_string()))
+            .await
+        {
+            debug!(message_id = %msg.id, error = %e, "Failed to acknowledge a text vote");
+        }
+    }
+
+    /// Withdraws a rejection, finalizing the session if approvals are now
+    /// far enough ahead.
+    async fn withdraw_rejection(
+        &self,
+        ctx: &Context,
+        session_id: MessageId,
+        user_id: UserId,
+    ) -> VoteOutcome {
+        let outcome = self.data.retract_rejection(session_id, user_id);
+        self.finalize_if_passed(ctx, session_id, outcome).await;
+        outcome
+    }
+
+    /// Pins a vote that passed. One that passed too soon after opening waits
+    /// out its minimum time first and pins then if it still passes. The pin
+    /// runs on its own task: it may wait out the channel's cooldown or the
+    /// guild's pin budget, and the reaction lane it was counted on must not
+    /// wait with it.
+    async fn finalize_if_passed(&self, ctx: &Context, session_id: MessageId, outcome: VoteOutcome) {
+        match outcome {
+            VoteOutcome::Passed {
+                channel_id,
+                message_id,
+                ..
+            } => {
+                let data = Arc::clone(&self.data);
+                let http = Arc::clone(&ctx.http);
+                tokio::spawn(
+                    async move {
+                        data.finalize_unless_quiet(&http, session_id, channel_id, message_id)
+                            .await;
+                    }
+                    .in_current_span(),
+                );
+            }
+            VoteOutcome::Deferred(_) => self.defer_pin(ctx, session_id).await,
+            _ => {}
+        }
+    }
+
+    async fn defer_pin(&self, ctx: &Context, session_id: MessageId) {
+        let Some((wait, vote_channel_id)) = self.data.schedule_deferred_pin(session_id) else {
+            return;
+        };
+        info!(session_id = %session_id, wait_secs = wait.as_secs(), "Vote passed early, pin deferred");
+        let pins_at = Timestamp::now().unix_timestamp() + wait.as_secs_f64().ceil() as i64;
+        let notice = CreateMessage::new()
+            .content(format!(
+                "✅ Threshold reached, pinning <t:{}:R> unless votes are withdrawn.",
+                pins_at
+            ))
+            .reference_message((vote_channel_id, session_id));
+        if let Err(e) = vote_channel_id.send_message(&ctx.http, notice).await {
+            warn!(session_id = %session_id, error = %e, "Failed to announce a deferred pin");
+        }
+
+        let data = Arc::clone(&self.data);
+        let http = Arc::clone(&ctx.http);
+        tokio::spawn(async move {
+            let mut wait = wait;
+            loop {
+                sleep(wait).await;
+                match data.deferred_pin_due(session_id) {
+                    Some(VoteOutcome::Passed {
+                        channel_id,
+                        message_id,
+                        ..
+                    }) => {
+                        data.finalize_unless_quiet(&http, session_id, channel_id, message_id)
+                            .await;
+                    }
+                    Some(VoteOutcome::Deferred(_)) => {
+                        wait = data
+                            .voting_sessions
+                            .get(&session_id)
+                            .map_or(Duration::ZERO, |session| session.open_time_left());
+                        continue;
+                    }
+                    // A held vote is explained by its review request
+                    Some(_) if !data.is_paused(session_id) => {
+                        let notice = CreateMessage::new()
+                            .content("Not pinning after all: votes were withdrawn during the wait.")
+                            .reference_message((vote_channel_id, session_id));
+                        if let Err(e) = vote_channel_id.send_message(&http, notice).await {
+                            warn!(session_id = %session_id, error = %e, "Failed to call off a deferred pin");
+                        }
+                    }
+                    _ => {}
+                }
+                break;
+            }
+        });
+    }
+
+    /// Opens a session again for a reaction vote the bot lost track of, as
+    /// after a restart, and counts the checkmarks already on the vote and
+    /// its target. One that already has enough votes passes right away. On
+    /// success, returns the reply for the moderator, otherwise the refusal.
+    async fn adopt_vote(
+        &self,
+        ctx: &Context,
+        guild_id: GuildId,
+        vote: &Message,
+        moderator_id: UserId,
+    ) -> Result<String, String> {
+        if self.data.session_for_reaction(vote.id).is_some() {
+            return Err("That vote is still open.".to_string());
+        }
+        let bot_id = ctx.cache.current_user().id;
+        let orphan = orphaned_vote(vote, guild_id, bot_id)?;
+        let target = orphan
+            .channel_id
+            .message(&ctx.http, orphan.message_id)
+            .await
+            .map_err(|e| {
+                warn!(message_id = %orphan.message_id, error = %e, "Failed to fetch an orphaned vote's target");
+                "I couldn't find the message that vote was about.".to_string()
+            })?;
+        if target.pinned {
+            return Err("The message that vote was about is already pinned.".to_string());
+        }
+        self.data.session_capacity(vote.channel_id)?;
+
+        let guild_config = self.data.guild_config(guild_id);
+        let threshold = self.threshold_in(ctx, Some(guild_id), target.channel_id);
+        let mut session = VotingSession::new(
+            target.id,
+            target.channel_id,
+            vote.channel_id,
+            Some(guild_id),
+            orphan.initiator_id.unwrap_or(moderator_id),
+            threshold,
+        );
+        session.target_snapshot = Some(TargetSnapshot::capture(&target));
+        session.default_archive = guild_config.archive_channel;
+        session.quorum = guild_config.quorum;
+        session.target_reacted = target.reactions.iter().any(|reaction| {
+            reaction.me && emoji::checkmark(&reaction.reaction_type) == Some(Checkmark::WhiteHeavy)
+        });
+        // A vote the bot posted itself shows a countdown to keep up to date
+        if vote.author.id == bot_id {
+            let timeout = Duration::from_secs(self.data.config().vote_timeout_secs);
+            session.notice = Some(VoteNotice {
+                message_id: vote.id,
+                text: strip_deadline(&vote.content).to_string(),
+                shown_expiry: session.expires_at(timeout),
+            });
+        }
+        let rejectable = session.quorum.is_some();
+        self.data.insert_session(vote.id, session)?;
+        // Listen first, so reactions landing while the old ones are read
+        // aren't missed; reading one twice only counts it once
+        self.collect_reactions(ctx, vote.id, target.id);
+
+        for (message, on_target) in [(vote, false), (&target, true)] {
+            for reaction in &message.reactions {
+                let checkmark = emoji::checkmark(&reaction.reaction_type);
+                let rejects = rejectable && !on_target && is_reject_emoji(&reaction.reaction_type);
+                if checkmark.is_none() && !rejects {
+                    continue;
+                }
+                let voters = match reaction_voters(
+                    ctx,
+                    message.channel_id,
+                    message.id,
+                    &reaction.reaction_type,
+                )
+                .await
+                {
+                    Ok(voters) => voters,
+                    Err(e) => {
+                        warn!(message_id = %message.id, error = %e, "Failed to read an orphaned vote's reactions");
+                        continue;
+                    }
+                };
+                for user_id in voters {
+                    if !self
+                        .is_eligible_voter(ctx, Some(guild_id), user_id, None, vote.id)
+                        .await
+                    {
+                        continue;
+                    }
+                    match checkmark {
+                        Some(checkmark) => {
+                            let source = VoteSource::reaction(checkmark, on_target, false);
+                            self.cast_vote(ctx, vote.id, user_id, None, source).await
+                        }
+                        None => self.cast_rejection(ctx, vote.id, user_id, None).await,
+                    };
+                }
+            }
+        }
+
+        let link = target.id.link(target.channel_id, Some(guild_id));
+        let progress = self
+            .data
+            .voting_sessions
+            .get(&vote.id)
+            .map(|session| (session.progress(), session.is_pinning()));
+        info!(
+            session_id = %vote.id,
+            target_id = %target.id,
+            moderator_id = %moderator_id,
+            progress = progress.as_ref().map_or("ended", |(progress, _)| progress),
+            "Adopted an orphaned vote"
+        );
+        Ok(match progress {
+            Some((progress, false)) => format!(
+                "Picked the vote on {} back up at {}; new votes count from now on.",
+                link, progress
+            ),
+            _ => format!(
+                "Picked the vote on {} back up; it already had enough votes.",
+                link
+            ),
+        })
+    }
+
+    /// Cancels the session on behalf of its initiator or a moderator. Anyone
+    /// else, or a session that already finished, is a no-op.
+    async fn cancel_session(
+        &self,
+        ctx: &Context,
+        session_id: MessageId,
+        channel_id: ChannelId,
+        user_id: UserId,
+        is_moderator: bool,
+    ) {
+        let may_cancel = match self.data.voting_sessions.get(&session_id) {
+            // Too late once the pin is underway
+            Some(session) if session.is_pinning() => return,
+            Some(session) => session.initiator_id == user_id || is_moderator,
+            None => return,
+        };
+        if !may_cancel {
+            debug!(session_id = %session_id, user_id = %user_id, "Ignoring cancel");
+            return;
+        }
+
+        let notice = format!("Vote cancelled by <@{}>.", user_id);
+        if self
+            .data
+            .end_cancelled_session(&ctx.http, session_id, channel_id, notice)
+            .await
+        {
+            info!(session_id = %session_id, user_id = %user_id, "Vote cancelled");
+        }
+    }
+
+    async fn post_vote_prompt(
+        &self,
+        ctx: &Context,
+        msg: &Message,
+        content: String,
+        rejectable: bool,
+    ) -> Option<MessageId> {
+        let prompt = CreateMessage::new()
+            .content(content)
+            .components(vote_buttons(msg.id, false, rejectable))
+            .reference_message(msg);
+
+        match msg.channel_id.send_message(&ctx.http, prompt).await {
+            Ok(prompt) => Some(prompt.id),
+            Err(e) => {
+                warn!(message_id = %msg.id, error = %e, "Failed to post vote prompt");
+                None
+            }
+        }
+    }
+
+    async fn handle_vote_button(&self, ctx: &Context, component: &ComponentInteraction) {
+        let Some((action, session_id)) = parse_vote_button(&component.data.custom_id) else {
+            return;
+        };
+        let user_id = component.user.id;
+        let (cap, quorum, needed) =
+            self.data
+                .voting_sessions
+                .get(&session_id)
+                .map_or((0, false, 0), |session| {
+                    (
+                        session.threshold,
+                        session.quorum.is_some(),
+                        session.quorum.map_or(session.threshold, |rule| rule.quorum),
+                    )
+                });
+        let guild_config = component
+            .guild_id
+            .map(|guild_id| self.data.guild_config(guild_id))
+            .unwrap_or_default();
+        // Quorum votes show both sides; a session that is gone just passed
+        let progress = |votes: u32| {
+            if !quorum {
+                return format!("{}/{}", votes, cap);
+            }
+            self.data
+                .voting_sessions
+                .get(&session_id)
+                .map_or_else(|| "the vote passed".to_string(), |session| session.tally())
+        };
+
+        let reply = match action {
+            VoteAction::Approve => {
+                if !self
+                    .is_eligible_voter(
+                        ctx,
+                        component.guild_id,
+                        user_id,
+                        component.member.as_ref(),
+                        session_id,
+                    )
+                    .await
+                {
+                    "You're not eligible to vote on this.".to_string()
+                } else {
+                    match self
+                        .cast_vote(
+                            ctx,
+                            session_id,
+                            user_id,
+                            component.member.as_ref(),
+                            VoteSource::Button,
+                        )
+                        .await
+                    {
+                        VoteOutcome::Counted(votes) | VoteOutcome::Passed { votes, .. } => {
+                            templates::render(
+                                guild_config.template(TemplateKey::VoteProgress),
+                                &[
+                                    ("count", &votes.to_string()),
+                                    ("needed", &needed.to_string()),
+                                    ("progress", &progress(votes)),
+                                ],
+                            )
+                        }
+                        VoteOutcome::Deferred(votes) => format!(
+                            "Vote recorded, {}. It passes, and pins once it has been open long \
+                             enough.",
+                            progress(votes)
+                        ),
+                        VoteOutcome::Flagged(_) => {
+                            "Vote recorded. The vote is now on hold for moderator review."
+                                .to_string()
+                        }
+                        VoteOutcome::Paused => ON_HOLD_NOTICE.to_string(),
+                        VoteOutcome::Duplicate => "You already voted.".to_string(),
+                        VoteOutcome::NoSession => "This vote has ended.".to_string(),
+                    }
+                }
+            }
+            VoteAction::Reject => {
+                if !self
+                    .is_eligible_voter(
+                        ctx,
+                        component.guild_id,
+                        user_id,
+                        component.member.as_ref(),
+                        session_id,
+                    )
+                    .await
+                {
+                    "You're not eligible to vote on this.".to_string()
+                } else {
+                    match self
+                        .cast_rejection(ctx, session_id, user_id, component.member.as_ref())
+                        .await
+                    {
+                        VoteOutcome::Counted(votes)
+                        | VoteOutcome::Flagged(votes)
+                        | VoteOutcome::Deferred(votes)
+                        | VoteOutcome::Passed { votes, .. } => {
+                            format!("Rejection recorded, {}", progress(votes))
+                        }
+                        VoteOutcome::Paused => ON_HOLD_NOTICE.to_string(),
+                        VoteOutcome::Duplicate => "You already voted.".to_string(),
+                        VoteOutcome::NoSession => "This vote has ended.".to_string(),
+                    }
+                }
+            }
+            VoteAction::Cancel => {
+                let mut outcome = self
+                    .data
+                    .retract_vote(session_id, user_id, VoteSource::Button);
+                // Cancel takes back whichever side the voter was on
+                if quorum && outcome == VoteOutcome::Duplicate {
+                    outcome = self.withdraw_rejection(ctx, session_id, user_id).await;
+                }
+                match outcome {
+                    VoteOutcome::Counted(votes)
+                    | VoteOutcome::Flagged(votes)
+                    | VoteOutcome::Deferred(votes)
+                    | VoteOutcome::Passed { votes, .. } => {
+                        format!("Vote withdrawn, {}", progress(votes))
+                    }
+                    VoteOutcome::Paused => ON_HOLD_NOTICE.to_string(),
+                    VoteOutcome::Duplicate => "You haven't voted.".to_string(),
+                    VoteOutcome::NoSession => "This vote has ended.".to_string(),
+                }
+            }
+        };
+
+        let response = CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .content(reply)
+                .ephemeral(true),
+        );
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            warn!(error = %e, "Failed to acknowledge vote button");
+        }
+    }
+
+    /// Tells the vote's channel it is on hold and asks moderators to decide,
+    /// in the audit channel when there is one and on the vote otherwise.
+    async fn request_review(&self, ctx: &Context, session_id: MessageId) {
+        let Some((guild_id, vote_channel_id, target_link, progress, guard)) = self
+            .data
+            .voting_sessions
+            .get(&session_id)
+            .and_then(|session| {
+                let guild_id = session.guild_id?;
+                Some((
+                    guild_id,
+                    session.vote_channel_id,
+                    session
+                        .target_message_id
+                        .link(session.target_channel_id, Some(guild_id)),
+                    session.progress(),
+                    session.brigade_guard?,
+                ))
+            })
+        else {
+            return;
+        };
+        let guild_config = self.data.guild_config(guild_id);
+
+        let mut content = String::new();
+        if let Some(role_id) = guild_config.review_role {
+            content.push_str(&format!("<@&{}> ", role_id));
+        }
+        content.push_str(&format!(
+            "⏸️ The vote on {} in <#{}> reached {} with {}, so it's on hold. New votes aren't \
+             counted until a moderator with Manage Messages there decides.",
+            target_link,
+            vote_channel_id,
+            progress,
+            guard.describe()
+        ));
+        let mentions = CreateAllowedMentions::new().roles(guild_config.review_role);
+        let review = CreateMessage::new()
+            .content(content)
+            .allowed_mentions(mentions)
+            .components(review_buttons(session_id, false));
+
+        let posted = match guild_config.audit_channel {
+            Some(audit_channel) => {
+                let notice = CreateMessage::new()
+                    .content(ON_HOLD_NOTICE)
+                    .reference_message((vote_channel_id, session_id));
+                if let Err(e) = vote_channel_id.send_message(&ctx.http, notice).await {
+                    warn!(session_id = %session_id, error = %e, "Failed to announce a held vote");
+                }
+                audit_channel.send_message(&ctx.http, review).await
+            }
+            None => {
+                vote_channel_id
+                    .send_message(
+                        &ctx.http,
+                        review.reference_message((vote_channel_id, session_id)),
+                    )
+                    .await
+            }
+        };
+        if let Err(e) = posted {
+            warn!(session_id = %session_id, error = %e, "Failed to ask for a review of a held vote");
+        }
+    }
+
+    /// Turns a paged reply for whoever ran the command; anyone else gets a
+    /// private note instead.
+    async fn handle_page_button(&self, ctx: &Context, component: &ComponentInteraction) {
+        let Some(turn) = PageTurn::parse(&component.data.custom_id) else {
+            return;
+        };
+        let page = match self.data.paginations.get_mut(&component.message.id) {
+            Some(mut paginator) if !paginator.is_expired() => {
+                if paginator.may_turn(component.user.id) {
+                    paginator.turn(turn);
+                    Ok((paginator.embed(), paginator.buttons(false)))
+                } else {
+                    Err("Only whoever ran the command can turn its pages.")
+                }
+            }
+            _ => Err("These pages have expired, run the command again."),
+        };
+        let response = match page {
+            Ok((embed, buttons)) => CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .embed(embed)
+                    .components(buttons),
+            ),
+            Err(notice) => CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(notice)
+                    .ephemeral(true),
+            ),
+        };
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            warn!(error = %e, "Failed to turn a page");
+        }
+    }
+
+    /// Carries out a moderator's decision on a held vote: approving lets the
+    /// vote go on, finalizing it if it already passed; denying cancels it.
+    async fn handle_review_button(&self, ctx: &Context, component: &ComponentInteraction) {
+        let Some((approved, session_id)) = parse_review_button(&component.data.custom_id) else {
+            return;
+        };
+        let moderator = component.user.id;
+        let respond = |content: String| async move {
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            );
+            if let Err(e) = component.create_response(&ctx.http, response).await {
+                warn!(session_id = %session_id, error = %e, "Failed to acknowledge review button");
+            }
+        };
+
+        let Some((guild_id, vote_channel_id, target_link, paused)) = self
+            .data
+            .voting_sessions
+            .get(&session_id)
+            .and_then(|session| {
+                let guild_id = session.guild_id?;
+                Some((
+                    guild_id,
+                    session.vote_channel_id,
+                    session
+                        .target_message_id
+                        .link(session.target_channel_id, Some(guild_id)),
+                    session.paused,
+                ))
+            })
+        else {
+            return respond("This vote has ended.".to_string()).await;
+        };
+        if !paused {
+            return respond("That vote was already reviewed.".to_string()).await;
+        }
+
+        if !may_manage_votes(ctx, component, guild_id, vote_channel_id) {
+            return respond(format!(
+                "You need Manage Messages in <#{}> to do that.",
+                vote_channel_id
+            ))
+            .await;
+        }
+
+        let line = if approved {
+            let Some(outcome) = self.data.resume_session(session_id) else {
+                return respond("That vote was already reviewed.".to_string()).await;
+            };
+            info!(session_id = %session_id, user_id = %moderator, "Held vote let through");
+            self.finalize_if_passed(ctx, session_id, outcome).await;
+            format!(
+                "▶️ <@{}> let the held vote on {} continue.",
+                moderator, target_link
+            )
+        } else {
+            let notice = format!(
+                "Vote called off by a moderator, <@{}>, after review.",
+                moderator
+            );
+            if !self
+                .data
+                .end_cancelled_session(&ctx.http, session_id, vote_channel_id, notice)
+                .await
+            {
+                return respond("This vote has ended.".to_string()).await;
+            }
+            info!(session_id = %session_id, user_id = %moderator, "Held vote called off");
+            format!(
+                "🛑 <@{}> called off the held vote on {}.",
+                moderator, target_link
+            )
+        };
+
+        // The review request keeps the decision and loses its buttons
+        let response = CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(format!("{}\n{}", component.message.content, line))
+                .components(review_buttons(session_id, true)),
+        );
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            warn!(session_id = %session_id, error = %e, "Failed to record a review decision");
+        }
+        // A review posted in the audit channel already shows the decision
+        if self.data.guild_config(guild_id).audit_channel != Some(component.channel_id) {
+            self.data.audit(&ctx.http, guild_id, line).await;
+        }
+    }
+
+    /// Carries out a moderator's decision on a passed vote waiting for their
+    /// sign-off: approving pins it, rejecting calls it off.
+    async fn handle_approval_button(&self, ctx: &Context, component: &ComponentInteraction) {
+        let Some((approved, session_id)) = parse_approval_button(&component.data.custom_id) else {
+            return;
+        };
+        let moderator = component.user.id;
+        let respond = |content: String| async move {
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            );
+            if let Err(e) = component.create_response(&ctx.http, response).await {
+                warn!(session_id = %session_id, error = %e, "Failed to acknowledge approval button");
+            }
+        };
+
+        let Some((guild_id, vote_channel_id, target_link)) = self
+            .data
+            .voting_sessions
+            .get(&session_id)
+            .filter(|session| session.awaiting_approval)
+            .and_then(|session| {
+                let guild_id = session.guild_id?;
+                Some((
+                    guild_id,
+                    session.vote_channel_id,
+                    session
+                        .target_message_id
+                        .link(session.target_channel_id, Some(guild_id)),
+                ))
+            })
+        else {
+            return respond("This vote isn't waiting for approval anymore.".to_string()).await;
+        };
+        if !may_manage_votes(ctx, component, guild_id, vote_channel_id) {
+            return respond(format!(
+                "You need Manage Messages in <#{}> to do that.",
+                vote_channel_id
+            ))
+            .await;
+        }
+
+        let line = if approved {
+            let Some(outcome) = self.data.grant_approval(session_id) else {
+                return respond("This vote isn't waiting for approval anymore.".to_string()).await;
+            };
+            info!(session_id = %session_id, user_id = %moderator, "Pin approved");
+            let passed = matches!(outcome, VoteOutcome::Passed { .. });
+            self.finalize_if_passed(ctx, session_id, outcome).await;
+            if passed {
+                format!("✅ <@{}> approved the pin of {}.", moderator, target_link)
+            } else {
+                format!(
+                    "✅ <@{}> approved the pin of {}, but votes were withdrawn meanwhile; it \
+                     pins once the vote passes again.",
+                    moderator, target_link
+                )
+            }
+        } else {
+            let notice = format!("Pin rejected by a moderator, <@{}>.", moderator);
+            if !self
+                .data
+                .end_cancelled_session(&ctx.http, session_id, vote_channel_id, notice)
+                .await
+            {
+                return respond("This vote has ended.".to_string()).await;
+            }
+            info!(session_id = %session_id, user_id = %moderator, "Pin rejected");
+            format!("🛑 <@{}> rejected the pin of {}.", moderator, target_link)
+        };
+
+        let response = CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .content(format!("{}\n{}", component.message.content, line))
+                .components(approval_buttons(session_id, true)),
+        );
+        if let Err(e) = component.create_response(&ctx.http, response).await {
+            warn!(session_id = %session_id, error = %e, "Failed to record an approval decision");
+        }
+        self.data.audit(&ctx.http, guild_id, line).await;
+    }
+
+    /// Sets up a guild the bot just joined: registers its commands when
+    /// they are registered per guild, and posts how to get started in the
+    /// system channel, or the first channel the bot may speak in.
+    async fn onboard_guild(&self, ctx: &Context, guild: &Guild) {
+        let config = self.data.config();
+        let commands_registered = config.register_guild_commands
+            && commands::register(&ctx.http, &[guild.id], true).await;
+
+        let setup_channel = onboarding_channel(guild, ctx.cache.current_user().id);
+        let mut setup_posted = false;
+        if let Some(channel_id) = setup_channel {
+            let message = CreateMessage::new()
+                .content(onboarding_message(
+                    ctx.cache.current_user().id,
+                    config.threshold(Some(guild.id)),
+                    self.data.interaction_only(),
+                ))
+                .allowed_mentions(CreateAllowedMentions::new());
+            match channel_id.send_message(&ctx.http, message).await {
+                Ok(_) => setup_posted = true,
+                Err(e) => {
+                    warn!(channel_id = %channel_id, error = %e, "Failed to post the setup message");
+                }
+            }
+        }
+        info!(
+            guild_name = %guild.name,
+            members = guild.member_count,
+            commands_registered,
+            setup_channel = ?setup_channel,
+            setup_posted,
+            "Onboarded a new guild"
+        );
+    }
+
+    /// Spawns the cleanup loop unless it is already running. Returns whether
+    /// a task was started.
+    fn start_cleanup_task(&self, http: Arc<Http>) -> bool {
+        if self.cleanup_started.swap(true, Ordering::AcqRel) {
+            debug!("Cleanup task already running, not starting another");
+            return false;
+        }
+
+        let data = Arc::clone(&self.data);
+        let mut shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(data.config().cleanup_interval_secs));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait_for(|stop| *stop) => {
+                        info!("Cleanup task stopped");
+                        break;
+                    }
+                }
+                data.clean_up(&http).await;
+            }
+        });
+        true
+    }
+
+    /// Counts a reaction on a vote or its target: checkmarks vote, the
+    /// slash cancels, crosses reject and category emojis steer the archive.
+    async fn handle_reaction_add(&self, ctx: &Context, reaction: &Reaction) {
+        let Some((session_id, on_target)) = self.data.session_for_reaction(reaction.message_id)
+        else {
+            return;
+        };
+
+        // Ignore bot reactions
+        if self.is_bot_reaction(ctx, reaction).await {
+            return;
+        }
+
+        let user_id = match reaction.user_id {
+            Some(id) => id,
+            None => return,
+        };
+
+        // On the target message only checkmarks mean anything
+        if on_target {
+            let Some(checkmark) = emoji::checkmark(&reaction.emoji) else {
+                return;
+            };
+            if self
+                .is_eligible_voter(
+                    ctx,
+                    reaction.guild_id,
+                    user_id,
+                    reaction.member.as_ref(),
+                    session_id,
+                )
+                .await
+            {
+                self.cast_vote(
+                    ctx,
+                    session_id,
+                    user_id,
+                    reaction.member.as_ref(),
+                    VoteSource::reaction(checkmark, true, reaction.burst),
+                )
+                .await;
+            } else {
+                self.drop_timed_out_reaction(ctx, reaction).await;
+            }
+            return;
+        }
+
+        // The slash reaction cancels the vote for its initiator or a moderator
+        if self.data.is_cancel_emoji(&reaction.emoji) {
+            let is_moderator = match reaction.guild_id {
+                Some(guild_id) => {
+                    match resolve_member(ctx, guild_id, user_id, reaction.member.as_ref()).await {
+                        Some(member) => channel_permissions(
+                            ctx,
+                            guild_id,
+                            reaction.channel_id,
+                            |guild, channel| guild.user_permissions_in(channel, &member),
+                        )
+                        .is_some_and(|permissions| permissions.manage_messages()),
+                        None => false,
+                    }
+                }
+                None => false,
+            };
+            self.cancel_session(
+                ctx,
+                reaction.message_id,
+                reaction.channel_id,
+                user_id,
+                is_moderator,
+            )
+            .await;
+            return;
+        }
+
+        // Category reactions only steer which archive the pin is mirrored to
+        if self
+            .data
+            .voting_sessions
+            .get(&reaction.message_id)
+            .is_some_and(|session| session.has_category(&reaction.emoji))
+        {
+            if self
+                .is_eligible_voter(
+                    ctx,
+                    reaction.guild_id,
+                    user_id,
+                    reaction.member.as_ref(),
+                    reaction.message_id,
+                )
+                .await
+            {
+                if let Some(mut session) = self.data.voting_sessions.get_mut(&reaction.message_id) {
+                    session.tally_category(&reaction.emoji, user_id, true);
+                }
+            }
+            return;
+        }
+
+        // A cross on a quorum vote counts against it
+        if is_reject_emoji(&reaction.emoji) {
+            if self
+                .is_eligible_voter(
+                    ctx,
+                    reaction.guild_id,
+                    user_id,
+                    reaction.member.as_ref(),
+                    reaction.message_id,
+                )
+                .await
+            {
+                self.cast_rejection(ctx, reaction.message_id, user_id, reaction.member.as_ref())
+                    .await;
+            } else {
+                self.drop_timed_out_reaction(ctx, reaction).await;
+            }
+            return;
+        }
+
+        // Only handle checkmark reactions
+        let Some(checkmark) = emoji::checkmark(&reaction.emoji) else {
+            return;
+        };
+
+        if !self
+            .is_eligible_voter(
+                ctx,
+                reaction.guild_id,
+                user_id,
+                reaction.member.as_ref(),
+                reaction.message_id,
+            )
+            .await
+        {
+            self.drop_timed_out_reaction(ctx, reaction).await;
+            return;
+        }
+
+        self.cast_vote(
+            ctx,
+            reaction.message_id,
+            user_id,
+            reaction.member.as_ref(),
+            VoteSource::reaction(checkmark, false, reaction.burst),
+        )
+        .await;
+    }
+
+    /// Takes back what a removed reaction counted.
+    async fn handle_reaction_remove(&self, ctx: &Context, reaction: &Reaction) {
+        let Some((session_id, on_target)) = self.data.session_for_reaction(reaction.message_id)
+        else {
+            return;
+        };
+
+        // Ignore bot reactions
+        if self.is_bot_reaction(ctx, reaction).await {
+            return;
+        }
+
+        let user_id = match reaction.user_id {
+            Some(id) => id,
+            None => return,
+        };
+
+        if !on_target {
+            if let Some(mut session) = self.data.voting_sessions.get_mut(&session_id) {
+                if session.tally_category(&reaction.emoji, user_id, false) {
+                    return;
+                }
+            }
+            if is_reject_emoji(&reaction.emoji) {
+                self.withdraw_rejection(ctx, session_id, user_id).await;
+                return;
+            }
+        }
+
+        // Only handle checkmark reactions
+        let Some(checkmark) = emoji::checkmark(&reaction.emoji) else {
+            return;
+        };
+
+        // The voter's stored weight comes off, whichever reaction set it
+        let source = VoteSource::reaction(checkmark, on_target, reaction.burst);
+        self.data.retract_vote(session_id, user_id, source);
+    }
+
+    /// Spawns the reaction workers once.
+    fn start_reaction_workers(&self) {
+        self.reactions.get_or_init(|| {
+            let worker = Arc::new(self.worker_view());
+            ReactionQueue::start(
+                REACTION_WORKERS,
+                REACTION_QUEUE_CAPACITY,
+                move |job: ReactionJob| {
+                    let worker = Arc::clone(&worker);
+                    async move { worker.process_reaction(job).await }
+                },
+            )
+        });
+    }
+
+    /// Reactions waiting for a worker, and the most one worker ever had
+    /// waiting.
+    fn reaction_queue_depth(&self) -> (usize, usize) {
+        self.reactions
+            .get()
+            .map_or((0, 0), |queue| (queue.depth(), queue.peak()))
+    }
+
+    /// Follows the reactions on a new vote and its target until the vote
+    /// ends, expiring it once its time is up. A vote held past its deadline,
+    /// like one waiting out quiet hours, keeps its collector until it is
+    /// pinned or cancelled.
+    fn collect_reactions(&self, ctx: &Context, session_id: MessageId, target_id: MessageId) {
+        let watched = [session_id, target_id];
+        let mut reactions = Box::pin(collector::collect(&ctx.shard, move |event| match event {
+            Event::ReactionAdd(ReactionAddEvent { reaction, .. })
+                if watched.contains(&reaction.message_id) =>
+            {
+                Some((reaction.clone(), true))
+            }
+            Event::ReactionRemove(ReactionRemoveEvent { reaction, .. })
+                if watched.contains(&reaction.message_id) =>
+            {
+                Some((reaction.clone(), false))
+            }
+            _ => None,
+        }));
+        let handler = self.worker_view();
+        let ctx = ctx.clone();
+        let collecting = async move {
+            let mut open = true;
+            while let Some(deadline) = handler.data.session_deadline(session_id) {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                // Past its deadline, a claimed vote neither expires nor needs
+                // a tally until the claim is handed back or the vote ends
+                let held = wait
+                    .is_zero()
+                    .then(|| handler.data.session_hold(session_id))
+                    .flatten();
+                let released = async {
+                    match &held {
+                        Some(released) => released.notified().await,
+                        None => std::future::pending().await,
+                    }
+                };
+                tokio::select! {
+                    reaction = reactions.next(), if open => match reaction {
+                        Some((reaction, added)) => {
+                            handler
+                                .dispatch_reaction(ctx.clone(), session_id, reaction, added)
+                                .await;
+                        }
+                        None => {
+                            warn!("Reaction collector closed, the vote only runs out now");
+                            open = false;
+                        }
+                    },
+                    _ = released => {}
+                    _ = sleep(wait), if held.is_none() => {
+                        handler.tally_poll(&ctx, session_id).await;
+                        if handler.data.expire_session(session_id) {
+                            handler.data.settle_vote_deadlines(&ctx.http).await;
+                            handler.data.announce_failed_votes(&ctx.http).await;
+                            handler.data.clear_target_reactions(ctx.http.as_ref()).await;
+                            handler.data.archive_vote_threads(&ctx.http).await;
+                            break;
+                        }
+                    }
+                }
+            }
+            debug!("Reaction collector finished");
+        };
+        tokio::spawn(collecting.instrument(tracing::info_span!(
+            "collector",
+            session_id = %session_id
+        )));
+    }
+
+    /// Hands a reaction on `session_id` to its worker, or handles it here
+    /// when the workers aren't running. A full queue makes this wait rather
+    /// than drop a reaction, since any of them may be the one that passes
+    /// the vote.
+    async fn dispatch_reaction(
+        &self,
+        ctx: Context,
+        session_id: MessageId,
+        reaction: Reaction,
+        added: bool,
+    ) {
+        if !self.data.is_guild_allowed(reaction.guild_id) {
+            return;
+        }
+        // A target shared by several votes counts toward the newest, whose
+        // collector handles it
+        if self
+            .data
+            .session_for_guild_reaction(&reaction)
+            .is_none_or(|(id, _)| id != session_id)
+        {
+            return;
+        }
+
+        let span = tracing::info_span!(
+            "reaction",
+            guild_id = reaction.guild_id.map(|id| id.get()),
+            channel_id = %reaction.channel_id,
+            message_id = %reaction.message_id,
+            user_id = reaction.user_id.map(|id| id.get()),
+            added,
+        );
+        let job = ReactionJob {
+            ctx,
+            reaction,
+            added,
+            span,
+        };
+        let job = match self.reactions.get() {
+            Some(queue) => match queue.push(session_id.get(), job).await {
+                Ok(()) => return,
+                Err(job) => {
+                    warn!("Reaction worker stopped, handling the reaction inline");
+                    job
+                }
+            },
+            None => job,
+        };
+        self.process_reaction(job).await;
+    }
+
+    async fn process_reaction(&self, job: ReactionJob) {
+        let ReactionJob {
+            ctx,
+            reaction,
+            added,
+            span,
+        } = job;
+        if added {
+            self.handle_reaction_add(&ctx, &reaction)
+                .instrument(span)
+                .await;
+        } else {
+            self.handle_reaction_remove(&ctx, &reaction)
+                .instrument(span)
+                .await;
+        }
+    }
+
+    /// Spawns the pin worker. Only the first call finds the queue's
+    /// receiver, so reconnects never start a second worker.
+    fn start_pin_worker(&self, http: Arc<Http>) {
+        let Some(requests) = self
+            .data
+            .pin_requests
+            .lock()
+            .expect("pin queue mutex poisoned")
+            .take()
+        else {
+            return;
+        };
+        let data = Arc::clone(&self.data);
+        let shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            data.run_pin_worker(http.as_ref(), requests, shutdown).await;
+        });
+    }
+
+    /// Spawns the weekly digest loop once. It wakes at the next scheduled
+    /// run, checking at least every `DIGEST_POLL_SECS`, and catches up on a
+    /// run missed while the bot was down.
+    fn start_digest_task(&self, http: Arc<Http>) {
+        if self.digest_started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let data = Arc::clone(&self.data);
+        let mut shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            loop {
+                let now = Timestamp::now().unix_timestamp();
+                for (guild_id, schedule, run) in data.due_digests(now) {
+                    data.post_digest(&http, guild_id, schedule, run).await;
+                }
+                let wait = Duration::from_secs(data.digest_wait(Timestamp::now().unix_timestamp()));
+                tokio::select! {
+                    _ = sleep(wait) => {}
+                    _ = shutdown.wait_for(|stop| *stop) => break,
+                }
+            }
+        });
+    }
+
+    /// Spawns the pin retention loop once. Every `RETENTION_POLL_SECS` it
+    /// prunes the channels not pruned in the last day, one after another
+    /// and `PRUNE_SPACING` apart.
+    fn start_retention_task(&self, http: Arc<Http>) {
+        if self.retention_started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let data = Arc::clone(&self.data);
+        let mut shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(RETENTION_POLL_SECS));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait_for(|stop| *stop) => break,
+                }
+                for (guild_id, channel_id, retention) in
+                    data.due_prunings(Timestamp::now().unix_timestamp())
+                {
+                    if *shutdown.borrow() {
+                        break;
+                    }
+                    data.prune_channel(
+                        &http,
+                        guild_id,
+                        channel_id,
+                        retention,
+                        Timestamp::now().unix_timestamp(),
+                    )
+                    .await;
+                    sleep(PRUNE_SPACING).await;
+                }
+            }
+        });
+    }
+
+    /// Spawns the shard monitor once. Every `SHARD_POLL_SECS` it logs each
+    /// shard's stage and latency, warns when one drops, and recovers any
+    /// that went `shard_stale_secs` without a heartbeat.
+    fn start_shard_monitor(&self) {
+        let Some(manager) = self.data.shard_manager.get().cloned() else {
+            return;
+        };
+        if self.shard_monitor_started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+
+        let data = Arc::clone(&self.data);
+        let mut shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(SHARD_POLL_SECS));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait_for(|stop| *stop) => break,
+                }
+                let shards: Vec<_> = manager
+                    .runners
+                    .lock()
+                    .await
+                    .iter()
+                    .map(|(shard_id, info)| (*shard_id, info.stage, info.latency))
+                    .collect();
+
+                let now = Instant::now();
+                let config = data.config();
+                let stale = {
+                    let mut monitor = data.shard_monitor.lock().unwrap();
+                    let running: Vec<ShardId> = shards.iter().map(|(id, ..)| *id).collect();
+                    monitor.retain(&running);
+                    for (shard_id, stage, latency) in shards {
+                        let latency_ms = latency.map(|latency| latency.as_millis() as u64);
+                        debug!(shard_id = shard_id.0, %stage, latency_ms, "Shard status");
+                        match monitor.observe(shard_id, stage, latency, now) {
+                            ShardChange::Steady => {}
+                            ShardChange::Lost => {
+                                warn!(shard_id = shard_id.0, %stage, "Shard disconnected")
+                            }
+                            ShardChange::Recovered(outage) => info!(
+                                shard_id = shard_id.0,
+                                outage_secs = outage.as_secs(),
+                                "Shard reconnected"
+                            ),
+                        }
+                    }
+                    match config.shard_stale_secs {
+                        0 => Vec::new(),
+                        secs => monitor.stale(now, Duration::from_secs(secs)),
+                    }
+                };
+
+                for (shard_id, quiet) in stale {
+                    match config.shard_stale_action {
+                        StaleShardAction::Restart => {
+                            warn!(
+                                shard_id = shard_id.0,
+                                quiet_secs = quiet.as_secs(),
+                                "Restarting a shard without heartbeats"
+                            );
+                            manager.restart(shard_id).await;
+                            data.shard_monitor
+                                .lock()
+                                .unwrap()
+                                .restarted(shard_id, Instant::now());
+                        }
+                        // Stopping the client lets main flush the logs and
+                        // error reports before it exits with the code
+                        StaleShardAction::Exit => {
+                            error!(
+                                shard_id = shard_id.0,
+                                quiet_secs = quiet.as_secs(),
+                                "Exiting for a restart, a shard went without heartbeats"
+                            );
+                            data.stale_shard_exit.store(true, Ordering::Release);
+                            manager.shutdown_all().await;
+                            return;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Sets how the config is rebuilt for `/config-reload` and the config
+    /// watch: `load` re-reads the file and environment and reapplies the
+    /// command line flags.
+    pub fn set_config_loader(&self, load: impl Fn() -> Result<Config> + Send + Sync + 'static) {
+        if self.data.config_loader.set(Box::new(load)).is_err() {
+            warn!("Config loader already set, keeping the first");
+        }
+    }
+
+    /// Polls the config file and reloads the config when it changes. A file
+    /// that fails to load leaves the current config alone.
+    pub fn start_config_watch(&self, path: PathBuf) {
+        let data = Arc::clone(&self.data);
+        let mut shutdown = self.shutdown.clone();
+        let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(CONFIG_POLL_SECS));
+            let mut last_modified = modified(&path);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = shutdown.wait_for(|stop| *stop) => break,
+                }
+                let current = modified(&path);
+                if current == last_modified {
+                    continue;
+                }
+                last_modified = current;
+
+                match data.reload_config() {
+                    Ok(reload) => {
+                        if !reload.pending.is_empty() {
+                            warn!(
+                                "Config changes to {} take effect after a restart",
+                                reload.pending.join(", ")
+                            );
+                        }
+                        info!(
+                            changes = %reload.applied.join("; "),
+                            "Reloaded config from {}",
+                            path.display()
+                        );
+                    }
+                    Err(e) => error!("Keeping the current config: {:#}", e),
+                }
+            }
+        });
+    }
+
+    /// Refreshes the presence on a fixed interval, skipping the gateway
+    /// update when the text hasn't changed to stay clear of presence limits.
+    fn start_presence_task(&self, ctx: Context) {
+        if !self.data.config().presence_enabled
+            || self.presence_started.swap(true, Ordering::AcqRel)
+        {
+            return;
+        }
+
+        let data = Arc::clone(&self.data);
+        let mut shutdown = self.shutdown.clone();
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(PRESENCE_INTERVAL_SECS));
+            let mut last_text = String::new();
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let text = data.presence_text();
+                        if text != last_text {
+                            ctx.set_activity(Some(ActivityData::watching(text.clone())));
+                            last_text = text;
+                        }
+                    }
+                    _ = shutdown.wait_for(|stop| *stop) => break,
+                }
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    #[instrument(skip_all, fields(user_id = %ready.user.id))]
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!(
+            guilds = ready.guilds.len(),
+            "Bot {} is ready!", ready.user.name
+        );
+        self.data
+            .app_emojis
+            .get_or_init(|| app_emojis::sync(&ctx))
+            .await;
+        let guilds: Vec<GuildId> = ready.guilds.iter().map(|guild| guild.id).collect();
+        commands::register(
+            &ctx.http,
+            &guilds,
+            self.data.config().register_guild_commands,
+        )
+        .await;
+        self.start_cleanup_task(Arc::clone(&ctx.http));
+        self.start_pin_worker(Arc::clone(&ctx.http));
+        self.start_reaction_workers();
+        self.start_digest_task(Arc::clone(&ctx.http));
+        self.start_retention_task(Arc::clone(&ctx.http));
+        self.start_shard_monitor();
+        self.start_presence_task(ctx);
+    }
+
+    #[instrument(skip_all, fields(guild_id = %guild.id))]
+    async fn guild_create(&self, ctx: Context, guild: Guild, is_new: Option<bool>) {
+        if self.data.is_guild_allowed(Some(guild.id)) {
+            if self.data.claim_onboarding(guild.id, is_new) {
+                self.onboard_guild(&ctx, &guild).await;
+            }
+            return;
+        }
+
+        warn!(
+            guild_id = %guild.id,
+            guild_name = %guild.name,
+            "Leaving guild: not in ALLOWED_GUILDS"
+        );
+        if let Some(channel_id) = guild.system_channel_id {
+            if let Err(e) = channel_id
+                .say(
+                    &ctx.http,
+                    "Thanks for the invite! This pin bot is a private deployment and \
+                     isn't available for this server, so I'll be leaving now.",
+                )
+                .await
+            {
+                warn!(guild_id = %guild.id, error = %e, "Failed to send farewell message");
+            }
+        }
+        if let Err(e) = guild.id.leave(&ctx.http).await {
+            error!(guild_id = %guild.id, error = %e, "Failed to leave guild");
+        }
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            guild_id = field::Empty,
+            channel_id = field::Empty,
+            message_id = field::Empty,
+            user_id = field::Empty,
+        )
+    )]
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let span = Span::current();
+        match interaction {
+            Interaction::Command(command) => {
+                if let Some(guild_id) = command.guild_id {
+                    span.record("guild_id", guild_id.get());
+                }
+                span.record("channel_id", command.channel_id.get());
+                span.record("user_id", command.user.id.get());
+                if !self.data.is_guild_allowed(command.guild_id) {
+                    return;
+                }
+                commands::handle(&ctx, self, &command).await;
+            }
+            Interaction::Component(component) => {
+                if let Some(guild_id) = component.guild_id {
+                    span.record("guild_id", guild_id.get());
+                }
+                span.record("channel_id", component.channel_id.get());
+                span.record("message_id", component.message.id.get());
+                span.record("user_id", component.user.id.get());
+                if !self.data.is_guild_allowed(component.guild_id) {
+                    return;
+                }
+                if component.data.custom_id.starts_with(REVIEW_BUTTON_PREFIX) {
+                    self.handle_review_button(&ctx, &component).await;
+                } else if component.data.custom_id.starts_with(APPROVAL_BUTTON_PREFIX) {
+                    self.handle_approval_button(&ctx, &component).await;
+                } else if component.data.custom_id.starts_with(PAGE_BUTTON_PREFIX) {
+                    self.handle_page_button(&ctx, &component).await;
+                } else {
+                    self.handle_vote_button(&ctx, &component).await;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[instrument(
+        skip_all,
+        fields(
+            guild_id = msg.guild_id.map(|id| id.get()),
+            channel_id = %msg.channel_id,
+            message_id = %msg.id,
+            user_id = %msg.author.id,
+        )
+    )]
+    async fn message(&self, ctx: Context, msg: Message) {
+        // Ignore bots, including ourselves, unless they're trusted to start votes
+        let current_user_id = ctx.cache.current_user().id;
+        if msg.author.bot
+            && (msg.author.id == current_user_id
+                || !self
+                    .data
+                    .config()
+                    .is_trusted_bot(msg.guild_id, msg.author.id))
+        {
+            return;
+        }
+
+        // Nothing works outside a server, so DMs only get a pointer to one
+        if msg.guild_id.is_none() {
+            if let Some(reply) = self.data.dm_reply(&msg) {
+                if let Err(e) = msg.channel_id.say(&ctx.http, reply).await {
+                    debug!(user_id = %msg.author.id, error = %e, "Failed to answer a DM");
+                }
+            }
+            return;
+        }
+
+        if !self.data.is_guild_allowed(msg.guild_id) {
+            return;
+        }
+
+        // Guild messages only arrive here to follow deleted and edited votes
+        if self.data.interaction_only() {
+            return;
+        }
+
+        if lacks_content(&msg) {
+            if !self.data.empty_content_warned.swap(true, Ordering::Relaxed) {
+                warn!(
+                    "Guild messages arrive without their text, so the Message Content intent \
+                     is probably not granted and @mention requests can't work. Enable it in the \
+                     Developer Portal, or set INTERACTION_ONLY=true to rely on /pin and the \
+                     context menu"
+                );
+            }
+            return;
+        }
+
+        // Check if bot is mentioned; other replies may be votes, though never
+        // a trusted bot's
+        let Some(command_text) = strip_mention(&msg.content, current_user_id) else {
+            if !msg.author.bot {
+                self.handle_text_vote(&ctx, &msg).await;
+            }
+            return;
+        };
+
+        let permissions_in = |channel_id| match (msg.guild_id, msg.member.as_deref()) {
+            (Some(guild_id), Some(member)) => {
+                channel_permissions(&ctx, guild_id, channel_id, |guild, channel| {
+                    guild.partial_member_permissions_in(channel, msg.author.id, member)
+                })
+            }
+            _ => None,
+        };
+        let is_moderator =
+            permissions_in(msg.channel_id).is_some_and(|permissions| permissions.manage_messages());
+
+        // "@bot cancel" in reply to a vote cancels it
+        if command_text.eq_ignore_ascii_case("cancel") {
+            if let Some(session_id) = msg.message_reference.as_ref().and_then(|r| r.message_id) {
+                if self.data.voting_sessions.contains_key(&session_id) {
+                    self.cancel_session(
+                        &ctx,
+                        session_id,
+                        msg.channel_id,
+                        msg.author.id,
+                        is_moderator,
+                    )
+                    .await;
+                    return;
+                }
+            }
+        }
+
+        // Message links after the mention win over the replied-to message,
+        // a duration like `3d` makes the pin temporary and any other words
+        // are the reason for the pin. `confirm` goes ahead with a vote on a
+        // message pinned recently, and `within 10m` sets the vote's length.
+        let mut linked = Vec::new();
+        let mut pin_duration = None;
+        let mut timeout = None;
+        let mut reason_words = Vec::new();
+        let mut confirmed = false;
+        let mut tokens = command_text.split_whitespace();
+        while let Some(token) = tokens.next() {
+            if token.eq_ignore_ascii_case("confirm") {
+                confirmed = true;
+            } else if token.eq_ignore_ascii_case("within") {
+                let parsed = self
+                    .data
+                    .config()
+                    .vote_timeout(tokens.next().unwrap_or_default());
+                match parsed {
+                    Ok(parsed) => timeout = Some(parsed),
+                    Err(refusal) => {
+                        reply_or_warn(&ctx, &msg, &refusal).await;
+                        return;
+                    }
+                }
+            } else if let Some(parsed) = duration::parse_duration(token) {
+                pin_duration = Some(parsed);
+            } else if let Some(parsed) = links::parse_message_ref(token) {
+                linked.push(parsed);
+            } else {
+                reason_words.push(token);
+            }
+        }
+        let reason = reason::clean_reason(&reason_words.join(" "));
+        if let Some(Err(refusal)) = pin_duration.map(duration::check_pin_duration) {
+            reply_or_warn(&ctx, &msg, &refusal).await;
+            return;
+        }
+        let mut target_refs = Vec::new();
+        for link in &linked {
+            if link.channel_id.is_some() && link.guild_id != msg.guild_id {
+                reply_or_warn(&ctx, &msg, CROSS_GUILD_REFUSAL).await;
+                return;
+            }
+            let target_ref = (link.channel_id.unwrap_or(msg.channel_id), link.message_id);
+            if !target_refs.contains(&target_ref) {
+                target_refs.push(target_ref);
+            }
+        }
+        if target_refs.len() > MAX_BATCH_TARGETS {
+            reply_or_warn(&ctx, &msg, &batch_too_large_refusal()).await;
+            return;
+        }
+        if target_refs.is_empty() {
+            match msg.message_reference.as_ref().and_then(|r| r.message_id) {
+                Some(target_id) => target_refs.push((msg.channel_id, target_id)),
+                None => return,
+            }
+        }
+        let batch = target_refs.len() > 1;
+
+        // Fetching targets and setting up the vote takes a while, so say the
+        // request was seen. Every refusal from here on leaves a ❌ instead.
+        let pending = PendingReaction::place(Arc::clone(&ctx.http), &msg).await;
+
+        let mut targets = Vec::with_capacity(target_refs.len());
+        for (target_channel_id, target_id) in target_refs {
+            // In a batch, say which message a refusal is about
+            let refuse = |refusal: &str| {
+                if batch {
+                    format!(
+                        "{}: {}",
+                        target_id.link(target_channel_id, msg.guild_id),
+                        refusal
+                    )
+                } else {
+                    refusal.to_string()
+                }
+            };
+
+            let is_moderator = if target_channel_id == msg.channel_id {
+                is_moderator
+            } else {
+                let permissions = permissions_in(target_channel_id);
+                if !permissions.is_some_and(can_read_history) {
+                    reply_or_warn(&ctx, &msg, &refuse(&no_access_refusal(target_channel_id))).await;
+                    return;
+                }
+                permissions.is_some_and(|permissions| permissions.manage_messages())
+            };
+
+            // The gateway usually embeds the referenced message; fall back to a
+            // fetch when it was left out so the pinned flag is always known.
+            let embedded = msg
+                .referenced_message
+                .as_deref()
+                .filter(|target| target.id == target_id);
+            let target_msg = match embedded {
+                Some(target) => target.clone(),
+                None => match ctx.http.get_message(target_channel_id, target_id).await {
+                    Ok(target) => target,
+                    Err(e) => {
+                        warn!(message_id = %target_id, error = %e, "Failed to fetch pin target");
+                        if !linked.is_empty() {
+                            reply_or_warn(&ctx, &msg, &refuse("I couldn't find that message."))
+                                .await;
+                        }
+                        return;
+                    }
+                },
+            };
+
+            if self
+                .refuse_blocked(&ctx, msg.guild_id, &target_msg, msg.author.id)
+                .await
+            {
+                reply_briefly(&ctx, &msg, BLOCKED_REFUSAL).await;
+                return;
+            }
+            if let Err(refusal) = self
+                .vet_request(
+                    &ctx,
+                    msg.guild_id,
+                    msg.channel_id,
+                    &target_msg,
+                    msg.author.id,
+                    is_moderator,
+                )
+                .await
+            {
+                reply_or_warn(&ctx, &msg, &refuse(&refusal)).await;
+                return;
+            }
+            targets.push(target_msg);
+        }
+
+        // If no votes are needed or a superuser asked, pin immediately. A
+        // batch needs as many votes as its strictest channel.
+        let config = self.data.config();
+        let threshold = targets
+            .iter()
+            .map(|target| self.threshold_in(&ctx, msg.guild_id, target.channel_id))
+            .max()
+            .unwrap_or_default();
+        if threshold == 0 || config.superusers.contains(&msg.author.id) {
+            let (pinned, failed) = self
+                .data
+                .pin_each(msg.guild_id, targets.iter().map(PinTarget::from).collect())
+                .await;
+            let order = PinOrder {
+                guild_id: msg.guild_id,
+                initiator_id: msg.author.id,
+                votes: 0,
+                voters: 0,
+                duration: pin_duration,
+                archive: self.data.default_archive(msg.guild_id),
+                reason: reason.as_deref(),
+            };
+            self.data.after_pins(&ctx.http, &order, &pinned).await;
+            self.data
+                .report_pins(&ctx.http, msg.channel_id, &order, &pinned)
+                .await;
+            if pinned.is_empty() {
+                let guild_config = msg
+                    .guild_id
+                    .map(|guild_id| self.data.guild_config(guild_id))
+                    .unwrap_or_default();
+                let notice = pin_failed_notice(
+                    guild_config.template(TemplateKey::PinFailed),
+                    msg.guild_id,
+                    &targets,
+                );
+                reply_or_warn(&ctx, &msg, &notice).await;
+                return;
+            }
+            pending.settle().await;
+            if batch && !failed.is_empty() {
+                let report = self.data.dry_run_tagged(partial_batch_report(
+                    msg.guild_id,
+                    pinned.len(),
+                    &failed,
+                ));
+                reply_or_warn(&ctx, &msg, &report).await;
+            }
+            return;
+        }
+
+        if !confirmed {
+            for target in &targets {
+                if let Some(pinned_at) = self.data.recent_pin(msg.guild_id, target.id) {
+                    let mut warning =
+                        recent_pin_warning(pinned_at, "ask again with `confirm` after the mention");
+                    if batch {
+                        warning = format!(
+                            "{}: {}",
+                            target.id.link(target.channel_id, msg.guild_id),
+                            warning
+                        );
+                    }
+                    reply_or_warn(&ctx, &msg, &warning).await;
+                    return;
+                }
+            }
+        }
+
+        if let Err(refusal) = self.data.session_capacity(msg.channel_id) {
+            reply_or_warn(&ctx, &msg, &refusal).await;
+            return;
+        }
+        if let Err(refusal) =
+            self.data
+                .initiator_capacity(msg.guild_id, msg.author.id, is_moderator)
+        {
+            reply_or_warn(&ctx, &msg, &refusal).await;
+            return;
+        }
+        if let Err(refusal) = self.vet_pin_quota(&ctx, msg.guild_id, &targets) {
+            reply_or_warn(&ctx, &msg, &refusal).await;
+            return;
+        }
+
+        let started = self
+            .start_session(
+                &ctx,
+                VoteAnchor::Reply(&msg),
+                &targets,
+                threshold,
+                VoteTerms {
+                    pin_duration,
+                    reason,
+                    timeout,
+                },
+            )
+            .await;
+        if started {
+            pending.settle().await;
+        }
+    }
+
+    /// Each vote's collector sees single reactions come and go; clearing
+    /// all of a message's reactions at once only reaches the handler.
+    #[instrument(skip_all, fields(message_id = %message_id))]
+    async fn reaction_remove_all(
+        &self,
+        _ctx: Context,
+        _channel_id: ChannelId,
+        message_id: MessageId,
+    ) {
+        let Some((session_id, on_target)) = self.data.session_for_reaction(message_id) else {
+            return;
+        };
+        let guild_id = self
+            .data
+            .voting_sessions
+            .get(&session_id)
+            .and_then(|session| session.guild_id);
+        if self.data.is_guild_allowed(guild_id) {
+            self.data.retract_reaction_votes(session_id, on_target);
+        }
+    }
+
+    /// Keeps the snapshot of an edited vote target current, if configured.
+    #[instrument(skip_all, fields(message_id = %event.id))]
+    async fn message_update(
+        &self,
+        _ctx: Context,
+        _old_if_available: Option<Message>,
+        _new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        if self.data.refresh_target_snapshot(
+            event.id,
+            event.content.as_deref(),
+            event.attachments.as_deref(),
+        ) {
+            debug!("Refreshed the snapshot of an edited vote target");
+        }
+    }
+
+    /// An answer picked on a vote's poll.
+    #[instrument(skip_all, fields(message_id = %event.message_id, user_id = %event.user_id))]
+    async fn poll_vote_add(&self, ctx: Context, event: MessagePollVoteAddEvent) {
+        self.handle_poll_vote(
+            &ctx,
+            event.guild_id,
+            event.message_id,
+            event.user_id,
+            event.answer_id,
+            true,
+        )
+        .await;
+    }
+
+    /// An answer taken back on a vote's poll.
+    #[instrument(skip_all, fields(message_id = %event.message_id, user_id = %event.user_id))]
+    async fn poll_vote_remove(&self, ctx: Context, event: MessagePollVoteRemoveEvent) {
+        self.handle_poll_vote(
+            &ctx,
+            event.guild_id,
+            event.message_id,
+            event.user_id,
+            event.answer_id,
+            false,
+        )
+        .await;
+    }
+
+    /// Calls off a vote whose message or target was deleted.
+    #[instrument(skip_all, fields(message_id = %message_id))]
+    async fn message_delete(
+        &self,
+        ctx: Context,
+        _channel_id: ChannelId,
+        message_id: MessageId,
+        _guild_id: Option<GuildId>,
+    ) {
+        match self.data.session_for_reaction(message_id) {
+            Some((session_id, false))
+                if self
+                    .data
+                    .end_session(session_id, VoteEnding::Cancelled)
+                    .is_some() =>
+            {
+                info!("Vote message deleted, dropped the vote");
+                self.data.clear_target_reactions(ctx.http.as_ref()).await;
+            }
+            Some((session_id, true)) => {
+                let Some(vote_channel_id) = self
+                    .data
+                    .voting_sessions
+                    .get(&session_id)
+                    .map(|session| session.vote_channel_id)
+                else {
+                    return;
+                };
+                info!(session_id = %session_id, "Vote target deleted, calling off the vote");
+                self.data
+                    .end_cancelled_session(
+                        &ctx.http,
+                        session_id,
+                        vote_channel_id,
+                        "The message up for a vote was deleted, so the vote is off.".to_string(),
+                    )
+                    .await;
+            }
+            _ => {}
+        }
+    }
+
+    async fn channel_pins_update(&self, ctx: Context, update: ChannelPinsUpdateEvent) {
+        let Some(guild_id) = update.guild_id else {
+            return;
+        };
+        if !self.data.is_guild_allowed(Some(guild_id)) {
+            return;
+        }
+        // Pins change in bursts; one look once they settle covers them all
+        if !self.data.pending_pin_syncs.insert(update.channel_id) {
+            return;
+        }
+        let data = Arc::clone(&self.data);
+        tokio::spawn(async move {
+            sleep(PIN_SYNC_DEBOUNCE).await;
+            data.pending_pin_syncs.remove(&update.channel_id);
+            data.sync_pins(&ctx.http, guild_id, update.channel_id).await;
+        });
+    }
+
+    /// Only delivered with `DROP_DEPARTED_VOTES`, which asks for the
+    /// Server Members intent.
+    async fn guild_member_removal(
+        &self,
+        ctx: Context,
+        guild_id: GuildId,
+        user: User,
+        _member: Option<Member>,
+    ) {
+        if !self.data.is_guild_allowed(Some(guild_id)) {
+            return;
+        }
+        for (session_id, outcome) in self.data.drop_departed_voter(guild_id, user.id) {
+            self.finalize_if_passed(&ctx, session_id, outcome).await;
+        }
+    }
+}
+
+/// Reads a reply as a vote: true for an approval ("+1", "✅", "yes"),
+/// false for a rejection ("-1", "no"). Anything else is just a reply.
+fn parse_text_vote(content: &str) -> Option<bool> {
+    let vote = content.trim().trim_end_matches('\u{FE0F}');
+    if ["+1", CHECKMARK_EMOJI].contains(&vote) || vote.eq_ignore_ascii_case("yes") {
+        Some(true)
+    } else if vote == "-1" || vote.eq_ignore_ascii_case("no") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Returns the text after a leading mention of the bot, if the message
+/// starts with one.
+fn strip_mention(content: &str, bot_id: UserId) -> Option<&str> {
+    content
+        .strip_prefix(&format!("<@{}>", bot_id))
+        .or_else(|| content.strip_prefix(&format!("<@!{}>", bot_id)))
+        .map(str::trim)
+}
+
+fn is_reject_emoji(reaction: &ReactionType) -> bool {
+    emoji::same_emoji(reaction, &ReactionType::Unicode(REJECT_EMOJI.to_string()))
+}
+
+/// Whether the member is serving a timeout at `now`.
+fn is_timed_out(member: &Member, now: i64) -> bool {
+    member
+        .communication_disabled_until
+        .is_some_and(|until| until.unix_timestamp() > now)
+}
+
+/// Viewing a message over the API needs both of these.
+fn can_read_history(permissions: Permissions) -> bool {
+    permissions.view_channel() && permissions.read_message_history()
+}
+
+fn full_channel_refusal(open: usize) -> String {
+    format!(
+        "There are already {} open votes in this channel, let one finish first.",
+        open
+    )
+}
+
+fn initiator_cap_refusal(open: usize, votes: &[String]) -> String {
+    format!(
+        "You already have {} open votes, the most one member may run at once. Wait for one to \
+         end or call one off by replying `cancel` to it:\n{}",
+        open,
+        votes.join("\n")
+    )
+}
+
+fn no_access_refusal(channel_id: ChannelId) -> String {
+    format!(
+        "You need to be able to read <#{}> to pin messages from it.",
+        channel_id
+    )
+}
+
+/// Says a channel is out of voted pins for today and when that changes.
+fn pin_quota_refusal(channel_id: ChannelId, quota: u32, now: i64) -> String {
+    let resets_at = now - now.rem_euclid(digest::DAY_SECS) + digest::DAY_SECS;
+    format!(
+        "<#{}> already had its {} voted pins for today. The quota resets <t:{}:R>.",
+        channel_id, quota, resets_at
+    )
+}
+
+/// Asks the requester to confirm a vote on a message pinned not long ago.
+fn recent_pin_warning(pinned_at: i64, how_to_confirm: &str) -> String {
+    format!(
+        "That message was already pinned <t:{}:R>. If it deserves another vote, {}.",
+        pinned_at, how_to_confirm
+    )
+}
+
+fn batch_too_large_refusal() -> String {
+    format!("I can pin at most {} messages at once.", MAX_BATCH_TARGETS)
+}
+
+/// The text of a vote message the bot posts itself, for a vote started by
+/// mentioning the bot.
+fn vote_prompt_text(guild_config: &GuildConfig, session: &VotingSession, threshold: u32) -> String {
+    vote_message(
+        guild_config.template(TemplateKey::VoteStarted),
+        &VoteIntro {
+            guild_id: session.guild_id,
+            targets: &session.pin_targets(),
+            subject: "this message",
+            initiator_id: session.initiator_id,
+            threshold,
+            quorum: session.quorum,
+            reason: session.reason.as_deref(),
+        },
+    )
+}
+
+/// Everyone who picked `answer` on the poll, a page at a time.
+async fn poll_answer_voters(
+    ctx: &Context,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    answer: AnswerId,
+) -> serenity::Result<HashSet<UserId>> {
+    const PAGE: u8 = 100;
+    let mut voters = HashSet::new();
+    let mut after = None;
+    loop {
+        let page = channel_id
+            .get_poll_answer_voters(&ctx.http, message_id, answer, after, Some(PAGE))
+            .await?;
+        after = page.last().map(|user| user.id);
+        let full = page.len() == usize::from(PAGE);
+        voters.extend(page.into_iter().map(|user| user.id));
+        if !full {
+            return Ok(voters);
+        }
+    }
+}
+
+/// What a vote message the bot lost track of says about its vote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct OrphanedVote {
+    channel_id: ChannelId,
+    message_id: MessageId,
+    /// Who asked for the pin, when the message tells.
+    initiator_id: Option<UserId>,
+}
+
+/// Recognizes a reaction vote from its message alone: a request mentioning
+/// the bot in reply to the target, or the bot's own vote message linking
+/// to it, either with the bot's checkmark on it. Returns the refusal for
+/// anything else.
+fn orphaned_vote(
+    vote: &Message,
+    guild_id: GuildId,
+    bot_id: UserId,
+) -> Result<OrphanedVote, String> {
+    let ours = vote.author.id == bot_id;
+    if !ours && !vote.mentions.iter().any(|user| user.id == bot_id) {
+        return Err("That isn't a vote: I didn't post it and it doesn't mention me.".to_string());
+    }
+    if !vote
+        .reactions
+        .iter()
+        .any(|reaction| reaction.me && emoji::checkmark(&reaction.reaction_type).is_some())
+    {
+        return Err(
+            "That message doesn't have my ✅ on it, so it isn't a reaction vote.".to_string(),
+        );
+    }
+
+    if !ours {
+        let (channel_id, message_id) = vote
+            .message_reference
+            .as_ref()
+            .and_then(|reference| Some((reference.channel_id, reference.message_id?)))
+            .ok_or("That request doesn't reply to the message it was about.")?;
+        return Ok(OrphanedVote {
+            channel_id,
+            message_id,
+            initiator_id: Some(vote.author.id),
+        });
+    }
+    let (channel_id, message_id) = vote
+        .content
+        // Anything a message link can't contain ends it
+        .split(|c: char| !c.is_ascii_alphanumeric() && !matches!(c, ':' | '/' | '.' | '@'))
+        .filter_map(links::parse_message_ref)
+        .find_map(|link| {
+            (link.guild_id == Some(guild_id)).then_some((link.channel_id?, link.message_id))
+        })
+        .ok_or("That vote doesn't link to the message it was about.")?;
+    let initiator_id = match vote.interaction_metadata.as_deref() {
+        Some(MessageInteractionMetadata::Command(command)) => Some(command.user.id),
+        _ => None,
+    };
+    Ok(OrphanedVote {
+        channel_id,
+        message_id,
+        initiator_id,
+    })
+}
+
+/// Everyone but bots who reacted to a message with `emoji`, a page at a
+/// time.
+async fn reaction_voters(
+    ctx: &Context,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    emoji: &ReactionType,
+) -> serenity::Result<Vec<UserId>> {
+    const PAGE: u8 = 100;
+    let mut voters = Vec::new();
+    let mut after = None;
+    loop {
+        let page = channel_id
+            .reaction_users(&ctx.http, message_id, emoji.clone(), Some(PAGE), after)
+            .await?;
+        after = page.last().map(|user| user.id);
+        let full = page.len() == usize::from(PAGE);
+        voters.extend(
+            page.into_iter()
+                .filter(|user| !user.bot)
+                .map(|user| user.id),
+        );
+        if !full {
+            return Ok(voters);
+        }
+    }
+}
+
+/// Where the setup message goes in a new guild: the system channel when
+/// the bot may post there, else the first text channel it may post in.
+fn onboarding_channel(guild: &Guild, bot_id: UserId) -> Option<ChannelId> {
+    let member = guild.members.get(&bot_id)?;
+    let may_post = |channel: &GuildChannel| {
+        channel.kind == ChannelType::Text && {
+            let permissions = guild.user_permissions_in(channel, member);
+            permissions.view_channel() && permissions.send_messages()
+        }
+    };
+    if let Some(channel) = guild
+        .system_channel_id
+        .and_then(|channel_id| guild.channels.get(&channel_id))
+        .filter(|channel| may_post(channel))
+    {
+        return Some(channel.id);
+    }
+    let mut channels: Vec<&GuildChannel> = guild
+        .channels
+        .values()
+        .filter(|channel| may_post(channel))
+        .collect();
+    channels.sort_by_key(|channel| (channel.position, channel.id));
+    channels.first().map(|channel| channel.id)
+}
+
+/// The setup message posted in a guild the bot joined.
+fn onboarding_message(bot_id: UserId, threshold: u32, interaction_only: bool) -> String {
+    let asking = if interaction_only {
+        "run `/pin` with a message link, or right-click a message → Apps → **Pin this message**"
+            .to_string()
+    } else {
+        format!(
+            "reply to a message and mention <@{}>, run `/pin` with a message link, or \
+             right-click a message → Apps → **Pin this message**",
+            bot_id
+        )
+    };
+    format!(
+        "👋 Thanks for adding me! I pin messages your members vote for. To ask for a pin, {}. \
+         A message is pinned once it gets {} ✅ {}.\n\
+         Run `/help` to see how voting works in a channel, and `/config` (Manage Server) to \
+         change the threshold, where votes are allowed and more.",
+        asking,
+        threshold,
+        if threshold == 1 { "vote" } else { "votes" }
+    )
+}
+
+async fn application_owners(http: &Http) -> serenity::Result<HashSet<UserId>> {
+    let application = http.get_current_application_info().await?;
+    let mut owners: HashSet<UserId> = application.owner.iter().map(|owner| owner.id).collect();
+    if let Some(team) = application.team {
+        owners.insert(team.owner_user_id);
+        owners.extend(
+            team.members
+                .iter()
+                .filter(|member| matches!(member.role, TeamMemberRole::Admin))
+                .map(|member| member.user.id),
+        );
+    }
+    Ok(owners)
+}
+
+/// What a vote message presents.
+struct VoteIntro<'a> {
+    guild_id: Option<GuildId>,
+    targets: &'a [PinTarget],
+    /// How a single target is referred to, e.g. "this message".
+    subject: &'a str,
+    initiator_id: UserId,
+    threshold: u32,
+    quorum: Option<QuorumRule>,
+    reason: Option<&'a str>,
+}
+
+/// The vote message in the guild's wording, followed by a batch's list
+/// of messages and the reason.
+fn vote_message(template: &str, intro: &VoteIntro) -> String {
+    let VoteIntro {
+        guild_id,
+        targets,
+        subject,
+        initiator_id,
+        threshold,
+        quorum,
+        reason,
+    } = *intro;
+    let requirement = match quorum {
+        Some(rule) => format!("It passes with {}.", rule.describe()),
+        None => format!("{} approvals needed.", threshold),
+    };
+    let target = if targets.len() > 1 {
+        format!("these {} messages", targets.len())
+    } else {
+        subject.to_string()
+    };
+    let target_link = targets
+        .first()
+        .map(|target| target.message_id.link(target.channel_id, guild_id))
+        .unwrap_or_default();
+    let mut content = templates::render(
+        template,
+        &[
+            ("target", &target),
+            ("target_link", &target_link),
+            ("initiator", &format!("<@{}>", initiator_id)),
+            (
+                "needed",
+                &quorum.map_or(threshold, |rule| rule.quorum).to_string(),
+            ),
+            ("requirement", &requirement),
+        ],
+    );
+    if targets.len() > 1 {
+        for target in targets {
+            content.push_str("\n• ");
+            content.push_str(&target.message_id.link(target.channel_id, guild_id));
+        }
+    }
+    if let Some(reason) = reason {
+        content.push_str("\nReason: ");
+        content.push_str(reason);
+    }
+    content
+}
+
+/// A vote message with a live countdown to `expires_at` below it.
+fn with_deadline(text: &str, expires_at: i64) -> String {
+    format!("{}{}Ends <t:{}:R>", text, DEADLINE_MARKER, expires_at)
+}
+
+/// Undoes `with_deadline`; other text comes back unchanged.
+fn strip_deadline(content: &str) -> &str {
+    content
+        .rsplit_once(DEADLINE_MARKER)
+        .map_or(content, |(text, _)| text)
+}
+
+/// Links to the messages a request pinned, in the guild's wording.
+fn pinned_report(template: &str, order: &PinOrder, pinned: &[PinTarget]) -> String {
+    let links: Vec<String> = pinned
+        .iter()
+        .map(|target| target.message_id.link(target.channel_id, order.guild_id))
+        .collect();
+    templates::render(
+        template,
+        &[
+            ("target_link", &links.join(", ")),
+            ("count", &order.votes.to_string()),
+            ("initiator", &format!("<@{}>", order.initiator_id)),
+        ],
+    )
+}
+
+/// Says how an expired vote fell short, in the guild's wording.
+fn expired_notice(template: &str, session: &VotingSession) -> String {
+    let requirement = match session.quorum {
+        Some(rule) => rule.describe(),
+        None => format!("{} approvals", session.threshold),
+    };
+    templates::render(
+        template,
+        &[
+            (
+                "target_link",
+                &session
+                    .target_message_id
+                    .link(session.target_channel_id, session.guild_id),
+            ),
+            ("initiator", &format!("<@{}>", session.initiator_id)),
+            ("progress", &session.progress()),
+            ("requirement", &requirement),
+        ],
+    )
+}
+
+/// Tells the requester that nothing could be pinned, in the guild's
+/// wording.
+fn pin_failed_notice(template: &str, guild_id: Option<GuildId>, targets: &[Message]) -> String {
+    let links: Vec<String> = targets
+        .iter()
+        .map(|target| target.id.link(target.channel_id, guild_id))
+        .collect();
+    let target = if targets.len() > 1 {
+        "those messages"
+    } else {
+        "that message"
+    };
+    templates::render(
+        template,
+        &[("target", target), ("target_link", &links.join(", "))],
+    )
+}
+
+/// Tells the requester which messages of a batch didn't make it.
+fn partial_batch_report(guild_id: Option<GuildId>, pinned: usize, failed: &[PinTarget]) -> String {
+    let links: Vec<String> = failed
+        .iter()
+        .map(|target| target.message_id.link(target.channel_id, guild_id))
+        .collect();
+    format!(
+        "📌 Pinned {} of {} messages. I couldn't pin {}",
+        pinned,
+        pinned + failed.len(),
+        links.join(", ")
+    )
+}
+
+/// A regular message with nothing in it, as guild messages look when the
+/// Message Content intent is missing.
+fn lacks_content(msg: &Message) -> bool {
+    matches!(msg.kind, MessageType::Regular | MessageType::InlineReply)
+        && msg.content.is_empty()
+        && msg.attachments.is_empty()
+        && msg.embeds.is_empty()
+        && msg.sticker_items.is_empty()
+        && msg.poll.is_none()
+}
+
+/// Errors worth another try: Discord's own failures and dropped requests.
+fn is_transient(error: &serenity::Error) -> bool {
+    match error {
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response)) => {
+            response.status_code.is_server_error()
+                || response.status_code == StatusCode::TOO_MANY_REQUESTS
+        }
+        serenity::Error::Http(HttpError::Request(_)) => true,
+        _ => false,
+    }
+}
+
+fn is_rate_limited(error: &serenity::Error) -> bool {
+    matches!(
+        error,
+        serenity::Error::Http(HttpError::UnsuccessfulRequest(response))
+            if response.status_code == StatusCode::TOO_MANY_REQUESTS
+    )
+}
+
+/// The channel of a route to one pinned message, `/channels/{id}/pins/{id}`,
+/// in a path as serenity's ratelimiter reports it.
+fn pin_route_channel(path: &str) -> Option<ChannelId> {
+    let mut segments = path.split('/').skip_while(|segment| *segment != "channels");
+    segments.next()?;
+    let channel_id = segments.next()?.parse::<u64>().ok().filter(|id| *id != 0)?;
+    (segments.next()? == "pins" && segments.next().is_some_and(|id| !id.is_empty()))
+        .then(|| ChannelId::new(channel_id))
+}
+
+/// Join notices, boost announcements and the like. Command responses count
+/// as regular content.
+fn is_system_message(kind: MessageType) -> bool {
+    !matches!(
+        kind,
+        MessageType::Regular
+            | MessageType::InlineReply
+            | MessageType::ChatInputCommand
+            | MessageType::ContextMenuCommand
+    )
+}
+
+/// Computes permissions in a channel from the cache. Threads are resolved
+/// to their parent, whose overwrites they inherit.
+fn channel_permissions<T>(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+    compute: impl FnOnce(&Guild, &GuildChannel) -> T,
+) -> Option<T> {
+    let lineage = channel_lineage(ctx, guild_id, channel_id);
+    let guild = ctx.cache.guild(guild_id)?;
+    let channel = lineage.iter().rev().find_map(|id| guild.channels.get(id))?;
+    Some(compute(&guild, channel))
+}
+
+/// The bot's own permissions in a channel, from the cache. The check before
+/// a reaction vote and `/diagnose` both read this, so they can't disagree.
+fn bot_permissions_in(
+    ctx: &Context,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Option<Permissions> {
+    let bot_id = ctx.cache.current_user().id;
+    channel_permissions(ctx, guild_id, channel_id, |guild, channel| {
+        guild
+            .members
+            .get(&bot_id)
+            .map(|member| guild.user_permissions_in(channel, member))
+    })
+    .flatten()
+}
+
+async fn reply_or_warn(ctx: &Context, msg: &Message, content: &str) {
+    if let Err(e) = msg.reply(&ctx.http, content).await {
+        warn!(message_id = %msg.id, error = %e, "Failed to reply");
+    }
+}
+
+/// Replies like `reply_or_warn`, then deletes the reply after `BRIEF_REPLY`
+/// so it doesn't linger in the channel.
+async fn reply_briefly(ctx: &Context, msg: &Message, content: &str) {
+    let reply = match msg.reply(&ctx.http, content).await {
+        Ok(reply) => reply,
+        Err(e) => {
+            warn!(message_id = %msg.id, error = %e, "Failed to reply");
+            return;
+        }
+    };
+    let http = Arc::clone(&ctx.http);
+    tokio::spawn(async move {
+        sleep(BRIEF_REPLY).await;
+        if let Err(e) = reply.delete(&http).await {
+            debug!(message_id = %reply.id, error = %e, "Failed to delete brief reply");
+        }
+    });
+}
+
+/// The ⏳ on an @mention request while it's worked on. `settle` takes it
+/// off once the pin or vote is in place; dropped unsettled, as on any
+/// refusal or failed setup, it's swapped for a ❌ so the initiator knows the
+/// request didn't take.
+struct PendingReaction<A: PinApi + Send + 'static> {
+    api: Arc<A>,
+    channel_id: ChannelId,
+    message_id: MessageId,
+    /// Whether the ⏳ is on and still to be dealt with.
+    placed: bool,
+}
+
+impl<A: PinApi + Send + 'static> PendingReaction<A> {
+    async fn place(api: Arc<A>, msg: &Message) -> Self {
+        let pending = ReactionType::Unicode(PENDING_EMOJI.to_string());
+        let placed = match api.react(msg.channel_id, msg.id, pending).await {
+            Ok(()) => true,
+            Err(e) => {
+                debug!(message_id = %msg.id, error = %e, "Failed to acknowledge a request");
+                false
+            }
+        };
+        Self {
+            api,
+            channel_id: msg.channel_id,
+            message_id: msg.id,
+            placed,
+        }
+    }
+
+    /// The request took; just the ⏳ comes off.
+    async fn settle(mut self) {
+        if std::mem::take(&mut self.placed) {
+            let pending = ReactionType::Unicode(PENDING_EMOJI.to_string());
+            if let Err(e) = self
+                .api
+                .unreact(self.channel_id, self.message_id, pending)
+                .await
+            {
+                debug!(message_id = %self.message_id, error = %e, "Failed to remove ⏳");
+            }
+        }
+    }
+}
+
+impl<A: PinApi + Send + 'static> Drop for PendingReaction<A> {
+    fn drop(&mut self) {
+        if !self.placed {
+            return;
+        }
+        let (api, channel_id, message_id) =
+            (Arc::clone(&self.api), self.channel_id, self.message_id);
+        tokio::spawn(async move {
+            let pending = ReactionType::Unicode(PENDING_EMOJI.to_string());
+            let failed = ReactionType::Unicode(FAILED_EMOJI.to_string());
+            if let Err(e) = api.unreact(channel_id, message_id, pending).await {
+                debug!(message_id = %message_id, error = %e, "Failed to remove ⏳");
+            }
+            if let Err(e) = api.react(channel_id, message_id, failed).await {
+                debug!(message_id = %message_id, error = %e, "Failed to mark a request as failed");
+            }
+        });
+    }
+}
+
+/// Resolves a member from the event payload when present, falling back to the
+/// cache and finally HTTP.
+async fn resolve_member(
+    ctx: &Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    provided: Option<&Member>,
+) -> Option<Member> {
+    if let Some(member) = provided {
+        return Some(member.clone());
+    }
+
+    match guild_id.member(ctx, user_id).await {
+        Ok(member) => Some(member),
+        Err(e) => {
+            warn!(user_id = %user_id, guild_id = %guild_id, error = %e, "Failed to resolve member");
+            None
+        }
+    }
+}
+
+/// Returns the channel followed by its parent when it is a thread, using the
+/// cache so the check costs nothing on the hot path.
+fn channel_lineage(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> Vec<ChannelId> {
+    let parent = ctx.cache.guild(guild_id).and_then(|guild| {
+        guild
+            .threads
+            .iter()
+            .find(|thread| thread.id == channel_id)
+            .and_then(|thread| thread.parent_id)
+    });
+
+    std::iter::once(channel_id).chain(parent).collect()
+}
+
+/// How long a poll for a vote of `timeout` runs: rounded up to whole hours,
+/// within what Discord allows.
+fn poll_duration(timeout: Duration) -> Duration {
+    let hours = timeout
+        .as_secs()
+        .div_ceil(3600)
+        .clamp(POLL_MIN_HOURS, POLL_MAX_HOURS);
+    Duration::from_secs(hours * 3600)
+}
+
+/// Builds the Approve/Cancel row for a button vote, with Reject between them
+/// for quorum votes. Custom ids carry the session key so presses can be
+/// routed without extra state.
+fn vote_buttons(session_id: MessageId, disabled: bool, rejectable: bool) -> Vec<CreateActionRow> {
+    let mut buttons =
+        vec![
+            CreateButton::new(format!("{}approve:{}", VOTE_BUTTON_PREFIX, session_id))
+                .label("Approve")
+                .style(ButtonStyle::Success)
+                .disabled(disabled),
+        ];
+    if rejectable {
+        buttons.push(
+            CreateButton::new(format!("{}reject:{}", VOTE_BUTTON_PREFIX, session_id))
+                .label("Reject")
+                .style(ButtonStyle::Danger)
+                .disabled(disabled),
+        );
+    }
+    buttons.push(
+        CreateButton::new(format!("{}cancel:{}", VOTE_BUTTON_PREFIX, session_id))
+            .label("Cancel")
+            .style(ButtonStyle::Secondary)
+            .disabled(disabled),
+    );
+    vec![CreateActionRow::Buttons(buttons)]
+}
+
+/// Approve and Deny on a held vote's review request.
+fn review_buttons(session_id: MessageId, disabled: bool) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}approve:{}", REVIEW_BUTTON_PREFIX, session_id))
+            .label("Let it continue")
+            .style(ButtonStyle::Success)
+            .disabled(disabled),
+        CreateButton::new(format!("{}deny:{}", REVIEW_BUTTON_PREFIX, session_id))
+            .label("Call it off")
+            .style(ButtonStyle::Danger)
+            .disabled(disabled),
+    ])]
+}
+
+/// Whether the member pressing `component` has Manage Messages in the
+/// vote's channel, which may not be where the button is.
+fn may_manage_votes(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    guild_id: GuildId,
+    vote_channel_id: ChannelId,
+) -> bool {
+    component.member.as_ref().is_some_and(|member| {
+        let permissions = if vote_channel_id == component.channel_id {
+            member.permissions
+        } else {
+            channel_permissions(ctx, guild_id, vote_channel_id, |guild, channel| {
+                guild.user_permissions_in(channel, member)
+            })
+        };
+        permissions.is_some_and(|permissions| permissions.manage_messages())
+    })
+}
+
+/// Names a vote's discussion thread from the guild's template, with
+/// `{excerpt}` standing for the target's text, kept to Discord's limit.
+fn vote_thread_name(template: &str, content: &str) -> String {
+    let excerpt = digest::excerpt(content);
+    let excerpt = if excerpt.is_empty() {
+        "a message"
+    } else {
+        excerpt.as_str()
+    };
+    let name = template.replace("{excerpt}", excerpt);
+    if name.chars().count() <= MAX_THREAD_NAME_CHARS {
+        return name;
+    }
+    let mut short: String = name.chars().take(MAX_THREAD_NAME_CHARS - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Approve and Reject on a passed vote waiting for a moderator's sign-off.
+fn approval_buttons(session_id: MessageId, disabled: bool) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}approve:{}", APPROVAL_BUTTON_PREFIX, session_id))
+            .label("Approve pin")
+            .style(ButtonStyle::Success)
+            .disabled(disabled),
+        CreateButton::new(format!("{}reject:{}", APPROVAL_BUTTON_PREFIX, session_id))
+            .label("Reject")
+            .style(ButtonStyle::Danger)
+            .disabled(disabled),
+    ])]
+}
+
+/// Whether an approval button approves, with the session it is about.
+fn parse_approval_button(custom_id: &str) -> Option<(bool, MessageId)> {
+    let (action, session_id) = custom_id
+        .strip_prefix(APPROVAL_BUTTON_PREFIX)?
+        .split_once(':')?;
+    let approved = match action {
+        "approve" => true,
+        "reject" => false,
+        _ => return None,
+    };
+    Some((approved, session_id.parse().ok()?))
+}
+
+/// Whether a review button approves, with the session it is about.
+fn parse_review_button(custom_id: &str) -> Option<(bool, MessageId)> {
+    let (action, session_id) = custom_id
+        .strip_prefix(REVIEW_BUTTON_PREFIX)?
+        .split_once(':')?;
+    let approved = match action {
+        "approve" => true,
+        "deny" => false,
+        _ => return None,
+    };
+    Some((approved, session_id.parse().ok()?))
+}
+
+fn parse_vote_button(custom_id: &str) -> Option<(VoteAction, MessageId)> {
+    let (action, session_id) = custom_id
+        .strip_prefix(VOTE_BUTTON_PREFIX)?
+        .split_once(':')?;
+    let action = match action {
+        "approve" => VoteAction::Approve,
+        "reject" => VoteAction::Reject,
+        "cancel" => VoteAction::Cancel,
+        _ => return None,
+    };
+    Some((action, session_id.parse().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn handler() -> (Handler, watch::Sender<bool>) {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        let store = Store::open(":memory:").unwrap();
+        (
+            Handler::new(Config::default(), store, shutdown_rx),
+            shutdown_tx,
+        )
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn repeated_ready_runs_a_single_cleanup_loop() {
+        let (handler, shutdown_tx) = handler();
+
+        let http = Arc::new(Http::new(""));
+        assert!(handler.start_cleanup_task(Arc::clone(&http)));
+        assert!(!handler.start_cleanup_task(http));
+
+        // The first tick fires immediately, then once per interval
+        for _ in 0..3 {
+            tokio::time::sleep(Duration::from_secs(Config::default().cleanup_interval_secs)).await;
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(handler.data.cleanup_runs.load(Ordering::Relaxed), 4);
+
+        shutdown_tx.send(true).unwrap();
+        tokio::time::sleep(Duration::from_secs(
+            Config::default().cleanup_interval_secs * 2,
+        ))
+        .await;
+        assert_eq!(handler.data.cleanup_runs.load(Ordering::Relaxed), 4);
+    }
+
+    fn session() -> VotingSession {
+        VotingSession::new(
+            MessageId::new(1),
+            ChannelId::new(2),
+            ChannelId::new(2),
+            None,
+            UserId::new(3),
+            3,
+        )
+    }
+
+    #[test]
+    fn target_edits_only_reach_the_snapshot_when_configured() {
+        let (handler, _shutdown) = handler();
+        let data = &handler.data;
+        let mut target = Message::default();
+        target.id = MessageId::new(1);
+        target.content = "original".to_string();
+        let mut session = session();
+        session.target_snapshot = Some(TargetSnapshot::capture(&target));
+        data.insert_session(MessageId::new(100), session).unwrap();
+        let excerpt = || {
+            data.voting_sessions
+                .get(&MessageId::new(100))
+                .unwrap()
+                .pin_targets()[0]
+                .excerpt
+                .clone()
+        };
+
+        assert!(!data.refresh_target_snapshot(MessageId::new(1), Some("rewritten"), None));
+        assert_eq!(excerpt(), "original");
+
+        data.set_config(Config {
+            refresh_target_snapshots: true,
+            ..Config::default()
+        });
+        assert!(!data.refresh_target_snapshot(MessageId::new(2), Some("elsewhere"), None));
+        assert!(data.refresh_target_snapshot(MessageId::new(1), Some("rewritten"), None));
+        assert_eq!(excerpt(), "rewritten");
+    }
+
+    #[test]
+    fn different_checkmarks_from_one_user_count_once() {
+        let mut session = session();
+        let user = UserId::new(10);
+        let white = VoteSource::Reaction(Checkmark::WhiteHeavy);
+        let ballot = VoteSource::Reaction(Checkmark::Ballot);
+
+        assert!(session.add_vote(user, white, 1));
+        assert!(!session.add_vote(user, ballot, 1));
+        assert_eq!(session.get_vote_count(), 1);
+
+        // Removing one variant keeps the vote while the other remains
+        assert!(!session.remove_vote(user, white));
+        assert_eq!(session.get_vote_count(), 1);
+        assert!(session.remove_vote(user, ballot));
+        assert_eq!(session.get_vote_count(), 0);
+    }
+
+    #[test]
+    fn retracting_subtracts_the_weight_the_vote_was_added_at() {
+        let mut session = session();
+        let booster = UserId::new(10);
+        let white = VoteSource::Reaction(Checkmark::WhiteHeavy);
+
+        assert!(session.add_vote(booster, white, 2));
+        assert!(session.add_vote(UserId::new(11), white, 1));
+        assert_eq!(session.get_vote_count(), 3);
+
+        // A second approval after the boost lapsed keeps the original weight
+        assert!(!session.add_vote(booster, VoteSource::Button, 1));
+        assert!(!session.remove_vote(booster, white));
+        assert_eq!(session.get_vote_count(), 3);
+        assert!(session.remove_vote(booster, VoteSource::Button));
+        assert_eq!(session.get_vote_count(), 1);
+    }
+
+    #[test]
+    fn super_reactions_count_apart_from_normal_ones_at_their_weight() {
+        let mut session = session();
+        let user = UserId::new(10);
+        let normal = VoteSource::reaction(Checkmark::WhiteHeavy, false, false);
+        let burst = VoteSource::reaction(Checkmark::WhiteHeavy, false, true);
+        assert!(burst.is_super() && !normal.is_super());
+        assert_ne!(normal, burst);
+        assert_eq!(GuildConfig::default().super_reaction_factor(), 1);
+
+        // A super ✅ first sets a doubled weight, which its removal takes back
+        assert!(session.add_vote(user, burst, 2));
+        assert!(!session.add_vote(user, normal, 1));
+        assert_eq!(session.get_vote_count(), 2);
+        assert!(!session.remove_vote(user, burst));
+        assert_eq!(session.get_vote_count(), 2);
+        assert!(session.remove_vote(user, normal));
+        assert_eq!(session.get_vote_count(), 0);
+
+        assert!(session.add_vote(user, burst, 2));
+        assert!(session.remove_vote(user, burst));
+        assert_eq!(session.get_vote_count(), 0);
+    }
+
+    #[test]
+    fn quiet_hours_hold_passed_votes_until_the_window_ends() {
+        use digest::DAY_SECS;
+        use guild_config::{parse_clock, parse_utc_offset, QuietHours};
+
+        assert_eq!(parse_clock("23:30"), Some(23 * 60 + 30));
+        assert_eq!(parse_clock("7"), Some(7 * 60));
+        assert_eq!(parse_clock("24:00"), None);
+        assert_eq!(parse_utc_offset(""), Some(0));
+        assert_eq!(parse_utc_offset("+02:00"), Some(120));
+        assert_eq!(parse_utc_offset("UTC-5:30"), Some(-330));
+        assert_eq!(parse_utc_offset("+15"), None);
+
+        // 23:00 to 07:00 at UTC+02:00 is 21:00 to 05:00 UTC
+        let quiet = QuietHours {
+            start: 23 * 60,
+            end: 7 * 60,
+            utc_offset: 120,
+        };
+        let day = 10 * DAY_SECS;
+        assert_eq!(quiet.ends_after(day + 20 * 3600), None);
+        assert_eq!(
+            quiet.ends_after(day + 22 * 3600),
+            Some(day + DAY_SECS + 5 * 3600)
+        );
+        assert_eq!(quiet.ends_after(day + 3600), Some(day + 5 * 3600));
+        assert_eq!(quiet.ends_after(day + 5 * 3600), None);
+        let daytime = QuietHours {
+            start: 9 * 60,
+            end: 17 * 60,
+            utc_offset: 0,
+        };
+        assert_eq!(daytime.ends_after(day + 12 * 3600), Some(day + 17 * 3600));
+        assert_eq!(daytime.ends_after(day + 18 * 3600), None);
+        assert_eq!(quiet.describe(), "23:00–07:00 UTC+02:00");
+
+        let data = data();
+        let guild = GuildId::new(9);
+        let mut session = session_in(2);
+        session.guild_id = Some(guild);
+        session.add_vote(UserId::new(10), VoteSource::Button, 3);
+        let session_id = MessageId::new(1);
+        data.voting_sessions.insert(session_id, session);
+        assert_eq!(data.hold_for_quiet_hours(session_id, day + 22 * 3600), None);
+
+        data.update_guild_config(guild, |config| config.quiet_hours = Some(quiet))
+            .unwrap();
+        assert!(matches!(
+            data.record_vote(session_id, UserId::new(11), VoteSource::Button, 1),
+            VoteOutcome::Passed { .. }
+        ));
+        let ends = day + DAY_SECS + 5 * 3600;
+        assert_eq!(
+            data.hold_for_quiet_hours(session_id, day + 22 * 3600),
+            Some((ends, ChannelId::new(2)))
+        );
+        let session = data.voting_sessions.get(&session_id).unwrap();
+        assert!(session.is_finalizing() && !session.is_pinning());
+        drop(session);
+
+        // Held votes outlive their timeout
+        data.set_config(Config {
+            vote_timeout_secs: 0,
+            ..Config::default()
+        });
+        data.cleanup_expired_sessions();
+        assert!(data.voting_sessions.contains_key(&session_id));
+        assert_eq!(data.quiet_pins.lock().unwrap().as_slice(), [session_id]);
+    }
+
+    #[test]
+    fn replies_vote_only_with_a_bare_yes_or_no() {
+        for approval in ["+1", " ✅ ", "yes", "YES"] {
+            assert_eq!(parse_text_vote(approval), Some(true), "{}", approval);
+        }
+        for rejection in ["-1", "no", "No"] {
+            assert_eq!(parse_text_vote(rejection), Some(false), "{}", rejection);
+        }
+        for chatter in ["", "yes please", "+1 nice", "nope", "✅✅"] {
+            assert_eq!(parse_text_vote(chatter), None, "{}", chatter);
+        }
+
+        // A reply and a reaction from one member are one vote
+        let mut session = session();
+        let user = UserId::new(10);
+        assert!(session.add_vote(user, VoteSource::Reply, 1));
+        assert!(!session.add_vote(user, VoteSource::Reaction(Checkmark::WhiteHeavy), 1));
+        assert_eq!(session.get_vote_count(), 1);
+    }
+
+    fn data_with_caps(per_channel: usize, overall: usize) -> BotData {
+        let config = Config {
+            max_sessions_per_channel: per_channel,
+            max_sessions: overall,
+            ..Config::default()
+        };
+        BotData::new(config, Store::open(":memory:").unwrap())
+    }
+
+    fn session_in(channel: u64) -> VotingSession {
+        VotingSession::new(
+            MessageId::new(1),
+            ChannelId::new(channel),
+            ChannelId::new(channel),
+            None,
+            UserId::new(3),
+            3,
+        )
+    }
+
+    #[test]
+    fn orphaned_votes_are_recognized_by_their_mention_or_link_and_checkmark() {
+        let (guild_id, bot_id) = (GuildId::new(1), UserId::new(2));
+        let checkmark = |me: bool| -> serenity::all::MessageReaction {
+            serde_json::from_value(serde_json::json!({
+                "count": 3,
+                "count_details": { "burst": 0, "normal": 3 },
+                "me": me,
+                "me_burst": false,
+                "emoji": { "name": "✅" },
+                "burst_colors": [],
+            }))
+            .unwrap()
+        };
+
+        let mut request = Message::default();
+        request.author.id = UserId::new(7);
+        assert!(orphaned_vote(&request, guild_id, bot_id).is_err());
+        request.mentions = vec![User::default()];
+        request.mentions[0].id = bot_id;
+        request.reactions = vec![checkmark(false)];
+        assert!(orphaned_vote(&request, guild_id, bot_id)
+            .unwrap_err()
+            .contains("my ✅"));
+        request.reactions = vec![checkmark(true)];
+        assert!(orphaned_vote(&request, guild_id, bot_id).is_err());
+        request.message_reference = Some(
+            serde_json::from_value(serde_json::json!({
+                "message_id": "30",
+                "channel_id": "20",
+            }))
+            .unwrap(),
+        );
+        assert_eq!(
+            orphaned_vote(&request, guild_id, bot_id),
+            Ok(OrphanedVote {
+                channel_id: ChannelId::new(20),
+                message_id: MessageId::new(30),
+                initiator_id: Some(UserId::new(7)),
+            })
+        );
+
+        let mut posted = Message::default();
+        posted.author.id = bot_id;
+        posted.reactions = vec![checkmark(true)];
+        posted.content =
+            "Vote to pin https://discord.com/channels/9/20/30! 3 approvals needed.".to_string();
+        // A link into another server is no target
+        assert!(orphaned_vote(&posted, guild_id, bot_id).is_err());
+        posted.content = posted.content.replace("/9/", "/1/");
+        assert_eq!(
+            orphaned_vote(&posted, guild_id, bot_id)
+                .map(|vote| (vote.message_id, vote.initiator_id)),
+            Ok((MessageId::new(30), None))
+        );
+    }
+
+    #[test]
+    fn a_guild_is_onboarded_once_unless_the_bot_is_added_again() {
+        let data = data();
+        let guild_id = GuildId::new(40);
+        // A guild from Ready that never got a record, as when the bot
+        // joined while offline
+        assert!(data.claim_onboarding(guild_id, Some(false)));
+        assert!(data.store.load_guild_config(guild_id).unwrap().is_some());
+        assert!(!data.claim_onboarding(guild_id, Some(false)));
+        assert!(!data.claim_onboarding(guild_id, None));
+        // Kicked and invited back
+        assert!(data.claim_onboarding(guild_id, Some(true)));
+    }
+
+    #[test]
+    fn the_setup_message_names_the_threshold_and_the_ways_to_ask() {
+        let message = onboarding_message(UserId::new(9), 3, false);
+        assert!(message.contains("mention <@9>"));
+        assert!(message.contains("3 ✅ votes"));
+        assert!(message.contains("`/help`"));
+
+        let message = onboarding_message(UserId::new(9), 1, true);
+        assert!(!message.contains("<@9>"));
+        assert!(message.contains("1 ✅ vote."));
+    }
+
+    #[test]
+    fn a_reload_applies_live_settings_and_leaves_open_votes_alone() {
+        let data = data();
+        assert!(data.reload_config().is_err());
+
+        data.config_loader
+            .set(Box::new(|| {
+                Ok(Config {
+                    confirm_cap: 5,
+                    database_path: "elsewhere.db".to_string(),
+                    ..Config::default()
+                })
+            }))
+            .unwrap_or_else(|_| panic!("loader already set"));
+        data.insert_session(MessageId::new(100), session_in(5))
+            .unwrap();
+
+        let reload = data.reload_config().unwrap();
+        assert_eq!(reload.applied, ["confirm_cap: 3 → 5"]);
+        assert_eq!(reload.pending, ["database_path"]);
+        assert_eq!(data.config().confirm_cap, 5);
+        assert_eq!(data.config().database_path, "pin-bot.db");
+        assert_eq!(
+            data.voting_sessions
+                .get(&MessageId::new(100))
+                .unwrap()
+                .threshold,
+            3
+        );
+        // Nothing new the second time, the restart-only change still waits
+        let reload = data.reload_config().unwrap();
+        assert!(reload.applied.is_empty());
+        assert_eq!(reload.pending, ["database_path"]);
+    }
+
+    #[test]
+    fn global_cap_evicts_the_oldest_sessions_first() {
+        let data = data_with_caps(10, 2);
+        for id in 1..=4 {
+            let mut session = session_in(id);
+            session.guild_id = Some(GuildId::new(9));
+            data.insert_session(MessageId::new(id), session).unwrap();
+        }
+
+        assert_eq!(data.voting_sessions.len(), 2);
+        assert!(!data.voting_sessions.contains_key(&MessageId::new(1)));
+        assert!(!data.voting_sessions.contains_key(&MessageId::new(2)));
+        assert!(data.voting_sessions.contains_key(&MessageId::new(3)));
+        assert!(data.voting_sessions.contains_key(&MessageId::new(4)));
+        // Evicted sessions give their channel slot back
+        assert!(!data.channel_session_counts.contains_key(&ChannelId::new(1)));
+        // and end like any other vote, stats and notice included
+        let stats = data
+            .store
+            .vote_stats_between(GuildId::new(9), 0, i64::MAX)
+            .unwrap();
+        assert_eq!(stats.len(), 2);
+        assert!(stats.iter().all(|vote| vote.ending == VoteEnding::Evicted));
+        let notified: Vec<MessageId> = data
+            .failed_votes
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, session_id, _)| *session_id)
+            .collect();
+        assert_eq!(notified, [MessageId::new(1), MessageId::new(2)]);
+    }
+
+    #[test]
+    fn a_refused_session_leaves_no_channel_count_behind() {
+        let data = data_with_caps(0, 10);
+        assert!(data
+            .insert_session(MessageId::new(1), session_in(5))
+            .is_err());
+        assert!(!data.channel_session_counts.contains_key(&ChannelId::new(5)));
+
+        let data = data_with_caps(1, 10);
+        data.insert_session(MessageId::new(1), session_in(5))
+            .unwrap();
+        assert!(data
+            .insert_session(MessageId::new(2), session_in(5))
+            .is_err());
+        assert_eq!(
+            data.channel_session_counts
+                .get(&ChannelId::new(5))
+                .map(|open| *open),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn eviction_skips_sessions_that_already_ended() {
+        let data = data_with_caps(10, 2);
+        data.insert_session(MessageId::new(1), session_in(1))
+            .unwrap();
+        data.insert_session(MessageId::new(2), session_in(2))
+            .unwrap();
+        data.remove_session(MessageId::new(1));
+        data.insert_session(MessageId::new(3), session_in(3))
+            .unwrap();
+        data.insert_session(MessageId::new(4), session_in(4))
+            .unwrap();
+
+        assert!(!data.voting_sessions.contains_key(&MessageId::new(2)));
+        assert!(data.voting_sessions.contains_key(&MessageId::new(3)));
+        assert!(data.voting_sessions.contains_key(&MessageId::new(4)));
+    }
+
+    #[test]
+    fn initiators_are_capped_until_a_vote_ends_however_it_ends() {
+        let data = data_with_caps(10, 100);
+        let member = UserId::new(3);
+        let guild = Some(GuildId::new(9));
+        for (id, channel) in [(1, 7), (2, 8), (3, 9)] {
+            data.insert_session(MessageId::new(id), session_in(channel))
+                .unwrap();
+        }
+
+        let refusal = data.initiator_capacity(guild, member, false).unwrap_err();
+        assert!(refusal.contains("https://discord.com/channels/9/8/2"));
+        assert!(data.initiator_capacity(guild, member, true).is_ok());
+        assert!(data
+            .initiator_capacity(guild, UserId::new(4), false)
+            .is_ok());
+
+        data.remove_session(MessageId::new(1));
+        assert!(data.initiator_capacity(guild, member, false).is_ok());
+        let mut quick = session_in(7);
+        quick.timeout = Some(Duration::ZERO);
+        data.insert_session(MessageId::new(4), quick).unwrap();
+        assert!(data.initiator_capacity(guild, member, false).is_err());
+
+        // Expiry in the cleanup task frees the slot too
+        data.cleanup_expired_sessions();
+        assert!(data.initiator_capacity(guild, member, false).is_ok());
+        data.remove_session(MessageId::new(2));
+        data.remove_session(MessageId::new(3));
+        assert!(data.initiator_session_counts.is_empty());
+    }
+
+    #[test]
+    fn full_channel_refuses_new_sessions_until_one_ends() {
+        let data = data_with_caps(2, 100);
+        let channel = ChannelId::new(7);
+        data.insert_session(MessageId::new(1), session_in(7))
+            .unwrap();
+        data.insert_session(MessageId::new(2), session_in(7))
+            .unwrap();
+
+        assert!(data.session_capacity(channel).is_err());
+        assert!(data
+            .insert_session(MessageId::new(3), session_in(7))
+            .is_err());
+        assert!(!data.voting_sessions.contains_key(&MessageId::new(3)));
+        // Other channels are unaffected
+        assert!(data
+            .insert_session(MessageId::new(4), session_in(8))
+            .is_ok());
+
+        data.remove_session(MessageId::new(1));
+        assert!(data.session_capacity(channel).is_ok());
+        assert!(data
+            .insert_session(MessageId::new(5), session_in(7))
+            .is_ok());
+        assert_eq!(
+            data.channel_session_counts.get(&channel).map(|c| *c),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn removing_an_unrecorded_source_is_a_no_op() {
+        let mut session = session();
+        let user = UserId::new(10);
+
+        assert!(!session.remove_vote(user, VoteSource::Button));
+        session.add_vote(user, VoteSource::Button, 1);
+        assert!(!session.remove_vote(user, VoteSource::Reaction(Checkmark::Heavy)));
+        assert_eq!(session.get_vote_count(), 1);
+    }
+
+    #[test]
+    fn the_leading_category_picks_the_archive_and_ties_fall_back() {
+        let memes = ReactionType::Unicode("😂".to_string());
+        let useful = ReactionType::Unicode("📚".to_string());
+        let mut session = session();
+        session.default_archive = Some(ChannelId::new(100));
+        session.categories = [(&memes, 101), (&useful, 102)]
+            .into_iter()
+            .map(|(emoji, channel)| {
+                CategoryTally::from(&ArchiveCategory {
+                    emoji: emoji.clone(),
+                    channel_id: ChannelId::new(channel),
+                })
+            })
+            .collect();
+
+        assert_eq!(session.archive_channel(), Some(ChannelId::new(100)));
+
+        assert!(session.tally_category(&memes, UserId::new(10), true));
+        assert!(!session.tally_category(&memes, UserId::new(10), true));
+        assert_eq!(session.archive_channel(), Some(ChannelId::new(101)));
+
+        session.tally_category(&useful, UserId::new(11), true);
+        assert_eq!(session.archive_channel(), Some(ChannelId::new(100)));
+
+        session.tally_category(&memes, UserId::new(10), false);
+        assert_eq!(session.archive_channel(), Some(ChannelId::new(102)));
+        assert!(!session.tally_category(&'✅'.into(), UserId::new(10), true));
+    }
+
+    /// Records every call and answers from canned state instead of Discord.
+    #[derive(Default)]
+    struct MockApi {
+        pins: Mutex<Vec<(ChannelId, MessageId)>>,
+        unpins: Mutex<Vec<(ChannelId, MessageId)>>,
+        unreactions: Mutex<Vec<(ChannelId, MessageId)>>,
+        reactions: Mutex<Vec<(MessageId, ReactionType)>>,
+        /// Reported by `message()`, for the already-pinned check.
+        pinned: bool,
+        /// Fail every call as Discord would on a missing permission.
+        fail: bool,
+    }
+
+    impl MockApi {
+        fn check(&self) -> Option<serenity::Error> {
+            self.fail.then_some(serenity::Error::Other("mock failure"))
+        }
+
+        fn pin_count(&self) -> usize {
+            self.pins.lock().unwrap().len()
+        }
+    }
+
+    #[async_trait]
+    impl PinApi for MockApi {
+        async fn pin(&self, channel_id: ChannelId, message_id: MessageId) -> serenity::Result<()> {
+            if let Some(e) = self.check() {
+                return Err(e);
+            }
+            self.pins.lock().unwrap().push((channel_id, message_id));
+            Ok(())
+        }
+
+        async fn unpin(
+            &self,
+            channel_id: ChannelId,
+            message_id: MessageId,
+        ) -> serenity::Result<()> {
+            if let Some(e) = self.check() {
+                return Err(e);
+            }
+            self.unpins.lock().unwrap().push((channel_id, message_id));
+            Ok(())
+        }
+
+        async fn react(
+            &self,
+            _channel_id: ChannelId,
+            message_id: MessageId,
+            reaction: ReactionType,
+        ) -> serenity::Result<()> {
+            if let Some(e) = self.check() {
+                return Err(e);
+            }
+            self.reactions.lock().unwrap().push((message_id, reaction));
+            Ok(())
+        }
+
+        async fn unreact(
+            &self,
+            channel_id: ChannelId,
+            message_id: MessageId,
+            _reaction: ReactionType,
+        ) -> serenity::Result<()> {
+            if let Some(e) = self.check() {
+                return Err(e);
+            }
+            self.unreactions
+                .lock()
+                .unwrap()
+                .push((channel_id, message_id));
+            Ok(())
+        }
+
+        async fn message(
+            &self,
+            channel_id: ChannelId,
+            message_id: MessageId,
+        ) -> serenity::Result<Message> {
+            if let Some(e) = self.check() {
+                return Err(e);
+            }
+            let mut message = Message::default();
+            message.id = message_id;
+            message.channel_id = channel_id;
+            message.pinned = self.pinned;
+            Ok(message)
+        }
+    }
+
+    fn data() -> BotData {
+        BotData::new(Config::default(), Store::open(":memory:").unwrap())
+    }
+
+    #[test]
+    fn interaction_only_mode_asks_for_no_message_content() {
+        assert!(intents(false, false).contains(GatewayIntents::MESSAGE_CONTENT));
+        assert!(!intents(true, false).contains(GatewayIntents::MESSAGE_CONTENT));
+        // Deleted votes and targets end their votes in either mode
+        assert!(intents(true, false).contains(GatewayIntents::GUILD_MESSAGES));
+        assert!(intents(true, false).contains(GatewayIntents::GUILD_MESSAGE_REACTIONS));
+        // DMs only ever get a pointer to a server, and never count as votes
+        assert!(intents(false, false).contains(GatewayIntents::DIRECT_MESSAGES));
+        assert!(intents(true, false).contains(GatewayIntents::GUILD_MESSAGE_POLLS));
+        assert!(!intents(true, false).contains(GatewayIntents::DIRECT_MESSAGES));
+        assert!(!intents(false, false).contains(GatewayIntents::DIRECT_MESSAGE_REACTIONS));
+        // Server Members is only asked for when departures are watched
+        assert!(!intents(false, false).contains(GatewayIntents::GUILD_MEMBERS));
+        assert!(intents(true, true).contains(GatewayIntents::GUILD_MEMBERS));
+        assert!(server_members_granted(
+            ApplicationFlags::GATEWAY_GUILD_MEMBERS
+        ));
+        assert!(!server_members_granted(
+            ApplicationFlags::GATEWAY_MESSAGE_CONTENT
+        ));
+
+        assert!(message_content_granted(
+            ApplicationFlags::GATEWAY_MESSAGE_CONTENT_LIMITED
+        ));
+        assert!(!message_content_granted(ApplicationFlags::empty()));
+    }
+
+    #[test]
+    fn channel_thresholds_win_over_the_guild_and_the_default() {
+        let guild = GuildId::new(1);
+        let mut config = Config {
+            confirm_cap: 4,
+            ..Config::default()
+        };
+        config.guilds.insert(
+            guild,
+            config::GuildOverrides {
+                confirm_cap: Some(2),
+                ..Default::default()
+            },
+        );
+        let data = BotData::new(config, Store::open(":memory:").unwrap());
+        data.update_guild_config(guild, |config| {
+            config.channel_thresholds.insert(ChannelId::new(10), 8);
+        })
+        .unwrap();
+
+        assert_eq!(data.threshold(Some(guild), &[ChannelId::new(10)]), 8);
+        // A thread inherits its parent's override
+        let thread_lineage = [ChannelId::new(11), ChannelId::new(10)];
+        assert_eq!(data.threshold(Some(guild), &thread_lineage), 8);
+        assert_eq!(data.threshold(Some(guild), &[ChannelId::new(12)]), 2);
+        assert_eq!(
+            data.threshold(Some(GuildId::new(2)), &[ChannelId::new(10)]),
+            4
+        );
+        assert_eq!(data.threshold(None, &[]), 4);
+    }
+
+    #[test]
+    fn daily_pin_quota_counts_voted_pins_since_utc_midnight() {
+        let data = data();
+        let guild = GuildId::new(9);
+        let channel = ChannelId::new(2);
+        let midnight = 20_000 * digest::DAY_SECS;
+        for (id, pinned_at) in [(1, midnight - 60), (2, midnight + 60), (3, midnight + 120)] {
+            data.store
+                .record_pin(&PinRecord {
+                    guild_id: guild,
+                    channel_id: channel,
+                    message_id: MessageId::new(id),
+                    author_id: Some(UserId::new(3)),
+                    excerpt: String::new(),
+                    votes: 3,
+                    pinned_at,
+                    reason: None,
+                    voters: 0,
+                })
+                .unwrap();
+        }
+
+        // Unlimited until the guild sets a quota
+        assert_eq!(data.daily_pin_quota(Some(guild), &[channel]), 0);
+        data.update_guild_config(guild, |config| {
+            config.daily_pin_quota = Some(3);
+            config.channel_pin_quotas.insert(ChannelId::new(5), 0);
+        })
+        .unwrap();
+        assert_eq!(data.daily_pin_quota(Some(guild), &[channel]), 3);
+        // A thread inherits its parent's override
+        let thread_lineage = [ChannelId::new(6), ChannelId::new(5)];
+        assert_eq!(data.daily_pin_quota(Some(guild), &thread_lineage), 0);
+
+        // Yesterday's pin doesn't count toward today's two
+        let now = midnight + 3600;
+        assert_eq!(data.over_pin_quota(&[(channel, 3)], now), None);
+        assert_eq!(
+            data.over_pin_quota(&[(channel, 3), (channel, 3)], now),
+            Some((channel, 3))
+        );
+        assert_eq!(
+            data.over_pin_quota(&[(channel, 2)], now),
+            Some((channel, 2))
+        );
+        assert_eq!(data.over_pin_quota(&[(channel, 0)], now), None);
+    }
+
+    #[test]
+    fn votes_landing_too_fast_hold_the_session_until_reviewed() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        // Without a guard nothing is held, however fast the votes come
+        let mut organic = session();
+        assert!((0..50).all(|_| !organic.note_vote_at(start)));
+
+        let mut spaced = session();
+        spaced.brigade_guard = Some(BrigadeGuard {
+            votes: 2,
+            window_secs: 10,
+        });
+        assert!(!spaced.note_vote_at(at(0)));
+        assert!(!spaced.note_vote_at(at(6)));
+        assert!(!spaced.note_vote_at(at(12)));
+        assert!(spaced.note_vote_at(at(13)));
+        spaced.reviewed = true;
+        assert!(!spaced.note_vote_at(at(13)));
+
+        let data = data();
+        let mut held = session();
+        held.brigade_guard = Some(BrigadeGuard {
+            votes: 2,
+            window_secs: 60,
+        });
+        data.voting_sessions.insert(MessageId::new(100), held);
+        let vote = |user: u64| {
+            data.record_vote(
+                MessageId::new(100),
+                UserId::new(user),
+                VoteSource::Button,
+                1,
+            )
+        };
+        assert_eq!(vote(10), VoteOutcome::Counted(1));
+        assert_eq!(vote(11), VoteOutcome::Counted(2));
+        // Enough to pass, but held rather than pinned
+        assert_eq!(vote(12), VoteOutcome::Flagged(3));
+        assert_eq!(vote(13), VoteOutcome::Paused);
+        assert_eq!(
+            data.record_rejection(MessageId::new(100), UserId::new(14), 1),
+            VoteOutcome::Paused
+        );
+
+        assert!(matches!(
+            data.resume_session(MessageId::new(100)),
+            Some(VoteOutcome::Passed { votes: 3, .. })
+        ));
+        assert_eq!(data.resume_session(MessageId::new(100)), None);
+    }
+
+    #[test]
+    fn passed_votes_in_approval_channels_wait_for_a_moderator() {
+        let data = data();
+        let guild_id = GuildId::new(50);
+        data.update_guild_config(guild_id, |config| {
+            config.approval_channels.insert(ChannelId::new(2));
+        })
+        .unwrap();
+        let wait = Duration::from_secs(Config::default().approval_timeout_secs);
+        assert_eq!(
+            data.approval_timeout(Some(guild_id), &[ChannelId::new(7), ChannelId::new(2)]),
+            Some(wait)
+        );
+        assert_eq!(
+            data.approval_timeout(Some(guild_id), &[ChannelId::new(7)]),
+            None
+        );
+        assert_eq!(data.approval_timeout(None, &[ChannelId::new(2)]), None);
+
+        let mut gated = session();
+        gated.approval_timeout = Some(wait);
+        data.voting_sessions.insert(MessageId::new(100), gated);
+        let vote = |user: u64| {
+            data.record_vote(
+                MessageId::new(100),
+                UserId::new(user),
+                VoteSource::Button,
+                1,
+            )
+        };
+        vote(10);
+        vote(11);
+        assert!(matches!(vote(12), VoteOutcome::Passed { votes: 3, .. }));
+
+        // Passing hands it to the moderators instead of pinning
+        assert!(data.await_approval(MessageId::new(100)).is_some());
+        assert_eq!(data.await_approval(MessageId::new(100)), None);
+        {
+            let session = data.voting_sessions.get(&MessageId::new(100)).unwrap();
+            assert_eq!(session.outcome(), VoteOutcome::Counted(3));
+            assert!(!session.is_finalizing());
+            assert!(session.timeout.is_some_and(|timeout| timeout >= wait));
+        }
+        assert_eq!(vote(13), VoteOutcome::Counted(4));
+
+        assert!(matches!(
+            data.grant_approval(MessageId::new(100)),
+            Some(VoteOutcome::Passed { votes: 4, .. })
+        ));
+        assert_eq!(data.grant_approval(MessageId::new(100)), None);
+        assert_eq!(data.await_approval(MessageId::new(100)), None);
+
+        assert_eq!(
+            parse_approval_button("pin_approval:approve:100"),
+            Some((true, MessageId::new(100)))
+        );
+        assert_eq!(
+            parse_approval_button("pin_approval:reject:100"),
+            Some((false, MessageId::new(100)))
+        );
+        assert_eq!(parse_approval_button("pin_review:approve:100"), None);
+    }
+
+    #[test]
+    fn vote_threads_are_named_from_the_template_and_retired_with_the_vote() {
+        assert_eq!(
+            vote_thread_name("Pin vote: {excerpt}", "  the   best\nmeme "),
+            "Pin vote: the best meme"
+        );
+        assert_eq!(
+            vote_thread_name("Vote on {excerpt}", ""),
+            "Vote on a message"
+        );
+        let long = vote_thread_name("{excerpt}", &"word ".repeat(60));
+        assert_eq!(long.chars().count(), MAX_THREAD_NAME_CHARS);
+        assert!(long.ends_with('…'));
+
+        let data = data();
+        let mut threaded = session();
+        threaded.thread_id = Some(ChannelId::new(40));
+        data.voting_sessions.insert(MessageId::new(100), threaded);
+        assert!(data.stale_threads.lock().unwrap().is_empty());
+        data.remove_session(MessageId::new(100));
+        assert_eq!(*data.stale_threads.lock().unwrap(), [ChannelId::new(40)]);
+    }
+
+    #[test]
+    fn failed_crossposts_are_retried_once_when_due() {
+        let data = data();
+        let now = Instant::now();
+        let pending = |id: u64, secs: u64| PendingCrosspost {
+            guild_id: GuildId::new(9),
+            channel_id: ChannelId::new(2),
+            message_id: MessageId::new(id),
+            retry_at: now + Duration::from_secs(secs),
+        };
+        data.pending_crossposts
+            .lock()
+            .unwrap()
+            .extend([pending(1, 0), pending(2, 60)]);
+
+        assert_eq!(data.take_due_crossposts(now), [pending(1, 0)]);
+        assert!(data.take_due_crossposts(now).is_empty());
+        let later = now + Duration::from_secs(60);
+        assert_eq!(data.take_due_crossposts(later), [pending(2, 60)]);
+        assert!(data.pending_crossposts.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn votes_passing_early_wait_out_the_minimum_time() {
+        let data = data();
+        let mut early = session();
+        early.min_open = Duration::from_secs(600);
+        data.voting_sessions.insert(MessageId::new(100), early);
+        let vote = |user: u64| {
+            data.record_vote(
+                MessageId::new(100),
+                UserId::new(user),
+                VoteSource::Button,
+                1,
+            )
+        };
+        vote(10);
+        vote(11);
+        assert_eq!(vote(12), VoteOutcome::Deferred(3));
+
+        // Only the first of the votes past the threshold starts the wait
+        let (wait, _) = data.schedule_deferred_pin(MessageId::new(100)).unwrap();
+        assert!(wait > Duration::from_secs(590));
+        assert_eq!(vote(13), VoteOutcome::Deferred(4));
+        assert_eq!(data.schedule_deferred_pin(MessageId::new(100)), None);
+
+        // Withdrawn votes call the pin off once the wait is over
+        data.retract_vote(MessageId::new(100), UserId::new(12), VoteSource::Button);
+        data.retract_vote(MessageId::new(100), UserId::new(13), VoteSource::Button);
+        data.voting_sessions
+            .get_mut(&MessageId::new(100))
+            .unwrap()
+            .min_open = Duration::ZERO;
+        assert_eq!(
+            data.deferred_pin_due(MessageId::new(100)),
+            Some(VoteOutcome::Counted(2))
+        );
+        assert!(matches!(vote(12), VoteOutcome::Passed { votes: 3, .. }));
+    }
+
+    #[test]
+    fn vote_messages_carry_a_deadline_that_can_come_off_again() {
+        let mut session = session();
+        session.started_at = 1_000;
+        let expires_at = session.expires_at(Duration::from_secs(3_600));
+        assert_eq!(expires_at, 4_600);
+
+        let text = "Vote to pin this message! 3 approvals needed.\nReason: raid recap";
+        let shown = with_deadline(text, expires_at);
+        assert!(shown.ends_with("<t:4600:R>"));
+        assert_eq!(strip_deadline(&shown), text);
+        assert_eq!(strip_deadline(text), text);
+    }
+
+    #[test]
+    fn open_votes_keep_the_threshold_they_started_with() {
+        let guild = GuildId::new(1);
+        let channel = ChannelId::new(2);
+        let data = BotData::new(
+            Config {
+                confirm_cap: 3,
+                ..Config::default()
+            },
+            Store::open(":memory:").unwrap(),
+        );
+        let open = |session_id: u64, data: &BotData| {
+            let threshold = data.threshold(Some(guild), &[channel]);
+            let session = VotingSession::new(
+                MessageId::new(session_id),
+                channel,
+                channel,
+                Some(guild),
+                UserId::new(3),
+                threshold,
+            );
+            data.insert_session(MessageId::new(session_id), session)
+                .unwrap();
+        };
+        let vote = |session_id: u64, user: u64| {
+            data.record_vote(
+                MessageId::new(session_id),
+                UserId::new(user),
+                VoteSource::Button,
+                1,
+            )
+        };
+
+        open(100, &data);
+        // Raising the default and the channel's threshold mid-vote
+        data.set_config(Config {
+            confirm_cap: 5,
+            ..Config::default()
+        });
+        data.update_guild_config(guild, |config| {
+            config.channel_thresholds.insert(channel, 6);
+        })
+        .unwrap();
+        open(200, &data);
+        // ...then lowering it again before anyone votes on the second
+        data.update_guild_config(guild, |config| {
+            config.channel_thresholds.insert(channel, 2);
+        })
+        .unwrap();
+
+        for user in 10..12 {
+            assert!(matches!(vote(100, user), VoteOutcome::Counted(_)));
+            assert!(matches!(vote(200, user), VoteOutcome::Counted(_)));
+        }
+        assert!(matches!(
+            vote(100, 12),
+            VoteOutcome::Passed { votes: 3, .. }
+        ));
+        for user in 12..15 {
+            assert!(matches!(vote(200, user), VoteOutcome::Counted(_)));
+        }
+        assert!(matches!(
+            vote(200, 15),
+            VoteOutcome::Passed { votes: 6, .. }
+        ));
+    }
+
+    #[test]
+    fn votes_pass_once_the_threshold_is_reached() {
+        let data = data();
+        let session_id = MessageId::new(100);
+        data.insert_session(session_id, session()).unwrap();
+        let white = VoteSource::Reaction(Checkmark::WhiteHeavy);
+
+        assert_eq!(
+            data.record_vote(session_id, UserId::new(10), white, 1),
+            VoteOutcome::Counted(1)
+        );
+        assert_eq!(
+            data.record_vote(session_id, UserId::new(11), VoteSource::Button, 2),
+            VoteOutcome::Passed {
+                votes: 3,
+                channel_id: ChannelId::new(2),
+                message_id: MessageId::new(1),
+            }
+        );
+        assert_eq!(
+            data.record_vote(MessageId::new(101), UserId::new(10), white, 1),
+            VoteOutcome::NoSession
+        );
+    }
+
+    #[test]
+    fn only_one_of_many_simultaneous_votes_finalizes() {
+        let data = data();
+        let session_id = MessageId::new(100);
+        data.insert_session(session_id, session()).unwrap();
+
+        // Twelve voters race past a threshold of three
+        let outcomes: Vec<VoteOutcome> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (10..22)
+                .map(|user| {
+                    let data = &data;
+                    scope.spawn(move || {
+                        data.record_vote(session_id, UserId::new(user), VoteSource::Button, 1)
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        let passed = outcomes
+            .iter()
+            .filter(|outcome| matches!(outcome, VoteOutcome::Passed { .. }))
+            .count();
+        assert_eq!(passed, 1);
+        assert_eq!(
+            data.voting_sessions
+                .get(&session_id)
+                .unwrap()
+                .get_vote_count(),
+            12
+        );
+
+        // A failed pin hands the vote back, and the next vote claims it again
+        data.voting_sessions
+            .get(&session_id)
+            .unwrap()
+            .release_finalization();
+        assert!(matches!(
+            data.record_vote(session_id, UserId::new(30), VoteSource::Button, 1),
+            VoteOutcome::Passed { votes: 13, .. }
+        ));
+    }
+
+    #[test]
+    fn duplicate_votes_and_retractions_leave_the_count_alone() {
+        let data = data();
+        let session_id = MessageId::new(100);
+        data.insert_session(session_id, session()).unwrap();
+        let user = UserId::new(10);
+
+        assert_eq!(
+            data.record_vote(session_id, user, VoteSource::Button, 1),
+            VoteOutcome::Counted(1)
+        );
+        assert_eq!(
+            data.record_vote(session_id, user, VoteSource::Button, 1),
+            VoteOutcome::Duplicate
+        );
+        assert_eq!(
+            data.retract_vote(session_id, UserId::new(11), VoteSource::Button),
+            VoteOutcome::Duplicate
+        );
+        assert_eq!(
+            data.retract_vote(session_id, user, VoteSource::Button),
+            VoteOutcome::Counted(0)
+        );
+        assert_eq!(
+            data.retract_vote(session_id, user, VoteSource::Button),
+            VoteOutcome::Duplicate
+        );
+    }
+
+    #[test]
+    fn cleanup_drops_expired_sessions_and_frees_their_slots() {
+        let config = Config {
+            vote_timeout_secs: 60,
+            ..Config::default()
+        };
+        let data = BotData::new(config, Store::open(":memory:").unwrap());
+        let mut stale = session_in(5);
+        stale.created_at = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        data.insert_session(MessageId::new(100), stale).unwrap();
+        data.insert_session(MessageId::new(101), session_in(6))
+            .unwrap();
+
+        data.cleanup_expired_sessions();
+
+        assert!(!data.voting_sessions.contains_key(&MessageId::new(100)));
+        assert!(data.voting_sessions.contains_key(&MessageId::new(101)));
+        assert!(data
+            .channel_session_counts
+            .get(&ChannelId::new(5))
+            .is_none());
+    }
+
+    #[test]
+    fn a_vote_expires_on_its_own_deadline_unless_held() {
+        let config = Config {
+            vote_timeout_secs: 60,
+            ..Config::default()
+        };
+        let data = BotData::new(config, Store::open(":memory:").unwrap());
+        let mut quick = session_in(5);
+        quick.timeout = Some(Duration::from_secs(10));
+        quick.created_at = Instant::now().checked_sub(Duration::from_secs(11)).unwrap();
+        let deadline = quick.created_at + Duration::from_secs(10);
+        data.insert_session(MessageId::new(100), quick).unwrap();
+        let mut held = session_in(6);
+        held.created_at = Instant::now().checked_sub(Duration::from_secs(61)).unwrap();
+        held.finalizing.store(true, Ordering::Release);
+        data.insert_session(MessageId::new(101), held).unwrap();
+
+        assert_eq!(data.session_deadline(MessageId::new(100)), Some(deadline));
+        assert!(data.expire_session(MessageId::new(100)));
+        assert_eq!(data.session_deadline(MessageId::new(100)), None);
+        assert!(!data.expire_session(MessageId::new(100)));
+        // A vote being pinned outlives its deadline
+        assert!(!data.expire_session(MessageId::new(101)));
+        assert!(data.voting_sessions.contains_key(&MessageId::new(101)));
+    }
+
+    #[test]
+    fn a_poll_vote_runs_whole_hours_and_outlasts_its_deadline_for_the_final_count() {
+        assert_eq!(
+            poll_duration(Duration::from_secs(600)),
+            Duration::from_secs(3600)
+        );
+        assert_eq!(
+            poll_duration(Duration::from_secs(3601)),
+            Duration::from_secs(7200)
+        );
+        assert_eq!(
+            poll_duration(Duration::from_secs(30 * 86_400)),
+            Duration::from_secs(168 * 3600)
+        );
+
+        let data = data();
+        let session_id = MessageId::new(100);
+        let poll = VotePoll {
+            message_id: MessageId::new(200),
+            yes: "1".parse().unwrap(),
+            no: "2".parse().unwrap(),
+        };
+        assert!(!data.attach_poll(session_id, poll, Duration::from_secs(3600)));
+        data.insert_session(session_id, session_in(5)).unwrap();
+        assert!(data.attach_poll(session_id, poll, Duration::from_secs(3600)));
+        assert_eq!(
+            data.session_for_poll(poll.message_id),
+            Some((session_id, poll))
+        );
+
+        data.record_vote(session_id, UserId::new(10), VoteSource::Poll, 1);
+        data.record_vote(session_id, UserId::new(11), VoteSource::Button, 1);
+        let session = data.voting_sessions.get(&session_id).unwrap().clone();
+        assert_eq!(session.vote_mode, VoteMode::Poll);
+        assert_eq!(session.poll_voters(), HashSet::from([UserId::new(10)]));
+
+        // Just past the poll's end it is expired, but cleanup waits out the
+        // grace for its collector to count the poll
+        let mut ended = session;
+        ended.created_at = Instant::now()
+            .checked_sub(Duration::from_secs(3601))
+            .unwrap();
+        assert!(ended.is_expired(Duration::from_secs(60)));
+        assert!(!ended.is_overdue(Duration::from_secs(60)));
+        ended.created_at -= POLL_TALLY_GRACE;
+        assert!(ended.is_overdue(Duration::from_secs(60)));
+
+        data.remove_session(session_id);
+        assert_eq!(data.session_for_poll(poll.message_id), None);
+        assert!(data.sessions_by_poll.is_empty());
+    }
+
+    #[test]
+    fn clearing_reactions_takes_back_only_the_votes_cast_there() {
+        let data = data();
+        let session_id = MessageId::new(100);
+        data.insert_session(session_id, session_in(5)).unwrap();
+        let white = Checkmark::WhiteHeavy;
+        data.record_vote(session_id, UserId::new(10), VoteSource::Reaction(white), 1);
+        data.record_vote(
+            session_id,
+            UserId::new(11),
+            VoteSource::TargetReaction(white),
+            1,
+        );
+        data.record_vote(session_id, UserId::new(12), VoteSource::Button, 1);
+
+        assert_eq!(data.retract_reaction_votes(session_id, false), 1);
+        assert_eq!(data.retract_reaction_votes(session_id, false), 0);
+        let votes = data
+            .voting_sessions
+            .get(&session_id)
+            .map(|session| session.get_vote_count());
+        assert_eq!(votes, Some(2));
+        assert_eq!(data.retract_reaction_votes(session_id, true), 1);
+    }
+
+    #[test]
+    fn imported_pins_keep_their_dates_and_import_only_once() {
+        let data = data();
+        let guild = GuildId::new(9);
+        let channel = ChannelId::new(2);
+        let pinned = |id: u64| {
+            let mut message = Message::default();
+            message.id = MessageId::new(id);
+            message.channel_id = channel;
+            message.author.id = UserId::new(3);
+            message.timestamp = Timestamp::from_unix_timestamp(1_600_000_000 + id as i64).unwrap();
+            message
+        };
+        data.store
+            .record_pin(&PinRecord {
+                guild_id: guild,
+                channel_id: channel,
+                message_id: MessageId::new(1),
+                author_id: Some(UserId::new(3)),
+                excerpt: String::new(),
+                votes: 3,
+                pinned_at: 0,
+                reason: None,
+                voters: 0,
+            })
+            .unwrap();
+        let pins = [pinned(1), pinned(5), pinned(6)];
+
+        assert_eq!(data.import_pins(guild, channel, &pins).unwrap(), 2);
+        assert_eq!(data.import_pins(guild, channel, &pins).unwrap(), 0);
+        let last_pinned = |id| data.store.last_pinned_at(MessageId::new(id)).unwrap();
+        assert_eq!(last_pinned(5), Some(1_600_000_005));
+        // The voted pin stays as it was, and imports stay out of the digest
+        assert_eq!(last_pinned(1), Some(0));
+        let voted = data.store.pins_in_channel(channel).unwrap();
+        assert_eq!(voted.len(), 1);
+
+        // The import took the snapshot, so the next pin is noticed
+        data.apply_pin_snapshot(
+            guild,
+            channel,
+            &[pinned(1), pinned(5), pinned(6), pinned(7)],
+        )
+        .unwrap();
+        assert!(data.store.is_recorded_pinned(MessageId::new(7)).unwrap());
+
+        let dates = data.store.pin_dates(channel).unwrap();
+        assert_eq!(dates[&MessageId::new(1)], (0, false));
+        assert_eq!(dates[&MessageId::new(5)], (1_600_000_005, true));
+    }
+
+    #[test]
+    fn top_pins_rank_by_votes_and_break_ties_by_pin_time() {
+        let data = data();
+        let guild = GuildId::new(9);
+        let record = |id: u64, channel: u64, votes: u32, pinned_at: i64| {
+            data.store
+                .record_pin(&PinRecord {
+                    guild_id: guild,
+                    channel_id: ChannelId::new(channel),
+                    message_id: MessageId::new(id),
+                    author_id: Some(UserId::new(3)),
+                    excerpt: String::new(),
+                    votes,
+                    voters: votes,
+                    pinned_at,
+                    reason: None,
+                })
+                .unwrap();
+        };
+        record(1, 2, 4, 300);
+        record(2, 2, 6, 100);
+        record(3, 5, 4, 200);
+        record(4, 2, 1, 400);
+        let ranked = |channel: Option<u64>, since: Option<i64>, limit| {
+            data.store
+                .top_pins(guild, channel.map(ChannelId::new), since, limit)
+                .unwrap()
+                .iter()
+                .map(|pin| pin.message_id.get())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ranked(None, None, 10), [2, 3, 1, 4]);
+        assert_eq!(ranked(None, None, 2), [2, 3]);
+        assert_eq!(ranked(Some(2), None, 10), [2, 1, 4]);
+        assert_eq!(ranked(None, Some(200), 10), [3, 1, 4]);
+    }
+
+    #[test]
+    fn channels_are_pruned_once_a_day() {
+        let data = data();
+        let guild = GuildId::new(9);
+        let retention = PinRetention {
+            days: 90,
+            keep_manual: true,
+        };
+        data.update_guild_config(guild, |config| {
+            config.pin_retention.insert(ChannelId::new(2), retention);
+        })
+        .unwrap();
+        let now = 1_700_000_000;
+
+        assert_eq!(
+            data.due_prunings(now),
+            [(guild, ChannelId::new(2), retention)]
+        );
+        data.store
+            .set_retention_last_run(ChannelId::new(2), now)
+            .unwrap();
+        assert!(data.due_prunings(now + digest::DAY_SECS - 1).is_empty());
+        assert_eq!(data.due_prunings(now + digest::DAY_SECS).len(), 1);
+    }
+
+    #[test]
+    fn pin_syncs_record_manual_pins_and_unpins() {
+        let data = data();
+        let guild = GuildId::new(9);
+        let channel = ChannelId::new(2);
+        let pinned = |id: u64| {
+            let mut message = Message::default();
+            message.id = MessageId::new(id);
+            message.channel_id = channel;
+            message.author.id = UserId::new(3);
+            message.content = format!("message {}", id);
+            message
+        };
+        let voted = |id: u64| {
+            data.store
+                .pins_in_channel(channel)
+                .unwrap()
+                .iter()
+                .any(|pin| pin.message_id == MessageId::new(id))
+        };
+        let recorded = |id: u64| data.store.is_recorded_pinned(MessageId::new(id)).unwrap();
+        data.store
+            .record_pin(&PinRecord {
+                guild_id: guild,
+                channel_id: channel,
+                message_id: MessageId::new(1),
+                author_id: Some(UserId::new(3)),
+                excerpt: String::new(),
+                votes: 3,
+                pinned_at: 0,
+                reason: None,
+                voters: 0,
+            })
+            .unwrap();
+
+        // The first look only takes a snapshot
+        assert!(!data
+            .apply_pin_snapshot(guild, channel, &[pinned(1), pinned(5)])
+            .unwrap());
+        assert!(!recorded(5));
+
+        assert!(!data
+            .apply_pin_snapshot(guild, channel, &[pinned(1), pinned(5), pinned(2)])
+            .unwrap());
+        assert!(recorded(2) && !voted(2));
+        assert!(voted(1));
+
+        // A moderator unpins the voted pin, then pins it again by hand
+        assert!(data
+            .apply_pin_snapshot(guild, channel, &[pinned(5), pinned(2)])
+            .unwrap());
+        assert!(!recorded(1));
+        assert!(!data
+            .apply_pin_snapshot(guild, channel, &[pinned(5), pinned(2), pinned(1)])
+            .unwrap());
+        assert!(recorded(1) && !voted(1));
+    }
+
+    #[test]
+    fn opted_out_authors_are_refused_everywhere_until_they_opt_back_in() {
+        let (handler, _shutdown_tx) = handler();
+        let mut target = Message::default();
+        target.author.id = UserId::new(3);
+        let vet = |guild_id: u64| {
+            handler.vet_target(Some(GuildId::new(guild_id)), &target, UserId::new(4), true)
+        };
+        assert_eq!(vet(9), Ok(()));
+
+        handler.data.set_opted_out(UserId::new(3), true).unwrap();
+        handler.data.set_opted_out(UserId::new(3), true).unwrap();
+        assert_eq!(vet(9), Err(OPTED_OUT_REFUSAL.to_string()));
+        assert_eq!(vet(10), Err(OPTED_OUT_REFUSAL.to_string()));
+        assert!(!handler.data.has_opted_out(UserId::new(4)));
+
+        handler.data.set_opted_out(UserId::new(3), false).unwrap();
+        assert_eq!(vet(9), Ok(()));
+    }
+
+    #[test]
+    fn locked_messages_refuse_votes_and_name_the_votes_to_cancel() {
+        let (handler, _shutdown_tx) = handler();
+        let data = &handler.data;
+        let guild = GuildId::new(9);
+        let mut target = Message::default();
+        target.id = MessageId::new(1);
+        target.author.id = UserId::new(3);
+
+        let mut batch = session_in(5);
+        batch.target_message_id = MessageId::new(7);
+        batch.batch.push(PinTarget {
+            channel_id: ChannelId::new(2),
+            message_id: MessageId::new(1),
+            author_id: None,
+            excerpt: String::new(),
+            snapshot: None,
+        });
+        data.voting_sessions.insert(MessageId::new(100), session());
+        data.voting_sessions.insert(MessageId::new(200), batch);
+        data.voting_sessions
+            .insert(MessageId::new(300), session_in(6));
+        data.voting_sessions
+            .get_mut(&MessageId::new(300))
+            .unwrap()
+            .target_message_id = MessageId::new(8);
+
+        data.update_guild_config(guild, |config| {
+            config.locked_messages.insert(target.id);
+        })
+        .unwrap();
+        assert_eq!(
+            handler.vet_target(Some(guild), &target, UserId::new(4), true),
+            Err("A moderator locked that message from pinning.".to_string())
+        );
+        assert!(!data.is_locked(Some(GuildId::new(10)), target.id));
+
+        let mut pinning = data.sessions_pinning(target.id);
+        pinning.sort();
+        assert_eq!(
+            pinning,
+            [
+                (MessageId::new(100), ChannelId::new(2)),
+                (MessageId::new(200), ChannelId::new(5))
+            ]
+        );
+    }
+
+    #[test]
+    fn the_pin_blocklist_only_applies_in_its_own_guild() {
+        let data = data();
+        let author = UserId::new(3);
+        data.update_guild_config(GuildId::new(9), |config| {
+            config.pin_blocklist.insert(author);
+        })
+        .unwrap();
+
+        assert!(data.is_pin_blocked(Some(GuildId::new(9)), author));
+        assert!(!data.is_pin_blocked(Some(GuildId::new(10)), author));
+        assert!(!data.is_pin_blocked(None, author));
+        assert!(!data.is_pin_blocked(Some(GuildId::new(9)), UserId::new(4)));
+    }
+
+    #[test]
+    fn departed_members_lose_their_weighted_votes_in_that_guild_only() {
+        let data = data();
+        let guild = GuildId::new(9);
+        let in_guild = |guild_id: GuildId| {
+            let mut session = session();
+            session.guild_id = Some(guild_id);
+            session
+        };
+        data.voting_sessions
+            .insert(MessageId::new(100), in_guild(guild));
+        data.voting_sessions
+            .insert(MessageId::new(101), in_guild(GuildId::new(8)));
+        let leaver = UserId::new(10);
+        for session_id in [100, 101] {
+            data.record_vote(
+                MessageId::new(session_id),
+                leaver,
+                VoteSource::Reaction(Checkmark::WhiteHeavy),
+                2,
+            );
+            data.record_vote(MessageId::new(session_id), leaver, VoteSource::Button, 2);
+        }
+        data.record_vote(MessageId::new(100), UserId::new(11), VoteSource::Button, 1);
+
+        assert_eq!(
+            data.drop_departed_voter(guild, leaver),
+            [(MessageId::new(100), VoteOutcome::Counted(1))]
+        );
+        let count = |id| {
+            data.voting_sessions
+                .get(&MessageId::new(id))
+                .unwrap()
+                .get_vote_count()
+        };
+        assert_eq!(count(100), 1);
+        assert_eq!(count(101), 2);
+        // Members who never voted change nothing
+        assert!(data.drop_departed_voter(guild, UserId::new(12)).is_empty());
+        assert!(data.drop_departed_voter(guild, leaver).is_empty());
+    }
+
+    #[test]
+    fn a_backup_opens_as_a_store_with_the_same_data() {
+        let store = Store::open(":memory:").unwrap();
+        store.set_pin_opt_out(UserId::new(3), true).unwrap();
+        let path = std::env::temp_dir().join(format!("pin-bot-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        store.backup_to(&path).unwrap();
+        let restored = Store::open(path.to_str().unwrap()).unwrap();
+        assert!(restored.is_pin_opted_out(UserId::new(3)).unwrap());
+        assert!(!restored.is_pin_opted_out(UserId::new(4)).unwrap());
+        // The target must be new, so an old backup is never overwritten
+        assert!(store.backup_to(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn purging_a_user_drops_their_votes_and_attribution_everywhere() {
+        let data = data();
+        let (user, other) = (UserId::new(10), UserId::new(11));
+        let channel = ChannelId::new(2);
+        for (id, author_id) in [(1, user), (2, other)] {
+            data.store
+                .record_pin(&PinRecord {
+                    guild_id: GuildId::new(9),
+                    channel_id: channel,
+                    message_id: MessageId::new(id),
+                    author_id: Some(author_id),
+                    excerpt: "hello".to_string(),
+                    votes: 3,
+                    pinned_at: 0,
+                    reason: None,
+                    voters: 0,
+                })
+                .unwrap();
+        }
+        data.set_opted_out(user, true).unwrap();
+        for guild_id in [8, 9] {
+            data.update_guild_config(GuildId::new(guild_id), |config| {
+                config.pin_blocklist.extend([user, other]);
+            })
+            .unwrap();
+        }
+        data.update_guild_config(GuildId::new(7), |config| {
+            config.pin_blocklist.insert(other);
+        })
+        .unwrap();
+        for (session_id, guild_id) in [(100, 8), (101, 9)] {
+            let mut session = session();
+            session.guild_id = Some(GuildId::new(guild_id));
+            data.voting_sessions
+                .insert(MessageId::new(session_id), session);
+            data.record_vote(MessageId::new(session_id), user, VoteSource::Button, 1);
+        }
+
+        let report = data.purge_user_data(user);
+        assert_eq!(report.pins_anonymized, Some(1));
+        assert_eq!(report.opt_out_cleared, Some(true));
+        assert_eq!(report.blocklists_cleared, Some(2));
+        assert_eq!(report.sessions.len(), 2);
+
+        let pins = data.store.pins_in_channel(channel).unwrap();
+        let pin = |id| {
+            pins.iter()
+                .find(|pin| pin.message_id == MessageId::new(id))
+                .unwrap()
+        };
+        assert_eq!((pin(1).author_id, pin(1).excerpt.as_str()), (None, ""));
+        assert_eq!(pin(2).author_id, Some(other));
+        assert!(!data.has_opted_out(user));
+        assert!(!data.is_pin_blocked(Some(GuildId::new(9)), user));
+        assert!(data.is_pin_blocked(Some(GuildId::new(9)), other));
+        let stored = data
+            .store
+            .load_guild_config(GuildId::new(8))
+            .unwrap()
+            .unwrap();
+        assert!(!stored.pin_blocklist.contains(&user));
+        assert!(data
+            .voting_sessions
+            .iter()
+            .all(|session| session.get_vote_count() == 0));
+
+        // A second purge finds nothing left
+        let again = data.purge_user_data(user);
+        assert_eq!(
+            (
+                again.pins_anonymized,
+                again.opt_out_cleared,
+                again.blocklists_cleared
+            ),
+            (Some(0), Some(false), Some(0))
+        );
+        assert!(again.sessions.is_empty());
+    }
+
+    #[test]
+    fn open_votes_list_live_sessions_in_the_guild_soonest_first() {
+        let data = BotData::new(
+            Config {
+                vote_timeout_secs: 600,
+                ..Config::default()
+            },
+            Store::open(":memory:").unwrap(),
+        );
+        let guild = GuildId::new(9);
+        let in_guild = |channel: u64, age: u64| {
+            let mut session = session_in(channel);
+            session.guild_id = Some(guild);
+            session.created_at = Instant::now()
+                .checked_sub(Duration::from_secs(age))
+                .unwrap();
+            session.started_at -= age as i64;
+            session
+        };
+        data.insert_session(MessageId::new(100), in_guild(5, 10))
+            .unwrap();
+        data.insert_session(MessageId::new(101), in_guild(5, 300))
+            .unwrap();
+        data.insert_session(MessageId::new(102), in_guild(6, 20))
+            .unwrap();
+        data.insert_session(MessageId::new(103), in_guild(5, 700))
+            .unwrap();
+        data.insert_session(MessageId::new(104), session_in(5))
+            .unwrap();
+        data.record_vote(MessageId::new(100), UserId::new(10), VoteSource::Button, 1);
+
+        let here = data.open_votes(guild, Some(ChannelId::new(5)));
+        let ids: Vec<MessageId> = here.iter().map(|vote| vote.session_id).collect();
+        assert_eq!(ids, [MessageId::new(101), MessageId::new(100)]);
+        assert_eq!(here[1].progress, "1/3");
+        assert!(here[0].expires_at < here[1].expires_at);
+
+        assert_eq!(data.open_votes(guild, None).len(), 3);
+        assert!(data.open_votes(GuildId::new(1), None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn pins_in_one_channel_wait_out_the_cooldown() {
+        let data = data();
+        let api = MockApi::default();
+        let channel = ChannelId::new(2);
+
+        assert!(
+            data.pin_message_safely(&api, channel, MessageId::new(1))
+                .await
+        );
+        assert!(
+            !data
+                .pin_message_safely(&api, channel, MessageId::new(2))
+                .await
+        );
+        assert!(matches!(
+            data.unpin_message_safely(&api, channel, MessageId::new(1))
+                .await,
+            UnpinOutcome::CoolingDown
+        ));
+        assert!(
+            data.pin_message_safely(&api, ChannelId::new(3), MessageId::new(3))
+                .await
+        );
+
+        // The refused calls never reached Discord
+        assert_eq!(api.pin_count(), 2);
+        assert!(api.unpins.lock().unwrap().is_empty());
+        assert_eq!(data.pins_served.load(Ordering::Relaxed), 2);
+    }
+
+    #[tokio::test]
+    async fn rotation_takes_down_only_the_previous_pin_the_bot_made() {
+        let config = Config {
+            pin_cooldown_secs: 0,
+            ..Config::default()
+        };
+        let data = BotData::new(config, Store::open(":memory:").unwrap());
+        let api = MockApi::default();
+        let (guild, channel) = (GuildId::new(1), ChannelId::new(2));
+        let record = |message: u64, manual: bool| {
+            let pin = PinRecord {
+                guild_id: guild,
+                channel_id: channel,
+                message_id: MessageId::new(message),
+                author_id: Some(UserId::new(9)),
+                excerpt: String::new(),
+                votes: 3,
+                voters: 3,
+                pinned_at: 1_700_000_000,
+                reason: None,
+            };
+            if manual {
+                data.store.record_manual_pin(&pin).unwrap();
+            } else {
+                data.store.record_pin(&pin).unwrap();
+            }
+        };
+
+        // Pins from before rotation was on stay up
+        record(10, false);
+        data.set_pin_rotation(guild, channel, true).unwrap();
+        record(11, false);
+        let (data, api) = (&data, &api);
+        let rotate =
+            move |message: u64| data.rotate_pin(api, guild, channel, MessageId::new(message));
+        assert_eq!(rotate(11).await, Ok(None));
+        record(12, false);
+        assert_eq!(rotate(12).await, Ok(Some(MessageId::new(11))));
+        assert!(!data.store.is_bot_pin(MessageId::new(11)).unwrap());
+
+        // A moderator unpinned the featured pin and pinned it again by hand
+        data.store.mark_unpinned(MessageId::new(12), 1).unwrap();
+        record(12, true);
+        record(13, false);
+        assert_eq!(rotate(13).await, Ok(None));
+        assert_eq!(*api.unpins.lock().unwrap(), [(channel, MessageId::new(11))]);
+
+        // Off forgets the featured pin, so turning it back on starts afresh
+        data.set_pin_rotation(guild, channel, false).unwrap();
+        assert_eq!(data.store.rotation_pin(channel).unwrap(), None);
+        record(14, false);
+        assert_eq!(rotate(14).await, Ok(None));
+        data.set_pin_rotation(guild, channel, true).unwrap();
+        assert_eq!(rotate(14).await, Ok(None));
+        assert_eq!(api.unpins.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn ended_votes_keep_their_peak_removals_and_ending() {
+        let config = Config {
+            vote_timeout_secs: 60,
+            ..Config::default()
+        };
+        let data = BotData::new(config, Store::open(":memory:").unwrap());
+        let guild = GuildId::new(1);
+        let guild_session = || {
+            let mut session = session();
+            session.guild_id = Some(guild);
+            session.threshold = 10;
+            session
+        };
+        let (expired, cancelled) = (MessageId::new(100), MessageId::new(101));
+        let mut stale = guild_session();
+        stale.created_at = Instant::now().checked_sub(Duration::from_secs(90)).unwrap();
+        data.insert_session(expired, stale).unwrap();
+        data.insert_session(cancelled, guild_session()).unwrap();
+
+        let source = VoteSource::Button;
+        data.record_vote(expired, UserId::new(10), source, 2);
+        data.record_vote(expired, UserId::new(11), source, 1);
+        data.retract_vote(expired, UserId::new(10), source);
+        data.record_vote(expired, UserId::new(10), source, 1);
+        // A repeat retraction is no removal
+        data.retract_vote(expired, UserId::new(12), source);
+        assert!(data.expire_session(expired));
+        assert!(data.end_session(cancelled, VoteEnding::Cancelled).is_some());
+        // A session without a guild keeps no stats
+        data.insert_session(MessageId::new(102), session()).unwrap();
+        assert!(data
+            .end_session(MessageId::new(102), VoteEnding::Cancelled)
+            .is_some());
+
+        let stats = data.store.vote_stats_between(guild, 0, i64::MAX).unwrap();
+        assert_eq!(stats.len(), 2);
+        let vote = stats
+            .iter()
+            .find(|vote| vote.session_id == expired)
+            .unwrap();
+        assert_eq!(vote.ending, VoteEnding::Expired);
+        assert_eq!(
+            (vote.votes, vote.peak_votes, vote.removals, vote.voters),
+            (2, 3, 1, 2)
+        );
+        assert!(vote.duration_secs >= 90);
+        assert_eq!(
+            VoteSummary::of(&stats).line(),
+            "2 votes, 1 expired, 1 cancelled"
+        );
+    }
+
+    #[test]
+    fn channel_listings_explain_why_a_channel_is_refused() {
+        let data = data();
+        let guild = GuildId::new(1);
+        let (thread, parent, other) = (ChannelId::new(2), ChannelId::new(3), ChannelId::new(4));
+
+        let open = data.channel_listing(guild, &[thread, parent]);
+        assert!(!open.denied && !open.has_allowlist && open.allows());
+
+        data.update_guild_config(guild, |config| {
+            config.allowed_channels.insert(parent);
+            config.denied_channels.insert(other);
+        })
+        .unwrap();
+        let listing = data.channel_listing(guild, &[thread, parent]);
+        assert!(listing.has_allowlist && listing.allowlisted && listing.allows());
+        let listing = data.channel_listing(guild, &[other]);
+        assert!(listing.denied && !listing.allowlisted && !listing.allows());
+        assert!(!data.is_channel_allowed(guild, &[ChannelId::new(5)]));
+    }
+
+    #[test]
+    fn a_linked_target_is_refused_from_a_channel_where_votes_are_disabled() {
+        let (handler, _shutdown_tx) = handler();
+        let guild = GuildId::new(1);
+        let (denied, allowed) = (ChannelId::new(2), ChannelId::new(3));
+        handler
+            .data
+            .update_guild_config(guild, |config| {
+                config.denied_channels.insert(denied);
+            })
+            .unwrap();
+        let mut target = Message::default();
+        target.author.id = UserId::new(3);
+        let vet = |invoked_in: ChannelId, target_in: ChannelId| {
+            handler.vet_request_in(
+                Some(guild),
+                [&[invoked_in], &[target_in]],
+                &target,
+                UserId::new(4),
+                false,
+            )
+        };
+
+        assert_eq!(vet(allowed, allowed), Ok(()));
+        assert_eq!(
+            vet(denied, allowed),
+            Err("Pin votes are disabled in this channel.".to_string())
+        );
+        assert!(vet(allowed, denied).is_err());
+    }
+
+    #[tokio::test]
+    async fn dry_runs_only_pretend_to_pin_and_unpin() {
+        let config = Config {
+            dry_run: true,
+            ..Config::default()
+        };
+        let dry = BotData::new(config, Store::open(":memory:").unwrap());
+        let api = MockApi::default();
+
+        assert!(
+            dry.pin_message_safely(&api, ChannelId::new(2), MessageId::new(1))
+                .await
+        );
+        assert!(matches!(
+            dry.unpin_message_safely(&api, ChannelId::new(3), MessageId::new(1))
+                .await,
+            UnpinOutcome::Unpinned
+        ));
+        // The cooldown still applies, as it would to real pins
+        assert!(
+            !dry.pin_message_safely(&api, ChannelId::new(2), MessageId::new(2))
+                .await
+        );
+        assert_eq!(api.pin_count(), 0);
+        assert!(api.unpins.lock().unwrap().is_empty());
+        assert_eq!(
+            dry.dry_run_tagged("📌 Pinned it".to_string()),
+            "[dry run] 📌 Pinned it"
+        );
+        assert_eq!(
+            data().dry_run_tagged("Unpinned it.".to_string()),
+            "Unpinned it."
+        );
+    }
+
+    #[tokio::test]
+    async fn a_rate_limit_sets_the_cooldown_to_discords_wait() {
+        let data = data();
+        let api = MockApi::default();
+        let (calm, busy) = (ChannelId::new(2), ChannelId::new(3));
+        assert_eq!(
+            pin_route_channel("https://discord.com/api/v10/channels/3/pins/9"),
+            Some(busy)
+        );
+        assert_eq!(pin_route_channel("/channels/3/pins"), None);
+        assert_eq!(pin_route_channel("/channels/3/messages/9"), None);
+
+        // Without any rate limit, only the short cooldown applies
+        assert!(data.pin_message_safely(&api, calm, MessageId::new(1)).await);
+        assert!(data.pin_cooldowns.time_left(&calm).unwrap() <= CALM_PIN_COOLDOWN);
+
+        data.note_rate_limit("/channels/3/messages/9", Duration::from_secs(60));
+        data.note_rate_limit("/channels/3/pins/9", Duration::from_secs(30));
+        assert!(data.pin_message_safely(&api, busy, MessageId::new(2)).await);
+        let left = data.pin_cooldowns.time_left(&busy).unwrap();
+        assert!(left > Duration::from_secs(25) && left <= Duration::from_secs(30));
+        assert!(data
+            .cooldowns()
+            .iter()
+            .any(|(channel_id, _, rate_limited)| *channel_id == busy && *rate_limited));
+
+        // The call after a rate limit still waits the configured cooldown,
+        // and the one after that is calm again
+        let cool_off = || data.pin_cooldowns.insert_for(busy, false, Duration::ZERO);
+        cool_off();
+        assert!(data.pin_message_safely(&api, busy, MessageId::new(3)).await);
+        assert!(data.pin_cooldowns.time_left(&busy).unwrap() > CALM_PIN_COOLDOWN);
+        cool_off();
+        assert!(data.pin_message_safely(&api, busy, MessageId::new(4)).await);
+        assert!(data.pin_cooldowns.time_left(&busy).unwrap() <= CALM_PIN_COOLDOWN);
+    }
+
+    #[tokio::test]
+    async fn the_pin_worker_waits_out_a_cooldown_without_holding_up_other_channels() {
+        let config = Config {
+            pin_cooldown_secs: 1,
+            ..Config::default()
+        };
+        let data = BotData::new(config, Store::open(":memory:").unwrap());
+        let api = MockApi::default();
+        let requests = data.pin_requests.lock().unwrap().take().unwrap();
+        let (stop, shutdown) = watch::channel(false);
+        let (busy, other) = (ChannelId::new(2), ChannelId::new(3));
+
+        let pins = async {
+            let results = tokio::join!(
+                data.queue_pin(None, busy, MessageId::new(1)),
+                data.queue_pin(None, busy, MessageId::new(2)),
+                data.queue_pin(None, other, MessageId::new(3)),
+            );
+            let _ = stop.send(true);
+            results
+        };
+        let (results, ()) = tokio::join!(pins, data.run_pin_worker(&api, requests, shutdown));
+
+        assert_eq!(results, (true, true, true));
+        assert_eq!(
+            *api.pins.lock().unwrap(),
+            [
+                (busy, MessageId::new(1)),
+                (other, MessageId::new(3)),
+                (busy, MessageId::new(2)),
+            ]
+        );
+        assert_eq!(data.pin_queue_depth(), 0);
+    }
+
+    #[test]
+    fn a_guild_over_its_pin_budget_waits_for_its_oldest_pin_to_age_out() {
+        let (busy, unlimited) = (GuildId::new(1), GuildId::new(2));
+        let mut config = Config {
+            guild_pins_per_minute: 2,
+            ..Config::default()
+        };
+        config.guilds.insert(
+            unlimited,
+            config::GuildOverrides {
+                pins_per_minute: Some(0),
+                ..Default::default()
+            },
+        );
+        let data = BotData::new(config, Store::open(":memory:").unwrap());
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        data.note_guild_pin(Some(busy), at(0));
+        assert_eq!(data.guild_pin_wait(Some(busy), at(1)), None);
+        data.note_guild_pin(Some(busy), at(10));
+        assert_eq!(
+            data.guild_pin_wait(Some(busy), at(20)),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(data.guild_pin_wait(Some(busy), at(60)), None);
+        data.note_guild_pin(Some(busy), at(60));
+        assert_eq!(
+            data.guild_pin_wait(Some(busy), at(61)),
+            Some(Duration::from_secs(9))
+        );
+
+        for _ in 0..5 {
+            data.note_guild_pin(Some(unlimited), at(0));
+        }
+        assert_eq!(data.guild_pin_wait(Some(unlimited), at(1)), None);
+        assert_eq!(data.guild_pin_wait(None, at(1)), None);
+    }
+
+    #[tokio::test]
+    async fn a_claimed_vote_wakes_its_collector_only_when_the_claim_ends() {
+        let data = data();
+        let session_id = MessageId::new(10);
+        data.insert_session(session_id, session()).unwrap();
+        let woken = |released: Arc<Notify>| async move {
+            tokio::time::timeout(Duration::from_millis(50), released.notified())
+                .await
+                .is_ok()
+        };
+        assert!(data.session_hold(session_id).is_none());
+
+        // Handing the claim back wakes it, as does the vote ending
+        let claimed = || {
+            assert!(data
+                .voting_sessions
+                .get(&session_id)
+                .unwrap()
+                .claim_finalization());
+            data.session_hold(session_id).unwrap()
+        };
+        let released = claimed();
+        assert!(!woken(Arc::clone(&released)).await);
+        data.voting_sessions
+            .get(&session_id)
+            .unwrap()
+            .release_finalization();
+        assert!(woken(released).await);
+
+        let released = claimed();
+        data.end_session(session_id, VoteEnding::Cancelled);
+        assert!(woken(released).await);
+        assert!(data.session_hold(session_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn a_request_that_does_not_take_swaps_its_hourglass_for_a_cross() {
+        let api = Arc::new(MockApi::default());
+        let mut request = Message::default();
+        request.id = MessageId::new(1);
+        request.channel_id = ChannelId::new(2);
+        let emojis = |api: &MockApi| {
+            api.reactions
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(_, reaction)| reaction.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        PendingReaction::place(Arc::clone(&api), &request)
+            .await
+            .settle()
+            .await;
+        assert_eq!(emojis(&api), [PENDING_EMOJI]);
+        assert_eq!(api.unreactions.lock().unwrap().len(), 1);
+
+        drop(PendingReaction::place(Arc::clone(&api), &request).await);
+        for _ in 0..5 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(emojis(&api), [PENDING_EMOJI, PENDING_EMOJI, FAILED_EMOJI]);
+        assert_eq!(api.unreactions.lock().unwrap().len(), 2);
+
+        // Without the ⏳ there's nothing to swap
+        let failing = Arc::new(MockApi {
+            fail: true,
+            ..MockApi::default()
+        });
+        drop(PendingReaction::place(Arc::clone(&failing), &request).await);
+        tokio::task::yield_now().await;
+        assert!(emojis(&failing).is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_failed_pin_starts_no_cooldown() {
+        let data = data();
+        let channel = ChannelId::new(2);
+        let failing = MockApi {
+            fail: true,
+            ..MockApi::default()
+        };
+
+        assert!(
+            !data
+                .pin_message_safely(&failing, channel, MessageId::new(1))
+                .await
+        );
+        // A failed fetch is treated as not pinned
+        assert!(
+            !data
+                .is_already_pinned(&failing, channel, MessageId::new(1))
+                .await
+        );
+
+        let api = MockApi {
+            pinned: true,
+            ..MockApi::default()
+        };
+        assert!(
+            data.is_already_pinned(&api, channel, MessageId::new(1))
+                .await
+        );
+        assert!(
+            data.pin_message_safely(&api, channel, MessageId::new(1))
+                .await
+        );
+        assert_eq!(data.pins_served.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn digests_come_due_once_per_scheduled_run() {
+        let data = data();
+        let guild = GuildId::new(1);
+        let schedule = DigestSchedule {
+            channel_id: ChannelId::new(2),
+            weekday: 0,
+            hour: 18,
+        };
+        let now = Timestamp::now().unix_timestamp();
+
+        // Turning it on counts as a run, so nothing is due until the next slot
+        data.set_digest(guild, Some(schedule)).unwrap();
+        assert!(data.due_digests(now).is_empty());
+
+        let later = schedule.next_run(now) + 60;
+        let due = data.due_digests(later);
+        assert_eq!(due, vec![(guild, schedule, schedule.latest_run(later))]);
+        data.store
+            .set_digest_last_run(guild, schedule.latest_run(later))
+            .unwrap();
+        assert!(data.due_digests(later).is_empty());
+
+        // A schedule without a recorded run starts from its latest slot
+        let other = GuildId::new(3);
+        data.update_guild_config(other, |config| config.digest = Some(schedule))
+            .unwrap();
+        assert!(data.due_digests(later).is_empty());
+        assert_eq!(data.due_digests(later + WEEK_SECS).len(), 2);
+    }
+
+    #[test]
+    fn checkmarks_on_the_target_share_the_voters() {
+        let data = data();
+        let session_id = MessageId::new(100);
+        data.insert_session(session_id, session()).unwrap();
+        let user = UserId::new(10);
+        let on_vote = VoteSource::Reaction(Checkmark::WhiteHeavy);
+        let on_target = VoteSource::TargetReaction(Checkmark::WhiteHeavy);
+
+        assert_eq!(
+            data.session_for_reaction(session_id),
+            Some((session_id, false))
+        );
+        assert_eq!(
+            data.session_for_reaction(MessageId::new(1)),
+            Some((session_id, true))
+        );
+        assert_eq!(data.session_for_reaction(MessageId::new(2)), None);
+
+        assert_eq!(
+            data.record_vote(session_id, user, on_target, 1),
+            VoteOutcome::Counted(1)
+        );
+        assert_eq!(
+            data.record_vote(session_id, user, on_vote, 1),
+            VoteOutcome::Duplicate
+        );
+        // Taking back one of the two reactions keeps the vote
+        assert_eq!(
+            data.retract_vote(session_id, user, on_vote),
+            VoteOutcome::Duplicate
+        );
+        assert_eq!(
+            data.retract_vote(session_id, user, on_target),
+            VoteOutcome::Counted(0)
+        );
+
+        data.remove_session(session_id);
+        assert_eq!(data.session_for_reaction(MessageId::new(1)), None);
+    }
+
+    #[tokio::test]
+    async fn ended_sessions_take_back_the_target_checkmark() {
+        let data = data();
+        let api = MockApi::default();
+        let mut first = session();
+        first.target_reacted = true;
+        let mut second = session_in(2);
+        second.target_reacted = true;
+        data.insert_session(MessageId::new(100), first).unwrap();
+        data.insert_session(MessageId::new(101), second).unwrap();
+
+        // Another vote on the same target still wants the ✅
+        data.remove_session(MessageId::new(100));
+        data.clear_target_reactions(&api).await;
+        assert!(api.unreactions.lock().unwrap().is_empty());
+
+        data.remove_session(MessageId::new(101));
+        data.clear_target_reactions(&api).await;
+        assert_eq!(
+            *api.unreactions.lock().unwrap(),
+            vec![(ChannelId::new(2), MessageId::new(1))]
+        );
+    }
+
+    #[test]
+    fn batch_votes_list_every_target_and_report_the_failures() {
+        let guild = Some(GuildId::new(9));
+        let mut session = session();
+        session.batch = vec![PinTarget {
+            channel_id: ChannelId::new(4),
+            message_id: MessageId::new(5),
+            author_id: None,
+            excerpt: String::new(),
+            snapshot: None,
+        }];
+        let targets = session.pin_targets();
+        assert_eq!(targets[0].message_id, MessageId::new(1));
+
+        let intro = |targets| VoteIntro {
+            guild_id: guild,
+            targets,
+            subject: "this message",
+            initiator_id: UserId::new(3),
+            threshold: 3,
+            quorum: None,
+            reason: None,
+        };
+        let default = TemplateKey::VoteStarted.default_text();
+        let content = vote_message(
+            default,
+            &VoteIntro {
+                reason: Some("raid recap"),
+                ..intro(&targets)
+            },
+        );
+        assert_eq!(
+            content,
+            "Vote to pin these 2 messages! 3 approvals needed.\n\
+             • https://discord.com/channels/9/2/1\n\
+             • https://discord.com/channels/9/4/5\n\
+             Reason: raid recap"
+        );
+        assert_eq!(
+            vote_message(default, &intro(&targets[..1])),
+            "Vote to pin this message! 3 approvals needed."
+        );
+        assert_eq!(
+            vote_message(
+                "{initiator} wants {target_link} up ({needed})",
+                &intro(&targets[..1])
+            ),
+            "<@3> wants https://discord.com/channels/9/2/1 up (3)"
+        );
+        assert_eq!(
+            partial_batch_report(guild, 1, &targets[1..]),
+            "📌 Pinned 1 of 2 messages. I couldn't pin https://discord.com/channels/9/4/5"
+        );
+    }
+
+    #[test]
+    fn recent_pins_are_only_flagged_inside_the_guild_window() {
+        let data = data();
+        let guild = GuildId::new(9);
+        let now = Timestamp::now().unix_timestamp();
+        for (id, days_ago) in [(1, 3), (2, 40)] {
+            data.store
+                .record_pin(&PinRecord {
+                    guild_id: guild,
+                    channel_id: ChannelId::new(2),
+                    message_id: MessageId::new(id),
+                    author_id: Some(UserId::new(3)),
+                    excerpt: String::new(),
+                    votes: 3,
+                    pinned_at: now - days_ago * 86_400,
+                    reason: None,
+                    voters: 0,
+                })
+                .unwrap();
+        }
+
+        // Off until the guild sets a window
+        assert_eq!(data.recent_pin(Some(guild), MessageId::new(1)), None);
+
+        data.update_guild_config(guild, |config| config.repin_window_days = Some(30))
+            .unwrap();
+        assert_eq!(
+            data.recent_pin(Some(guild), MessageId::new(1)),
+            Some(now - 3 * 86_400)
+        );
+        assert_eq!(data.recent_pin(Some(guild), MessageId::new(2)), None);
+        assert_eq!(data.recent_pin(Some(guild), MessageId::new(7)), None);
+    }
+
+    #[test]
+    fn only_an_unexpired_timeout_blocks_a_vote() {
+        let now = Timestamp::now().unix_timestamp();
+        let until = |offset: i64| {
+            let mut member = Member::default();
+            member.communication_disabled_until =
+                Some(Timestamp::from_unix_timestamp(now + offset).unwrap());
+            member
+        };
+        assert!(!is_timed_out(&Member::default(), now));
+        assert!(is_timed_out(&until(600), now));
+        assert!(!is_timed_out(&until(-600), now));
+    }
+
+    #[test]
+    fn quorum_votes_need_turnout_and_a_lead() {
+        let data = data();
+        let mut quorum = session();
+        quorum.quorum = Some(QuorumRule {
+            quorum: 4,
+            margin: 2,
+        });
+        data.voting_sessions.insert(MessageId::new(1), quorum);
+        let approve = |user| {
+            data.record_vote(
+                MessageId::new(1),
+                UserId::new(user),
+                VoteSource::Reaction(Checkmark::WhiteHeavy),
+                1,
+            )
+        };
+        let reject = |user| data.record_rejection(MessageId::new(1), UserId::new(user), 1);
+
+        assert_eq!(approve(10), VoteOutcome::Counted(1));
+        assert_eq!(approve(11), VoteOutcome::Counted(2));
+        assert_eq!(reject(12), VoteOutcome::Counted(2));
+        assert_eq!(reject(12), VoteOutcome::Duplicate);
+        assert_eq!(reject(13), VoteOutcome::Counted(2));
+        // Five took part but approvals lead by one
+        assert_eq!(approve(14), VoteOutcome::Counted(3));
+        assert_eq!(
+            data.voting_sessions
+                .get(&MessageId::new(1))
+                .unwrap()
+                .tally(),
+            "✅ 3 / ❌ 2"
+        );
+        // Withdrawing a rejection keeps the quorum and widens the lead
+        assert!(matches!(
+            data.retract_rejection(MessageId::new(1), UserId::new(13)),
+            VoteOutcome::Passed { votes: 3, .. }
+        ));
+
+        // Sessions without a quorum ignore rejections
+        data.voting_sessions
+            .insert(MessageId::new(5), session_in(5));
+        assert_eq!(
+            data.record_rejection(MessageId::new(5), UserId::new(10), 1),
+            VoteOutcome::Duplicate
+        );
+    }
+}
@@ -0,0 +1,57 @@
+use serenity::all::{ChannelId, GuildId, MessageId};
+
+/// A message reference typed by a user: either a full jump link or a bare id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageRef {
+    /// `None` for bare ids and for links to DMs (`@me`).
+    pub guild_id: Option<GuildId>,
+    /// `None` for bare ids, which refer to the current channel.
+    pub channel_id: Option<ChannelId>,
+    pub message_id: MessageId,
+}
+
+/// Parses `https://discord.com/channels/<guild>/<channel>/<message>` (including
+/// the ptb/canary hosts and the legacy discordapp.com domain) or a raw id.
+pub fn parse_message_ref(input: &str) -> Option<MessageRef> {
+    let input = input.trim().trim_start_matches('<').trim_end_matches('>');
+
+    if let Ok(message_id) = input.parse::<MessageId>() {
+        return Some(MessageRef {
+            guild_id: None,
+            channel_id: None,
+            message_id,
+        });
+    }
+
+    let path = input
+        .strip_prefix("https://")
+        .or_else(|| input.strip_prefix("http://"))?;
+    let (host, path) = path.split_once('/')?;
+    let host = host
+        .strip_prefix("ptb.")
+        .or_else(|| host.strip_prefix("canary."))
+        .unwrap_or(host);
+    if host != "discord.com" && host != "discordapp.com" {
+        return None;
+    }
+
+    let mut parts = path.strip_prefix("channels/")?.split('/');
+    let guild = parts.next()?;
+    let channel_id = parts.next()?.parse().ok()?;
+    let message_id = parts.next()?.parse().ok()?;
+    if parts.next().is_some_and(|rest| !rest.is_empty()) {
+        return None;
+    }
+
+    let guild_id = if guild == "@me" {
+        None
+    } else {
+        Some(guild.parse().ok()?)
+    };
+
+    Some(MessageRef {
+        guild_id,
+        channel_id: Some(channel_id),
+        message_id,
+    })
+}
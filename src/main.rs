@@ -1,381 +1,259 @@
-use anyhow::Result;
-use dashmap::DashMap;
-use dotenv::dotenv;
-use once_cell::sync::Lazy;
-use serenity::{
-    all::{
-        ChannelId, Context, EventHandler, GatewayIntents, Message, MessageId, Reaction,
-        ReactionType, Ready, UserId,
-    },
-    async_trait, Client,
-};
-use std::{
-    collections::HashSet,
-    env,
-    sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc,
-    },
-    time::{Duration, Instant},
-};
-use tokio::time::{interval, sleep};
-use tracing::{error, info, warn};
+//! Binary entry point: parses the command line, sets up logging and runs
+//! the bot or one of the maintenance subcommands.
 
-// Pre-computed number emojis for O(1) lookup
-static NUMBER_EMOJIS: Lazy<Vec<&'static str>> = Lazy::new(|| {
-    vec![
-        "<:1_:1404868671704272906>",
-        "<:2_:1404868687969910986>",
-        "<:3_:1404868696123375757>",
-        "<:4_:1404868709150888167>",
-        "<:5_:1404868718064042004>",
-        "<:6_:1404868725416661064>",
-        "<:7_:1404868732400173148>",
-        "<:8_:1404868741807996978>",
-        "<:9_:1404868751387660428>",
-        "<:10:1404868763710652547>",
-    ]
-});
+mod cli;
 
-const CHECKMARK_EMOJI: &str = "✅";
-const SLASH_EMOJI: &str = "<:slash:1404872667189743697>";
-const PIN_COOLDOWN_SECS: u64 = 5;
-const CLEANUP_INTERVAL_SECS: u64 = 300; // 5 minutes
-const SESSION_MAX_AGE_SECS: u64 = 3600; // 1 hour
-
-#[derive(Debug, Clone)]
-struct VotingSession {
-    target_message_id: MessageId,
-    target_channel_id: ChannelId,
-    voters: HashSet<UserId>,
-    vote_count: Arc<AtomicU32>,
-    created_at: Instant,
+use anyhow::{Context as _, Result};
+use clap::Parser;
+use cli::{Cli, RunArgs};
+use discord_pin_bot::{
+    config::Config, error_report, intents, message_content_granted, server_members_granted,
+    store::Store, Handler,
+};
+use dotenv::dotenv;
+use serenity::{client::ClientBuilder, http::Http};
+use std::{path::PathBuf, process::ExitCode, sync::Arc};
+use tokio::sync::watch;
+use tracing::{error, info, warn};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+/// Log files are named `pin-bot.<date>.log`, with the hour added when
+/// rotating hourly.
+const LOG_FILE_PREFIX: &str = "pin-bot";
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// What must outlive the bot for its last log lines to get out: the log
+/// file's writer guard and the error reports' final flush.
+struct LogGuards {
+    file: Option<WorkerGuard>,
+    reports: Option<error_report::FinalFlush>,
 }
 
-impl VotingSession {
-    fn new(target_message_id: MessageId, target_channel_id: ChannelId) -> Self {
-        Self {
-            target_message_id,
-            target_channel_id,
-            voters: HashSet::new(),
-            vote_count: Arc::new(AtomicU32::new(0)),
-            created_at: Instant::now(),
-        }
-    }
-
-    fn add_vote(&mut self, user_id: UserId) -> bool {
-        if self.voters.insert(user_id) {
-            self.vote_count.fetch_add(1, Ordering::Relaxed);
-            true
-        } else {
-            false
+impl LogGuards {
+    /// Sends the error reports still pending, then flushes the lines still
+    /// queued for the log file.
+    async fn flush(self) {
+        if let Some(reports) = self.reports {
+            reports.flush().await;
         }
-    }
-
-    fn remove_vote(&mut self, user_id: UserId) -> bool {
-        if self.voters.remove(&user_id) {
-            self.vote_count.fetch_sub(1, Ordering::Relaxed);
-            true
-        } else {
-            false
-        }
-    }
-
-    fn get_vote_count(&self) -> u32 {
-        self.vote_count.load(Ordering::Relaxed)
-    }
-
-    fn is_expired(&self) -> bool {
-        self.created_at.elapsed() > Duration::from_secs(SESSION_MAX_AGE_SECS)
+        drop(self.file);
     }
 }
 
-struct BotData {
-    voting_sessions: DashMap<MessageId, VotingSession>,
-    pin_cooldowns: DashMap<ChannelId, Instant>,
-    confirm_cap: u32,
-}
-
-impl BotData {
-    fn new(confirm_cap: u32) -> Self {
-        Self {
-            voting_sessions: DashMap::new(),
-            pin_cooldowns: DashMap::new(),
-            confirm_cap,
-        }
-    }
-
-    fn get_number_emoji(&self, num: u32) -> Option<&'static str> {
-        if num == 0 || num > 10 {
-            return None;
+#[tokio::main]
+async fn main() -> Result<ExitCode> {
+    // Load environment variables before tracing and clap read them
+    dotenv().ok();
+    let log_guards = init_tracing()?;
+
+    let cli = Cli::parse();
+    // A replay never talks to Discord, so it needs no token
+    let token = match cli.command {
+        Some(cli::Command::Replay { .. }) => String::new(),
+        _ => cli.token()?,
+    };
+    let config_path = cli.config.clone();
+    let config = Config::load(config_path.as_deref())?;
+
+    let result = match cli.command {
+        None => run(token, config, config_path, RunArgs::default()).await,
+        Some(cli::Command::Run(args)) => run(token, config, config_path, args).await,
+        Some(cli::Command::RegisterCommands) => cli::register_commands(&token, &config)
+            .await
+            .map(|()| ExitCode::SUCCESS),
+        Some(cli::Command::Validate) => cli::validate(&token).await.map(|()| ExitCode::SUCCESS),
+        Some(cli::Command::Replay { file }) => {
+            cli::replay(config, &file).await.map(|()| ExitCode::SUCCESS)
         }
-        NUMBER_EMOJIS.get((num - 1) as usize).copied()
-    }
-
-    async fn pin_message_safely(
-        &self,
-        ctx: &Context,
-        channel_id: ChannelId,
-        message_id: MessageId,
-    ) -> bool {
-        let now = Instant::now();
+    };
+    log_guards.flush().await;
+    result
+}
 
-        // Check rate limit
-        if let Some(last_pin) = self.pin_cooldowns.get(&channel_id) {
-            if now.duration_since(*last_pin) < Duration::from_secs(PIN_COOLDOWN_SECS) {
-                warn!("Pin rate limited for channel {}", channel_id);
-                return false;
-            }
+/// Plain text logs by default; `LOG_FORMAT=json` emits one JSON object per
+/// line with the handler span's ids alongside the event fields. With
+/// `LOG_DIR` set, the same lines also go to a rotating file in that
+/// directory. Error reporting is added when `SENTRY_DSN` or
+/// `ERROR_WEBHOOK_URL` is set. The returned guards must be flushed at
+/// shutdown.
+fn init_tracing() -> Result<LogGuards> {
+    let format = std::env::var("LOG_FORMAT").unwrap_or_default();
+    let json = match format.trim().to_ascii_lowercase().as_str() {
+        "" | "text" => false,
+        "json" => true,
+        other => anyhow::bail!("LOG_FORMAT must be \"text\" or \"json\", got {:?}", other),
+    };
+    let rotation = std::env::var("LOG_ROTATION").unwrap_or_default();
+    let rotation = match rotation.trim().to_ascii_lowercase().as_str() {
+        "" | "daily" => Rotation::DAILY,
+        "hourly" => Rotation::HOURLY,
+        other => anyhow::bail!(
+            "LOG_ROTATION must be \"daily\" or \"hourly\", got {:?}",
+            other
+        ),
+    };
+    let log_dir = std::env::var("LOG_DIR")
+        .ok()
+        .map(|dir| dir.trim().to_string())
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from);
+
+    let (file_layer, file) = match &log_dir {
+        Some(dir) => {
+            let appender = RollingFileAppender::builder()
+                .rotation(rotation)
+                .filename_prefix(LOG_FILE_PREFIX)
+                .filename_suffix(LOG_FILE_SUFFIX)
+                .build(dir)
+                .with_context(|| format!("Failed to open the log directory {}", dir.display()))?;
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(fmt_layer(json, writer, false)), Some(guard))
         }
+        None => (None, None),
+    };
+    let (error_layer, reports) = error_report::from_env()?.unzip();
+    let reporting = error_layer.is_some();
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with(fmt_layer(json, std::io::stdout, true))
+        .with(file_layer)
+        .with(error_layer)
+        .init();
 
-        match ctx.http.pin_message(channel_id, message_id, None).await {
-            Ok(_) => {
-                self.pin_cooldowns.insert(channel_id, now);
-                info!(
-                    "Successfully pinned message {} in channel {}",
-                    message_id, channel_id
-                );
-                true
-            }
-            Err(e) => {
-                error!("Failed to pin message {}: {}", message_id, e);
-                false
-            }
-        }
+    match &log_dir {
+        Some(dir) => info!(log_dir = %dir.display(), "Logging to stdout and to files"),
+        None => info!("Logging to stdout"),
     }
-
-    fn cleanup_expired_sessions(&self) {
-        let mut removed_count = 0;
-        self.voting_sessions.retain(|_, session| {
-            if session.is_expired() {
-                removed_count += 1;
-                false
-            } else {
-                true
-            }
-        });
-
-        if removed_count > 0 {
-            info!("Cleaned up {} expired voting sessions", removed_count);
-        }
+    if reporting {
+        info!("Reporting errors and panics");
     }
+    Ok(LogGuards { file, reports })
 }
 
-struct Handler {
-    data: Arc<BotData>,
-}
-
-impl Handler {
-    fn new(confirm_cap: u32) -> Self {
-        Self {
-            data: Arc::new(BotData::new(confirm_cap)),
-        }
-    }
-
-    fn start_cleanup_task(&self) {
-        let data = Arc::clone(&self.data);
-        tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(CLEANUP_INTERVAL_SECS));
-            loop {
-                interval.tick().await;
-                data.cleanup_expired_sessions();
-            }
-        });
+/// One output of the subscriber, in the configured format.
+fn fmt_layer<S, W>(json: bool, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+    W: for<'writer> tracing_subscriber::fmt::MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    if json {
+        layer
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed()
+    } else {
+        layer.with_ansi(ansi).boxed()
     }
 }
 
-#[async_trait]
-impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: Ready) {
-        info!("Bot {} is ready!", ready.user.name);
-        self.start_cleanup_task();
-    }
-
-    async fn message(&self, ctx: Context, msg: Message) {
-        // Ignore own messages and messages without references
-        if msg.author.bot || msg.referenced_message.is_none() {
-            return;
-        }
-
-        // Check if bot is mentioned
-        let current_user_id = ctx.cache.current_user().id;
-        if !msg.content.starts_with(&format!("<@{}>", current_user_id))
-            && !msg.content.starts_with(&format!("<@!{}>", current_user_id))
-        {
-            return;
-        }
-
-        let target_msg = match msg.referenced_message.as_ref() {
-            Some(target) => target,
-            None => return,
-        };
-
-        // If confirm_cap is 0, pin immediately
-        if self.data.confirm_cap == 0 {
-            self.data
-                .pin_message_safely(&ctx, msg.channel_id, target_msg.id)
-                .await;
-            return;
-        }
-
-        // Create voting session
-        let session = VotingSession::new(target_msg.id, msg.channel_id);
-        self.data.voting_sessions.insert(msg.id, session);
-
-        // Add reactions with error handling
-        let reactions = vec![
-            CHECKMARK_EMOJI,
-            SLASH_EMOJI,
-            self.data
-                .get_number_emoji(self.data.confirm_cap)
-                .unwrap_or("❓"),
-        ];
-
-        for &emoji in &reactions {
-            if let Err(e) = msg
-                .react(&ctx.http, ReactionType::Unicode(emoji.to_string()))
-                .await
-            {
-                warn!("Failed to add reaction {}: {}", emoji, e);
-                // For custom emojis, try parsing them
-                if emoji.starts_with('<') {
-                    if let Ok(custom_emoji) = emoji.parse::<ReactionType>() {
-                        if let Err(e2) = msg.react(&ctx.http, custom_emoji).await {
-                            warn!("Failed to add custom reaction {}: {}", emoji, e2);
-                        }
-                    }
+/// Falls back to interaction-only mode when the application lacks the
+/// Message Content intent, and stops watching departures without the Server
+/// Members intent, since the gateway refuses to connect when either is
+/// requested without being granted. Logs which entry points are active.
+async fn choose_entry_points(token: &str, config: &mut Config) {
+    if !config.interaction_only || config.drop_departed_votes {
+        match cli::application_http(token).await {
+            Ok((_, flags)) => {
+                if !config.interaction_only && !message_content_granted(flags) {
+                    warn!(
+                        "The Message Content intent is not enabled for this application, running \
+                         interaction-only; turn it on under Bot → Privileged Gateway Intents for \
+                         @mention requests"
+                    );
+                    config.interaction_only = true;
                 }
-            }
-            // Small delay to avoid rate limits
-            sleep(Duration::from_millis(100)).await;
-        }
-    }
-
-    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
-        // Ignore bot reactions
-        if let Ok(user) = reaction.user(&ctx.http).await {
-            if user.bot {
-                return;
-            }
-        } else {
-            return;
-        }
-
-        // Only handle checkmark reactions
-        if !matches!(&reaction.emoji, ReactionType::Unicode(s) if s == CHECKMARK_EMOJI) {
-            return;
-        }
-
-        let user_id = match reaction.user_id {
-            Some(id) => id,
-            None => return,
-        };
-
-        // Get and update voting session
-        if let Some(mut session_entry) = self.data.voting_sessions.get_mut(&reaction.message_id) {
-            let session = session_entry.value_mut();
-
-            if session.add_vote(user_id) {
-                let current_votes = session.get_vote_count();
-                info!(
-                    "Vote added by {} for message {}. Count: {}",
-                    user_id, reaction.message_id, current_votes
-                );
-
-                // Check if threshold reached
-                if current_votes >= self.data.confirm_cap {
-                    let target_message_id = session.target_message_id;
-                    let target_channel_id = session.target_channel_id;
-
-                    // Drop the session entry to release the lock
-                    drop(session_entry);
-
-                    let success = self
-                        .data
-                        .pin_message_safely(&ctx, target_channel_id, target_message_id)
-                        .await;
-
-                    if success {
-                        // Clean up the session
-                        self.data.voting_sessions.remove(&reaction.message_id);
-                    }
+                if config.drop_departed_votes && !server_members_granted(flags) {
+                    warn!(
+                        "The Server Members intent is not enabled for this application, so votes \
+                         from members who leave keep counting; turn it on under Bot → Privileged \
+                         Gateway Intents for DROP_DEPARTED_VOTES"
+                    );
+                    config.drop_departed_votes = false;
                 }
             }
+            // Connecting will show whether the intents are there
+            Err(e) => warn!("Failed to check the privileged intents: {:#}", e),
         }
     }
 
-    async fn reaction_remove(&self, _ctx: Context, reaction: Reaction) {
-        // Ignore bot reactions
-        if let Ok(user) = reaction.user(&_ctx.http).await {
-            if user.bot {
-                return;
-            }
-        } else {
-            return;
-        }
-
-        // Only handle checkmark reactions
-        if !matches!(&reaction.emoji, ReactionType::Unicode(s) if s == CHECKMARK_EMOJI) {
-            return;
-        }
-
-        let user_id = match reaction.user_id {
-            Some(id) => id,
-            None => return,
-        };
-
-        // Update voting session
-        if let Some(mut session_entry) = self.data.voting_sessions.get_mut(&reaction.message_id) {
-            let session = session_entry.value_mut();
-
-            if session.remove_vote(user_id) {
-                let current_votes = session.get_vote_count();
-                info!(
-                    "Vote removed by {} for message {}. Count: {}",
-                    user_id, reaction.message_id, current_votes
-                );
-            }
-        }
+    if config.interaction_only {
+        info!(
+            "Interaction-only mode: /pin, the Pin this message context menu, /unpin and \
+             reaction votes are active; @mention requests and replies are off"
+        );
+    } else {
+        info!("@mention requests, /pin, the context menu, /unpin and reaction votes are active");
     }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
-
-    // Load environment variables
-    dotenv().ok();
-
-    let token = env::var("TOKEN").expect("TOKEN environment variable not set");
-
-    let confirm_cap: u32 = env::var("CONFIRM_CAP")
-        .unwrap_or_else(|_| "3".to_string())
-        .parse()
-        .expect("CONFIRM_CAP must be a valid number");
-
-    if confirm_cap > 10 {
-        panic!("CONFIRM_CAP must be between 0 and 10");
+/// Runs the bot until it is stopped, returning the code to exit with.
+async fn run(
+    token: String,
+    mut config: Config,
+    config_path: Option<PathBuf>,
+    overrides: RunArgs,
+) -> Result<ExitCode> {
+    overrides.apply(&mut config);
+    choose_entry_points(&token, &mut config).await;
+    let store = Store::open(&config.database_path)?;
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    info!(confirm_cap = config.confirm_cap, "Starting bot");
+    if config.dry_run {
+        warn!("Dry run: votes run as usual, but pins and unpins are only logged");
     }
 
-    info!("Starting bot with confirm_cap: {}", confirm_cap);
-
-    // Set gateway intents - minimal for performance
-    let intents = GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::GUILD_MESSAGE_REACTIONS
-        | GatewayIntents::MESSAGE_CONTENT;
-
     // Create client
-    let mut client = Client::builder(&token, intents)
-        .event_handler(Handler::new(confirm_cap))
-        .await?;
+    let handler = Handler::new(config, store, shutdown_rx);
+    let reload_path = config_path.clone();
+    handler.set_config_loader(move || {
+        let mut config = Config::load(reload_path.as_deref())?;
+        overrides.apply(&mut config);
+        Ok(config)
+    });
+    if let Some(path) = config_path {
+        handler.start_config_watch(path);
+    }
+    let data = handler.data();
+    // Rate limits on pins set the channel's pin cooldown to Discord's wait
+    let mut http = Http::new(&token);
+    if let Some(ratelimiter) = http.ratelimiter.as_mut() {
+        let limits = Arc::clone(&data);
+        ratelimiter.set_ratelimit_callback(Box::new(move |info| {
+            limits.note_rate_limit(&info.path, info.timeout);
+        }));
+    }
+    let mut client = ClientBuilder::new_with_http(
+        http,
+        intents(data.interaction_only(), data.drop_departed_votes()),
+    )
+    .event_handler(handler)
+    .await?;
+    data.set_shard_manager(Arc::clone(&client.shard_manager));
+
+    // Stop the background tasks and close the gateway on Ctrl+C / SIGINT
+    let shard_manager = Arc::clone(&client.shard_manager);
+    tokio::spawn(async move {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for shutdown signal: {}", e);
+            return;
+        }
+        info!("Shutdown requested, stopping");
+        let _ = shutdown_tx.send(true);
+        shard_manager.shutdown_all().await;
+    });
 
     // Start the client
     if let Err(e) = client.start().await {
         error!("Client error: {}", e);
     }
 
-    Ok(())
+    Ok(data.exit_code())
 }
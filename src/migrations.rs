@@ -0,0 +1,153 @@
+//! Schema migrations, embedded in the binary and applied in order when the
+//! store opens.
+
+use anyhow::{bail, Result};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use tracing::info;
+
+/// Each entry moves the schema up one version. Append only: a released
+/// migration never changes, since databases already past it won't rerun it.
+const MIGRATIONS: &[&str] = &[
+    include_str!("../migrations/0001_initial.sql"),
+    include_str!("../migrations/0002_pin_history_author.sql"),
+    include_str!("../migrations/0003_retention_runs.sql"),
+    include_str!("../migrations/0004_pin_history_voters.sql"),
+    include_str!("../migrations/0005_rotation_pins.sql"),
+    include_str!("../migrations/0006_vote_stats.sql"),
+];
+
+/// The schema version this build brings databases up to.
+pub const LATEST: usize = MIGRATIONS.len();
+
+/// Brings the database up to [`LATEST`], each migration in its own
+/// transaction. Refuses a database from a newer build rather than guess
+/// at a schema it doesn't know.
+pub fn run(conn: &mut Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            id      INTEGER PRIMARY KEY CHECK (id = 1),
+            version INTEGER NOT NULL
+        )",
+    )?;
+    let current = version(conn)?;
+    if current > LATEST {
+        bail!(
+            "Database schema version {} is newer than this build supports ({}); \
+             upgrade the bot or restore a backup",
+            current,
+            LATEST
+        );
+    }
+
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        if index == 0 {
+            add_missing_columns(&tx)?;
+        }
+        tx.execute(
+            "INSERT INTO schema_version (id, version) VALUES (1, ?1)
+             ON CONFLICT(id) DO UPDATE SET version = excluded.version",
+            params![index as i64 + 1],
+        )?;
+        tx.commit()?;
+        info!(version = index + 1, "Applied database migration");
+    }
+    Ok(())
+}
+
+/// The last migration applied, 0 for a new database or one from before
+/// migrations.
+pub fn version(conn: &Connection) -> Result<usize> {
+    let version: Option<i64> = conn
+        .query_row(
+            "SELECT version FROM schema_version WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(version.unwrap_or(0) as usize)
+}
+
+/// Databases from before migrations may predate these pin_history columns.
+fn add_missing_columns(tx: &Transaction) -> Result<()> {
+    for (column, definition) in [
+        ("reason", "reason TEXT"),
+        ("manual", "manual INTEGER NOT NULL DEFAULT 0"),
+        ("unpinned_at", "unpinned_at INTEGER"),
+    ] {
+        let exists = tx
+            .prepare("SELECT 1 FROM pragma_table_info('pin_history') WHERE name = ?1")?
+            .exists(params![column])?;
+        if !exists {
+            tx.execute_batch(&format!(
+                "ALTER TABLE pin_history ADD COLUMN {}",
+                definition
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(conn: &Connection, table: &str) -> Vec<String> {
+        conn.prepare(&format!("SELECT name FROM pragma_table_info('{}')", table))
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn an_empty_database_runs_the_whole_chain() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), LATEST);
+        assert!(columns(&conn, "pin_history").contains(&"unpinned_at".to_string()));
+
+        // Running again is a no-op
+        run(&mut conn).unwrap();
+        assert_eq!(version(&conn).unwrap(), LATEST);
+    }
+
+    #[test]
+    fn a_database_from_before_migrations_is_adopted_with_its_data() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(include_str!("../migrations/fixtures/unversioned.sql"))
+            .unwrap();
+        run(&mut conn).unwrap();
+
+        assert_eq!(version(&conn).unwrap(), LATEST);
+        let pin_columns = columns(&conn, "pin_history");
+        for column in ["reason", "manual", "unpinned_at", "voters"] {
+            assert!(pin_columns.contains(&column.to_string()), "{}", column);
+        }
+        assert!(!columns(&conn, "pin_opt_outs").is_empty());
+        let (excerpt, manual): (String, i64) = conn
+            .query_row(
+                "SELECT excerpt, manual FROM pin_history WHERE message_id = 1",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((excerpt.as_str(), manual), ("hello", 0));
+    }
+
+    #[test]
+    fn a_database_from_a_newer_build_is_refused() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        run(&mut conn).unwrap();
+        conn.execute(
+            "UPDATE schema_version SET version = ?1",
+            params![LATEST as i64 + 1],
+        )
+        .unwrap();
+
+        let error = run(&mut conn).unwrap_err().to_string();
+        assert!(error.contains("newer than this build"), "{}", error);
+    }
+}
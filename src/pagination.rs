@@ -0,0 +1,206 @@
+//! Paged embeds for command output that outgrows one embed, turned with
+//! ⏮ ◀ ▶ ⏭ buttons by whoever ran the command.
+
+use serenity::all::{
+    ButtonStyle, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter, ReactionType,
+    UserId,
+};
+use std::time::{Duration, Instant};
+
+pub const PAGE_BUTTON_PREFIX: &str = "pin_page:";
+
+/// How long a paged reply's buttons keep working. Well within the
+/// 15 minutes an interaction token lasts, so they can still be greyed out.
+pub const PAGE_TIMEOUT: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageTurn {
+    First,
+    Previous,
+    Next,
+    Last,
+}
+
+impl PageTurn {
+    const ALL: [PageTurn; 4] = [Self::First, Self::Previous, Self::Next, Self::Last];
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::First => "first",
+            Self::Previous => "previous",
+            Self::Next => "next",
+            Self::Last => "last",
+        }
+    }
+
+    fn emoji(self) -> char {
+        match self {
+            Self::First => '⏮',
+            Self::Previous => '◀',
+            Self::Next => '▶',
+            Self::Last => '⏭',
+        }
+    }
+
+    pub fn parse(custom_id: &str) -> Option<Self> {
+        let id = custom_id.strip_prefix(PAGE_BUTTON_PREFIX)?;
+        Self::ALL.into_iter().find(|turn| turn.id() == id)
+    }
+
+    /// The page this turn lands on from `page`, staying within `pages`.
+    fn apply(self, page: usize, pages: usize) -> usize {
+        let last = pages.saturating_sub(1);
+        match self {
+            Self::First => 0,
+            Self::Previous => page.saturating_sub(1),
+            Self::Next => (page + 1).min(last),
+            Self::Last => last,
+        }
+    }
+}
+
+/// How many pages `len` entries take, at least one so an empty list still
+/// has a page to show.
+pub fn page_count(len: usize, per_page: usize) -> usize {
+    len.div_ceil(per_page.max(1)).max(1)
+}
+
+/// The entries on `page`, or none past the end.
+pub fn page_slice<T>(entries: &[T], per_page: usize, page: usize) -> &[T] {
+    let per_page = per_page.max(1);
+    let start = page.saturating_mul(per_page).min(entries.len());
+    let end = start.saturating_add(per_page).min(entries.len());
+    &entries[start..end]
+}
+
+/// One paged reply: the rendered entries and which page is showing.
+pub struct Paginator {
+    owner: UserId,
+    title: String,
+    entries: Vec<String>,
+    per_page: usize,
+    page: usize,
+    started: Instant,
+}
+
+impl Paginator {
+    pub fn new(
+        owner: UserId,
+        title: impl Into<String>,
+        entries: Vec<String>,
+        per_page: usize,
+    ) -> Self {
+        Self {
+            owner,
+            title: title.into(),
+            entries,
+            per_page,
+            page: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn pages(&self) -> usize {
+        page_count(self.entries.len(), self.per_page)
+    }
+
+    /// Only whoever ran the command turns its pages.
+    pub fn may_turn(&self, user_id: UserId) -> bool {
+        user_id == self.owner
+    }
+
+    pub fn turn(&mut self, turn: PageTurn) {
+        self.page = turn.apply(self.page, self.pages());
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.started.elapsed() >= PAGE_TIMEOUT
+    }
+
+    pub fn embed(&self) -> CreateEmbed {
+        let embed = CreateEmbed::new()
+            .title(&self.title)
+            .description(page_slice(&self.entries, self.per_page, self.page).join("\n"));
+        if self.pages() > 1 {
+            embed.footer(CreateEmbedFooter::new(format!(
+                "Page {} of {}",
+                self.page + 1,
+                self.pages()
+            )))
+        } else {
+            embed
+        }
+    }
+
+    /// The page buttons, or none when everything fits on one page. Buttons
+    /// that would go nowhere are disabled.
+    pub fn buttons(&self, disabled: bool) -> Vec<CreateActionRow> {
+        let pages = self.pages();
+        if pages <= 1 {
+            return Vec::new();
+        }
+        let buttons = PageTurn::ALL
+            .into_iter()
+            .map(|turn| {
+                CreateButton::new(format!("{}{}", PAGE_BUTTON_PREFIX, turn.id()))
+                    .emoji(ReactionType::Unicode(turn.emoji().to_string()))
+                    .style(ButtonStyle::Secondary)
+                    .disabled(disabled || turn.apply(self.page, pages) == self.page)
+            })
+            .collect();
+        vec![CreateActionRow::Buttons(buttons)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_slice_the_entries_in_order() {
+        let entries: Vec<u32> = (1..=23).collect();
+        assert_eq!(page_count(entries.len(), 10), 3);
+        assert_eq!(page_count(20, 10), 2);
+        assert_eq!(page_count(0, 10), 1);
+        assert_eq!(page_slice(&entries, 10, 0), (1..=10).collect::<Vec<_>>());
+        assert_eq!(page_slice(&entries, 10, 2), [21, 22, 23]);
+        assert!(page_slice(&entries, 10, 3).is_empty());
+        assert!(page_slice(&entries, 10, usize::MAX).is_empty());
+    }
+
+    #[test]
+    fn turns_stay_within_the_pages() {
+        let mut paginator = Paginator::new(
+            UserId::new(1),
+            "Votes",
+            (1..=23).map(|n| n.to_string()).collect(),
+            10,
+        );
+        paginator.turn(PageTurn::Previous);
+        assert_eq!(paginator.page, 0);
+        paginator.turn(PageTurn::Next);
+        assert_eq!(paginator.page, 1);
+        paginator.turn(PageTurn::Last);
+        paginator.turn(PageTurn::Next);
+        assert_eq!(paginator.page, 2);
+        paginator.turn(PageTurn::First);
+        assert_eq!(paginator.page, 0);
+    }
+
+    #[test]
+    fn only_the_invoker_may_turn_pages() {
+        let paginator = Paginator::new(UserId::new(1), "Votes", Vec::new(), 10);
+        assert!(paginator.may_turn(UserId::new(1)));
+        assert!(!paginator.may_turn(UserId::new(2)));
+    }
+
+    #[test]
+    fn button_ids_round_trip() {
+        for turn in PageTurn::ALL {
+            let custom_id = format!("{}{}", PAGE_BUTTON_PREFIX, turn.id());
+            assert_eq!(PageTurn::parse(&custom_id), Some(turn));
+        }
+        assert_eq!(PageTurn::parse("pin_page:sideways"), None);
+        assert_eq!(PageTurn::parse("pin_vote:approve:1"), None);
+    }
+}
@@ -0,0 +1,74 @@
+//! The Discord calls the pin flow depends on, behind a trait so the voting
+//! logic can be exercised against a mock instead of a live server.
+
+use serenity::{
+    all::{ChannelId, Http, Message, MessageId, ReactionType},
+    async_trait,
+};
+
+#[async_trait]
+pub trait PinApi: Sync {
+    async fn pin(&self, channel_id: ChannelId, message_id: MessageId) -> serenity::Result<()>;
+
+    async fn unpin(&self, channel_id: ChannelId, message_id: MessageId) -> serenity::Result<()>;
+
+    async fn react(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        reaction: ReactionType,
+    ) -> serenity::Result<()>;
+
+    /// Takes back one of the bot's own reactions.
+    async fn unreact(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        reaction: ReactionType,
+    ) -> serenity::Result<()>;
+
+    async fn message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> serenity::Result<Message>;
+}
+
+#[async_trait]
+impl PinApi for Http {
+    async fn pin(&self, channel_id: ChannelId, message_id: MessageId) -> serenity::Result<()> {
+        self.pin_message(channel_id, message_id, None).await
+    }
+
+    async fn unpin(&self, channel_id: ChannelId, message_id: MessageId) -> serenity::Result<()> {
+        self.unpin_message(channel_id, message_id, None).await
+    }
+
+    async fn react(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        reaction: ReactionType,
+    ) -> serenity::Result<()> {
+        self.create_reaction(channel_id, message_id, &reaction)
+            .await
+    }
+
+    async fn unreact(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        reaction: ReactionType,
+    ) -> serenity::Result<()> {
+        self.delete_reaction_me(channel_id, message_id, &reaction)
+            .await
+    }
+
+    async fn message(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> serenity::Result<Message> {
+        self.get_message(channel_id, message_id).await
+    }
+}
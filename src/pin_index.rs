@@ -0,0 +1,116 @@
+//! The pinned index message some channels keep: a list of the messages
+//! pinned there by vote, easier to find than Discord's pin panel.
+
+use crate::store::PinRecord;
+use serenity::all::{CreateEmbed, GuildId};
+
+/// Longest index description. Discord allows 4096 characters in an embed
+/// description; the margin covers the count of left-out pins.
+pub const INDEX_DESCRIPTION_LIMIT: usize = 4000;
+const INDEX_TITLE: &str = "📌 Pinned in this channel";
+const FEATURED_TITLE: &str = "🔄 Currently featured";
+
+/// The index for `pins`, newest first. Entries that don't fit are dropped
+/// from the oldest end and summarized as a count.
+pub fn index_embed(guild_id: GuildId, pins: &[PinRecord]) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(INDEX_TITLE)
+        .description(index_description(guild_id, pins))
+}
+
+/// The index of a channel that rotates its pins: only the one it
+/// features right now.
+pub fn featured_embed(guild_id: GuildId, featured: Option<&PinRecord>) -> CreateEmbed {
+    let description = featured.map_or_else(
+        || "*Nothing featured yet.*".to_string(),
+        |pin| entry(guild_id, pin),
+    );
+    CreateEmbed::new()
+        .title(FEATURED_TITLE)
+        .description(description)
+}
+
+fn index_description(guild_id: GuildId, pins: &[PinRecord]) -> String {
+    if pins.is_empty() {
+        return "*Nothing pinned by vote yet.*".to_string();
+    }
+
+    let mut pins: Vec<&PinRecord> = pins.iter().collect();
+    pins.sort_by_key(|pin| std::cmp::Reverse(pin.pinned_at));
+
+    let mut description = String::new();
+    let mut length = 0;
+    for (listed, pin) in pins.iter().enumerate() {
+        let entry = entry(guild_id, pin) + "\n";
+        let entry_length = entry.chars().count();
+        let left_out = pins.len() - listed;
+        // Leave room for the summary line unless this is the last entry
+        let reserve = if left_out > 1 { 40 } else { 0 };
+        if length + entry_length + reserve > INDEX_DESCRIPTION_LIMIT {
+            description.push_str(&format!(
+                "…and {} older {}",
+                left_out,
+                if left_out == 1 { "pin" } else { "pins" }
+            ));
+            return description;
+        }
+        length += entry_length;
+        description.push_str(&entry);
+    }
+    description
+}
+
+/// One pin's line: a jump link and its excerpt.
+fn entry(guild_id: GuildId, pin: &PinRecord) -> String {
+    let excerpt = if pin.excerpt.is_empty() {
+        "*(no text)*"
+    } else {
+        &pin.excerpt
+    };
+    format!(
+        "• [Jump]({}) {}",
+        pin.message_id.link(pin.channel_id, Some(guild_id)),
+        excerpt
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serenity::all::{ChannelId, MessageId, UserId};
+
+    fn pin(id: u64, excerpt: &str) -> PinRecord {
+        PinRecord {
+            guild_id: GuildId::new(1),
+            channel_id: ChannelId::new(2),
+            message_id: MessageId::new(id),
+            author_id: Some(UserId::new(3)),
+            excerpt: excerpt.to_string(),
+            votes: 3,
+            pinned_at: id as i64,
+            reason: None,
+            voters: 0,
+        }
+    }
+
+    #[test]
+    fn long_indexes_drop_the_oldest_pins() {
+        let pins: Vec<PinRecord> = (1..=60).map(|id| pin(id, &"x".repeat(119))).collect();
+        let description = index_description(GuildId::new(1), &pins);
+
+        assert!(description.chars().count() <= INDEX_DESCRIPTION_LIMIT);
+        // Newest first, and the oldest are the ones summarized
+        assert!(description.starts_with("• [Jump](https://discord.com/channels/1/2/60)"));
+        assert!(!description.contains("/2/1)"));
+        assert!(description.ends_with("older pins"));
+    }
+
+    #[test]
+    fn short_indexes_list_every_pin() {
+        let pins = [pin(1, "first"), pin(2, "")];
+        let description = index_description(GuildId::new(1), &pins);
+        assert_eq!(description.lines().count(), 2);
+        assert!(description.contains("*(no text)*"));
+        assert!(!description.contains("older"));
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, warn};
+
+/// Bounded lanes of jobs, each drained in order by its own worker task.
+/// Jobs with the same key share a lane, so they run one after another
+/// while other keys carry on in parallel.
+pub struct ReactionQueue<T> {
+    lanes: Vec<mpsc::Sender<T>>,
+    /// Most jobs ever waiting in one lane.
+    peak: AtomicUsize,
+}
+
+impl<T: Send + 'static> ReactionQueue<T> {
+    /// Spawns `lanes` workers, each running `work` on the jobs of its lane.
+    pub fn start<F, Fut>(lanes: usize, capacity: usize, work: F) -> Self
+    where
+        F: Fn(T) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = ()> + Send,
+    {
+        let lanes = (0..lanes.max(1))
+            .map(|_| {
+                let (sender, mut jobs) = mpsc::channel(capacity);
+                let work = work.clone();
+                tokio::spawn(async move {
+                    while let Some(job) = jobs.recv().await {
+                        work(job).await;
+                    }
+                });
+                sender
+            })
+            .collect();
+        Self {
+            lanes,
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Queues `job` on the lane for `key`, waiting while the lane is full.
+    /// Hands the job back if the lane's worker has stopped, so the caller
+    /// can still run it.
+    pub async fn push(&self, key: u64, job: T) -> Result<(), T> {
+        let lane = &self.lanes[(key % self.lanes.len() as u64) as usize];
+        let depth = lane.max_capacity() - lane.capacity() + 1;
+        self.peak.fetch_max(depth, Ordering::Relaxed);
+        match lane.try_send(job) {
+            Ok(()) => {
+                debug!(depth, "Queued reaction");
+                Ok(())
+            }
+            Err(TrySendError::Full(job)) => {
+                warn!(depth, "Reaction queue is full, waiting for a worker");
+                lane.send(job).await.map_err(|e| e.0)
+            }
+            Err(TrySendError::Closed(job)) => Err(job),
+        }
+    }
+
+    /// Jobs waiting across all lanes.
+    pub fn depth(&self) -> usize {
+        self.lanes
+            .iter()
+            .map(|lane| lane.max_capacity() - lane.capacity())
+            .sum()
+    }
+
+    /// Most jobs ever waiting in one lane.
+    pub fn peak(&self) -> usize {
+        self.peak.load(Ordering::Relaxed)
+    }
+
+    /// Whether some lane has no room left; work that can be skipped should
+    /// be then.
+    pub fn is_saturated(&self) -> bool {
+        self.lanes.iter().any(|lane| lane.capacity() == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tokio::sync::Semaphore;
+
+    #[tokio::test]
+    async fn jobs_with_one_key_run_in_order() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let queue = {
+            let seen = Arc::clone(&seen);
+            ReactionQueue::start(3, 4, move |job: (u64, u32)| {
+                let seen = Arc::clone(&seen);
+                async move {
+                    // Later jobs finish faster, which must not reorder a lane
+                    tokio::time::sleep(Duration::from_millis(u64::from(10 - job.1))).await;
+                    seen.lock().unwrap().push(job);
+                }
+            })
+        };
+        for i in 0..10 {
+            queue.push(7, (7, i)).await.unwrap();
+        }
+        while seen.lock().unwrap().len() < 10 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let order: Vec<u32> = seen.lock().unwrap().iter().map(|job| job.1).collect();
+        assert_eq!(order, (0..10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn a_full_lane_waits_instead_of_dropping() {
+        let gate = Arc::new(Semaphore::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+        let queue = {
+            let (gate, done) = (Arc::clone(&gate), Arc::clone(&done));
+            Arc::new(ReactionQueue::start(1, 2, move |_: u32| {
+                let (gate, done) = (Arc::clone(&gate), Arc::clone(&done));
+                async move {
+                    gate.acquire().await.unwrap().forget();
+                    done.fetch_add(1, Ordering::Relaxed);
+                }
+            }))
+        };
+        // One job is taken by the worker, two more fill the lane
+        for i in 0..3 {
+            queue.push(0, i).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(queue.is_saturated());
+        assert_eq!(queue.depth(), 2);
+
+        let pusher = {
+            let queue = Arc::clone(&queue);
+            tokio::spawn(async move { queue.push(0, 3).await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!pusher.is_finished());
+
+        gate.add_permits(4);
+        pusher.await.unwrap().unwrap();
+        while done.load(Ordering::Relaxed) < 4 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(queue.depth(), 0);
+        assert_eq!(queue.peak(), 3);
+    }
+}
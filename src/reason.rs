@@ -0,0 +1,48 @@
+//! Why someone asked for a pin, as typed after the mention or given to
+//! `/pin`, made safe for the bot to repeat.
+
+/// Longest reason kept; longer ones are cut with an ellipsis.
+pub const MAX_REASON_CHARS: usize = 200;
+
+/// The reason on one line, at most `MAX_REASON_CHARS` long, with
+/// `@everyone` and `@here` defused so echoing it never pings a server.
+/// `None` when nothing is left.
+pub fn clean_reason(text: &str) -> Option<String> {
+    let flat = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.is_empty() {
+        return None;
+    }
+    let mut reason: String = if flat.chars().count() > MAX_REASON_CHARS {
+        let mut short: String = flat.chars().take(MAX_REASON_CHARS - 1).collect();
+        short.push('…');
+        short
+    } else {
+        flat
+    };
+    // A zero-width space after the @ keeps the text but not the ping
+    for mention in ["@everyone", "@here"] {
+        reason = reason.replace(mention, &mention.replacen('@', "@\u{200B}", 1));
+    }
+    Some(reason)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reasons_are_flattened_defused_and_capped() {
+        assert_eq!(clean_reason("  \n "), None);
+        assert_eq!(
+            clean_reason("great  explanation\nof the raid"),
+            Some("great explanation of the raid".to_string())
+        );
+        let defused = clean_reason("@everyone look @here").unwrap();
+        assert!(!defused.contains("@everyone") && !defused.contains("@here"));
+        assert!(defused.contains("everyone") && defused.contains("here"));
+
+        let long = clean_reason(&"a".repeat(500)).unwrap();
+        assert_eq!(long.chars().count(), MAX_REASON_CHARS);
+        assert!(long.ends_with('…'));
+    }
+}
@@ -0,0 +1,930 @@
+//! Replays recorded gateway events through the bot against a stand-in for
+//! Discord, so a bug can be reproduced from a capture instead of a live
+//! server. A replay file is JSON Lines, one `ReplayEvent` per line, with
+//! the payloads in the gateway's own shape; what the bot does in return
+//! comes back as `ReplayAction`s.
+//!
+//! The events reach the real client and `Handler` over a local gateway, and
+//! the bot's REST calls land on a local server that answers pins, reactions
+//! and message fetches through `PinApi`. Nothing is in the cache beyond
+//! what the events bring, so role filters, booster weights and channel
+//! permissions see no guild: every voter counts once.
+
+use crate::{config::Config, intents, pin_api::PinApi, store::Store, BotData, Handler};
+use anyhow::{Context as _, Result};
+use futures::{SinkExt, StreamExt};
+use percent_encoding::percent_decode_str;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use serenity::{
+    all::{
+        ChannelId, ClientBuilder, EmojiId, GuildId, Http, HttpBuilder, Message, MessageId,
+        Reaction, ReactionType, ShardManager, Timestamp, UserId,
+    },
+    async_trait,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc, oneshot, watch, Mutex as AsyncMutex},
+    task::JoinHandle,
+    time::sleep,
+};
+use tokio_tungstenite::tungstenite;
+use tracing::{debug, error};
+
+/// How long the bot has to stay idle before the next event goes out.
+const SETTLE_QUIET: Duration = Duration::from_millis(100);
+
+/// Ids for the messages the bot posts, well clear of a capture's own.
+const FIRST_POSTED_ID: u64 = 1 << 40;
+
+/// One line of a replay file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ReplayEvent {
+    /// The bot's own user, which requests mention. Comes first, as on the
+    /// gateway.
+    Ready {
+        bot_id: UserId,
+    },
+    /// A new message. Every message is remembered, so a later request
+    /// replying to it can fetch it without `referenced_message`.
+    Message {
+        message: Box<Message>,
+    },
+    ReactionAdd {
+        reaction: Box<Reaction>,
+    },
+    ReactionRemove {
+        reaction: Box<Reaction>,
+    },
+    MessageDelete {
+        channel_id: ChannelId,
+        message_id: MessageId,
+    },
+    /// Lets this many seconds pass for the open votes, then runs a pass of
+    /// the cleanup loop that expires them.
+    Wait {
+        secs: u64,
+    },
+}
+
+/// Something the bot did in response to an event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayAction {
+    SessionCreated {
+        session_id: MessageId,
+        target_id: MessageId,
+        threshold: u32,
+    },
+    VoteCounted {
+        session_id: MessageId,
+        votes: u32,
+    },
+    /// `reason` is the ending recorded in the vote stats, which votes
+    /// outside a server don't keep.
+    SessionRemoved {
+        session_id: MessageId,
+        reason: Option<&'static str>,
+    },
+    ReactionAdded {
+        message_id: MessageId,
+        emoji: String,
+    },
+    ReactionRemoved {
+        message_id: MessageId,
+        emoji: String,
+    },
+    Pinned {
+        message_id: MessageId,
+    },
+    Unpinned {
+        message_id: MessageId,
+    },
+    Said {
+        channel_id: ChannelId,
+        content: String,
+    },
+    Edited {
+        message_id: MessageId,
+        content: Option<String>,
+    },
+    Deleted {
+        message_id: MessageId,
+    },
+    /// Any other call that changes something, which the stand-in refuses.
+    Called {
+        method: String,
+        path: String,
+    },
+}
+
+impl fmt::Display for ReplayAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SessionCreated {
+                session_id,
+                target_id,
+                threshold,
+            } => write!(
+                f,
+                "vote {} opened on {}, {} needed",
+                session_id, target_id, threshold
+            ),
+            Self::VoteCounted { session_id, votes } => {
+                write!(f, "vote {} at {}", session_id, votes)
+            }
+            Self::SessionRemoved {
+                session_id,
+                reason: Some(reason),
+            } => write!(f, "vote {} ended: {}", session_id, reason),
+            Self::SessionRemoved {
+                session_id,
+                reason: None,
+            } => write!(f, "vote {} ended", session_id),
+            Self::ReactionAdded { message_id, emoji } => {
+                write!(f, "react {} on {}", emoji, message_id)
+            }
+            Self::ReactionRemoved { message_id, emoji } => {
+                write!(f, "unreact {} on {}", emoji, message_id)
+            }
+            Self::Pinned { message_id } => write!(f, "pin {}", message_id),
+            Self::Unpinned { message_id } => write!(f, "unpin {}", message_id),
+            Self::Said {
+                channel_id,
+                content,
+            } => write!(f, "say in {}: {:?}", channel_id, content),
+            Self::Edited {
+                message_id,
+                content: Some(content),
+            } => write!(f, "edit {}: {:?}", message_id, content),
+            Self::Edited {
+                message_id,
+                content: None,
+            } => write!(f, "edit {}", message_id),
+            Self::Deleted { message_id } => write!(f, "delete {}", message_id),
+            Self::Called { method, path } => write!(f, "call {} {}", method, path),
+        }
+    }
+}
+
+/// What the replay last saw of an open vote.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct SessionView {
+    guild_id: Option<GuildId>,
+    target_id: MessageId,
+    threshold: u32,
+    votes: u32,
+}
+
+/// Stands in for Discord's side of every call: remembers the replayed and
+/// posted messages, and records each call along with the votes that opened,
+/// moved or ended before it.
+struct RecordingApi {
+    data: Arc<BotData>,
+    gateway_url: String,
+    bot_id: Mutex<Option<UserId>>,
+    messages: Mutex<HashMap<MessageId, Message>>,
+    next_id: AtomicU64,
+    actions: Mutex<Vec<ReplayAction>>,
+    sessions: Mutex<HashMap<MessageId, SessionView>>,
+    /// Calls still being answered, and when the bot last did anything.
+    in_flight: AtomicUsize,
+    last_activity: Mutex<Instant>,
+}
+
+impl RecordingApi {
+    fn new(data: Arc<BotData>, gateway_url: String) -> Self {
+        Self {
+            data,
+            gateway_url,
+            bot_id: Mutex::new(None),
+            messages: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(FIRST_POSTED_ID),
+            actions: Mutex::new(Vec::new()),
+            sessions: Mutex::new(HashMap::new()),
+            in_flight: AtomicUsize::new(0),
+            last_activity: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        *self.last_activity.lock().expect("replay clock poisoned") = Instant::now();
+    }
+
+    fn record(&self, action: ReplayAction) {
+        let mut actions = self.actions.lock().expect("replay actions poisoned");
+        self.note_sessions(&mut actions);
+        actions.push(action);
+        self.touch();
+    }
+
+    fn take_actions(&self) -> Vec<ReplayAction> {
+        let mut actions = self.actions.lock().expect("replay actions poisoned");
+        self.note_sessions(&mut actions);
+        std::mem::take(&mut *actions)
+    }
+
+    /// Records how the open votes changed since the last look, in the order
+    /// of their ids.
+    fn note_sessions(&self, actions: &mut Vec<ReplayAction>) {
+        let now: HashMap<MessageId, SessionView> = self
+            .data
+            .voting_sessions
+            .iter()
+            .map(|session| {
+                (
+                    *session.key(),
+                    SessionView {
+                        guild_id: session.guild_id,
+                        target_id: session.target_message_id,
+                        threshold: session.threshold,
+                        votes: session.get_vote_count(),
+                    },
+                )
+            })
+            .collect();
+        let mut seen = self.sessions.lock().expect("replay sessions poisoned");
+        if *seen == now {
+            return;
+        }
+
+        let mut ids: Vec<MessageId> = seen.keys().chain(now.keys()).copied().collect();
+        ids.sort_unstable();
+        ids.dedup();
+        for session_id in ids {
+            match (seen.get(&session_id), now.get(&session_id)) {
+                (None, Some(view)) => {
+                    actions.push(ReplayAction::SessionCreated {
+                        session_id,
+                        target_id: view.target_id,
+                        threshold: view.threshold,
+                    });
+                    if view.votes > 0 {
+                        actions.push(ReplayAction::VoteCounted {
+                            session_id,
+                            votes: view.votes,
+                        });
+                    }
+                }
+                (Some(before), Some(view)) if before.votes != view.votes => {
+                    actions.push(ReplayAction::VoteCounted {
+                        session_id,
+                        votes: view.votes,
+                    });
+                }
+                (Some(before), None) => actions.push(ReplayAction::SessionRemoved {
+                    session_id,
+                    reason: self.ending(before.guild_id, session_id),
+                }),
+                _ => {}
+            }
+        }
+        *seen = now;
+        self.touch();
+    }
+
+    fn ending(&self, guild_id: Option<GuildId>, session_id: MessageId) -> Option<&'static str> {
+        self.data
+            .store
+            .vote_stats_between(guild_id?, 0, i64::MAX)
+            .ok()?
+            .into_iter()
+            .find(|stats| stats.session_id == session_id)
+            .map(|stats| stats.ending.as_str())
+    }
+
+    fn remember(&self, message: Message) {
+        self.messages
+            .lock()
+            .expect("replay messages poisoned")
+            .insert(message.id, message);
+    }
+
+    fn set_pinned(&self, message_id: MessageId, pinned: bool) {
+        if let Some(message) = self
+            .messages
+            .lock()
+            .expect("replay messages poisoned")
+            .get_mut(&message_id)
+        {
+            message.pinned = pinned;
+        }
+    }
+
+    /// Answers one REST call from the bot with a status and a JSON body.
+    async fn respond(&self, method: &str, path: &str, body: &[u8]) -> (u16, String) {
+        let route = path.split('?').next().unwrap_or_default();
+        let route = route.strip_prefix("/api/v10").unwrap_or(route);
+        let segments: Vec<&str> = route.split('/').skip(1).collect();
+        let id = |segment: &str| segment.parse::<u64>().ok().filter(|id| *id != 0);
+        let unknown = |code: u32, message: &str| {
+            (404, json!({ "code": code, "message": message }).to_string())
+        };
+        let answer = |result: serenity::Result<()>| match result {
+            Ok(()) => (204, String::new()),
+            Err(e) => (
+                404,
+                json!({ "code": 0, "message": e.to_string() }).to_string(),
+            ),
+        };
+
+        match (method, segments.as_slice()) {
+            ("GET", ["gateway"] | ["gateway", "bot"]) => (
+                200,
+                json!({
+                    "url": self.gateway_url,
+                    "shards": 1,
+                    "session_start_limit": {
+                        "total": 1000,
+                        "remaining": 1000,
+                        "reset_after": 0,
+                        "max_concurrency": 1,
+                    },
+                })
+                .to_string(),
+            ),
+            // Slash commands register without a trace, since the replay
+            // never sends interactions
+            (
+                "PUT",
+                ["applications", _, "commands"] | ["applications", _, "guilds", _, "commands"],
+            ) => (200, "[]".to_string()),
+            (_, ["channels", channel_id, "pins", message_id]) => {
+                let (Some(channel_id), Some(message_id)) = (id(channel_id), id(message_id)) else {
+                    return unknown(10008, "Unknown Message");
+                };
+                let (channel_id, message_id) =
+                    (ChannelId::new(channel_id), MessageId::new(message_id));
+                match method {
+                    "PUT" => answer(self.pin(channel_id, message_id).await),
+                    "DELETE" => answer(self.unpin(channel_id, message_id).await),
+                    _ => self.refuse(method, route),
+                }
+            }
+            (_, ["channels", channel_id, "messages", message_id, "reactions", emoji, "@me"]) => {
+                let (Some(channel_id), Some(message_id), Some(reaction)) =
+                    (id(channel_id), id(message_id), reaction_from_path(emoji))
+                else {
+                    return unknown(10014, "Unknown Emoji");
+                };
+                let (channel_id, message_id) =
+                    (ChannelId::new(channel_id), MessageId::new(message_id));
+                match method {
+                    "PUT" => answer(self.react(channel_id, message_id, reaction).await),
+                    "DELETE" => answer(self.unreact(channel_id, message_id, reaction).await),
+                    _ => self.refuse(method, route),
+                }
+            }
+            ("GET", ["channels", channel_id, "messages", message_id]) => {
+                let (Some(channel_id), Some(message_id)) = (id(channel_id), id(message_id)) else {
+                    return unknown(10008, "Unknown Message");
+                };
+                match self
+                    .message(ChannelId::new(channel_id), MessageId::new(message_id))
+                    .await
+                {
+                    Ok(message) => (200, json!(message).to_string()),
+                    Err(_) => unknown(10008, "Unknown Message"),
+                }
+            }
+            ("POST", ["channels", channel_id, "messages"]) => match id(channel_id) {
+                Some(channel_id) => self.post(ChannelId::new(channel_id), body),
+                None => unknown(10003, "Unknown Channel"),
+            },
+            ("PATCH", ["channels", _, "messages", message_id]) => match id(message_id) {
+                Some(message_id) => self.edit(MessageId::new(message_id), body),
+                None => unknown(10008, "Unknown Message"),
+            },
+            ("DELETE", ["channels", _, "messages", message_id]) => {
+                let Some(message_id) = id(message_id).map(MessageId::new) else {
+                    return unknown(10008, "Unknown Message");
+                };
+                self.messages
+                    .lock()
+                    .expect("replay messages poisoned")
+                    .remove(&message_id);
+                self.record(ReplayAction::Deleted { message_id });
+                (204, String::new())
+            }
+            // Captures leave out who reacted, so everyone but the bot is an
+            // ordinary member without roles
+            ("GET", ["users", user_id]) => match id(user_id) {
+                Some(user_id) => (200, self.user(UserId::new(user_id)).to_string()),
+                None => unknown(10013, "Unknown User"),
+            },
+            ("GET", ["guilds", guild_id, "members", user_id]) => {
+                match (id(guild_id), id(user_id)) {
+                    (Some(guild_id), Some(user_id)) => (
+                        200,
+                        json!({
+                            "guild_id": guild_id.to_string(),
+                            "user": self.user(UserId::new(user_id)),
+                            "roles": [],
+                            "joined_at": "2024-01-01T00:00:00.000000+00:00",
+                            "deaf": false,
+                            "mute": false,
+                            "flags": 0,
+                        })
+                        .to_string(),
+                    ),
+                    _ => unknown(10007, "Unknown Member"),
+                }
+            }
+            // Lookups the replay has no answer for fail quietly, and the
+            // bot falls back as it would without access
+            ("GET", _) => unknown(0, "Not in the replay"),
+            _ => self.refuse(method, route),
+        }
+    }
+
+    fn user(&self, user_id: UserId) -> Value {
+        let bot_id = *self.bot_id.lock().expect("replay bot id poisoned");
+        json!({
+            "id": user_id,
+            "username": format!("user{}", user_id),
+            "avatar": null,
+            "bot": Some(user_id) == bot_id,
+        })
+    }
+
+    /// Stores a message the bot sent and answers with it under a fresh id.
+    fn post(&self, channel_id: ChannelId, body: &[u8]) -> (u16, String) {
+        let body: Value = serde_json::from_slice(body).unwrap_or_default();
+        let content = body["content"].as_str().unwrap_or_default().to_string();
+        let bot_id = self
+            .bot_id
+            .lock()
+            .expect("replay bot id poisoned")
+            .unwrap_or(UserId::new(1));
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let message = json!({
+            "id": id.to_string(),
+            "channel_id": channel_id,
+            "author": self.user(bot_id),
+            "content": content,
+            "timestamp": Timestamp::now(),
+            "edited_timestamp": null,
+            "tts": false,
+            "mention_everyone": false,
+            "mentions": [],
+            "mention_roles": [],
+            "attachments": [],
+            "embeds": body.get("embeds").cloned().unwrap_or_else(|| json!([])),
+            "pinned": false,
+            "type": 0,
+        });
+        if let Ok(posted) = serde_json::from_value::<Message>(message.clone()) {
+            self.remember(posted);
+        }
+        self.record(ReplayAction::Said {
+            channel_id,
+            content,
+        });
+        (200, message.to_string())
+    }
+
+    fn edit(&self, message_id: MessageId, body: &[u8]) -> (u16, String) {
+        let body: Value = serde_json::from_slice(body).unwrap_or_default();
+        let content = body["content"].as_str().map(str::to_string);
+        let mut messages = self.messages.lock().expect("replay messages poisoned");
+        let Some(message) = messages.get_mut(&message_id) else {
+            return (
+                404,
+                json!({ "code": 10008, "message": "Unknown Message" }).to_string(),
+            );
+        };
+        if let Some(content) = &content {
+            message.content.clone_from(content);
+        }
+        let edited = json!(message).to_string();
+        drop(messages);
+        self.record(ReplayAction::Edited {
+            message_id,
+            content,
+        });
+        (200, edited)
+    }
+
+    fn refuse(&self, method: &str, route: &str) -> (u16, String) {
+        self.record(ReplayAction::Called {
+            method: method.to_string(),
+            path: route.to_string(),
+        });
+        (
+            403,
+            json!({ "code": 50013, "message": "Missing Permissions" }).to_string(),
+        )
+    }
+}
+
+/// A reaction as it appears in a REST path: `name:id` for a custom emoji,
+/// percent-encoded otherwise.
+fn reaction_from_path(segment: &str) -> Option<ReactionType> {
+    let decoded = percent_decode_str(segment).decode_utf8().ok()?;
+    Some(match decoded.rsplit_once(':') {
+        Some((name, id)) => ReactionType::Custom {
+            animated: false,
+            id: EmojiId::new(id.parse().ok().filter(|id| *id != 0)?),
+            name: Some(name.to_string()),
+        },
+        None => ReactionType::Unicode(decoded.into_owned()),
+    })
+}
+
+#[async_trait]
+impl PinApi for RecordingApi {
+    async fn pin(&self, _channel_id: ChannelId, message_id: MessageId) -> serenity::Result<()> {
+        self.set_pinned(message_id, true);
+        self.record(ReplayAction::Pinned { message_id });
+        Ok(())
+    }
+
+    async fn unpin(&self, _channel_id: ChannelId, message_id: MessageId) -> serenity::Result<()> {
+        self.set_pinned(message_id, false);
+        self.record(ReplayAction::Unpinned { message_id });
+        Ok(())
+    }
+
+    async fn react(
+        &self,
+        _channel_id: ChannelId,
+        message_id: MessageId,
+        reaction: ReactionType,
+    ) -> serenity::Result<()> {
+        self.record(ReplayAction::ReactionAdded {
+            message_id,
+            emoji: reaction.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn unreact(
+        &self,
+        _channel_id: ChannelId,
+        message_id: MessageId,
+        reaction: ReactionType,
+    ) -> serenity::Result<()> {
+        self.record(ReplayAction::ReactionRemoved {
+            message_id,
+            emoji: reaction.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn message(
+        &self,
+        _channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> serenity::Result<Message> {
+        self.messages
+            .lock()
+            .expect("replay messages poisoned")
+            .get(&message_id)
+            .cloned()
+            .ok_or(serenity::Error::Other("message not in the replay"))
+    }
+}
+
+/// Serves the bot's REST calls over plain HTTP/1.1, one task per
+/// connection.
+async fn serve_rest(listener: TcpListener, api: Arc<RecordingApi>) {
+    while let Ok((stream, _)) = listener.accept().await {
+        let api = Arc::clone(&api);
+        tokio::spawn(async move {
+            if let Err(e) = serve_connection(stream, &api).await {
+                debug!(error = %e, "Replay REST connection closed");
+            }
+        });
+    }
+}
+
+async fn serve_connection(stream: TcpStream, api: &RecordingApi) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    loop {
+        let mut request_line = String::new();
+        if stream.read_line(&mut request_line).await? == 0 {
+            return Ok(());
+        }
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            stream.read_line(&mut header).await?;
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await?;
+
+        api.in_flight.fetch_add(1, Ordering::AcqRel);
+        api.touch();
+        let mut parts = request_line.split_whitespace();
+        let (method, path) = (
+            parts.next().unwrap_or_default(),
+            parts.next().unwrap_or_default(),
+        );
+        let (status, body) = api.respond(method, path, &body).await;
+        let head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n",
+            status,
+            if status < 300 { "OK" } else { "Error" },
+            body.len()
+        );
+        let written = async {
+            stream.get_mut().write_all(head.as_bytes()).await?;
+            stream.get_mut().write_all(body.as_bytes()).await?;
+            stream.get_mut().flush().await
+        }
+        .await;
+        api.touch();
+        api.in_flight.fetch_sub(1, Ordering::AcqRel);
+        written?;
+    }
+}
+
+/// A dispatch waiting for the gateway, with word back once it is sent.
+type Dispatch = (&'static str, Value, oneshot::Sender<()>);
+
+/// Plays the gateway for the bot's one shard: says hello, acknowledges
+/// heartbeats and sends the queued dispatches once the shard identified.
+async fn serve_gateway(
+    listener: TcpListener,
+    dispatches: mpsc::UnboundedReceiver<Dispatch>,
+    api: Arc<RecordingApi>,
+) {
+    let dispatches = Arc::new(AsyncMutex::new(dispatches));
+    let seq = Arc::new(AtomicU64::new(0));
+    while let Ok((stream, _)) = listener.accept().await {
+        let (dispatches, seq, api) = (Arc::clone(&dispatches), Arc::clone(&seq), Arc::clone(&api));
+        tokio::spawn(async move {
+            if let Err(e) = serve_shard(stream, &dispatches, &seq, &api).await {
+                debug!(error = %e, "Replay gateway connection closed");
+            }
+        });
+    }
+}
+
+async fn serve_shard(
+    stream: TcpStream,
+    dispatches: &AsyncMutex<mpsc::UnboundedReceiver<Dispatch>>,
+    seq: &AtomicU64,
+    api: &RecordingApi,
+) -> Result<(), tungstenite::Error> {
+    let mut socket = tokio_tungstenite::accept_async(stream).await?;
+    let hello = json!({ "op": 10, "d": { "heartbeat_interval": 41250 } });
+    socket
+        .send(tungstenite::Message::Text(hello.to_string()))
+        .await?;
+
+    let mut dispatches = dispatches.lock().await;
+    let mut identified = false;
+    loop {
+        tokio::select! {
+            frame = socket.next() => {
+                let Some(frame) = frame else {
+                    return Ok(());
+                };
+                let tungstenite::Message::Text(frame) = frame? else {
+                    continue;
+                };
+                let frame: Value = serde_json::from_str(&frame).unwrap_or_default();
+                match frame["op"].as_u64() {
+                    Some(1) => {
+                        let ack = json!({ "op": 11 });
+                        socket.send(tungstenite::Message::Text(ack.to_string())).await?;
+                    }
+                    Some(2) => identified = true,
+                    _ => {}
+                }
+            }
+            dispatch = dispatches.recv(), if identified => {
+                let Some((kind, payload, sent)) = dispatch else {
+                    return Ok(());
+                };
+                let frame = json!({
+                    "op": 0,
+                    "s": seq.fetch_add(1, Ordering::Relaxed) + 1,
+                    "t": kind,
+                    "d": payload,
+                });
+                socket.send(tungstenite::Message::Text(frame.to_string())).await?;
+                api.touch();
+                let _ = sent.send(());
+            }
+        }
+    }
+}
+
+/// Runs the bot against the stand-in Discord over a fresh in-memory store,
+/// feeding it one event at a time.
+pub struct Replay {
+    data: Arc<BotData>,
+    api: Arc<RecordingApi>,
+    http: Arc<Http>,
+    dispatches: mpsc::UnboundedSender<Dispatch>,
+    shard_manager: Arc<ShardManager>,
+    shutdown: watch::Sender<bool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Replay {
+    pub async fn new(config: Config) -> Result<Self> {
+        let store = Store::open(":memory:")?;
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let handler = Handler::new(config, store, shutdown_rx);
+        let data = handler.data();
+        // Without application emojis the votes use the Unicode ones, as
+        // when listing them fails
+        let _ = data.app_emojis.set(Default::default());
+
+        let rest = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to open the replay's REST server")?;
+        let gateway = TcpListener::bind("127.0.0.1:0")
+            .await
+            .context("Failed to open the replay's gateway")?;
+        let rest_url = format!("http://{}", rest.local_addr()?);
+        let api = Arc::new(RecordingApi::new(
+            Arc::clone(&data),
+            format!("ws://{}", gateway.local_addr()?),
+        ));
+        let (dispatches, queued) = mpsc::unbounded_channel();
+        let mut tasks = vec![
+            tokio::spawn(serve_rest(rest, Arc::clone(&api))),
+            tokio::spawn(serve_gateway(gateway, queued, Arc::clone(&api))),
+        ];
+
+        let http = HttpBuilder::new("replay")
+            .proxy(rest_url)
+            .ratelimiter_disabled(true)
+            .build();
+        let mut client = ClientBuilder::new_with_http(
+            http,
+            intents(data.interaction_only(), data.drop_departed_votes()),
+        )
+        .event_handler(handler)
+        .await
+        .context("Failed to start the replay client")?;
+        let shard_manager = Arc::clone(&client.shard_manager);
+        let http = Arc::clone(&client.http);
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = client.start().await {
+                error!(error = %e, "Replay client stopped");
+            }
+        }));
+
+        Ok(Self {
+            data,
+            api,
+            http,
+            dispatches,
+            shard_manager,
+            shutdown,
+            tasks,
+        })
+    }
+
+    /// Reads a replay file's events, naming the line of the first one that
+    /// doesn't parse. Blank lines are skipped.
+    pub fn parse(input: &str) -> Result<Vec<ReplayEvent>> {
+        input
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line)
+                    .with_context(|| format!("Line {} is not a replay event", index + 1))
+            })
+            .collect()
+    }
+
+    /// Replays every event in order and stops the bot, returning everything
+    /// it did.
+    pub async fn run(mut self, events: Vec<ReplayEvent>) -> Vec<ReplayAction> {
+        let mut actions = Vec::new();
+        for event in events {
+            actions.extend(self.feed(event).await);
+        }
+        self.stop().await;
+        actions
+    }
+
+    /// Handles one event and returns what the bot did in response, once it
+    /// has gone quiet.
+    pub async fn feed(&mut self, event: ReplayEvent) -> Vec<ReplayAction> {
+        match event {
+            ReplayEvent::Ready { bot_id } => {
+                *self.api.bot_id.lock().expect("replay bot id poisoned") = Some(bot_id);
+                self.dispatch(
+                    "READY",
+                    json!({
+                        "v": 10,
+                        "user": {
+                            "id": bot_id,
+                            "username": "pin-bot",
+                            "avatar": null,
+                            "bot": true,
+                        },
+                        "guilds": [],
+                        "session_id": "replay",
+                        "resume_gateway_url": self.api.gateway_url,
+                        "shard": [0, 1],
+                        "application": { "id": bot_id, "flags": 0 },
+                    }),
+                )
+                .await;
+            }
+            ReplayEvent::Message { message } => {
+                self.api.remember((*message).clone());
+                self.dispatch("MESSAGE_CREATE", json!(message)).await;
+            }
+            ReplayEvent::ReactionAdd { reaction } => {
+                self.dispatch("MESSAGE_REACTION_ADD", json!(reaction)).await;
+            }
+            ReplayEvent::ReactionRemove { reaction } => {
+                self.dispatch("MESSAGE_REACTION_REMOVE", json!(reaction))
+                    .await;
+            }
+            ReplayEvent::MessageDelete {
+                channel_id,
+                message_id,
+            } => {
+                self.api
+                    .messages
+                    .lock()
+                    .expect("replay messages poisoned")
+                    .remove(&message_id);
+                self.dispatch(
+                    "MESSAGE_DELETE",
+                    json!({ "id": message_id, "channel_id": channel_id }),
+                )
+                .await;
+            }
+            ReplayEvent::Wait { secs } => {
+                let elapsed = Duration::from_secs(secs);
+                let default = Duration::from_secs(self.data.config().vote_timeout_secs);
+                for mut session in self.data.voting_sessions.iter_mut() {
+                    session.timeout = Some(session.timeout(default).saturating_sub(elapsed));
+                }
+                self.data.clean_up(&self.http).await;
+            }
+        }
+        self.settle().await;
+        self.api.take_actions()
+    }
+
+    async fn dispatch(&self, kind: &'static str, payload: Value) {
+        let (sent, delivered) = oneshot::channel();
+        if self.dispatches.send((kind, payload, sent)).is_ok() {
+            let _ = delivered.await;
+        }
+    }
+
+    /// Waits until no call is being answered, no pin is queued and nothing
+    /// has happened for `SETTLE_QUIET`.
+    async fn settle(&self) {
+        loop {
+            sleep(Duration::from_millis(10)).await;
+            self.api
+                .note_sessions(&mut self.api.actions.lock().expect("replay actions poisoned"));
+            let quiet = self
+                .api
+                .last_activity
+                .lock()
+                .expect("replay clock poisoned")
+                .elapsed()
+                >= SETTLE_QUIET;
+            if quiet
+                && self.api.in_flight.load(Ordering::Acquire) == 0
+                && self.data.pin_queue_depth() == 0
+            {
+                return;
+            }
+        }
+    }
+
+    async fn stop(self) {
+        let _ = self.shutdown.send(true);
+        self.shard_manager.shutdown_all().await;
+        for task in self.tasks {
+            task.abort();
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Pruning of stale pins: which of a channel's pins its retention policy
+//! takes down.
+
+use crate::digest::DAY_SECS;
+use serde::{Deserialize, Serialize};
+use serenity::all::MessageId;
+
+/// Longest a channel may keep its pins before pruning, about ten years.
+pub const MAX_RETENTION_DAYS: u32 = 3_650;
+
+/// Unpins a channel's pins once they are older than `days`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PinRetention {
+    pub days: u32,
+    /// Leave pins made by hand alone, only pruning voted ones.
+    pub keep_manual: bool,
+}
+
+impl PinRetention {
+    /// The pins due to be taken down at `now`, in the order given.
+    pub fn expired(&self, pins: &[PinAge], now: i64) -> Vec<MessageId> {
+        let cutoff = now - i64::from(self.days) * DAY_SECS;
+        pins.iter()
+            .filter(|pin| pin.pinned_at < cutoff && !(self.keep_manual && pin.manual))
+            .map(|pin| pin.message_id)
+            .collect()
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "after {} {}{}",
+            self.days,
+            if self.days == 1 { "day" } else { "days" },
+            if self.keep_manual {
+                ", keeping manual pins"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+/// How old a pin is and whether it was made by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PinAge {
+    pub message_id: MessageId,
+    /// Unix timestamp, in seconds.
+    pub pinned_at: i64,
+    pub manual: bool,
+}
+
+impl PinAge {
+    /// Dates a pin from the history's record of it, `(pinned_at, manual)`,
+    /// when there is one. A pin the history doesn't know is dated when its
+    /// message was sent and counts as manual, since no vote made it.
+    pub fn of(message_id: MessageId, sent_at: i64, recorded: Option<(i64, bool)>) -> Self {
+        let (pinned_at, manual) = recorded.unwrap_or((sent_at, true));
+        Self {
+            message_id,
+            pinned_at,
+            manual,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_pins_past_the_cutoff_are_pruned() {
+        let now = 1_000 * DAY_SECS;
+        let retention = PinRetention {
+            days: 90,
+            keep_manual: true,
+        };
+        let voted = |id: u64, days_ago: i64| {
+            PinAge::of(
+                MessageId::new(id),
+                0,
+                Some((now - days_ago * DAY_SECS, false)),
+            )
+        };
+        let pins = [
+            voted(1, 91),
+            voted(2, 89),
+            PinAge::of(MessageId::new(3), 0, Some((now - 200 * DAY_SECS, true))),
+            // Not in the history: dated by the message, counted as manual
+            PinAge::of(MessageId::new(4), now - 300 * DAY_SECS, None),
+        ];
+
+        assert_eq!(retention.expired(&pins, now), [MessageId::new(1)]);
+        let everything = PinRetention {
+            keep_manual: false,
+            ..retention
+        };
+        assert_eq!(
+            everything.expired(&pins, now),
+            [MessageId::new(1), MessageId::new(3), MessageId::new(4)]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Tracks the gateway shards' health between polls, to catch a shard that
+//! looks online but has stopped hearing from Discord, like one stuck after
+//! a failed resume.
+
+use serenity::all::{ConnectionStage, ShardId};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Exit status when a stale shard makes the bot quit, so a supervisor can
+/// tell it apart from a crash. `EX_TEMPFAIL` from sysexits.h.
+pub const STALE_SHARD_EXIT_CODE: u8 = 75;
+
+/// What the monitor last saw of one shard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardHealth {
+    pub stage: ConnectionStage,
+    pub latency: Option<Duration>,
+    /// When a heartbeat last got through.
+    pub healthy_at: Instant,
+    /// When the current outage started: the last heartbeat before the
+    /// shard dropped or went quiet. `None` while it is up.
+    pub down_since: Option<Instant>,
+}
+
+/// How a shard changed since the previous poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShardChange {
+    Steady,
+    /// The shard left the connected stage.
+    Lost,
+    /// A heartbeat got through again after an outage this long.
+    Recovered(Duration),
+}
+
+#[derive(Debug, Default)]
+pub struct ShardMonitor {
+    shards: HashMap<ShardId, ShardHealth>,
+}
+
+impl ShardMonitor {
+    /// Records one poll of a shard. serenity only updates a shard's latency
+    /// when a heartbeat is acknowledged, so a connected shard whose latency
+    /// changed since the last poll has heard from Discord in between.
+    pub fn observe(
+        &mut self,
+        shard_id: ShardId,
+        stage: ConnectionStage,
+        latency: Option<Duration>,
+        now: Instant,
+    ) -> ShardChange {
+        let Some(health) = self.shards.get_mut(&shard_id) else {
+            let connected = stage == ConnectionStage::Connected;
+            self.shards.insert(
+                shard_id,
+                ShardHealth {
+                    stage,
+                    latency,
+                    healthy_at: now,
+                    down_since: (!connected).then_some(now),
+                },
+            );
+            return ShardChange::Steady;
+        };
+
+        let heartbeat =
+            stage == ConnectionStage::Connected && latency.is_some() && latency != health.latency;
+        health.stage = stage;
+        health.latency = latency;
+        if heartbeat {
+            health.healthy_at = now;
+            return match health.down_since.take() {
+                Some(since) => ShardChange::Recovered(now.saturating_duration_since(since)),
+                None => ShardChange::Steady,
+            };
+        }
+        if stage != ConnectionStage::Connected && health.down_since.is_none() {
+            health.down_since = Some(health.healthy_at);
+            return ShardChange::Lost;
+        }
+        ShardChange::Steady
+    }
+
+    /// Shards without a heartbeat for `threshold` or longer, with how long
+    /// they have been quiet.
+    pub fn stale(&self, now: Instant, threshold: Duration) -> Vec<(ShardId, Duration)> {
+        let mut stale: Vec<(ShardId, Duration)> = self
+            .shards
+            .iter()
+            .map(|(shard_id, health)| (*shard_id, now.saturating_duration_since(health.healthy_at)))
+            .filter(|(_, quiet)| *quiet >= threshold)
+            .collect();
+        stale.sort_by_key(|(shard_id, _)| *shard_id);
+        stale
+    }
+
+    /// Notes that a stale shard was restarted: it gets a full threshold to
+    /// come back before the next restart, and its outage keeps running
+    /// until it does.
+    pub fn restarted(&mut self, shard_id: ShardId, now: Instant) {
+        if let Some(health) = self.shards.get_mut(&shard_id) {
+            health.down_since.get_or_insert(health.healthy_at);
+            health.healthy_at = now;
+        }
+    }
+
+    /// Forgets shards the manager no longer runs.
+    pub fn retain(&mut self, running: &[ShardId]) {
+        self.shards.retain(|shard_id, _| running.contains(shard_id));
+    }
+
+    /// Every shard's last known health, by shard id.
+    pub fn shards(&self) -> Vec<(ShardId, ShardHealth)> {
+        let mut shards: Vec<(ShardId, ShardHealth)> = self
+            .shards
+            .iter()
+            .map(|(shard_id, health)| (*shard_id, *health))
+            .collect();
+        shards.sort_by_key(|(shard_id, _)| *shard_id);
+        shards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_quiet_shard_goes_stale_and_its_outage_ends_with_a_heartbeat() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let shard = ShardId(0);
+        let threshold = Duration::from_secs(300);
+        let latency = |millis: u64| Some(Duration::from_millis(millis));
+        let mut monitor = ShardMonitor::default();
+
+        use ConnectionStage::{Connected, Resuming};
+        assert_eq!(
+            monitor.observe(shard, Connected, latency(40), at(0)),
+            ShardChange::Steady
+        );
+        assert_eq!(
+            monitor.observe(shard, Connected, latency(42), at(30)),
+            ShardChange::Steady
+        );
+        // A resume that never completes
+        assert_eq!(
+            monitor.observe(shard, Resuming, latency(42), at(60)),
+            ShardChange::Lost
+        );
+        assert_eq!(
+            monitor.observe(shard, Resuming, latency(42), at(90)),
+            ShardChange::Steady
+        );
+        assert!(monitor.stale(at(300), threshold).is_empty());
+        assert_eq!(
+            monitor.stale(at(330), threshold),
+            [(shard, Duration::from_secs(300))]
+        );
+
+        // The restart gets a full threshold, and the outage counts from the
+        // last heartbeat before it
+        monitor.restarted(shard, at(330));
+        assert!(monitor.stale(at(600), threshold).is_empty());
+        assert_eq!(
+            monitor.observe(shard, Connected, latency(45), at(360)),
+            ShardChange::Recovered(Duration::from_secs(330))
+        );
+        assert_eq!(monitor.shards()[0].1.down_since, None);
+    }
+
+    #[test]
+    fn a_connected_shard_with_a_frozen_latency_counts_as_quiet() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let shard = ShardId(1);
+        let latency = Some(Duration::from_millis(40));
+        let mut monitor = ShardMonitor::default();
+
+        for secs in [0, 30, 60, 90] {
+            monitor.observe(shard, ConnectionStage::Connected, latency, at(secs));
+        }
+        assert_eq!(
+            monitor.stale(at(90), Duration::from_secs(60)),
+            [(shard, Duration::from_secs(90))]
+        );
+        monitor.retain(&[ShardId(0)]);
+        assert!(monitor.shards().is_empty());
+    }
+}
@@ -0,0 +1,99 @@
+//! What a vote's target looked like when the vote opened. The archive copy
+//! and the pin history show this rather than the live message, which its
+//! author may have rewritten or stripped of attachments by the time the
+//! vote passes. The pin itself still goes to the live message.
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{Attachment, Message, Timestamp, UserId};
+
+/// Longest text kept, Discord's own limit for a message with Nitro. It
+/// still fits an embed description.
+const SNAPSHOT_CHARS: usize = 4_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetSnapshot {
+    pub author_id: UserId,
+    pub author_name: String,
+    pub author_avatar: String,
+    pub content: String,
+    /// Every attachment's URL, in order.
+    pub attachments: Vec<String>,
+    /// The first image among them, shown in the archive copy.
+    pub image_url: Option<String>,
+    /// When the message was posted.
+    pub timestamp: Timestamp,
+}
+
+impl TargetSnapshot {
+    pub fn capture(message: &Message) -> Self {
+        let mut snapshot = Self {
+            author_id: message.author.id,
+            author_name: message.author.name.clone(),
+            author_avatar: message.author.face(),
+            content: String::new(),
+            attachments: Vec::new(),
+            image_url: None,
+            timestamp: message.timestamp,
+        };
+        snapshot.refresh(Some(&message.content), Some(&message.attachments));
+        snapshot
+    }
+
+    /// Takes in an edit of the message. `None` leaves that part as it was,
+    /// like a gateway update that didn't touch it.
+    pub fn refresh(&mut self, content: Option<&str>, attachments: Option<&[Attachment]>) {
+        if let Some(content) = content {
+            self.content = content.chars().take(SNAPSHOT_CHARS).collect();
+        }
+        if let Some(attachments) = attachments {
+            self.attachments = attachments
+                .iter()
+                .map(|attachment| attachment.url.clone())
+                .collect();
+            self.image_url = attachments
+                .iter()
+                .find(|attachment| {
+                    attachment
+                        .content_type
+                        .as_deref()
+                        .is_some_and(|kind| kind.starts_with("image/"))
+                })
+                .map(|attachment| attachment.url.clone());
+        }
+    }
+
+    /// The text on one line, as the pin history keeps it.
+    pub fn excerpt(&self) -> String {
+        crate::digest::excerpt(&self.content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_snapshot_keeps_its_text_until_refreshed_and_survives_a_round_trip() {
+        let mut message = Message::default();
+        message.author.id = UserId::new(7);
+        message.author.name = "poster".to_string();
+        message.content = "x".repeat(SNAPSHOT_CHARS + 10);
+
+        let mut snapshot = TargetSnapshot::capture(&message);
+        assert_eq!(snapshot.content.chars().count(), SNAPSHOT_CHARS);
+        message.content = "rewritten".to_string();
+        assert_eq!(TargetSnapshot::capture(&message).excerpt(), "rewritten");
+
+        snapshot.refresh(None, None);
+        assert_eq!(snapshot.content.len(), SNAPSHOT_CHARS);
+        snapshot.refresh(Some("edited  text\nhere"), None);
+        assert_eq!(snapshot.excerpt(), "edited text here");
+        assert_eq!(snapshot.author_id, UserId::new(7));
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_str::<TargetSnapshot>(&json).unwrap(),
+            snapshot
+        );
+    }
+}
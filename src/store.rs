@@ -0,0 +1,727 @@
+use crate::{
+    guild_config::GuildConfig,
+    migrations,
+    vote_stats::{VoteEnding, VoteStats},
+};
+use anyhow::{Context as _, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use serenity::all::{ChannelId, GuildId, MessageId, UserId};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::Mutex,
+};
+
+/// A temporary pin waiting to be taken down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledUnpin {
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    /// Unix timestamp, in seconds.
+    pub unpin_at: i64,
+}
+
+/// A pin, kept for the weekly digest and the pin index. Pins made by hand
+/// are recorded too when the bot notices them, but left out of both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinRecord {
+    pub guild_id: GuildId,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    /// Who wrote the message; `None` once their data was purged.
+    pub author_id: Option<UserId>,
+    pub excerpt: String,
+    /// The weighted total of the votes the pin got.
+    pub votes: u32,
+    /// How many members cast them. 0 for manual pins and for pins recorded
+    /// before voters were counted.
+    pub voters: u32,
+    /// Unix timestamp, in seconds.
+    pub pinned_at: i64,
+    /// Why the pin was requested, if the requester said.
+    pub reason: Option<String>,
+}
+
+/// SQLite-backed persistence. Queries are small and indexed, so they run
+/// synchronously behind a mutex rather than on a dedicated thread.
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: &str) -> Result<Self> {
+        let mut conn =
+            Connection::open(path).with_context(|| format!("Failed to open database {}", path))?;
+        migrations::run(&mut conn)
+            .with_context(|| format!("Failed to migrate database {}", path))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn load_guild_config(&self, guild_id: GuildId) -> Result<Option<GuildConfig>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let raw: Option<String> = conn
+            .query_row(
+                "SELECT config FROM guild_configs WHERE guild_id = ?1",
+                params![guild_id.get() as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        raw.map(|json| serde_json::from_str(&json).map_err(Into::into))
+            .transpose()
+    }
+
+    pub fn save_guild_config(&self, guild_id: GuildId, config: &GuildConfig) -> Result<()> {
+        let json = serde_json::to_string(config)?;
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "INSERT INTO guild_configs (guild_id, config) VALUES (?1, ?2)
+             ON CONFLICT(guild_id) DO UPDATE SET config = excluded.config",
+            params![guild_id.get() as i64, json],
+        )?;
+        Ok(())
+    }
+
+    /// Schedules an unpin, replacing any earlier schedule for the message.
+    pub fn schedule_unpin(&self, unpin: &ScheduledUnpin) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "INSERT INTO scheduled_unpins (message_id, channel_id, guild_id, unpin_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(message_id) DO UPDATE SET
+                channel_id = excluded.channel_id,
+                guild_id = excluded.guild_id,
+                unpin_at = excluded.unpin_at",
+            params![
+                unpin.message_id.get() as i64,
+                unpin.channel_id.get() as i64,
+                unpin.guild_id.map(|id| id.get() as i64),
+                unpin.unpin_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Every unpin scheduled at or before `now`.
+    pub fn due_unpins(&self, now: i64) -> Result<Vec<ScheduledUnpin>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let mut statement = conn.prepare(
+            "SELECT message_id, channel_id, guild_id, unpin_at
+             FROM scheduled_unpins WHERE unpin_at <= ?1",
+        )?;
+        let rows = statement.query_map(params![now], |row| {
+            Ok(ScheduledUnpin {
+                message_id: MessageId::new(row.get::<_, i64>(0)? as u64),
+                channel_id: ChannelId::new(row.get::<_, i64>(1)? as u64),
+                guild_id: row
+                    .get::<_, Option<i64>>(2)?
+                    .map(|id| GuildId::new(id as u64)),
+                unpin_at: row.get(3)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+    }
+
+    pub fn remove_scheduled_unpin(&self, message_id: MessageId) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "DELETE FROM scheduled_unpins WHERE message_id = ?1",
+            params![message_id.get() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Every stored guild config, for tasks that look across guilds.
+    pub fn guild_configs(&self) -> Result<Vec<(GuildId, GuildConfig)>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let mut statement = conn.prepare("SELECT guild_id, config FROM guild_configs")?;
+        let rows = statement.query_map([], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        rows.map(|row| {
+            let (guild_id, json) = row?;
+            Ok((GuildId::new(guild_id as u64), serde_json::from_str(&json)?))
+        })
+        .collect()
+    }
+
+    /// Records a pin, replacing an earlier record of the same message.
+    pub fn record_pin(&self, pin: &PinRecord) -> Result<()> {
+        self.insert_pin(
+            pin,
+            "INSERT OR REPLACE INTO pin_history
+                (message_id, guild_id, channel_id, author_id, excerpt, votes, pinned_at, reason,
+                 voters)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        )
+    }
+
+    /// Records a pin someone made by hand. A message the history already
+    /// has as pinned keeps its record, so a voted pin noticed after the
+    /// fact stays a voted pin.
+    pub fn record_manual_pin(&self, pin: &PinRecord) -> Result<()> {
+        self.insert_pin(
+            pin,
+            "INSERT INTO pin_history
+                (message_id, guild_id, channel_id, author_id, excerpt, votes, pinned_at, reason,
+                 voters, manual)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 1)
+             ON CONFLICT(message_id) DO UPDATE SET
+                channel_id = excluded.channel_id,
+                author_id = excluded.author_id,
+                excerpt = excluded.excerpt,
+                votes = excluded.votes,
+                voters = excluded.voters,
+                pinned_at = excluded.pinned_at,
+                reason = excluded.reason,
+                manual = 1,
+                unpinned_at = NULL
+             WHERE pin_history.unpinned_at IS NOT NULL",
+        )
+    }
+
+    /// Records pins that predate the bot as manual, in one transaction.
+    /// Messages the history already has are left alone, so importing twice
+    /// changes nothing. Returns how many were new.
+    pub fn import_pins(&self, pins: &[PinRecord]) -> Result<usize> {
+        let mut conn = self.conn.lock().expect("store mutex poisoned");
+        let tx = conn.transaction()?;
+        let mut imported = 0;
+        {
+            let mut statement = tx.prepare(
+                "INSERT OR IGNORE INTO pin_history
+                    (message_id, guild_id, channel_id, author_id, excerpt, votes, pinned_at,
+                     reason, manual)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 1)",
+            )?;
+            for pin in pins {
+                imported += statement.execute(params![
+                    pin.message_id.get() as i64,
+                    pin.guild_id.get() as i64,
+                    pin.channel_id.get() as i64,
+                    pin.author_id.map_or(0, |id| id.get() as i64),
+                    pin.excerpt,
+                    pin.votes,
+                    pin.pinned_at,
+                    pin.reason
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(imported)
+    }
+
+    fn insert_pin(&self, pin: &PinRecord, sql: &str) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            sql,
+            params![
+                pin.message_id.get() as i64,
+                pin.guild_id.get() as i64,
+                pin.channel_id.get() as i64,
+                pin.author_id.map_or(0, |id| id.get() as i64),
+                pin.excerpt,
+                pin.votes,
+                pin.pinned_at,
+                pin.reason,
+                pin.voters
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The guild's pins from `since` up to but excluding `until`, most voted
+    /// first, at most `limit` of them, along with how many there were in all.
+    pub fn pins_between(
+        &self,
+        guild_id: GuildId,
+        since: i64,
+        until: i64,
+        limit: usize,
+    ) -> Result<(Vec<PinRecord>, usize)> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let total: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pin_history
+             WHERE guild_id = ?1 AND pinned_at >= ?2 AND pinned_at < ?3 AND manual = 0",
+            params![guild_id.get() as i64, since, until],
+            |row| row.get(0),
+        )?;
+        let mut statement = conn.prepare(
+            "SELECT message_id, channel_id, author_id, excerpt, votes, pinned_at, reason, voters
+             FROM pin_history
+             WHERE guild_id = ?1 AND pinned_at >= ?2 AND pinned_at < ?3 AND manual = 0
+             ORDER BY votes DESC, pinned_at
+             LIMIT ?4",
+        )?;
+        let rows = statement.query_map(
+            params![guild_id.get() as i64, since, until, limit as i64],
+            |row| {
+                Ok(PinRecord {
+                    guild_id,
+                    message_id: MessageId::new(row.get::<_, i64>(0)? as u64),
+                    channel_id: ChannelId::new(row.get::<_, i64>(1)? as u64),
+                    author_id: stored_author(row.get(2)?),
+                    excerpt: row.get(3)?,
+                    votes: row.get(4)?,
+                    pinned_at: row.get(5)?,
+                    reason: row.get(6)?,
+                    voters: row.get(7)?,
+                })
+            },
+        )?;
+        let pins = rows.collect::<rusqlite::Result<_>>()?;
+        Ok((pins, total as usize))
+    }
+
+    /// The guild's most voted pins ever, or in `channel_id` and from
+    /// `since` on when given, including ones since unpinned. Ties go to the
+    /// earlier pin, then the older message, so the ranking is stable.
+    pub fn top_pins(
+        &self,
+        guild_id: GuildId,
+        channel_id: Option<ChannelId>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<PinRecord>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let mut statement = conn.prepare(
+            "SELECT message_id, channel_id, author_id, excerpt, votes, pinned_at, reason, voters
+             FROM pin_history
+             WHERE guild_id = ?1 AND manual = 0
+                AND (?2 IS NULL OR channel_id = ?2) AND (?3 IS NULL OR pinned_at >= ?3)
+             ORDER BY votes DESC, pinned_at, message_id
+             LIMIT ?4",
+        )?;
+        let rows = statement.query_map(
+            params![
+                guild_id.get() as i64,
+                channel_id.map(|id| id.get() as i64),
+                since,
+                limit as i64
+            ],
+            |row| {
+                Ok(PinRecord {
+                    guild_id,
+                    message_id: MessageId::new(row.get::<_, i64>(0)? as u64),
+                    channel_id: ChannelId::new(row.get::<_, i64>(1)? as u64),
+                    author_id: stored_author(row.get(2)?),
+                    excerpt: row.get(3)?,
+                    votes: row.get(4)?,
+                    pinned_at: row.get(5)?,
+                    reason: row.get(6)?,
+                    voters: row.get(7)?,
+                })
+            },
+        )?;
+        rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+    }
+
+    /// How many voted pins a channel got from `since` on, counting ones
+    /// since unpinned.
+    pub fn pins_in_channel_since(&self, channel_id: ChannelId, since: i64) -> Result<u32> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM pin_history
+             WHERE channel_id = ?1 AND pinned_at >= ?2 AND manual = 0",
+            params![channel_id.get() as i64, since],
+            |row| row.get(0),
+        )?;
+        Ok(count as u32)
+    }
+
+    /// Every voted pin recorded in a channel, including ones since unpinned.
+    pub fn pins_in_channel(&self, channel_id: ChannelId) -> Result<Vec<PinRecord>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let mut statement = conn.prepare(
+            "SELECT message_id, guild_id, author_id, excerpt, votes, pinned_at, reason, voters
+             FROM pin_history WHERE channel_id = ?1 AND manual = 0",
+        )?;
+        let rows = statement.query_map(params![channel_id.get() as i64], |row| {
+            Ok(PinRecord {
+                message_id: MessageId::new(row.get::<_, i64>(0)? as u64),
+                guild_id: GuildId::new(row.get::<_, i64>(1)? as u64),
+                channel_id,
+                author_id: stored_author(row.get(2)?),
+                excerpt: row.get(3)?,
+                votes: row.get(4)?,
+                pinned_at: row.get(5)?,
+                reason: row.get(6)?,
+                voters: row.get(7)?,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+    }
+
+    /// When the message was last pinned, if it ever was. A primary key
+    /// lookup, cheap enough to run on every request.
+    pub fn last_pinned_at(&self, message_id: MessageId) -> Result<Option<i64>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.query_row(
+            "SELECT pinned_at FROM pin_history WHERE message_id = ?1",
+            params![message_id.get() as i64],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    /// When each of the channel's current pins was pinned and whether it
+    /// was made by hand, as `(pinned_at, manual)`.
+    pub fn pin_dates(&self, channel_id: ChannelId) -> Result<HashMap<MessageId, (i64, bool)>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let mut statement = conn.prepare(
+            "SELECT message_id, pinned_at, manual FROM pin_history
+             WHERE channel_id = ?1 AND unpinned_at IS NULL",
+        )?;
+        let rows = statement.query_map(params![channel_id.get() as i64], |row| {
+            Ok((
+                MessageId::new(row.get::<_, i64>(0)? as u64),
+                (row.get(1)?, row.get::<_, i64>(2)? != 0),
+            ))
+        })?;
+        rows.collect::<rusqlite::Result<_>>().map_err(Into::into)
+    }
+
+    /// Notes that a message is no longer pinned, as of `at`. Returns
+    /// whether the history had it as pinned.
+    pub fn mark_unpinned(&self, message_id: MessageId, at: i64) -> Result<bool> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let changed = conn.execute(
+            "UPDATE pin_history SET unpinned_at = ?2
+             WHERE message_id = ?1 AND unpinned_at IS NULL",
+            params![message_id.get() as i64, at],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Whether the history has the message as currently pinned.
+    pub fn is_recorded_pinned(&self, message_id: MessageId) -> Result<bool> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let pinned = conn
+            .prepare("SELECT 1 FROM pin_history WHERE message_id = ?1 AND unpinned_at IS NULL")?
+            .exists(params![message_id.get() as i64])?;
+        Ok(pinned)
+    }
+
+    /// The channel's pins as last seen, or `None` before the first look.
+    pub fn pin_snapshot(&self, channel_id: ChannelId) -> Result<Option<HashSet<MessageId>>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let raw: Option<String> = conn
+            .query_row(
+                "SELECT message_ids FROM pin_snapshots WHERE channel_id = ?1",
+                params![channel_id.get() as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        raw.map(|json| serde_json::from_str(&json).map_err(Into::into))
+            .transpose()
+    }
+
+    pub fn save_pin_snapshot(
+        &self,
+        channel_id: ChannelId,
+        pins: &HashSet<MessageId>,
+    ) -> Result<()> {
+        let json = serde_json::to_string(pins)?;
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "INSERT INTO pin_snapshots (channel_id, message_ids) VALUES (?1, ?2)
+             ON CONFLICT(channel_id) DO UPDATE SET message_ids = excluded.message_ids",
+            params![channel_id.get() as i64, json],
+        )?;
+        Ok(())
+    }
+
+    /// Records whether the user wants their messages kept out of pin votes.
+    pub fn set_pin_opt_out(&self, user_id: UserId, opted_out: bool) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let sql = if opted_out {
+            "INSERT OR IGNORE INTO pin_opt_outs (user_id) VALUES (?1)"
+        } else {
+            "DELETE FROM pin_opt_outs WHERE user_id = ?1"
+        };
+        conn.execute(sql, params![user_id.get() as i64])?;
+        Ok(())
+    }
+
+    pub fn is_pin_opted_out(&self, user_id: UserId) -> Result<bool> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let opted_out = conn
+            .prepare("SELECT 1 FROM pin_opt_outs WHERE user_id = ?1")?
+            .exists(params![user_id.get() as i64])?;
+        Ok(opted_out)
+    }
+
+    /// Drops the user's opt-out. Returns whether they had one.
+    pub fn purge_pin_opt_out(&self, user_id: UserId) -> Result<bool> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let removed = conn.execute(
+            "DELETE FROM pin_opt_outs WHERE user_id = ?1",
+            params![user_id.get() as i64],
+        )?;
+        Ok(removed > 0)
+    }
+
+    /// Keeps the user's pins in the history but forgets who wrote them and
+    /// what they said. Returns how many pins were touched.
+    pub fn anonymize_pins(&self, user_id: UserId) -> Result<usize> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let updated = conn.execute(
+            "UPDATE pin_history SET author_id = 0, excerpt = '' WHERE author_id = ?1",
+            params![user_id.get() as i64],
+        )?;
+        Ok(updated)
+    }
+
+    /// Takes the user off every guild's pin blocklist in one transaction,
+    /// so either every guild is updated or none is. Returns the configs
+    /// that changed.
+    pub fn purge_from_blocklists(&self, user_id: UserId) -> Result<Vec<(GuildId, GuildConfig)>> {
+        let mut conn = self.conn.lock().expect("store mutex poisoned");
+        let tx = conn.transaction()?;
+        let mut changed = Vec::new();
+        {
+            let mut statement = tx.prepare("SELECT guild_id, config FROM guild_configs")?;
+            let rows = statement.query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for row in rows {
+                let (guild_id, json) = row?;
+                let mut config: GuildConfig = serde_json::from_str(&json)?;
+                if config.pin_blocklist.remove(&user_id) {
+                    changed.push((GuildId::new(guild_id as u64), config));
+                }
+            }
+        }
+        for (guild_id, config) in &changed {
+            tx.execute(
+                "UPDATE guild_configs SET config = ?2 WHERE guild_id = ?1",
+                params![guild_id.get() as i64, serde_json::to_string(config)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
+    /// Writes a consistent copy of the database to `path`, which must not
+    /// exist yet. Other queries wait until it's done.
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+        Ok(())
+    }
+
+    /// The channel's index message, if one was posted.
+    pub fn pin_index(&self, channel_id: ChannelId) -> Result<Option<MessageId>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.query_row(
+            "SELECT message_id FROM pin_indexes WHERE channel_id = ?1",
+            params![channel_id.get() as i64],
+            |row| Ok(MessageId::new(row.get::<_, i64>(0)? as u64)),
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    pub fn set_pin_index(&self, channel_id: ChannelId, message_id: MessageId) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "INSERT INTO pin_indexes (channel_id, message_id) VALUES (?1, ?2)
+             ON CONFLICT(channel_id) DO UPDATE SET message_id = excluded.message_id",
+            params![channel_id.get() as i64, message_id.get() as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_pin_index(&self, channel_id: ChannelId) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "DELETE FROM pin_indexes WHERE channel_id = ?1",
+            params![channel_id.get() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// Whether the message is pinned by the bot as far as the history
+    /// knows: recorded, not by hand, and not unpinned since.
+    pub fn is_bot_pin(&self, message_id: MessageId) -> Result<bool> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let pinned = conn
+            .prepare(
+                "SELECT 1 FROM pin_history
+                 WHERE message_id = ?1 AND manual = 0 AND unpinned_at IS NULL",
+            )?
+            .exists(params![message_id.get() as i64])?;
+        Ok(pinned)
+    }
+
+    /// The channel's current rotation pin, if the bot made one.
+    pub fn rotation_pin(&self, channel_id: ChannelId) -> Result<Option<MessageId>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.query_row(
+            "SELECT message_id FROM rotation_pins WHERE channel_id = ?1",
+            params![channel_id.get() as i64],
+            |row| Ok(MessageId::new(row.get::<_, i64>(0)? as u64)),
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    /// Makes `message_id` the channel's rotation pin, returning the one it
+    /// replaces. Both happen in one transaction, so two pins landing at
+    /// once can't both claim the same predecessor.
+    pub fn replace_rotation_pin(
+        &self,
+        channel_id: ChannelId,
+        message_id: MessageId,
+    ) -> Result<Option<MessageId>> {
+        let mut conn = self.conn.lock().expect("store mutex poisoned");
+        let tx = conn.transaction()?;
+        let previous = tx
+            .query_row(
+                "SELECT message_id FROM rotation_pins WHERE channel_id = ?1",
+                params![channel_id.get() as i64],
+                |row| Ok(MessageId::new(row.get::<_, i64>(0)? as u64)),
+            )
+            .optional()?;
+        tx.execute(
+            "INSERT INTO rotation_pins (channel_id, message_id) VALUES (?1, ?2)
+             ON CONFLICT(channel_id) DO UPDATE SET message_id = excluded.message_id",
+            params![channel_id.get() as i64, message_id.get() as i64],
+        )?;
+        tx.commit()?;
+        Ok(previous)
+    }
+
+    pub fn remove_rotation_pin(&self, channel_id: ChannelId) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "DELETE FROM rotation_pins WHERE channel_id = ?1",
+            params![channel_id.get() as i64],
+        )?;
+        Ok(())
+    }
+
+    /// When the guild's digest last went out, as a unix timestamp.
+    pub fn digest_last_run(&self, guild_id: GuildId) -> Result<Option<i64>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.query_row(
+            "SELECT last_run FROM digest_runs WHERE guild_id = ?1",
+            params![guild_id.get() as i64],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    pub fn set_digest_last_run(&self, guild_id: GuildId, last_run: i64) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "INSERT INTO digest_runs (guild_id, last_run) VALUES (?1, ?2)
+             ON CONFLICT(guild_id) DO UPDATE SET last_run = excluded.last_run",
+            params![guild_id.get() as i64, last_run],
+        )?;
+        Ok(())
+    }
+
+    /// Keeps what an ended vote came to. A session is recorded once; a
+    /// second ending of the same one is ignored.
+    pub fn record_vote_stats(&self, stats: &VoteStats) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "INSERT OR IGNORE INTO vote_stats (session_id, guild_id, channel_id, ended_at,
+                duration_secs, outcome, votes, peak_votes, removals, voters)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                stats.session_id.get() as i64,
+                stats.guild_id.get() as i64,
+                stats.channel_id.get() as i64,
+                stats.ended_at,
+                stats.duration_secs as i64,
+                stats.ending.as_str(),
+                stats.votes,
+                stats.peak_votes,
+                stats.removals,
+                stats.voters
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The guild's votes that ended from `since` up to but excluding
+    /// `until`, oldest first.
+    pub fn vote_stats_between(
+        &self,
+        guild_id: GuildId,
+        since: i64,
+        until: i64,
+    ) -> Result<Vec<VoteStats>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        let mut statement = conn.prepare(
+            "SELECT session_id, channel_id, ended_at, duration_secs, outcome, votes, peak_votes,
+                removals, voters
+             FROM vote_stats
+             WHERE guild_id = ?1 AND ended_at >= ?2 AND ended_at < ?3
+             ORDER BY ended_at, session_id",
+        )?;
+        let rows = statement.query_map(params![guild_id.get() as i64, since, until], |row| {
+            Ok((
+                VoteStats {
+                    guild_id,
+                    session_id: MessageId::new(row.get::<_, i64>(0)? as u64),
+                    channel_id: ChannelId::new(row.get::<_, i64>(1)? as u64),
+                    ended_at: row.get(2)?,
+                    duration_secs: row.get::<_, i64>(3)? as u64,
+                    ending: VoteEnding::Cancelled,
+                    votes: row.get(5)?,
+                    peak_votes: row.get(6)?,
+                    removals: row.get(7)?,
+                    voters: row.get(8)?,
+                },
+                row.get::<_, String>(4)?,
+            ))
+        })?;
+        let mut stats = Vec::new();
+        for row in rows {
+            let (mut vote, outcome) = row?;
+            // A row from a newer build with an ending this one doesn't know
+            // is left out rather than miscounted
+            let Some(ending) = VoteEnding::parse(&outcome) else {
+                continue;
+            };
+            vote.ending = ending;
+            stats.push(vote);
+        }
+        Ok(stats)
+    }
+
+    pub fn retention_last_run(&self, channel_id: ChannelId) -> Result<Option<i64>> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.query_row(
+            "SELECT last_run FROM retention_runs WHERE channel_id = ?1",
+            params![channel_id.get() as i64],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(Into::into)
+    }
+
+    pub fn set_retention_last_run(&self, channel_id: ChannelId, last_run: i64) -> Result<()> {
+        let conn = self.conn.lock().expect("store mutex poisoned");
+        conn.execute(
+            "INSERT INTO retention_runs (channel_id, last_run) VALUES (?1, ?2)
+             ON CONFLICT(channel_id) DO UPDATE SET last_run = excluded.last_run",
+            params![channel_id.get() as i64, last_run],
+        )?;
+        Ok(())
+    }
+}
+
+/// Reads an author column, where 0 marks a purged author.
+fn stored_author(raw: i64) -> Option<UserId> {
+    (raw != 0).then(|| UserId::new(raw as u64))
+}
@@ -0,0 +1,212 @@
+//! Per-guild wording for the bot's key messages. Each message has a
+//! default here; a guild may replace it with its own text, in which
+//! `{placeholder}`s are filled in when the message goes out.
+
+use serde::{Deserialize, Serialize};
+
+/// Longest template a guild may set. The bot appends batch lists, reasons
+/// and deadlines to some messages, which must still fit in one.
+pub const MAX_TEMPLATE_CHARS: usize = 300;
+
+/// The messages a guild can reword.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TemplateKey {
+    /// Opens a vote; a batch's list and the reason follow it.
+    VoteStarted,
+    /// Answers a vote cast with the buttons.
+    VoteProgress,
+    /// Reports pins made without a vote, and every voted pin once a guild
+    /// sets its own.
+    PinSuccess,
+    /// Posted when a quorum vote runs out, and for every vote once a guild
+    /// sets its own.
+    VoteExpired,
+    /// Answers a request to pin right away that didn't go through.
+    PinFailed,
+}
+
+impl TemplateKey {
+    pub const ALL: [Self; 5] = [
+        Self::VoteStarted,
+        Self::VoteProgress,
+        Self::PinSuccess,
+        Self::VoteExpired,
+        Self::PinFailed,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::VoteStarted => "vote-started",
+            Self::VoteProgress => "vote-progress",
+            Self::PinSuccess => "pin-success",
+            Self::VoteExpired => "vote-expired",
+            Self::PinFailed => "pin-failed",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+
+    pub fn default_text(self) -> &'static str {
+        match self {
+            Self::VoteStarted => "Vote to pin {target}! {requirement}",
+            Self::VoteProgress => "Vote recorded, {progress}",
+            Self::PinSuccess => "📌 Pinned {target_link}",
+            Self::VoteExpired => {
+                "⌛ This vote expired without passing: {progress}. It needed {requirement}."
+            }
+            Self::PinFailed => "I couldn't pin {target} right now.",
+        }
+    }
+
+    /// The placeholders the message fills in, with what each stands for.
+    pub fn placeholders(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Self::VoteStarted => &[
+                (
+                    "target",
+                    "\"this message\", its link or \"these 3 messages\"",
+                ),
+                ("target_link", "a link to the (first) message"),
+                ("initiator", "who asked for the pin"),
+                ("needed", "the votes needed"),
+                ("requirement", "\"3 approvals needed.\" or the quorum rule"),
+            ],
+            Self::VoteProgress => &[
+                ("count", "the votes so far"),
+                ("needed", "the votes needed"),
+                ("progress", "\"2/3\", or both tallies for a quorum vote"),
+            ],
+            Self::PinSuccess => &[
+                ("target_link", "links to the pinned messages"),
+                ("count", "the votes it got"),
+                ("initiator", "who asked for the pin"),
+            ],
+            Self::VoteExpired => &[
+                ("target_link", "a link to the message"),
+                ("initiator", "who asked for the pin"),
+                ("progress", "\"2/3\", or both tallies for a quorum vote"),
+                ("requirement", "\"3 approvals\" or the quorum rule"),
+            ],
+            Self::PinFailed => &[
+                ("target", "\"that message\" or \"those messages\""),
+                ("target_link", "links to the messages"),
+            ],
+        }
+    }
+}
+
+/// Checks a template before it is saved: within the length limit, braces
+/// balanced, and only placeholders the message fills in.
+pub fn validate(key: TemplateKey, text: &str) -> Result<(), String> {
+    if text.trim().is_empty() {
+        return Err("The template can't be empty.".to_string());
+    }
+    let length = text.chars().count();
+    if length > MAX_TEMPLATE_CHARS {
+        return Err(format!(
+            "The template is {} characters long; the limit is {}.",
+            length, MAX_TEMPLATE_CHARS
+        ));
+    }
+    let mut rest = text;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Err("The template has a `}` without a matching `{`.".to_string());
+        }
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('}') else {
+            return Err("The template has a `{` that is never closed.".to_string());
+        };
+        let name = &after[..close];
+        if !key.placeholders().iter().any(|(known, _)| *known == name) {
+            let known: Vec<String> = key
+                .placeholders()
+                .iter()
+                .map(|(known, _)| format!("`{{{}}}`", known))
+                .collect();
+            return Err(format!(
+                "`{{{}}}` isn't a placeholder of {}; it can use {}.",
+                name,
+                key.name(),
+                known.join(", ")
+            ));
+        }
+        rest = &after[close + 1..];
+    }
+    Ok(())
+}
+
+/// Fills a template's placeholders from `values`. Anything else in braces
+/// is left as written, which `validate` keeps out of saved templates.
+pub fn render(template: &str, values: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        rendered.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        let value = after.find('}').and_then(|close| {
+            let name = &after[..close];
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value, close))
+        });
+        match value {
+            Some((value, close)) => {
+                rendered.push_str(value);
+                rest = &after[close + 1..];
+            }
+            None => {
+                rendered.push('{');
+                rest = after;
+            }
+        }
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_use_only_their_own_placeholders() {
+        for key in TemplateKey::ALL {
+            assert_eq!(validate(key, key.default_text()), Ok(()), "{}", key.name());
+            assert_eq!(TemplateKey::parse(key.name()), Some(key));
+        }
+    }
+
+    #[test]
+    fn typos_are_caught_when_the_template_is_set() {
+        let key = TemplateKey::VoteProgress;
+        assert_eq!(validate(key, "PINNED, POGGERS ({count}/{needed})"), Ok(()));
+
+        let error = validate(key, "{cuont} votes").unwrap_err();
+        assert!(
+            error.contains("`{cuont}`") && error.contains("`{count}`"),
+            "{}",
+            error
+        );
+        assert!(validate(key, "{count votes").is_err());
+        assert!(validate(key, "count} votes").is_err());
+        assert!(validate(key, "{target_link}").is_err());
+        assert!(validate(key, "  ").is_err());
+        assert!(validate(key, &"a".repeat(MAX_TEMPLATE_CHARS + 1)).is_err());
+    }
+
+    #[test]
+    fn placeholders_are_filled_in_once() {
+        let values = [("count", "2"), ("needed", "{count}")];
+        assert_eq!(
+            render("{count} of {needed}, {count}!", &values),
+            "2 of {count}, 2!"
+        );
+        assert_eq!(render("{unknown} {count}", &values), "{unknown} 2");
+        assert_eq!(render("{ {count}", &values), "{ 2");
+    }
+}
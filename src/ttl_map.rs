@@ -0,0 +1,173 @@
+use dashmap::DashMap;
+use std::{
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// A concurrent map whose entries go stale a fixed time after insertion,
+/// or after their own lifetime when inserted with [`TtlMap::insert_for`].
+/// Stale entries are invisible to lookups and are dropped by
+/// [`TtlMap::retain_fresh`], which the cleanup task calls periodically.
+pub struct TtlMap<K, V> {
+    entries: DashMap<K, (Instant, Duration, V)>,
+    ttl: Duration,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlMap<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+        }
+    }
+
+    /// How long entries stay fresh unless inserted with their own TTL.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Inserts or refreshes `key`, starting its TTL now.
+    pub fn insert_now(&self, key: K, value: V) {
+        self.insert_at(key, value, Instant::now());
+    }
+
+    /// Inserts or refreshes `key` to go stale `ttl` from now instead of
+    /// after the map's own TTL.
+    pub fn insert_for(&self, key: K, value: V, ttl: Duration) {
+        self.entries.insert(key, (Instant::now(), ttl, value));
+    }
+
+    /// The value for `key` if it hasn't gone stale yet.
+    pub fn get_fresh(&self, key: &K) -> Option<V> {
+        self.get_fresh_at(key, Instant::now())
+    }
+
+    /// How long until `key` goes stale, if it is fresh.
+    pub fn time_left(&self, key: &K) -> Option<Duration> {
+        let entry = self.entries.get(key)?;
+        let (inserted, ttl, _) = entry.value();
+        Self::remaining(*inserted, *ttl, Instant::now())
+    }
+
+    /// Every fresh entry with the time it has left.
+    pub fn fresh_entries(&self) -> Vec<(K, V, Duration)> {
+        let now = Instant::now();
+        self.entries
+            .iter()
+            .filter_map(|entry| {
+                let (inserted, ttl, value) = entry.value();
+                let remaining = Self::remaining(*inserted, *ttl, now)?;
+                Some((entry.key().clone(), value.clone(), remaining))
+            })
+            .collect()
+    }
+
+    /// Drops stale entries, returning how many were removed.
+    pub fn retain_fresh(&self) -> usize {
+        self.retain_fresh_at(Instant::now())
+    }
+
+    fn insert_at(&self, key: K, value: V, now: Instant) {
+        self.entries.insert(key, (now, self.ttl, value));
+    }
+
+    fn get_fresh_at(&self, key: &K, now: Instant) -> Option<V> {
+        let entry = self.entries.get(key)?;
+        let (inserted, ttl, value) = entry.value();
+        Self::remaining(*inserted, *ttl, now).map(|_| value.clone())
+    }
+
+    fn retain_fresh_at(&self, now: Instant) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|_, (inserted, ttl, _)| Self::remaining(*inserted, *ttl, now).is_some());
+        before.saturating_sub(self.entries.len())
+    }
+
+    fn remaining(inserted: Instant, ttl: Duration, now: Instant) -> Option<Duration> {
+        ttl.checked_sub(now.saturating_duration_since(inserted))
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    const TTL: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn entries_are_fresh_until_the_ttl_passes() {
+        let map = TtlMap::new(TTL);
+        let start = Instant::now();
+        map.insert_at("a", 1, start);
+
+        assert_eq!(map.get_fresh_at(&"a", start), Some(1));
+        assert_eq!(
+            map.get_fresh_at(&"a", start + Duration::from_secs(4)),
+            Some(1)
+        );
+        assert_eq!(map.get_fresh_at(&"a", start + TTL), None);
+        assert_eq!(map.get_fresh_at(&"missing", start), None);
+    }
+
+    #[test]
+    fn inserting_again_restarts_the_ttl() {
+        let map = TtlMap::new(TTL);
+        let start = Instant::now();
+        map.insert_at("a", 1, start);
+        map.insert_at("a", 2, start + Duration::from_secs(4));
+
+        assert_eq!(
+            map.get_fresh_at(&"a", start + Duration::from_secs(8)),
+            Some(2)
+        );
+    }
+
+    #[test]
+    fn retain_fresh_drops_only_stale_entries() {
+        let map = TtlMap::new(TTL);
+        let start = Instant::now();
+        map.insert_at("old", 1, start);
+        map.insert_at("new", 2, start + Duration::from_secs(3));
+
+        assert_eq!(map.retain_fresh_at(start + Duration::from_secs(6)), 1);
+        assert_eq!(map.len(), 1);
+        assert_eq!(
+            map.get_fresh_at(&"new", start + Duration::from_secs(6)),
+            Some(2)
+        );
+        assert_eq!(map.retain_fresh_at(start + Duration::from_secs(6)), 0);
+    }
+
+    #[test]
+    fn concurrent_inserts_and_sweeps_keep_every_fresh_entry() {
+        let map = Arc::new(TtlMap::new(Duration::from_secs(60)));
+        let writers: Vec<_> = (0..8u32)
+            .map(|thread_id| {
+                let map = Arc::clone(&map);
+                thread::spawn(move || {
+                    for i in 0..250 {
+                        map.insert_now(thread_id * 1000 + i, i);
+                        if i % 50 == 0 {
+                            map.retain_fresh();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(map.len(), 2000);
+        assert_eq!(map.fresh_entries().len(), 2000);
+        assert_eq!(map.get_fresh(&7249), Some(249));
+    }
+}
@@ -0,0 +1,170 @@
+//! What each vote came to once it ended, and the roundups `/votestats` and
+//! the weekly audit line build from those records.
+
+use crate::duration::format_duration;
+use serenity::all::{ChannelId, GuildId, MessageId};
+use std::time::Duration;
+
+/// How a vote ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoteEnding {
+    Pinned,
+    Expired,
+    /// Called off by a moderator, the initiator or a deleted message, or
+    /// made moot by a pin by hand.
+    Cancelled,
+    /// Dropped to make room under the cap on open votes.
+    Evicted,
+}
+
+impl VoteEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            VoteEnding::Pinned => "pinned",
+            VoteEnding::Expired => "expired",
+            VoteEnding::Cancelled => "cancelled",
+            VoteEnding::Evicted => "evicted",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "pinned" => Some(VoteEnding::Pinned),
+            "expired" => Some(VoteEnding::Expired),
+            "cancelled" => Some(VoteEnding::Cancelled),
+            "evicted" => Some(VoteEnding::Evicted),
+            _ => None,
+        }
+    }
+}
+
+/// One ended vote, as the store keeps it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoteStats {
+    pub guild_id: GuildId,
+    /// Where the vote took place.
+    pub channel_id: ChannelId,
+    pub session_id: MessageId,
+    /// Unix timestamp, in seconds.
+    pub ended_at: i64,
+    /// How long the vote was open.
+    pub duration_secs: u64,
+    pub ending: VoteEnding,
+    /// The weighted total when the vote ended.
+    pub votes: u32,
+    /// The most the total reached while the vote was open.
+    pub peak_votes: u32,
+    /// How many times a counted vote was taken back.
+    pub removals: u32,
+    pub voters: u32,
+}
+
+/// A period's votes taken together.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VoteSummary {
+    pub votes: usize,
+    pub pinned: usize,
+    pub expired: usize,
+    pub cancelled: usize,
+    pub evicted: usize,
+    pub removals: u32,
+    /// The median time a pinned vote was open; `None` without pins.
+    pub median_time_to_pin: Option<Duration>,
+}
+
+impl VoteSummary {
+    pub fn of(stats: &[VoteStats]) -> Self {
+        let count = |ending| stats.iter().filter(|vote| vote.ending == ending).count();
+        let mut times: Vec<u64> = stats
+            .iter()
+            .filter(|vote| vote.ending == VoteEnding::Pinned)
+            .map(|vote| vote.duration_secs)
+            .collect();
+        times.sort_unstable();
+        let median = match times.len() {
+            0 => None,
+            len if len % 2 == 1 => Some(times[len / 2]),
+            len => Some((times[len / 2 - 1] + times[len / 2]) / 2),
+        };
+        Self {
+            votes: stats.len(),
+            pinned: count(VoteEnding::Pinned),
+            expired: count(VoteEnding::Expired),
+            cancelled: count(VoteEnding::Cancelled),
+            evicted: count(VoteEnding::Evicted),
+            removals: stats.iter().map(|vote| vote.removals).sum(),
+            median_time_to_pin: median.map(Duration::from_secs),
+        }
+    }
+
+    /// E.g. `14 votes, 9 pinned, 4 expired, median time-to-pin 6m`. Counts
+    /// of none are left out, past the total.
+    pub fn line(&self) -> String {
+        let mut line = format!(
+            "{} {}",
+            self.votes,
+            if self.votes == 1 { "vote" } else { "votes" }
+        );
+        for (count, label) in [
+            (self.pinned, "pinned"),
+            (self.expired, "expired"),
+            (self.cancelled, "cancelled"),
+            (self.evicted, "evicted"),
+        ] {
+            if count > 0 {
+                line.push_str(&format!(", {} {}", count, label));
+            }
+        }
+        if let Some(median) = self.median_time_to_pin {
+            line.push_str(&format!(", median time-to-pin {}", format_duration(median)));
+        }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vote(ending: VoteEnding, duration_secs: u64, removals: u32) -> VoteStats {
+        VoteStats {
+            guild_id: GuildId::new(1),
+            channel_id: ChannelId::new(2),
+            session_id: MessageId::new(3),
+            ended_at: 1_700_000_000,
+            duration_secs,
+            ending,
+            votes: 3,
+            peak_votes: 4,
+            removals,
+            voters: 3,
+        }
+    }
+
+    #[test]
+    fn a_summary_counts_endings_and_takes_the_median_time_to_pin() {
+        let mut stats = vec![
+            vote(VoteEnding::Pinned, 300, 1),
+            vote(VoteEnding::Pinned, 420, 0),
+            vote(VoteEnding::Expired, 3_600, 2),
+        ];
+        let summary = VoteSummary::of(&stats);
+        assert_eq!(summary.removals, 3);
+        assert_eq!(
+            summary.line(),
+            "3 votes, 2 pinned, 1 expired, median time-to-pin 6m"
+        );
+
+        stats.push(vote(VoteEnding::Pinned, 7_200, 0));
+        stats.push(vote(VoteEnding::Cancelled, 60, 0));
+        assert_eq!(
+            VoteSummary::of(&stats).line(),
+            "5 votes, 3 pinned, 1 expired, 1 cancelled, median time-to-pin 7m"
+        );
+        assert_eq!(VoteSummary::of(&[]).line(), "0 votes");
+        assert_eq!(
+            VoteEnding::parse(VoteEnding::Cancelled.as_str()),
+            Some(VoteEnding::Cancelled)
+        );
+    }
+}
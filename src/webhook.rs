@@ -0,0 +1,170 @@
+//! Outbound notifications about pins for external dashboards.
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{ChannelId, GuildId, MessageId, Timestamp, UserId};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinEventKind {
+    /// A message was pinned, by vote or directly.
+    Pinned,
+    VoteStarted,
+    /// The vote timed out before reaching the threshold.
+    VoteExpired,
+}
+
+/// The JSON body posted to `WEBHOOK_URL`. Ids are strings, as in the
+/// Discord API, and `timestamp` is RFC 3339.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PinEvent {
+    pub event: PinEventKind,
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub jump_url: String,
+    pub initiator_id: UserId,
+    pub voter_count: u32,
+    pub timestamp: Timestamp,
+}
+
+impl PinEvent {
+    pub fn new(
+        event: PinEventKind,
+        guild_id: Option<GuildId>,
+        channel_id: ChannelId,
+        message_id: MessageId,
+        initiator_id: UserId,
+        voter_count: u32,
+    ) -> Self {
+        Self {
+            event,
+            guild_id,
+            channel_id,
+            message_id,
+            jump_url: message_id.link(channel_id, guild_id),
+            initiator_id,
+            voter_count,
+            timestamp: Timestamp::now(),
+        }
+    }
+}
+
+pub struct Webhook {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Webhook {
+    pub fn new(url: String) -> reqwest::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+        Ok(Self { client, url })
+    }
+
+    /// Posts the event, retrying a couple of times. Failures are only logged.
+    pub async fn send(&self, event: &PinEvent) {
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = self
+                .client
+                .post(&self.url)
+                .json(event)
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+
+            match result {
+                Ok(_) => {
+                    debug!(event = ?event.event, message_id = %event.message_id, "Delivered webhook");
+                    return;
+                }
+                Err(e) if attempt < MAX_ATTEMPTS => {
+                    debug!(attempt, error = %e, "Webhook attempt failed");
+                    tokio::time::sleep(RETRY_DELAY * attempt).await;
+                }
+                Err(e) => warn!(
+                    event = ?event.event,
+                    message_id = %event.message_id,
+                    error = %e,
+                    "Giving up on webhook"
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event() -> PinEvent {
+        PinEvent {
+            event: PinEventKind::Pinned,
+            guild_id: Some(GuildId::new(1)),
+            channel_id: ChannelId::new(2),
+            message_id: MessageId::new(3),
+            jump_url: "https://discord.com/channels/1/2/3".to_string(),
+            initiator_id: UserId::new(4),
+            voter_count: 3,
+            timestamp: Timestamp::parse("2024-05-01T12:00:00Z").unwrap(),
+        }
+    }
+
+    #[test]
+    fn serializes_to_the_documented_schema() {
+        let value = serde_json::to_value(event()).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "event": "pinned",
+                "guild_id": "1",
+                "channel_id": "2",
+                "message_id": "3",
+                "jump_url": "https://discord.com/channels/1/2/3",
+                "initiator_id": "4",
+                "voter_count": 3,
+                "timestamp": "2024-05-01T12:00:00Z",
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips_every_event_kind() {
+        for (kind, name) in [
+            (PinEventKind::Pinned, "pinned"),
+            (PinEventKind::VoteStarted, "vote_started"),
+            (PinEventKind::VoteExpired, "vote_expired"),
+        ] {
+            let original = PinEvent {
+                event: kind,
+                guild_id: None,
+                ..event()
+            };
+            let encoded = serde_json::to_string(&original).unwrap();
+            assert!(encoded.contains(&format!("\"event\":\"{}\"", name)));
+            assert!(encoded.contains("\"guild_id\":null"));
+            let decoded: PinEvent = serde_json::from_str(&encoded).unwrap();
+            assert_eq!(decoded, original);
+        }
+    }
+
+    #[test]
+    fn new_builds_the_jump_url() {
+        let event = PinEvent::new(
+            PinEventKind::VoteStarted,
+            Some(GuildId::new(10)),
+            ChannelId::new(20),
+            MessageId::new(30),
+            UserId::new(40),
+            0,
+        );
+        assert_eq!(event.jump_url, "https://discord.com/channels/10/20/30");
+    }
+}
@@ -0,0 +1,103 @@
+//! Replays the recorded fixtures and checks what the bot did, line by line
+//! as `discord-pin-bot replay` prints it.
+
+use discord_pin_bot::{config::Config, replay::Replay};
+
+/// The pointer a direct message gets, as the replay prints it.
+fn dm_pointer(channel_id: u64) -> String {
+    format!(
+        "say in {}: \"I only work in servers. To pin a message there, reply to it with a mention \
+         of me, use `/pin` with its link, or pick **Pin this message** from its Apps menu; \
+         members then vote with ✅. `/help` in a server explains how pinning works in that \
+         channel.\"",
+        channel_id
+    )
+}
+
+async fn replay(fixture: &str) -> Vec<String> {
+    let events = Replay::parse(fixture).expect("fixture should parse");
+    let replay = Replay::new(Config::default()).await.unwrap();
+    replay
+        .run(events)
+        .await
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn a_vote_reaching_the_threshold_pins_its_target() {
+    let actions = replay(include_str!("fixtures/successful_vote.jsonl")).await;
+    assert_eq!(
+        actions,
+        [
+            // ⏳ marks the request until its vote is up
+            "react ⏳ on 1002",
+            "vote 1002 opened on 1001, 3 needed",
+            "react ✅ on 1002",
+            "react 🚫 on 1002",
+            "react 3\u{fe0f}\u{20e3} on 1002",
+            "react ✅ on 1001",
+            "unreact ⏳ on 1002",
+            // The bot's own ✅ is ignored, and 13 counts once for both of theirs
+            "vote 1002 at 1",
+            "vote 1002 at 2",
+            "vote 1002 at 3",
+            "pin 1001",
+            "vote 1002 ended: pinned",
+            "unreact ✅ on 1001",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_vote_left_short_expires_on_its_deadline() {
+    let actions = replay(include_str!("fixtures/expired_vote.jsonl")).await;
+    assert_eq!(
+        actions,
+        [
+            "react ⏳ on 2002",
+            "vote 2002 opened on 2001, 3 needed",
+            "react ✅ on 2002",
+            "react 🚫 on 2002",
+            "react 3\u{fe0f}\u{20e3} on 2002",
+            "react ✅ on 2001",
+            "unreact ⏳ on 2002",
+            "vote 2002 at 1",
+            "vote 2002 at 2",
+            "vote 2002 at 1",
+            // Half the default hour changes nothing, the rest expires it
+            "vote 2002 ended: expired",
+            "unreact ✅ on 2001",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn direct_messages_get_one_pointer_and_guildless_reactions_no_vote() {
+    let actions = replay(include_str!("fixtures/direct_messages.jsonl")).await;
+    assert_eq!(
+        actions,
+        [
+            // The second DM within the hour goes unanswered
+            dm_pointer(300),
+            "react ⏳ on 3004".to_string(),
+            "vote 3004 opened on 3003, 3 needed".to_string(),
+            "react ✅ on 3004".to_string(),
+            "react 🚫 on 3004".to_string(),
+            "react 3\u{fe0f}\u{20e3} on 3004".to_string(),
+            "react ✅ on 3003".to_string(),
+            "unreact ⏳ on 3004".to_string(),
+            // Only the reaction that came with its guild counts
+            "vote 3004 at 1".to_string(),
+            dm_pointer(301),
+        ]
+    );
+}
+
+#[test]
+fn a_malformed_line_is_named() {
+    let error =
+        Replay::parse("{\"event\":\"ready\",\"bot_id\":\"1\"}\n\n{\"event\":\"nap\"}").unwrap_err();
+    assert_eq!(error.to_string(), "Line 3 is not a replay event");
+}